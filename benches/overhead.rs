@@ -0,0 +1,92 @@
+//! Overhead that cata adds to commands and instrumentation.
+//!
+//! Run with `cargo bench`.
+#![allow(missing_docs, unused_crate_dependencies)]
+
+use cata::{
+    command::{Container, ExecuteOptions},
+    telemetry::{posthog::Posthog, Event, Handler, Telemetry},
+    Command,
+};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use tracing::field::ValueSet;
+use tracing_subscriber::prelude::*;
+
+/// A command tree that is `depth` levels deep.
+struct Node {
+    child: Option<Box<Node>>,
+}
+
+impl Node {
+    fn new(depth: usize) -> Self {
+        Self {
+            child: (depth > 1).then(|| Box::new(Node::new(depth - 1))),
+        }
+    }
+}
+
+impl Container for Node {
+    fn next(&self) -> Option<&dyn Command> {
+        self.child.as_deref().map(|child| child as &dyn Command)
+    }
+}
+
+impl Command for Node {}
+
+fn execute(c: &mut Criterion) {
+    let rt = tokio::runtime::Runtime::new().expect("runtime");
+    let mut group = c.benchmark_group("execute");
+
+    for depth in [1, 10, 100] {
+        let tree = Node::new(depth);
+
+        group.bench_with_input(BenchmarkId::from_parameter(depth), &tree, |b, tree| {
+            // The deepest tree is past the default limit.
+            b.to_async(&rt)
+                .iter(|| cata::execute_with(tree, ExecuteOptions::default().with_max_depth(depth)));
+        });
+    }
+
+    group.finish();
+}
+
+/// Builds events like Posthog does, without sending them anywhere.
+#[derive(Clone)]
+struct Discard(Posthog);
+
+impl Handler for Discard {
+    fn on_span(&self, user_id: String, meta: &tracing_core::Metadata, values: &ValueSet) -> Event {
+        self.0.on_span(user_id, meta, values)
+    }
+
+    fn on_event(&self, user_id: String, event: &tracing_core::Event) -> Event {
+        self.0.on_event(user_id, event)
+    }
+
+    fn capture(&self, event: Event) -> eyre::Result<()> {
+        drop(event);
+
+        Ok(())
+    }
+}
+
+fn telemetry(c: &mut Criterion) {
+    let rt = tokio::runtime::Runtime::new().expect("runtime");
+    let _guard = rt.enter();
+
+    let subscriber = tracing_subscriber::registry()
+        .with(Telemetry::new(Discard(Posthog::new("api-key"))).with_activity());
+
+    tracing::subscriber::with_default(subscriber, || {
+        c.bench_function("telemetry/event", |b| {
+            b.iter(|| tracing::info!(activity = "bench", count = 1, "event"));
+        });
+
+        c.bench_function("telemetry/span", |b| {
+            b.iter(|| tracing::info_span!("bench", activity = "bench", count = 1));
+        });
+    });
+}
+
+criterion_group!(benches, execute, telemetry);
+criterion_main!(benches);
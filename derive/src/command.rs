@@ -0,0 +1,152 @@
+use proc_macro2::{Ident, TokenStream};
+use quote::quote;
+use syn::spanned::Spanned;
+
+/// The `#[cata(...)]` attributes of a command.
+#[derive(Default)]
+struct Attrs {
+    /// The `activity` in `#[cata(activity = "...")]` or `#[cata(telemetry =
+    /// "...")]`, if there is one. It can also be a constant, such as
+    /// `Self::PATH`.
+    activity: Option<syn::Expr>,
+    /// The `false` in `#[cata(telemetry = false)]`, or `true` for a bare
+    /// `#[cata(telemetry)]`, if there is one.
+    telemetry: Option<syn::LitBool>,
+    /// The function in `#[cata(run = ...)]` that `run` calls, if there is one.
+    run: Option<syn::ExprPath>,
+}
+
+impl Attrs {
+    fn set_activity(
+        &mut self,
+        meta: &syn::meta::ParseNestedMeta,
+        activity: syn::Expr,
+    ) -> syn::Result<()> {
+        if self.activity.is_some() {
+            return Err(meta.error("the activity is already set"));
+        }
+
+        self.activity = Some(activity);
+
+        Ok(())
+    }
+}
+
+/// Parse the `#[cata(...)]` attributes of a command.
+///
+/// Keys that belong to the `Container` derive, such as `example` and `path`,
+/// are skipped over.
+fn attrs(attrs: &[syn::Attribute]) -> syn::Result<Attrs> {
+    let mut parsed = Attrs::default();
+
+    for attr in attrs {
+        if !attr.path().is_ident("cata") {
+            continue;
+        }
+
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("activity") {
+                let activity = meta.value()?.parse()?;
+                parsed.set_activity(&meta, activity)?;
+            } else if meta.path.is_ident("telemetry") {
+                if !meta.input.peek(syn::Token![=]) {
+                    parsed.telemetry = Some(syn::LitBool::new(true, meta.path.span()));
+
+                    return Ok(());
+                }
+
+                match meta.value()?.parse()? {
+                    syn::Expr::Lit(syn::ExprLit {
+                        lit: syn::Lit::Bool(enabled),
+                        ..
+                    }) => parsed.telemetry = Some(enabled),
+                    activity => parsed.set_activity(&meta, activity)?,
+                }
+            } else if meta.path.is_ident("run") {
+                parsed.run = Some(meta.value()?.parse()?);
+            } else if meta.path.is_ident("example") || meta.path.is_ident("path") {
+                meta.value()?.parse::<syn::LitStr>()?;
+            } else if meta.path.is_ident("parent") {
+                meta.value()?.parse::<syn::Type>()?;
+            } else if !meta.path.is_ident("describe") {
+                return Err(meta.error("unsupported cata attribute"));
+            }
+
+            Ok(())
+        })?;
+    }
+
+    if let (Some(activity), Some(telemetry)) = (&parsed.activity, &parsed.telemetry) {
+        if !telemetry.value {
+            return Err(syn::Error::new_spanned(
+                activity,
+                "commands with `telemetry = false` aren't reported, they don't have an activity",
+            ));
+        }
+    }
+
+    Ok(parsed)
+}
+
+/// Generate an implementation of `Command` from the attributes of a struct or
+/// an enum.
+///
+/// Every hook keeps its default, except for `activity()`, `telemetry()` and
+/// `run()` when the attributes ask for them.
+///
+/// Generics are carried over, with the bounds `Command` puts on its
+/// implementors.
+pub fn derive(input: &syn::DeriveInput) -> syn::Result<TokenStream> {
+    let name: &Ident = &input.ident;
+    let mut generics = input.generics.clone();
+    let (_, ty_generics, _) = input.generics.split_for_impl();
+    generics
+        .make_where_clause()
+        .predicates
+        .push(syn::parse_quote!(#name #ty_generics: Send + Sync + 'static));
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    let Attrs {
+        activity,
+        telemetry,
+        run,
+    } = attrs(&input.attrs)?;
+
+    let activity_impl = activity.map(|activity| {
+        quote! {
+            fn activity(&self) -> Option<&'static str> {
+                Some(#activity)
+            }
+        }
+    });
+
+    let telemetry_impl = telemetry.map(|enabled| {
+        quote! {
+            fn telemetry(&self) -> bool {
+                #enabled
+            }
+        }
+    });
+
+    let run_impl = run.map(|run| {
+        quote! {
+            async fn run(
+                &self,
+                ctx: &mut ::cata::Context,
+            ) -> ::cata::__private::Result<()> {
+                #run(self, ctx).await
+            }
+        }
+    });
+
+    Ok(quote! {
+        #[automatically_derived]
+        #[::cata::__private::async_trait]
+        impl #impl_generics ::cata::Command for #name #ty_generics #where_clause {
+            #activity_impl
+
+            #telemetry_impl
+
+            #run_impl
+        }
+    })
+}
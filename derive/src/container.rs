@@ -1,86 +1,1097 @@
 use eyre::Result;
+use heck::{ToKebabCase, ToLowerCamelCase, ToShoutySnakeCase, ToSnakeCase, ToUpperCamelCase};
 use proc_macro2::{Ident, TokenStream};
-use quote::quote;
-use syn::visit::{self, Visit};
+use quote::{quote, quote_spanned};
+use syn::{ext::IdentExt, spanned::Spanned};
 
-/// Find the command field in a struct.
+use crate::describe;
+
+/// Whether `attrs` have `#[command(key)]`, any other keys are skipped over.
+pub(crate) fn has_command_key(attrs: &[syn::Attribute], key: &str) -> syn::Result<bool> {
+    let mut found = false;
+
+    for attr in attrs {
+        if !attr.path().is_ident("command") || !matches!(attr.meta, syn::Meta::List(_)) {
+            continue;
+        }
+
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident(key) {
+                found = true;
+            }
+
+            while !meta.input.is_empty() && !meta.input.peek(syn::Token![,]) {
+                meta.input.parse::<proc_macro2::TokenTree>()?;
+            }
+
+            Ok(())
+        })?;
+    }
+
+    Ok(found)
+}
+
+/// Whether a field has `#[command(subcommand)]`.
 ///
-/// Looks for the identifier of the command field in a struct. The first field
-/// with a `#[command]` attribute is returned. It does not support a struct
-/// having multiple commands and is naive about whether that is a subcommand or
-/// not currently.
-fn get_field_name(data: &syn::DataStruct, attr_name: &str) -> Option<Ident> {
+/// Other keys, such as `flatten`, are skipped over. Flattened groups of
+/// arguments aren't commands.
+fn is_subcommand(field: &syn::Field) -> syn::Result<bool> {
+    has_command_key(&field.attrs, "subcommand")
+}
+
+/// Find the subcommand field in a struct.
+///
+/// Clap only allows a single `#[command(subcommand)]` field, it has to be
+/// named so that `next()` can refer to it.
+pub(crate) fn get_field(data: &syn::DataStruct) -> syn::Result<Option<&syn::Field>> {
+    let mut found: Option<&syn::Field> = None;
+
+    for field in &data.fields {
+        if !is_subcommand(field)? {
+            continue;
+        }
+
+        if found.is_some() {
+            return Err(syn::Error::new_spanned(
+                field,
+                "only one `#[command(subcommand)]` field is supported",
+            ));
+        }
+
+        if field.ident.is_none() {
+            return Err(syn::Error::new_spanned(
+                field,
+                "`#[command(subcommand)]` must be on a named field",
+            ));
+        }
+
+        found = Some(field);
+    }
+
+    Ok(found)
+}
+
+/// The `#[cata(...)]` attributes of a field.
+#[derive(Default)]
+struct FieldAttrs {
+    /// Whether the field has `#[cata(global)]`.
+    global: bool,
+    /// Whether the field has `#[cata(output)]`.
+    output: bool,
+    /// Whether the field has `#[cata(delegate)]`, the struct is then a
+    /// container through it.
+    delegate: bool,
+}
+
+/// Parse the `#[cata(...)]` attributes of a field.
+fn field_attrs(field: &syn::Field) -> syn::Result<FieldAttrs> {
+    let mut attrs = FieldAttrs::default();
+
+    for attr in &field.attrs {
+        if !attr.path().is_ident("cata") {
+            continue;
+        }
+
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("global") {
+                attrs.global = true;
+
+                return Ok(());
+            }
+
+            if meta.path.is_ident("output") {
+                attrs.output = true;
+
+                return Ok(());
+            }
+
+            if meta.path.is_ident("delegate") {
+                attrs.delegate = true;
+
+                return Ok(());
+            }
+
+            Err(meta.error("unsupported cata attribute"))
+        })?;
+    }
+
+    Ok(attrs)
+}
+
+/// Provide the fields with `#[cata(global)]` and the format in the one with
+/// `#[cata(output)]` to the commands below, followed by whatever `delegated`
+/// provides.
+fn provide_impl(
+    data: &syn::DataStruct,
+    delegated: Option<&TokenStream>,
+) -> syn::Result<Option<TokenStream>> {
+    let mut globals = Vec::new();
+    let mut output = None;
+
     for field in &data.fields {
-        for attr in &field.attrs {
-            if attr.path().is_ident(attr_name) {
-                return field.ident.clone();
+        let attrs = field_attrs(field)?;
+        if !attrs.global && !attrs.output {
+            continue;
+        }
+
+        let Some(ident) = &field.ident else {
+            return Err(syn::Error::new_spanned(
+                field,
+                "only named fields can be global or the output",
+            ));
+        };
+
+        if attrs.global {
+            globals.push(ident);
+        }
+
+        if attrs.output {
+            if output.is_some() {
+                return Err(syn::Error::new_spanned(
+                    field,
+                    "only one `#[cata(output)]` field is supported",
+                ));
             }
+
+            output = Some(quote_spanned! {field.ty.span()=>
+                ctx.provide::<::cata::output::Format>(::std::convert::Into::into(
+                    ::std::clone::Clone::clone(&self.#ident),
+                ));
+            });
         }
     }
 
-    None
+    if globals.is_empty() && output.is_none() && delegated.is_none() {
+        return Ok(None);
+    }
+
+    Ok(Some(quote! {
+        fn provide_globals(&self, ctx: &mut ::cata::Context) {
+            #(ctx.provide_global(
+                stringify!(#globals),
+                ::std::clone::Clone::clone(&self.#globals),
+            );)*
+
+            #output
+
+            #delegated
+        }
+    }))
 }
 
-/// Dispatch to the enum if it exists or return None.
+/// Find the field with `#[cata(delegate)]` in a struct, along with how to
+/// refer to it.
+pub(crate) fn delegate_field(
+    data: &syn::DataStruct,
+) -> syn::Result<Option<(syn::Member, &syn::Type)>> {
+    let mut found = None;
+
+    for (index, field) in data.fields.iter().enumerate() {
+        if !field_attrs(field)?.delegate {
+            continue;
+        }
+
+        if found.is_some() {
+            return Err(syn::Error::new_spanned(
+                field,
+                "only one `#[cata(delegate)]` field is supported",
+            ));
+        }
+
+        if is_subcommand(field)? {
+            return Err(syn::Error::new_spanned(
+                field,
+                "subcommands are already dispatched to, remove `#[cata(delegate)]`",
+            ));
+        }
+
+        let member = field.ident.clone().map_or_else(
+            || syn::Member::Unnamed(syn::Index::from(index)),
+            syn::Member::Named,
+        );
+        found = Some((member, &field.ty));
+    }
+
+    Ok(found)
+}
+
+/// Forward every method to the field with `#[cata(delegate)]`, for wrapping
+/// commands that come from another crate.
 ///
-/// Looks for `#[command]` in a struct and on the first hit generates an
-/// implementation that calls into the `next()` field of that enum.
-fn struct_impl(name: &Ident, data: &syn::DataStruct) -> TokenStream {
-    #[allow(clippy::single_match_else)]
-    let next_impl = match get_field_name(data, "command") {
-        Some(field_name) => quote! {
+/// Subcommands, flags and examples of the field are those of the struct.
+fn delegate_impl(
+    header: &TokenStream,
+    name_impl: &TokenStream,
+    examples: &[String],
+    data: &syn::DataStruct,
+    member: &syn::Member,
+    ty: &syn::Type,
+) -> syn::Result<TokenStream> {
+    if let Some(field) = get_field(data)? {
+        return Err(syn::Error::new_spanned(
+            field,
+            "the subcommands of a struct with `#[cata(delegate)]` come from that field, move \
+             this one into the delegated type",
+        ));
+    }
+
+    if !examples.is_empty() {
+        return Err(syn::Error::new_spanned(
+            member,
+            "the examples of a delegated command come from this field, move `#[cata(example = \
+             \"...\")]` to its type",
+        ));
+    }
+
+    let container = quote_spanned! {ty.span()=> <#ty as ::cata::command::Container>};
+    let delegated = quote! { #container::provide_globals(&self.#member, ctx); };
+    let provide_impl = provide_impl(data, Some(&delegated))?;
+
+    Ok(quote! {
+        #[automatically_derived]
+        #header {
             fn next(&self) -> Option<&dyn ::cata::command::Command> {
-                self.#field_name.next()
+                #container::next(&self.#member)
+            }
+
+            fn next_mut(&mut self) -> Option<&mut dyn ::cata::command::Command> {
+                #container::next_mut(&mut self.#member)
+            }
+
+            fn subcommand(&self) -> Option<&dyn ::cata::command::Command> {
+                #container::subcommand(&self.#member)
+            }
+
+            fn subcommand_mut(&mut self) -> Option<&mut dyn ::cata::command::Command> {
+                #container::subcommand_mut(&mut self.#member)
+            }
+
+            fn path_segment(&self) -> Option<&'static str> {
+                #container::path_segment(&self.#member)
+            }
+
+            #name_impl
+
+            fn children(&self) -> &'static [&'static str] {
+                #container::children(&self.#member)
+            }
+
+            fn flag(&self) -> Option<&'static str> {
+                #container::flag(&self.#member)
+            }
+
+            fn flags() -> Vec<(Vec<&'static str>, &'static str)> {
+                #container::flags()
+            }
+
+            fn examples(&self) -> &'static [&'static str] {
+                #container::examples(&self.#member)
+            }
+
+            fn all_examples() -> Vec<(Vec<&'static str>, &'static [&'static str])> {
+                #container::all_examples()
+            }
+
+            #provide_impl
+        }
+    })
+}
+
+/// The `#[cata(...)]` attributes of a struct or an enum.
+#[derive(Default)]
+struct Attrs {
+    /// The `example` in every `#[cata(example = "...")]`, in order.
+    examples: Vec<String>,
+    /// The `path` in `#[cata(path = "...")]`, if there is one.
+    path: Option<syn::LitStr>,
+    /// The type in `#[cata(parent = ...)]` that `path` is below, if there is
+    /// one.
+    parent: Option<syn::Type>,
+    /// Whether there is `#[cata(describe)]`, for a descriptor that includes
+    /// the subcommands.
+    describe: bool,
+}
+
+/// Parse the `#[cata(...)]` attributes of a struct or an enum.
+fn attrs(attrs: &[syn::Attribute]) -> syn::Result<Attrs> {
+    let mut parsed = Attrs::default();
+
+    for attr in attrs {
+        if !attr.path().is_ident("cata") {
+            continue;
+        }
+
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("example") {
+                parsed
+                    .examples
+                    .push(meta.value()?.parse::<syn::LitStr>()?.value());
+
+                return Ok(());
+            }
+
+            if meta.path.is_ident("path") {
+                parsed.path = Some(meta.value()?.parse()?);
+
+                return Ok(());
+            }
+
+            if meta.path.is_ident("parent") {
+                parsed.parent = Some(meta.value()?.parse()?);
+
+                return Ok(());
+            }
+
+            if meta.path.is_ident("describe") {
+                parsed.describe = true;
+
+                return Ok(());
+            }
+
+            // These belong to the `Command` derive.
+            if meta.path.is_ident("activity") || meta.path.is_ident("run") {
+                meta.value()?.parse::<syn::Expr>()?;
+
+                return Ok(());
+            }
+
+            if meta.path.is_ident("telemetry") {
+                if meta.input.peek(syn::Token![=]) {
+                    meta.value()?.parse::<syn::Expr>()?;
+                }
+
+                return Ok(());
+            }
+
+            Err(meta.error("unsupported cata attribute"))
+        })?;
+    }
+
+    if let (None, Some(parent)) = (&parsed.path, &parsed.parent) {
+        return Err(syn::Error::new_spanned(
+            parent,
+            "`parent` needs a `path`, add `#[cata(path = \"...\")]` with this command's name",
+        ));
+    }
+
+    Ok(parsed)
+}
+
+/// Generate the `PATH` constant and `command_path()` from `#[cata(path =
+/// "...")]`, joined onto the `PATH` of `#[cata(parent = ...)]` when there is
+/// one.
+fn path_impl(input: &syn::DeriveInput, attrs: &Attrs) -> Option<TokenStream> {
+    let path = attrs.path.as_ref()?;
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    let value = match &attrs.parent {
+        Some(parent) => quote_spanned! {parent.span()=>
+            {
+                const PARENT: &str = <#parent>::PATH;
+                const LEN: usize = ::cata::__private::path_len(PARENT, #path);
+                const JOINED: [u8; LEN] = ::cata::__private::join_path(PARENT, #path);
+
+                ::cata::__private::path_str(&JOINED)
             }
         },
-        None => quote! {
+        None => quote! { #path },
+    };
+
+    Some(quote! {
+        #[automatically_derived]
+        impl #impl_generics #name #ty_generics #where_clause {
+            /// The path of the command below the root, such as `cluster create`.
+            pub const PATH: &'static str = #value;
+
+            /// The path of the command below the root, see `PATH`.
+            #[must_use]
+            pub fn command_path() -> &'static str {
+                Self::PATH
+            }
+        }
+    })
+}
+
+/// Find the name clap will use for a variant.
+///
+/// An explicit `#[command(name = "...")]` wins, otherwise this is the
+/// variant's identifier in the casing of `#[command(rename_all = "...")]`. That
+/// is taken from the variant before the enum (`attrs`) and defaults to
+/// kebab-case, just like clap. Any other keys in the attribute are skipped over.
+pub(crate) fn variant_name(
+    attrs: &[syn::Attribute],
+    variant: &syn::Variant,
+) -> syn::Result<String> {
+    let mut name = None;
+    let mut casing = rename_all(attrs)?;
+
+    for attr in &variant.attrs {
+        if !attr.path().is_ident("command") {
+            continue;
+        }
+
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("name") {
+                name = Some(meta.value()?.parse::<syn::LitStr>()?.value());
+
+                return Ok(());
+            }
+
+            if meta.path.is_ident("rename_all") {
+                casing = Some(meta.value()?.parse::<syn::LitStr>()?);
+
+                return Ok(());
+            }
+
+            while !meta.input.is_empty() && !meta.input.peek(syn::Token![,]) {
+                meta.input.parse::<proc_macro2::TokenTree>()?;
+            }
+
+            Ok(())
+        })?;
+    }
+
+    if let Some(name) = name {
+        return Ok(name);
+    }
+
+    let ident = variant.ident.unraw().to_string();
+    let Some(casing) = casing else {
+        return Ok(ident.to_kebab_case());
+    };
+
+    // The same spellings clap accepts, such as `snake_case` or `SNAKE`.
+    Ok(
+        match casing.value().to_upper_camel_case().to_lowercase().as_str() {
+            "camel" | "camelcase" => ident.to_lower_camel_case(),
+            "kebab" | "kebabcase" => ident.to_kebab_case(),
+            "pascal" | "pascalcase" => ident.to_upper_camel_case(),
+            "screamingsnake" | "screamingsnakecase" => ident.to_shouty_snake_case(),
+            "snake" | "snakecase" => ident.to_snake_case(),
+            "lower" | "lowercase" => ident.to_snake_case().replace('_', ""),
+            "upper" | "uppercase" => ident.to_shouty_snake_case().replace('_', ""),
+            "verbatim" | "verbatimcase" => ident,
+            _ => {
+                return Err(syn::Error::new_spanned(
+                    casing,
+                    "unsupported casing, clap won't accept it either",
+                ))
+            }
+        },
+    )
+}
+
+/// The casing in `#[command(rename_all = "...")]`, if there is one. Any other
+/// keys in the attribute are skipped over.
+fn rename_all(attrs: &[syn::Attribute]) -> syn::Result<Option<syn::LitStr>> {
+    let mut casing = None;
+
+    for attr in attrs {
+        if !attr.path().is_ident("command") || !matches!(attr.meta, syn::Meta::List(_)) {
+            continue;
+        }
+
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("rename_all") {
+                casing = Some(meta.value()?.parse::<syn::LitStr>()?);
+
+                return Ok(());
+            }
+
+            while !meta.input.is_empty() && !meta.input.peek(syn::Token![,]) {
+                meta.input.parse::<proc_macro2::TokenTree>()?;
+            }
+
+            Ok(())
+        })?;
+    }
+
+    Ok(casing)
+}
+
+/// The `#[cata(...)]` attributes of a variant.
+#[derive(Default)]
+pub(crate) struct VariantAttrs {
+    /// The feature flag in `#[cata(flag = "...")]`, if there is one.
+    pub(crate) flag: Option<String>,
+    /// Whether the variant has `#[cata(skip)]` because it isn't a command.
+    pub(crate) skip: bool,
+    /// Whether the variant has `#[cata(default)]`, it runs when the subcommand
+    /// is optional and wasn't passed.
+    default: bool,
+    /// The function in `#[cata(external = ...)]` that runs the arguments of an
+    /// external subcommand, if there is one.
+    external: Option<syn::ExprPath>,
+}
+
+/// Parse the `#[cata(...)]` attributes of a variant.
+pub(crate) fn variant_attrs(variant: &syn::Variant) -> syn::Result<VariantAttrs> {
+    let mut attrs = VariantAttrs::default();
+
+    for attr in &variant.attrs {
+        if !attr.path().is_ident("cata") {
+            continue;
+        }
+
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("flag") {
+                attrs.flag = Some(meta.value()?.parse::<syn::LitStr>()?.value());
+
+                return Ok(());
+            }
+
+            if meta.path.is_ident("skip") {
+                attrs.skip = true;
+
+                return Ok(());
+            }
+
+            if meta.path.is_ident("default") {
+                attrs.default = true;
+
+                return Ok(());
+            }
+
+            if meta.path.is_ident("external") {
+                attrs.external = Some(meta.value()?.parse()?);
+
+                return Ok(());
+            }
+
+            Err(meta.error("unsupported cata attribute"))
+        })?;
+    }
+
+    Ok(attrs)
+}
+
+/// The type of the command in a variant, `Child` in `Child(Child)`.
+///
+/// Variants with named fields, or none at all, don't have one. The enum itself
+/// is their command.
+pub(crate) fn variant_type(variant: &syn::Variant) -> syn::Result<Option<&syn::Type>> {
+    match &variant.fields {
+        syn::Fields::Unnamed(fields) if fields.unnamed.len() == 1 => {
+            Ok(Some(&fields.unnamed[0].ty))
+        }
+        syn::Fields::Named(_) | syn::Fields::Unit => Ok(None),
+        syn::Fields::Unnamed(fields) => Err(syn::Error::new_spanned(
+            fields,
+            format!(
+                "variants must have a single unnamed field, named fields or none, move the \
+                 fields of `{ident}` into a struct that derives `Parser` and use \
+                 `{ident}({ident}Args)` instead",
+                ident = variant.ident,
+            ),
+        )),
+    }
+}
+
+/// The command in a `Box<Child>`, which is dispatched to through the box.
+///
+/// `Box<dyn Command>` is left as it is, it is a command of its own.
+pub(crate) fn unbox(ty: &syn::Type) -> Option<&syn::Type> {
+    let syn::Type::Path(path) = ty else {
+        return None;
+    };
+
+    let segment = path.path.segments.last()?;
+    if segment.ident != "Box" {
+        return None;
+    }
+
+    let syn::PathArguments::AngleBracketed(args) = &segment.arguments else {
+        return None;
+    };
+
+    match args.args.first()? {
+        syn::GenericArgument::Type(syn::Type::TraitObject(_)) => None,
+        syn::GenericArgument::Type(inner) if args.args.len() == 1 => Some(inner),
+        _ => None,
+    }
+}
+
+/// The `T` in the `Vec<T>` of an external subcommand.
+fn vec_element(ty: &syn::Type) -> Option<&syn::Type> {
+    let syn::Type::Path(path) = ty else {
+        return None;
+    };
+
+    let segment = path.path.segments.last()?;
+    if segment.ident != "Vec" {
+        return None;
+    }
+
+    let syn::PathArguments::AngleBracketed(args) = &segment.arguments else {
+        return None;
+    };
+
+    match args.args.first()? {
+        syn::GenericArgument::Type(inner) if args.args.len() == 1 => Some(inner),
+        _ => None,
+    }
+}
+
+/// The external subcommand of an enum that is handed to a function, with the
+/// `T` of its `Vec<T>` and the function.
+///
+/// Variants with `#[command(external_subcommand)]` that don't have
+/// `#[cata(external = ...)]` are skipped instead.
+fn external_variant(
+    variant: &syn::Variant,
+    attrs: &VariantAttrs,
+) -> syn::Result<Option<(syn::Type, syn::ExprPath)>> {
+    let is_external = has_command_key(&variant.attrs, "external_subcommand")?;
+
+    let Some(handler) = &attrs.external else {
+        return Ok(None);
+    };
+
+    if !is_external {
+        return Err(syn::Error::new_spanned(
+            handler,
+            "`external` is only for variants with `#[command(external_subcommand)]`",
+        ));
+    }
+
+    let element = variant_type(variant)?
+        .and_then(vec_element)
+        .ok_or_else(|| {
+            syn::Error::new_spanned(
+                &variant.fields,
+                "external subcommands hold their arguments, such as `Vec<OsString>`",
+            )
+        })?;
+
+    Ok(Some((element.clone(), handler.clone())))
+}
+
+/// Implement `ExternalHandler` for an enum whose external subcommand has
+/// `#[cata(external = ...)]`, by calling the function.
+fn external_impl(input: &syn::DeriveInput, data: &syn::DataEnum) -> syn::Result<TokenStream> {
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    let mut handlers = TokenStream::new();
+    for variant in &data.variants {
+        let Some((element, handler)) = external_variant(variant, &variant_attrs(variant)?)? else {
+            continue;
+        };
+
+        handlers.extend(quote! {
+            #[automatically_derived]
+            #[::cata::__private::async_trait]
+            impl #impl_generics ::cata::command::ExternalHandler<#element>
+                for #name #ty_generics #where_clause
+            {
+                async fn handle(
+                    args: &[#element],
+                    ctx: &mut ::cata::Context,
+                ) -> ::cata::__private::Result<()> {
+                    #handler(args, ctx).await
+                }
+            }
+        });
+    }
+
+    Ok(handlers)
+}
+
+/// Dispatch to the enum if it exists or return None.
+///
+/// Looks for `#[command(subcommand)]` in a struct and generates an
+/// implementation that calls into the `subcommand()` and `subcommand_mut()` of
+/// that enum. Calls go through the field's type, spanned to it, so that a field
+/// that isn't a `Container` is reported there.
+fn struct_impl(
+    header: &TokenStream,
+    input: &syn::DeriveInput,
+    examples: &[String],
+    data: &syn::DataStruct,
+) -> syn::Result<TokenStream> {
+    let name_impl = name_impl(input)?;
+
+    if let Some((member, ty)) = delegate_field(data)? {
+        return delegate_impl(header, &name_impl, examples, data, &member, ty);
+    }
+
+    let provide_impl = provide_impl(data, None)?;
+    let field = get_field(data)?;
+
+    #[allow(clippy::single_match_else)]
+    let next_impl = match field {
+        Some(syn::Field {
+            ident: Some(field_name),
+            ty,
+            ..
+        }) => {
+            let container = quote_spanned! {ty.span()=> <#ty as ::cata::command::Container>};
+
+            quote! {
+                fn next(&self) -> Option<&dyn ::cata::command::Command> {
+                    #container::subcommand(&self.#field_name)
+                }
+
+                fn next_mut(&mut self) -> Option<&mut dyn ::cata::command::Command> {
+                    #container::subcommand_mut(&mut self.#field_name)
+                }
+
+                fn path_segment(&self) -> Option<&'static str> {
+                    #container::path_segment(&self.#field_name)
+                }
+
+                fn children(&self) -> &'static [&'static str] {
+                    #container::children(&self.#field_name)
+                }
+
+                fn flag(&self) -> Option<&'static str> {
+                    #container::flag(&self.#field_name)
+                }
+
+                fn flags() -> Vec<(Vec<&'static str>, &'static str)> {
+                    #container::flags()
+                }
+            }
+        }
+        _ => quote! {
              fn next(&self) -> Option<&dyn ::cata::command::Command> {
                  None
              }
         },
     };
 
-    quote! {
+    let own_examples = (!examples.is_empty()).then(|| {
+        quote! {
+            examples.push((Vec::new(), &[#(#examples),*] as &'static [&'static str]));
+        }
+    });
+    let child_examples = field.map(|syn::Field { ty, .. }| {
+        quote_spanned! {ty.span()=>
+            examples.extend(<#ty as ::cata::command::Container>::all_examples());
+        }
+    });
+    let examples_impl = (own_examples.is_some() || child_examples.is_some()).then(|| {
+        quote! {
+            fn examples(&self) -> &'static [&'static str] {
+                &[#(#examples),*]
+            }
+
+            fn all_examples() -> Vec<(Vec<&'static str>, &'static [&'static str])> {
+                let mut examples = Vec::new();
+                #own_examples
+                #child_examples
+
+                examples
+            }
+        }
+    });
+
+    Ok(quote! {
         #[automatically_derived]
-        impl ::cata::command::Container for #name {
+        #header {
             #next_impl
+
+            #name_impl
+
+            #examples_impl
+
+            #provide_impl
         }
-    }
+    })
+}
+
+/// The name clap gives the struct when it is parsed as the root, its
+/// `#[command(name = "...")]` or the name of the package.
+fn name_impl(input: &syn::DeriveInput) -> syn::Result<TokenStream> {
+    let name = describe::command_str(&input.attrs, "command", "name")?.map_or_else(
+        || quote!(::core::env!("CARGO_PKG_NAME")),
+        |name| quote!(#name),
+    );
+
+    Ok(quote! {
+        fn command_name(&self) -> Option<&'static str> {
+            Some(#name)
+        }
+    })
 }
 
-/// Accumulate all the variants in an enum.
+/// Return the enum itself for variants that don't hold a command, which is
+/// why the enum has to implement `Command` when it has any.
 ///
-/// This is used to generate the match statement in the `next()` function.
+/// An enum that doesn't is reported at the first of those variants.
+fn subcommand_impl(inline: &[&Ident]) -> Option<TokenStream> {
+    let first = inline.first()?;
+    let this = quote_spanned! {first.span()=> self};
+
+    Some(quote! {
+        fn subcommand(&self) -> Option<&dyn ::cata::command::Command> {
+            if matches!(self, #(Self::#inline { .. })|*) {
+                Some(#this)
+            } else {
+                self.next()
+            }
+        }
+
+        fn subcommand_mut(&mut self) -> Option<&mut dyn ::cata::command::Command> {
+            if matches!(self, #(Self::#inline { .. })|*) {
+                Some(#this)
+            } else {
+                self.next_mut()
+            }
+        }
+    })
+}
+
+/// The `Dispatch` implementation of a variant's command, spanned to its type
+/// which is where a type that isn't a command is reported.
+fn dispatch(ty: &syn::Type) -> TokenStream {
+    let ty = unbox(ty).unwrap_or(ty);
+
+    quote_spanned! {ty.span()=> <#ty as ::cata::command::Dispatch>}
+}
+
+/// The variants of an enum, by how they are dispatched to.
 #[derive(Default)]
-struct UnnamedTypes<'ast> {
-    commands: Vec<&'ast syn::Ident>,
+struct Variants<'a> {
+    /// Variants that hold their command, with its name, flag and type.
+    commands: Vec<(&'a Ident, String, TokenStream, &'a syn::Type)>,
+    /// Variants that the enum itself is the command for.
+    inline: Vec<(&'a Ident, String, TokenStream)>,
+    /// Variants with `#[cata(skip)]`, and external subcommands without a
+    /// function to hand them to.
+    skipped: Vec<&'a Ident>,
+    /// External subcommands with `#[cata(external = ...)]`.
+    external: Vec<&'a Ident>,
+}
+
+impl<'a> Variants<'a> {
+    /// The variants without a name, external subcommands don't have one of
+    /// their own.
+    fn unnamed(&self) -> Vec<&'a Ident> {
+        self.skipped.iter().chain(&self.external).copied().collect()
+    }
+}
+
+fn partition<'a>(
+    enum_attrs: &[syn::Attribute],
+    data: &'a syn::DataEnum,
+) -> syn::Result<Variants<'a>> {
+    let mut variants = Variants::default();
+
+    for variant in &data.variants {
+        let attrs = variant_attrs(variant)?;
+        let name = variant_name(enum_attrs, variant)?;
+        let flag = attrs
+            .flag
+            .as_ref()
+            .map_or_else(|| quote! { None }, |flag| quote! { Some(#flag) });
+
+        if external_variant(variant, &attrs)?.is_some() {
+            variants.external.push(&variant.ident);
+        } else if attrs.skip || has_command_key(&variant.attrs, "external_subcommand")? {
+            variants.skipped.push(&variant.ident);
+        } else if let Some(ty) = variant_type(variant)? {
+            variants.commands.push((&variant.ident, name, flag, ty));
+        } else {
+            variants.inline.push((&variant.ident, name, flag));
+        }
+    }
+
+    Ok(variants)
 }
 
-impl<'ast> Visit<'ast> for UnnamedTypes<'ast> {
-    fn visit_variant(&mut self, i: &'ast syn::Variant) {
-        self.commands.push(&i.ident);
+/// Find the variant with `#[cata(default)]`, along with the name it is parsed
+/// from.
+fn default_variant<'a>(
+    enum_attrs: &[syn::Attribute],
+    data: &'a syn::DataEnum,
+) -> syn::Result<Option<(&'a syn::Variant, String)>> {
+    let mut found: Option<(&syn::Variant, String)> = None;
+
+    for variant in &data.variants {
+        let attrs = variant_attrs(variant)?;
+        if !attrs.default {
+            continue;
+        }
+
+        if let Some((other, _)) = found {
+            return Err(syn::Error::new_spanned(
+                &variant.ident,
+                format!("`{}` is already the default subcommand", other.ident),
+            ));
+        }
+
+        if attrs.skip || has_command_key(&variant.attrs, "external_subcommand")? {
+            return Err(syn::Error::new_spanned(
+                &variant.ident,
+                "only commands can be the default subcommand, remove `#[cata(default)]`",
+            ));
+        }
 
-        visit::visit_variant(self, i);
+        found = Some((variant, variant_name(enum_attrs, variant)?));
     }
+
+    Ok(found)
+}
+
+/// Build the variant with `#[cata(default)]` by parsing its name, as if it had
+/// been passed on its own.
+fn default_impl(
+    attrs: &[syn::Attribute],
+    data: &syn::DataEnum,
+) -> syn::Result<Option<TokenStream>> {
+    let Some((variant, name)) = default_variant(attrs, data)? else {
+        return Ok(None);
+    };
+
+    let parse = quote_spanned! {variant.ident.span()=>
+        ::cata::__private::parse_subcommand::<Self>(#name)
+    };
+
+    Ok(Some(quote! {
+        fn default_subcommand() -> Option<Result<Self, ::clap::Error>> {
+            Some(#parse)
+        }
+
+        fn shared_default_subcommand(
+        ) -> Option<Result<&'static dyn ::cata::command::Container, &'static ::clap::Error>> {
+            Some(
+                ::cata::__private::leak_once(|| #parse)
+                    .as_ref()
+                    .map(|cmd| cmd as &dyn ::cata::command::Container),
+            )
+        }
+    }))
 }
 
 /// Dispatch to the correct command in the enum via variants.
-fn enum_impl(name: &Ident, data: &syn::DataEnum) -> TokenStream {
-    let mut visitor = UnnamedTypes::default();
-    visitor.visit_data_enum(data);
+///
+/// Variants with `#[cata(skip)]` aren't commands, they are never dispatched to.
+/// Neither is `#[command(external_subcommand)]`, unless it has
+/// `#[cata(external = ...)]` and the arguments go to that function.
+fn enum_impl(
+    header: &TokenStream,
+    attrs: &[syn::Attribute],
+    data: &syn::DataEnum,
+) -> Result<TokenStream, syn::Error> {
+    let all = partition(attrs, data)?;
+    let unnamed = all.unnamed();
+    let Variants {
+        commands: variants,
+        inline,
+        skipped,
+        external,
+    } = all;
+
+    let commands = variants.iter().map(|(ident, ..)| ident).collect::<Vec<_>>();
+    let names = variants
+        .iter()
+        .map(|(_, name, ..)| name)
+        .collect::<Vec<_>>();
+    let flags = variants
+        .iter()
+        .map(|(_, _, flag, _)| flag)
+        .collect::<Vec<_>>();
+    let types = variants
+        .iter()
+        .map(|(.., ty)| dispatch(ty))
+        .collect::<Vec<_>>();
+    let (cmds, cmds_mut): (Vec<_>, Vec<_>) = variants
+        .iter()
+        .map(|(.., ty)| match unbox(ty) {
+            Some(_) => (quote! { &**cmd }, quote! { &mut **cmd }),
+            None => (quote! { cmd }, quote! { cmd }),
+        })
+        .unzip();
 
-    let commands = visitor.commands;
+    let inline_commands = inline.iter().map(|(ident, ..)| *ident).collect::<Vec<_>>();
+    let inline_names = inline.iter().map(|(_, name, _)| name).collect::<Vec<_>>();
+    let inline_flags = inline.iter().map(|(.., flag)| flag).collect::<Vec<_>>();
 
-    quote! {
+    let default_impl = default_impl(attrs, data)?;
+    let subcommand_impl = [subcommand_impl(&inline_commands), default_impl];
+
+    Ok(quote! {
         #[automatically_derived]
-        impl ::cata::command::Container for #name {
+        #header {
             fn next(&self) -> Option<&dyn ::cata::command::Command> {
                 match self {
-                    #(Self::#commands(cmd) => Some(cmd),)*
+                    #(Self::#commands(cmd) => Some(#types::command(#cmds)),)*
+                    #(Self::#external(args) => Some(::cata::command::ExternalArgs::<_, Self>::new(args)),)*
+                    #(Self::#inline_commands { .. } => None,)*
+                    #(Self::#skipped { .. } => None,)*
                 }
             }
+
+            fn next_mut(&mut self) -> Option<&mut dyn ::cata::command::Command> {
+                match self {
+                    #(Self::#commands(cmd) => #types::command_mut(#cmds_mut),)*
+                    #(Self::#external(args) => Some(::cata::command::ExternalArgs::<_, Self>::new_mut(args)),)*
+                    #(Self::#inline_commands { .. } => None,)*
+                    #(Self::#skipped { .. } => None,)*
+                }
+            }
+
+            #(#subcommand_impl)*
+
+            fn path_segment(&self) -> Option<&'static str> {
+                match self {
+                    #(Self::#commands(_) => Some(#names),)*
+                    #(Self::#inline_commands { .. } => Some(#inline_names),)*
+                    #(Self::#unnamed { .. } => None,)*
+                }
+            }
+
+            fn children(&self) -> &'static [&'static str] {
+                &[#(#names,)* #(#inline_names),*]
+            }
+
+            fn flag(&self) -> Option<&'static str> {
+                match self {
+                    #(Self::#commands(_) => #flags,)*
+                    #(Self::#inline_commands { .. } => #inline_flags,)*
+                    #(Self::#unnamed { .. } => None,)*
+                }
+            }
+
+            fn flags() -> Vec<(Vec<&'static str>, &'static str)> {
+                let mut flags = Vec::new();
+
+                #(
+                    if let Some(flag) = #flags {
+                        flags.push((vec![#names], flag));
+                    }
+
+                    for (mut path, flag) in #types::flags() {
+                        path.insert(0, #names);
+                        flags.push((path, flag));
+                    }
+                )*
+
+                #(
+                    if let Some(flag) = #inline_flags {
+                        flags.push((vec![#inline_names], flag));
+                    }
+                )*
+
+                flags
+            }
+
+            fn all_examples() -> Vec<(Vec<&'static str>, &'static [&'static str])> {
+                let mut examples = Vec::new();
+
+                #(
+                    for (mut path, example) in #types::all_examples() {
+                        path.insert(0, #names);
+                        examples.push((path, example));
+                    }
+                )*
+
+                examples
+            }
         }
-    }
+    })
 }
 
 /// Generate implementation of the `Container` trait for either a struct or an
@@ -91,15 +1102,68 @@ fn enum_impl(name: &Ident, data: &syn::DataEnum) -> TokenStream {
 /// correct command in the enum. Instead, a simple implementation in the struct
 /// is generated pointing to `next()` on the enum which ends up doing the heavy
 /// lifting.
+///
+/// Generics and their bounds are carried over to the implementation as they
+/// are.
 pub fn derive(input: syn::DeriveInput) -> Result<TokenStream, syn::Error> {
     let name = &input.ident;
+    let mut generics = input.generics.clone();
 
-    match input.data {
-        syn::Data::Struct(ref data) => Ok(struct_impl(name, data)),
-        syn::Data::Enum(ref data) => Ok(enum_impl(name, data)),
-        syn::Data::Union(_) => Err(syn::Error::new_spanned(
-            input,
-            "Command can only be derived for structs or enums",
-        )),
+    // The default subcommand is kept around once it has been built.
+    if let syn::Data::Enum(data) = &input.data {
+        if default_variant(&input.attrs, data)?.is_some() {
+            let (_, ty_generics, _) = input.generics.split_for_impl();
+            generics
+                .make_where_clause()
+                .predicates
+                .push(syn::parse_quote!(#name #ty_generics: Send + Sync + 'static));
+        }
     }
+
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+    let header = quote! {
+        impl #impl_generics ::cata::command::Container for #name #ty_generics #where_clause
+    };
+
+    let attrs = attrs(&input.attrs)?;
+    let path_impl = path_impl(&input, &attrs);
+
+    let container_impl = match input.data {
+        syn::Data::Struct(ref data) => {
+            let struct_impl = struct_impl(&header, &input, &attrs.examples, data)?;
+            let describe_impl =
+                describe::struct_impl(&input, data, &attrs.examples, attrs.describe)?;
+
+            quote! {
+                #struct_impl
+
+                #describe_impl
+            }
+        }
+        syn::Data::Enum(ref data) => {
+            let external_impl = external_impl(&input, data)?;
+            let enum_impl = enum_impl(&header, &input.attrs, data)?;
+            let describe_impl = describe::enum_impl(&input, data, attrs.describe)?;
+
+            quote! {
+                #enum_impl
+
+                #external_impl
+
+                #describe_impl
+            }
+        }
+        syn::Data::Union(_) => {
+            return Err(syn::Error::new_spanned(
+                input,
+                "Container can only be derived for structs or enums",
+            ))
+        }
+    };
+
+    Ok(quote! {
+        #container_impl
+
+        #path_impl
+    })
 }
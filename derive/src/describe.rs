@@ -0,0 +1,328 @@
+use heck::ToKebabCase;
+use proc_macro2::TokenStream;
+use quote::{quote, quote_spanned};
+use syn::{ext::IdentExt, spanned::Spanned};
+
+use crate::container::{
+    delegate_field, get_field, has_command_key, unbox, variant_attrs, variant_name, variant_type,
+};
+
+/// The first paragraph of the doc comments, which is what clap uses for the
+/// short help. A trailing period is dropped, like clap does.
+fn docs(attrs: &[syn::Attribute]) -> Option<String> {
+    let mut lines = Vec::new();
+
+    for attr in attrs {
+        let syn::Meta::NameValue(syn::MetaNameValue {
+            path,
+            value:
+                syn::Expr::Lit(syn::ExprLit {
+                    lit: syn::Lit::Str(doc),
+                    ..
+                }),
+            ..
+        }) = &attr.meta
+        else {
+            continue;
+        };
+
+        if !path.is_ident("doc") {
+            continue;
+        }
+
+        let doc = doc.value();
+        let line = doc.trim();
+        if line.is_empty() && !lines.is_empty() {
+            break;
+        }
+
+        if !line.is_empty() {
+            lines.push(line.to_string());
+        }
+    }
+
+    let paragraph = lines.join(" ");
+    let paragraph = paragraph.strip_suffix('.').unwrap_or(&paragraph);
+
+    (!paragraph.is_empty()).then(|| paragraph.to_string())
+}
+
+/// The string in `#[<attr>(<key> = "...")]`, any other keys are skipped over.
+pub(crate) fn command_str(
+    attrs: &[syn::Attribute],
+    attr: &str,
+    key: &str,
+) -> syn::Result<Option<String>> {
+    let mut found = None;
+
+    for candidate in attrs {
+        if !candidate.path().is_ident(attr) || !matches!(candidate.meta, syn::Meta::List(_)) {
+            continue;
+        }
+
+        candidate.parse_nested_meta(|meta| {
+            if meta.path.is_ident(key) && meta.input.peek(syn::Token![=]) {
+                if let syn::Expr::Lit(syn::ExprLit {
+                    lit: syn::Lit::Str(value),
+                    ..
+                }) = meta.value()?.parse::<syn::Expr>()?
+                {
+                    found = Some(value.value());
+                }
+
+                return Ok(());
+            }
+
+            while !meta.input.is_empty() && !meta.input.peek(syn::Token![,]) {
+                meta.input.parse::<proc_macro2::TokenTree>()?;
+            }
+
+            Ok(())
+        })?;
+    }
+
+    Ok(found)
+}
+
+/// The short description of a command, an explicit `#[command(about =
+/// "...")]` wins over the doc comments.
+fn about(attrs: &[syn::Attribute]) -> syn::Result<Option<TokenStream>> {
+    let about = command_str(attrs, "command", "about")?.or_else(|| docs(attrs));
+
+    Ok(about.map(|about| quote!(.with_about(#about))))
+}
+
+/// The `#[arg(...)]` attributes that show up in a descriptor.
+#[derive(Default)]
+struct ArgAttrs {
+    /// The name in `long`, which defaults to the field's in kebab-case.
+    long: Option<String>,
+    /// The character in `short`, which defaults to the field's first.
+    short: Option<char>,
+    /// The text in `help = "..."`, if there is any.
+    help: Option<String>,
+    /// Whether the field has `#[arg(skip)]` and isn't an argument.
+    skip: bool,
+}
+
+fn arg_attrs(field: &syn::Field, id: &str) -> syn::Result<ArgAttrs> {
+    let mut parsed = ArgAttrs::default();
+
+    for attr in &field.attrs {
+        if !attr.path().is_ident("arg") || !matches!(attr.meta, syn::Meta::List(_)) {
+            continue;
+        }
+
+        attr.parse_nested_meta(|meta| {
+            let value = if meta.input.peek(syn::Token![=]) {
+                Some(meta.value()?.parse::<syn::Expr>()?)
+            } else {
+                None
+            };
+            let lit = match &value {
+                Some(syn::Expr::Lit(syn::ExprLit { lit, .. })) => Some(lit),
+                _ => None,
+            };
+
+            if meta.path.is_ident("long") {
+                parsed.long = Some(match lit {
+                    Some(syn::Lit::Str(long)) => long.value(),
+                    _ => id.to_kebab_case(),
+                });
+            } else if meta.path.is_ident("short") {
+                parsed.short = match lit {
+                    Some(syn::Lit::Char(short)) => Some(short.value()),
+                    _ => id.chars().next(),
+                };
+            } else if meta.path.is_ident("help") {
+                if let Some(syn::Lit::Str(help)) = lit {
+                    parsed.help = Some(help.value());
+                }
+            } else if meta.path.is_ident("skip") {
+                parsed.skip = true;
+            }
+
+            while !meta.input.is_empty() && !meta.input.peek(syn::Token![,]) {
+                meta.input.parse::<proc_macro2::TokenTree>()?;
+            }
+
+            Ok(())
+        })?;
+    }
+
+    Ok(parsed)
+}
+
+/// The arguments of the fields, leaving out subcommands, flattened groups and
+/// skipped fields.
+fn args<'a>(fields: impl IntoIterator<Item = &'a syn::Field>) -> syn::Result<TokenStream> {
+    let mut args = Vec::new();
+
+    for field in fields {
+        let Some(ident) = &field.ident else {
+            continue;
+        };
+
+        let id = ident.unraw().to_string();
+        let attrs = arg_attrs(field, &id)?;
+        if attrs.skip
+            || has_command_key(&field.attrs, "subcommand")?
+            || has_command_key(&field.attrs, "flatten")?
+        {
+            continue;
+        }
+
+        let long = attrs.long.map(|long| quote!(.with_long(#long)));
+        let short = attrs.short.map(|short| quote!(.with_short(#short)));
+        let help = attrs
+            .help
+            .or_else(|| docs(&field.attrs))
+            .map(|help| quote!(.with_help(#help)));
+
+        args.push(quote! {
+            ::cata::describe::ArgDescriptor::new(#id) #long #short #help
+        });
+    }
+
+    Ok(quote!(&[#(#args),*]))
+}
+
+/// The descriptor of a struct, with the subcommands of its
+/// `#[command(subcommand)]` field, or those of its `#[cata(delegate)]` field.
+///
+/// Without either, it is a leaf and always has one. Otherwise, only when
+/// `describe` is set, which is `#[cata(describe)]`.
+pub fn struct_impl(
+    input: &syn::DeriveInput,
+    data: &syn::DataStruct,
+    examples: &[String],
+    describe: bool,
+) -> syn::Result<Option<TokenStream>> {
+    let below = match (get_field(data)?, delegate_field(data)?) {
+        (Some(field), _) => Some(&field.ty),
+        (None, Some((_, ty))) => Some(ty),
+        (None, None) => None,
+    };
+
+    if below.is_some() && !describe {
+        return Ok(None);
+    }
+
+    let name = command_str(&input.attrs, "command", "name")?
+        .unwrap_or_else(|| input.ident.unraw().to_string().to_kebab_case());
+    let about = about(&input.attrs)?;
+    let args = args(&data.fields)?;
+    let children = below.map(|ty| {
+        quote_spanned! {ty.span()=>
+            .with_children(<#ty as ::cata::describe::Describe>::DESCRIPTOR.children())
+        }
+    });
+    let examples = (!examples.is_empty()).then(|| quote!(.with_examples(&[#(#examples),*])));
+
+    Ok(Some(describe_impl(
+        input,
+        &quote! {
+            ::cata::describe::Descriptor::new(#name)
+                #about
+                .with_args(#args)
+                #children
+                #examples
+        },
+    )))
+}
+
+/// Whether a variant holds a `Box<dyn Command>` or an `Arc<dyn Command>`,
+/// which are only known at runtime.
+fn is_dyn(ty: &syn::Type) -> bool {
+    let syn::Type::Path(path) = ty else {
+        return false;
+    };
+
+    let Some(segment) = path.path.segments.last() else {
+        return false;
+    };
+
+    let syn::PathArguments::AngleBracketed(args) = &segment.arguments else {
+        return false;
+    };
+
+    (segment.ident == "Box" || segment.ident == "Arc")
+        && matches!(
+            args.args.first(),
+            Some(syn::GenericArgument::Type(syn::Type::TraitObject(_)))
+        )
+}
+
+/// The descriptor of an enum, which is only there for its parent to take the
+/// subcommands from. They are the variants, renamed to what clap matches on.
+///
+/// Only generated with `#[cata(describe)]`.
+pub fn enum_impl(
+    input: &syn::DeriveInput,
+    data: &syn::DataEnum,
+    describe: bool,
+) -> syn::Result<Option<TokenStream>> {
+    if !describe {
+        return Ok(None);
+    }
+
+    let mut children = Vec::new();
+
+    for variant in &data.variants {
+        let attrs = variant_attrs(variant)?;
+        if attrs.skip || has_command_key(&variant.attrs, "external_subcommand")? {
+            continue;
+        }
+
+        let name = variant_name(&input.attrs, variant)?;
+        let about = about(&variant.attrs)?;
+        let flag = attrs.flag.map(|flag| quote!(.with_flag(#flag)));
+
+        let child = match variant_type(variant)? {
+            Some(ty) if is_dyn(ty) => quote! {
+                ::cata::describe::Descriptor::new(#name)
+            },
+            Some(ty) => {
+                let ty = unbox(ty).unwrap_or(ty);
+
+                quote_spanned! {ty.span()=>
+                    <#ty as ::cata::describe::Describe>::DESCRIPTOR.with_name(#name)
+                }
+            }
+            None => {
+                let args = args(&variant.fields)?;
+
+                quote! {
+                    ::cata::describe::Descriptor::new(#name).with_args(#args)
+                }
+            }
+        };
+
+        children.push(quote!(#child #about #flag));
+    }
+
+    let name = input.ident.unraw().to_string().to_kebab_case();
+
+    Ok(Some(describe_impl(
+        input,
+        &quote! {
+            ::cata::describe::Descriptor::new(#name).with_children(&[#(#children),*])
+        },
+    )))
+}
+
+/// The `Describe` implementation, which cata drops without its `describe`
+/// feature.
+fn describe_impl(input: &syn::DeriveInput, descriptor: &TokenStream) -> TokenStream {
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    quote! {
+        ::cata::__private::describe! {
+            #[automatically_derived]
+            impl #impl_generics ::cata::describe::Describe for #name #ty_generics #where_clause {
+                const DESCRIPTOR: ::cata::describe::Descriptor = #descriptor;
+            }
+        }
+    }
+}
@@ -1,22 +1,144 @@
 use proc_macro2::TokenStream;
 use quote::quote;
 
-/// Generate implementation of the `ValueParserFactory` trait for structs.
+/// The `Format` variant for `format` in `#[file(format = "...")]`.
+fn format(lit: &syn::LitStr) -> syn::Result<TokenStream> {
+    match lit.value().as_str() {
+        "json" => Ok(quote!(::cata::file::Format::Json)),
+        "yaml" => Ok(quote!(::cata::file::Format::Yaml)),
+        "toml" => Ok(quote!(::cata::file::Format::Toml)),
+        _ => Err(syn::Error::new(
+            lit.span(),
+            "unsupported format, expected `json`, `yaml` or `toml`",
+        )),
+    }
+}
+
+/// The field of a newtype, `Vec<Host>` in `struct Hosts(Vec<Host>)`.
+fn newtype_field(input: &syn::DeriveInput) -> Option<&syn::Type> {
+    let syn::Data::Struct(syn::DataStruct {
+        fields: syn::Fields::Unnamed(fields),
+        ..
+    }) = &input.data
+    else {
+        return None;
+    };
+
+    match fields.unnamed.first() {
+        Some(field) if fields.unnamed.len() == 1 => Some(&field.ty),
+        _ => None,
+    }
+}
+
+/// Convert a newtype from its field and deref to it, which is how the file
+/// gets into it.
+fn newtype_impl(input: &syn::DeriveInput, inner: &syn::Type) -> TokenStream {
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    quote! {
+        #[automatically_derived]
+        impl #impl_generics ::std::convert::From<#inner> for #name #ty_generics #where_clause {
+            fn from(inner: #inner) -> Self {
+                Self(inner)
+            }
+        }
+
+        #[automatically_derived]
+        impl #impl_generics ::std::ops::Deref for #name #ty_generics #where_clause {
+            type Target = #inner;
+
+            fn deref(&self) -> &Self::Target {
+                &self.0
+            }
+        }
+
+        #[automatically_derived]
+        impl #impl_generics ::std::ops::DerefMut for #name #ty_generics #where_clause {
+            fn deref_mut(&mut self) -> &mut Self::Target {
+                &mut self.0
+            }
+        }
+    }
+}
+
+/// Generate implementation of the `ValueParserFactory` trait for structs and
+/// enums.
 ///
-/// This relies on the `TypedValueParser` implementation for [`File`].
+/// This relies on the `TypedValueParser` implementation for [`File`]. Generic
+/// types get the bounds that it needs added to their own. Newtypes are
+/// deserialized as their field instead, see [`newtype_impl`].
 ///
 /// [`File`]: cata::file::File<T>
-pub fn derive(input: &syn::DeriveInput) -> TokenStream {
+pub fn derive(input: &syn::DeriveInput) -> syn::Result<TokenStream> {
     let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    let predicates = where_clause.map(|clause| &clause.predicates);
 
-    quote! {
+    let mut format_impl = None;
+    let mut validate_impl = None;
+    for attr in &input.attrs {
+        if !attr.path().is_ident("file") {
+            continue;
+        }
+
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("format") {
+                let format = format(&meta.value()?.parse()?)?;
+                format_impl = Some(quote!(.with_format(#format)));
+            } else if meta.path.is_ident("validate") {
+                let validate = meta
+                    .value()?
+                    .parse::<syn::LitStr>()?
+                    .parse::<syn::ExprPath>()?;
+                validate_impl = Some(quote! {
+                    .with_validate(|value| {
+                        #validate(value).map_err(::std::convert::Into::into)
+                    })
+                });
+            } else {
+                return Err(meta.error("unsupported file attribute"));
+            }
+
+            Ok(())
+        })?;
+    }
+
+    let inner = newtype_field(input);
+    let (deserialize, parser) = inner.map_or_else(
+        || {
+            (
+                quote!(#name #ty_generics: ::cata::__private::DeserializeOwned,),
+                quote!(::cata::file::File::default()),
+            )
+        },
+        |inner| {
+            (
+                quote!(#inner: ::cata::__private::DeserializeOwned,),
+                quote!(::cata::file::File::newtype::<#inner>()),
+            )
+        },
+    );
+    let newtype_impl = inner.map(|inner| newtype_impl(input, inner));
+
+    Ok(quote! {
         #[automatically_derived]
-        impl ::clap::builder::ValueParserFactory for #name {
-            type Parser = ::cata::file::File<#name>;
+        impl #impl_generics ::clap::builder::ValueParserFactory for #name #ty_generics
+        where
+            #deserialize
+            #name #ty_generics: ::std::clone::Clone
+                + ::std::marker::Send
+                + ::std::marker::Sync
+                + 'static,
+            #predicates
+        {
+            type Parser = ::cata::file::File<#name #ty_generics>;
 
             fn value_parser() -> Self::Parser {
-                ::cata::file::File::default()
+                #parser #format_impl #validate_impl
             }
         }
-    }
+
+        #newtype_impl
+    })
 }
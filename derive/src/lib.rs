@@ -1,23 +1,272 @@
 //! Procedural macros to derive traits for CLI construction.
 //!
+//! - [`Command`] for the boilerplate of commands, with their telemetry
+//!   activity.
 //! - [`Container`] for dispatching enums to subcommands.
 //! - [`File`] for deserializing values from files, implementing clap's
 //!   `value_parser`.
+//! - [`Row`] for the rows of output tables, implementing `Serialize` and
+//!   `Tabled`.
 
+mod command;
 mod container;
+mod describe;
 mod file;
+mod row;
 
 use proc_macro::TokenStream;
+// Only used by the doc tests.
+#[cfg(test)]
+use {
+    async_trait as _, cata as _, clap as _, serde as _, serde_json as _, tabled as _, tokio as _,
+    tracing as _, tracing_subscriber as _,
+};
+
+/// Derive the [`Command`] trait for structs and enums.
+///
+/// Every hook keeps its default, which is all that commands with subcommands
+/// usually need. A few attributes fill in the rest:
+///
+/// - `#[cata(run = path::to::function)]` calls the function, which takes
+///   `&Self` and `&mut cata::Context`, from `run`. Usually this is an inherent
+///   `async fn` of the command.
+/// - `#[cata(activity = "...")]` is the `activity` of the span around `run`,
+///   what [`cata::telemetry`] reports it as. It can also be a constant, such
+///   as the `Self::PATH` from `#[derive(Container)]`.
+/// - `#[cata(telemetry = ...)]` is the same as `activity` when given a name. A
+///   bare `#[cata(telemetry)]` reports the command as its dotted path, such as
+///   `my-cli.deploy`. Commands without either aren't reported at all, and
+///   `#[cata(telemetry = false)]` says so explicitly.
+///
+/// Hooks other than `run` still need a hand written `impl Command`.
+///
+/// # Examples
+///
+/// ```
+/// use cata::{Command, Container};
+/// use clap::Parser;
+///
+/// #[derive(Parser, Container, Command)]
+/// #[cata(activity = "deploy", run = Self::deploy)]
+/// pub struct Deploy {
+///   #[arg(long)]
+///   env: String,
+/// }
+///
+/// impl Deploy {
+///   async fn deploy(&self, _: &mut cata::Context) -> eyre::Result<()> {
+///     Err(eyre::eyre!("unable to deploy to {}", self.env))
+///   }
+/// }
+///
+/// # #[tokio::main]
+/// # async fn main() {
+/// let deploy = Deploy::parse_from(["deploy", "--env", "production"]);
+/// assert_eq!(cata::Command::activity(&deploy), Some("deploy"));
+///
+/// let err = cata::execute(&deploy).await.unwrap_err();
+/// assert_eq!(err.to_string(), "unable to deploy to production");
+/// # }
+/// ```
+///
+/// With a bare `#[cata(telemetry)]`, the activity is the dotted path of the
+/// command, while commands without an attribute aren't reported:
+///
+/// ```
+/// use std::sync::{Arc, Mutex};
+///
+/// use cata::{Command, Container};
+/// use clap::{Parser, Subcommand};
+/// use tracing::field::{Field, Visit};
+/// use tracing_subscriber::{layer::Context, prelude::*, Layer};
+///
+/// #[derive(Parser, Container, Command)]
+/// #[command(name = "my-cli")]
+/// pub struct Root {
+///   #[command(subcommand)]
+///   cmd: RootCmd,
+/// }
+///
+/// #[derive(Subcommand, Container)]
+/// pub enum RootCmd {
+///   Deploy(Deploy),
+/// }
+///
+/// #[derive(Parser, Container, Command)]
+/// #[cata(telemetry)]
+/// pub struct Deploy {}
+///
+/// #[derive(Clone, Default)]
+/// struct Activities(Arc<Mutex<Vec<String>>>);
+///
+/// impl Visit for Activities {
+///   fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+///     if field.name() == "activity" {
+///       self.0.lock().unwrap().push(format!("{value:?}"));
+///     }
+///   }
+/// }
+///
+/// impl<S: tracing::Subscriber> Layer<S> for Activities {
+///   fn on_new_span(
+///     &self,
+///     attrs: &tracing::span::Attributes<'_>,
+///     _: &tracing::span::Id,
+///     _: Context<'_, S>,
+///   ) {
+///     attrs.record(&mut self.clone());
+///   }
+/// }
+///
+/// # #[tokio::main(flavor = "current_thread")]
+/// # async fn main() {
+/// let activities = Activities::default();
+/// let _guard = tracing_subscriber::registry()
+///   .with(activities.clone())
+///   .set_default();
+///
+/// cata::execute(&Root::parse_from(["my-cli", "deploy"])).await.unwrap();
+///
+/// assert_eq!(*activities.0.lock().unwrap(), ["my-cli.deploy"]);
+/// # }
+/// ```
+///
+/// Renaming the activity, or leaving a command out of telemetry explicitly:
+///
+/// ```
+/// use cata::{Command, Container};
+/// use clap::Parser;
+///
+/// #[derive(Parser, Container, Command)]
+/// #[cata(telemetry = "rollout")]
+/// pub struct Deploy {}
+///
+/// #[derive(Parser, Container, Command)]
+/// #[cata(telemetry = false)]
+/// pub struct Completions {}
+///
+/// let deploy = Deploy::parse_from(["deploy"]);
+/// assert_eq!(cata::Command::activity(&deploy), Some("rollout"));
+/// assert!(cata::Command::telemetry(&deploy));
+///
+/// let completions = Completions::parse_from(["completions"]);
+/// assert!(!cata::Command::telemetry(&completions));
+/// ```
+///
+/// Generic commands keep their parameters:
+///
+/// ```
+/// use cata::{Command, Container};
+/// use clap::{Args, Parser};
+///
+/// #[derive(Args)]
+/// pub struct Target {
+///   #[arg(long)]
+///   cluster: String,
+/// }
+///
+/// #[derive(Parser, Container, Command)]
+/// #[cata(telemetry = "rollout")]
+/// pub struct Deploy<T: Args> {
+///   #[command(flatten)]
+///   target: T,
+/// }
+///
+/// let deploy = Deploy::<Target>::parse_from(["deploy", "--cluster", "prod"]);
+/// assert_eq!(deploy.target.cluster, "prod");
+/// assert_eq!(cata::Command::activity(&deploy), Some("rollout"));
+/// ```
+///
+/// [`Command`]: cata::Command
+#[proc_macro_derive(Command, attributes(cata))]
+pub fn derive_command(input: TokenStream) -> TokenStream {
+    command::derive(&syn::parse_macro_input!(input))
+        .unwrap_or_else(|err| err.to_compile_error())
+        .into()
+}
 
 /// Derive the [`Container`] trait for structs and enums.
 ///
-/// Looks for clap's `#[command]` in structs to generate a `next()` that can
-/// dispatch to the next command which was initially parsed. Any structs without
-/// `#[command]` will have a `next()` that returns `None`.
+/// Looks for clap's `#[command(subcommand)]` in structs to generate a `next()`
+/// that can dispatch to the next command which was initially parsed, along
+/// with a `next_mut()` for mutable execution. Any structs without
+/// `#[command(subcommand)]` will have a `next()` that returns `None`. Fields
+/// with `#[command(flatten)]` are groups of arguments, not commands, and are
+/// left alone. Only one subcommand field is supported, it can be an `Option`
+/// for roots that also run on their own.
+///
+/// Enums additionally get `path_segment()` and `children()`, which return the
+/// names clap uses for each variant. Those are kebab-case unless there is a
+/// `#[command(rename_all = "...")]` on the enum or the variant, and
+/// `#[command(name = "...")]` on a variant overrides the name.
+///
+/// `#[cata(global)]` on a field of a struct makes its value available to every
+/// command below it, see [`cata::Context::global`]. The field has to be
+/// `Clone`.
+///
+/// `#[cata(output)]` on a flattened [`cata::output::Output`] provides the
+/// chosen `--output` format to every command below, see
+/// [`cata::Context::output`]. Declaring the flag once on the root is enough,
+/// it is global.
+///
+/// `#[cata(flag = "...")]` on a variant puts the command behind a feature flag,
+/// see [`cata::flags`].
+///
+/// `#[cata(skip)]` on a variant that isn't a command leaves it out. `next()`
+/// returns `None` for it. The same goes for `#[command(external_subcommand)]`,
+/// unless it has `#[cata(external = path::to::function)]`. Its arguments are
+/// then handed to the function, which takes them and `&mut cata::Context`, see
+/// [`cata::command::ExternalHandler`].
+///
+/// `#[cata(default)]` on a variant runs it when the subcommand is an `Option`
+/// that wasn't passed, as if only its name had been. The variant is parsed by
+/// clap, its arguments need defaults or running it returns clap's error, and
+/// the enum has to be `Send + Sync`.
+///
+/// `#[cata(example = "...")]` on a struct adds an example invocation of the
+/// command, see [`cata::examples`]. It can be repeated.
+///
+/// `#[cata(path = "...")]` generates a `PATH` constant and a `command_path()`
+/// that return it, a stable name for the command to use in telemetry, docs
+/// and errors. Paths are joined with spaces, the way they are typed, and
+/// don't include the binary. `ctx.path()` is the dotted version of them,
+/// starting with the name of the root.
+/// With `#[cata(parent = Type)]`, the path is joined onto the `PATH` of
+/// `Type` at compile time, which has to be a type without generics of its own.
+///
+/// `#[cata(delegate)]` on a field of a struct forwards the whole implementation
+/// to that field, for wrapping a command from another crate in a local type to
+/// give it hooks of its own. The field's subcommands, flags and examples are
+/// the struct's, its own hooks are only run when the struct's `Command` calls
+/// them.
+///
+/// Structs without subcommands also get a `const` [`cata::describe::Descriptor`]
+/// of their name, help and arguments. `#[cata(describe)]` generates one for
+/// structs and enums with subcommands too, which every command below them then
+/// needs, see [`cata::describe`].
+///
+/// Variants can hold a `Box<dyn Command>` or an `Arc<dyn Command>` for
+/// subcommands that are built at runtime, see [`cata::command::Dispatch`].
+/// Large commands can be boxed, `Child(Box<Child>)` dispatches to the `Child`.
+///
+/// Variants with named fields, or without any, don't hold a command of their
+/// own. The enum is the command for them and has to implement
+/// [`cata::Command`], its `run` can match on the variant. Unit variants that
+/// the parent handles instead, such as a `Version` it checks for in its own
+/// `run`, can be marked with `#[cata(skip)]`. The enum then doesn't need to
+/// implement [`cata::Command`] and the parent is the last command run.
 ///
 /// For commands with subcommands, the enum must also have
 /// `#[derive(Container)]`.
 ///
+/// Generic commands work too, their generics and bounds are carried over to
+/// the implementation.
+///
+/// Mistakes are reported at the field or variant they are about. A variant
+/// holding a type that isn't a [`cata::Command`], or a subcommand field whose
+/// type doesn't derive `Container`, fails to compile with a note on how to fix
+/// it.
+///
 /// # Examples
 ///
 /// ```
@@ -26,13 +275,529 @@ use proc_macro::TokenStream;
 ///
 /// #[derive(Parser, Container)]
 /// pub struct Root {
+///   #[command(flatten)]
+///   pub yes: cata::command::Yes,
+///
+///   #[command(subcommand)]
+///   pub cmd: RootCmd,
+/// }
+///
+/// impl cata::Command for Root {}
+///
+/// #[derive(Subcommand, Container)]
+/// pub enum RootCmd {
+///   Child(Child),
+///   Large(Box<Child>),
+/// }
+///
+/// #[derive(Parser, Container)]
+/// pub struct Child {}
+///
+/// impl cata::Command for Child {}
+///
+/// let root = Root::parse_from(["root", "large"]);
+/// assert_eq!(cata::command::path(&root), ["large"]);
+/// ```
+///
+/// Commands declared right on the variants:
+///
+/// ```
+/// use cata::{Command, Container};
+/// use clap::{Parser, Subcommand};
+///
+/// #[derive(Parser, Container)]
+/// pub struct Root {
+///   #[command(subcommand)]
+///   pub cmd: RootCmd,
+/// }
+///
+/// impl Command for Root {}
+///
+/// #[derive(Subcommand, Container)]
+/// pub enum RootCmd {
+///   Greet {
+///     #[arg(long)]
+///     name: String,
+///   },
+///   Version,
+/// }
+///
+/// #[async_trait::async_trait]
+/// impl Command for RootCmd {
+///   async fn run(&self, _: &mut cata::Context) -> eyre::Result<()> {
+///     match self {
+///       Self::Greet { name } => Err(eyre::eyre!("hello {name}")),
+///       Self::Version => Ok(()),
+///     }
+///   }
+/// }
+///
+/// # #[tokio::main]
+/// # async fn main() {
+/// let root = Root::parse_from(["root", "greet", "--name", "world"]);
+/// let err = cata::execute(&root).await.unwrap_err();
+///
+/// assert_eq!(err.to_string(), "hello world");
+/// # }
+/// ```
+///
+/// Reaching the subcommand mutably, for [`cata::execute_mut`]:
+///
+/// ```
+/// use cata::{command::Container as _, Command, Container};
+/// use clap::{Parser, Subcommand};
+///
+/// #[derive(Parser, Container)]
+/// pub struct Root {
+///   #[command(subcommand)]
+///   pub cmd: RootCmd,
+/// }
+///
+/// impl Command for Root {}
+///
+/// #[derive(Subcommand, Container)]
+/// pub enum RootCmd {
+///   Counter(Box<Counter>),
+/// }
+///
+/// #[derive(Parser, Container)]
+/// pub struct Counter {
+///   #[arg(skip)]
+///   pub runs: usize,
+/// }
+///
+/// #[async_trait::async_trait]
+/// impl Command for Counter {
+///   async fn run_mut(&mut self, _: &mut cata::Context) -> eyre::Result<()> {
+///     self.runs += 1;
+///
+///     Ok(())
+///   }
+/// }
+///
+/// # #[tokio::main]
+/// # async fn main() -> eyre::Result<()> {
+/// let mut root = Root::parse_from(["root", "counter"]);
+/// assert!(root.next_mut().is_some());
+///
+/// cata::execute_mut(&mut root).await?;
+///
+/// let RootCmd::Counter(counter) = &root.cmd;
+/// assert_eq!(counter.runs, 1);
+/// # Ok(())
+/// # }
+/// ```
+///
+/// Paths known at compile time:
+///
+/// ```
+/// use cata::{Command, Container};
+/// use clap::{Parser, Subcommand};
+///
+/// #[derive(Parser, Container)]
+/// #[cata(path = "cluster")]
+/// pub struct Cluster {
+///   #[command(subcommand)]
+///   pub cmd: ClusterCmd,
+/// }
+///
+/// impl Command for Cluster {}
+///
+/// #[derive(Subcommand, Container)]
+/// pub enum ClusterCmd {
+///   Create(Create),
+/// }
+///
+/// #[derive(Parser, Container, Command)]
+/// #[cata(path = "create", parent = Cluster, activity = Self::PATH)]
+/// pub struct Create {}
+///
+/// assert_eq!(Create::PATH, "cluster create");
+/// assert_eq!(Create::command_path(), "cluster create");
+///
+/// let create = Create::parse_from(["create"]);
+/// assert_eq!(cata::Command::activity(&create), Some("cluster create"));
+/// ```
+///
+/// Unit variants that the parent handles:
+///
+/// ```
+/// use cata::{Command, Container};
+/// use clap::{Parser, Subcommand};
+///
+/// #[derive(Parser, Container)]
+/// pub struct Root {
+///   #[command(subcommand)]
+///   pub cmd: RootCmd,
+/// }
+///
+/// #[async_trait::async_trait]
+/// impl Command for Root {
+///   async fn run(&self, _: &mut cata::Context) -> eyre::Result<()> {
+///     if matches!(self.cmd, RootCmd::Version) {
+///       return Err(eyre::eyre!("v1.0.0"));
+///     }
+///
+///     Ok(())
+///   }
+/// }
+///
+/// #[derive(Subcommand, Container)]
+/// pub enum RootCmd {
+///   #[cata(skip)]
+///   Version,
+///   Child(Child),
+/// }
+///
+/// #[derive(Parser, Container)]
+/// pub struct Child {}
+///
+/// impl Command for Child {}
+///
+/// # #[tokio::main]
+/// # async fn main() {
+/// let err = cata::execute(&Root::parse_from(["root", "version"]))
+///   .await
+///   .unwrap_err();
+///
+/// assert_eq!(err.to_string(), "v1.0.0");
+/// # }
+/// ```
+///
+/// Generic commands:
+///
+/// ```
+/// use std::marker::PhantomData;
+///
+/// use cata::{Command, Container};
+/// use clap::{Parser, Subcommand};
+///
+/// pub trait Resource: Send + Sync + 'static {
+///   const KIND: &'static str;
+/// }
+///
+/// pub struct Deployment;
+///
+/// impl Resource for Deployment {
+///   const KIND: &'static str = "deployment";
+/// }
+///
+/// #[derive(Parser, Container)]
+/// pub struct Root<T: Resource> {
+///   #[command(subcommand)]
+///   pub cmd: RootCmd<T>,
+/// }
+///
+/// impl<T: Resource> Command for Root<T> {}
+///
+/// #[derive(Subcommand, Container)]
+/// pub enum RootCmd<T: Resource> {
+///   Apply(Apply<T>),
+/// }
+///
+/// #[derive(Parser, Container)]
+/// pub struct Apply<T>
+/// where
+///   T: Resource,
+/// {
+///   #[arg(skip)]
+///   resource: PhantomData<T>,
+/// }
+///
+/// #[async_trait::async_trait]
+/// impl<T: Resource> Command for Apply<T> {
+///   async fn run(&self, _: &mut cata::Context) -> eyre::Result<()> {
+///     Err(eyre::eyre!("applied a {}", T::KIND))
+///   }
+/// }
+///
+/// # #[tokio::main]
+/// # async fn main() {
+/// let root = Root::<Deployment>::parse_from(["root", "apply"]);
+/// let err = cata::execute(&root).await.unwrap_err();
+///
+/// assert_eq!(err.to_string(), "applied a deployment");
+/// # }
+/// ```
+///
+/// Leaving out a variant that passes anything else on to another binary:
+///
+/// ```
+/// use cata::{command::Container as _, Container};
+/// use clap::{Parser, Subcommand};
+///
+/// #[derive(Parser, Container)]
+/// pub struct Root {
+///   #[command(subcommand)]
+///   pub cmd: RootCmd,
+/// }
+///
+/// #[derive(Subcommand, Container)]
+/// pub enum RootCmd {
+///   Child(Child),
+///   #[command(external_subcommand)]
+///   External(Vec<String>),
+/// }
+///
+/// #[derive(Parser, Container)]
+/// pub struct Child {}
+///
+/// impl cata::Command for Child {}
+///
+/// let root = Root::parse_from(["root", "other", "--flag"]);
+/// assert!(root.next().is_none());
+/// assert_eq!(root.cmd.children(), ["child"]);
+/// ```
+///
+/// Naming the variants the way clap does:
+///
+/// ```
+/// use cata::{command::Container as _, Container};
+/// use clap::{Parser, Subcommand};
+///
+/// #[derive(Parser, Container)]
+/// pub struct Root {
+///   #[command(subcommand)]
+///   pub cmd: RootCmd,
+/// }
+///
+/// #[derive(Subcommand, Container)]
+/// #[command(rename_all = "snake_case")]
+/// pub enum RootCmd {
+///   ApplyAll(Child),
+///   #[command(rename_all = "UPPER")]
+///   DryRun(Child),
+///   #[command(name = "rm")]
+///   Remove(Child),
+/// }
+///
+/// #[derive(Parser, Container)]
+/// pub struct Child {}
+///
+/// impl cata::Command for Child {}
+///
+/// let root = Root::parse_from(["root", "apply_all"]);
+/// assert_eq!(root.cmd.path_segment(), Some("apply_all"));
+/// assert_eq!(root.cmd.children(), ["apply_all", "DRYRUN", "rm"]);
+///
+/// let root = Root::parse_from(["root", "DRYRUN"]);
+/// assert_eq!(root.cmd.path_segment(), Some("DRYRUN"));
+/// ```
+///
+/// Handing those arguments to a function instead, such as
+/// [`cata::plugin::Plugins::run`]:
+///
+/// ```
+/// use cata::{command::Container as _, Command, Container};
+/// use clap::{Parser, Subcommand};
+///
+/// #[derive(Parser, Container)]
+/// pub struct Root {
+///   #[command(subcommand)]
+///   pub cmd: RootCmd,
+/// }
+///
+/// impl Command for Root {}
+///
+/// #[derive(Subcommand, Container)]
+/// pub enum RootCmd {
+///   #[command(external_subcommand)]
+///   #[cata(external = Self::external)]
+///   External(Vec<String>),
+/// }
+///
+/// impl RootCmd {
+///   async fn external(args: &[String], _: &mut cata::Context) -> eyre::Result<()> {
+///     Err(eyre::eyre!("would run my-cli-{}", args.join(" ")))
+///   }
+/// }
+///
+/// # #[tokio::main]
+/// # async fn main() {
+/// let root = Root::parse_from(["root", "other", "--flag"]);
+/// assert!(root.next().is_some());
+///
+/// let err = cata::execute(&root).await.unwrap_err();
+/// assert_eq!(err.to_string(), "would run my-cli-other --flag");
+/// # }
+/// ```
+///
+/// Running the most common subcommand when none is passed:
+///
+/// ```
+/// use cata::{Command, Container};
+/// use clap::{Parser, Subcommand};
+///
+/// #[derive(Parser, Container)]
+/// pub struct Root {
+///   #[command(subcommand)]
+///   pub cmd: Option<RootCmd>,
+/// }
+///
+/// impl Command for Root {}
+///
+/// #[derive(Subcommand, Container)]
+/// pub enum RootCmd {
+///   #[cata(default)]
+///   Status(Status),
+///   Stop(Status),
+/// }
+///
+/// #[derive(Parser, Container)]
+/// pub struct Status {
+///   #[arg(long, default_value = "short")]
+///   format: String,
+/// }
+///
+/// #[async_trait::async_trait]
+/// impl Command for Status {
+///   async fn run(&self, _: &mut cata::Context) -> eyre::Result<()> {
+///     Err(eyre::eyre!("{} status", self.format))
+///   }
+/// }
+///
+/// # #[tokio::main]
+/// # async fn main() {
+/// let mut root = Root::parse_from(["root"]);
+/// assert_eq!(cata::command::path(&root), ["status"]);
+///
+/// let err = cata::execute(&root).await.unwrap_err();
+/// assert_eq!(err.to_string(), "short status");
+///
+/// cata::execute_mut(&mut root).await.unwrap_err();
+/// assert!(matches!(root.cmd, Some(RootCmd::Status(_))));
+/// # }
+/// ```
+///
+/// A default that needs arguments of its own fails the same way clap would
+/// have, had it been passed without them:
+///
+/// ```
+/// use cata::{Command, Container};
+/// use clap::{Parser, Subcommand};
+///
+/// #[derive(Parser, Container)]
+/// pub struct Root {
+///   #[command(subcommand)]
+///   pub cmd: Option<RootCmd>,
+/// }
+///
+/// impl Command for Root {}
+///
+/// #[derive(Subcommand, Container)]
+/// pub enum RootCmd {
+///   #[cata(default)]
+///   Logs(Logs),
+/// }
+///
+/// #[derive(Parser, Container)]
+/// pub struct Logs {
+///   pod: String,
+/// }
+///
+/// impl Command for Logs {}
+///
+/// # #[tokio::main]
+/// # async fn main() {
+/// let mut root = Root::parse_from(["root"]);
+///
+/// for err in [
+///   cata::execute(&root).await.unwrap_err(),
+///   cata::execute_mut(&mut root).await.unwrap_err(),
+/// ] {
+///   let err = err.downcast_ref::<clap::Error>().unwrap();
+///   assert_eq!(err.kind(), clap::error::ErrorKind::MissingRequiredArgument);
+/// }
+/// # }
+/// ```
+///
+/// Wrapping a command from another crate:
+///
+/// ```
+/// use cata::{Command, Container};
+/// use clap::{Args, Parser, Subcommand};
+///
+/// // Say this comes from another crate.
+/// mod vendor {
+///   use cata::{Command, Container};
+///   use clap::{Args, Subcommand};
+///
+///   #[derive(Args, Container)]
+///   pub struct Db {
+///     #[command(subcommand)]
+///     pub cmd: DbCmd,
+///   }
+///
+///   impl Command for Db {}
+///
+///   #[derive(Subcommand, Container)]
+///   pub enum DbCmd {
+///     Migrate(Migrate),
+///   }
+///
+///   #[derive(Args, Container)]
+///   pub struct Migrate {}
+///
+///   #[async_trait::async_trait]
+///   impl Command for Migrate {
+///     async fn run(&self, ctx: &mut cata::Context) -> eyre::Result<()> {
+///       Err(eyre::eyre!("migrating {}", ctx.get::<String>().unwrap()))
+///     }
+///   }
+/// }
+///
+/// #[derive(Parser, Container)]
+/// pub struct Root {
 ///   #[command(subcommand)]
 ///   pub cmd: RootCmd,
 /// }
 ///
+/// impl Command for Root {}
+///
 /// #[derive(Subcommand, Container)]
 /// pub enum RootCmd {
-///   Child(Child)
+///   Db(Database),
+/// }
+///
+/// #[derive(Args, Container)]
+/// pub struct Database {
+///   #[arg(long, default_value = "postgres://localhost")]
+///   url: String,
+///
+///   #[command(flatten)]
+///   #[cata(delegate)]
+///   inner: vendor::Db,
+/// }
+///
+/// #[async_trait::async_trait]
+/// impl Command for Database {
+///   async fn pre_run(&self, ctx: &mut cata::Context) -> eyre::Result<cata::command::PreRun> {
+///     ctx.provide(self.url.clone());
+///
+///     self.inner.pre_run(ctx).await
+///   }
+/// }
+///
+/// # #[tokio::main]
+/// # async fn main() {
+/// let root = Root::parse_from(["root", "db", "migrate"]);
+/// assert_eq!(cata::command::path(&root), ["db", "migrate"]);
+///
+/// let err = cata::execute(&root).await.unwrap_err();
+/// assert_eq!(err.to_string(), "migrating postgres://localhost");
+/// # }
+/// ```
+///
+/// Forgetting to implement [`cata::Command`] for a variant's type:
+///
+/// ```compile_fail
+/// use cata::Container;
+/// use clap::{Parser, Subcommand};
+///
+/// #[derive(Subcommand, Container)]
+/// pub enum RootCmd {
+///   // error: `Child` is not a command that can be dispatched to
+///   Child(Child),
 /// }
 ///
 /// #[derive(Parser, Container)]
@@ -40,7 +805,7 @@ use proc_macro::TokenStream;
 /// ```
 ///
 /// [`Container`]: cata::command::Container
-#[proc_macro_derive(Container)]
+#[proc_macro_derive(Container, attributes(cata))]
 pub fn derive_container(input: TokenStream) -> TokenStream {
     container::derive(syn::parse_macro_input!(input))
         .unwrap_or_else(|err| err.to_compile_error())
@@ -51,14 +816,20 @@ pub fn derive_container(input: TokenStream) -> TokenStream {
 ///
 /// Implements the [`ValueParserFactory`] trait for struct which uses the
 /// [`File<T>`] value parser to deserialize a path passed in as an argument into
-/// the provided struct.
+/// the provided struct. Enums and generic types work too, generic ones are
+/// only parsed when they can be deserialized.
+///
+/// Tuple structs with a single field, such as `struct Hosts(Vec<Host>)`, are
+/// newtypes. The file is deserialized as the field, which is all that needs
+/// `Deserialize`, and the newtype gets `From` and `Deref` implementations for
+/// it.
 ///
 /// # Examples
 ///
 /// ```
 /// use cata::File;
 ///
-/// #[derive(Clone, Debug, Deserialize)]
+/// #[derive(Clone, Debug, serde::Deserialize, File)]
 /// struct Thing {
 ///   single: String,
 /// }
@@ -69,9 +840,179 @@ pub fn derive_container(input: TokenStream) -> TokenStream {
 /// }
 /// ```
 ///
+/// Tagged enums and generic wrappers:
+///
+/// ```
+/// use cata::File;
+/// use serde::Deserialize;
+///
+/// #[derive(Clone, Debug, Deserialize, File)]
+/// #[serde(tag = "kind", rename_all = "lowercase")]
+/// enum Source {
+///   Git { url: String },
+///   Local { path: String },
+/// }
+///
+/// #[derive(Clone, Debug, Deserialize, File)]
+/// struct Manifest<T> {
+///   name: String,
+///   spec: T,
+/// }
+///
+/// #[derive(clap::Parser)]
+/// struct Cmd {
+///   manifest: Manifest<Source>,
+/// }
+///
+/// let path = std::env::temp_dir().join("cata-derive-file-generic.json");
+/// std::fs::write(
+///   &path,
+///   r#"{"name": "web", "spec": {"kind": "git", "url": "https://example.com"}}"#,
+/// )
+/// .unwrap();
+///
+/// let cmd = <Cmd as clap::Parser>::parse_from([std::ffi::OsStr::new("cmd"), path.as_os_str()]);
+/// assert_eq!(cmd.manifest.name, "web");
+/// assert!(matches!(cmd.manifest.spec, Source::Git { .. }));
+/// ```
+///
+/// The format is detected from the extension, `#[file(format = "...")]` fixes
+/// it to one of `json`, `yaml` or `toml` instead:
+///
+/// ```
+/// use cata::File;
+///
+/// #[derive(Clone, Debug, serde::Deserialize, File)]
+/// #[file(format = "toml")]
+/// struct Thing {
+///   single: String,
+///   nested: Nested,
+/// }
+///
+/// #[derive(Clone, Debug, serde::Deserialize)]
+/// struct Nested {
+///   count: u32,
+/// }
+///
+/// #[derive(clap::Parser)]
+/// struct Cmd {
+///   input: Thing,
+/// }
+///
+/// // No extension to go by.
+/// let path = std::env::temp_dir().join("cata-derive-file-toml");
+/// std::fs::write(&path, "single = \"one\"\n\n[nested]\ncount = 2\n").unwrap();
+///
+/// let cmd = <Cmd as clap::Parser>::parse_from([std::ffi::OsStr::new("cmd"), path.as_os_str()]);
+/// assert_eq!(cmd.input.single, "one");
+/// assert_eq!(cmd.input.nested.count, 2);
+/// ```
+///
+/// `#[file(validate = "...")]` names a function that checks the value once it
+/// has been deserialized. Its error is reported by clap, along with the path:
+///
+/// ```
+/// use cata::File;
+/// use clap::error::ErrorKind;
+///
+/// #[derive(Clone, Debug, serde::Deserialize, File)]
+/// #[file(validate = "Deployment::check")]
+/// struct Deployment {
+///   replicas: u32,
+/// }
+///
+/// impl Deployment {
+///   fn check(&self) -> eyre::Result<()> {
+///     if self.replicas == 0 {
+///       eyre::bail!("replicas must be at least 1");
+///     }
+///
+///     Ok(())
+///   }
+/// }
+///
+/// #[derive(Debug, clap::Parser)]
+/// struct Cmd {
+///   deployment: Deployment,
+/// }
+///
+/// let path = std::env::temp_dir().join("cata-derive-file-validate.json");
+/// std::fs::write(&path, r#"{"replicas": 0}"#).unwrap();
+///
+/// let err = <Cmd as clap::Parser>::try_parse_from([std::ffi::OsStr::new("cmd"), path.as_os_str()])
+///   .unwrap_err();
+/// assert_eq!(err.kind(), ErrorKind::InvalidValue);
+/// assert!(err.to_string().contains("replicas must be at least 1"));
+/// assert!(err.to_string().contains("cata-derive-file-validate.json"));
+/// ```
+///
 /// [`Container`]: cata::command::Container
 /// [`ValueParserFactory`]: clap::builder::ValueParserFactory
-#[proc_macro_derive(File)]
+#[proc_macro_derive(File, attributes(file))]
 pub fn derive_file(input: TokenStream) -> TokenStream {
-    file::derive(&syn::parse_macro_input!(input)).into()
+    file::derive(&syn::parse_macro_input!(input))
+        .unwrap_or_else(|err| err.to_compile_error())
+        .into()
+}
+
+/// Derive `Serialize` and `Tabled` for the rows of [`cata::output`] from one
+/// set of attributes.
+///
+/// Every field is serialized under its own name. The table has a column for
+/// each of them, titled with the field's name, and the cell is the field's
+/// `Display`. Fields can change that with `#[row(...)]`:
+///
+/// - `title = "..."` is the column's header in pretty output.
+/// - `order = N` puts the column at position `N`, starting at 0. The other
+///   columns keep the order they were declared in.
+/// - `display_with = path::to::function` formats the cell, the function takes
+///   a reference to the field and returns a `String`.
+/// - `skip_pretty` leaves the field out of the table, it is still serialized.
+/// - `skip` leaves the field out everywhere.
+///
+/// Only structs with named fields are supported.
+///
+/// # Examples
+///
+/// ```
+/// use cata::{output::tabled::display, Row};
+/// use tabled::Tabled;
+///
+/// #[derive(Row)]
+/// struct Pod {
+///   #[row(title = "NAME")]
+///   name: String,
+///   #[row(order = 0, title = "NAMESPACE")]
+///   namespace: String,
+///   #[row(display_with = display)]
+///   containers: Vec<String>,
+///   #[row(skip_pretty)]
+///   uid: String,
+///   #[row(skip)]
+///   #[allow(dead_code)]
+///   cached: bool,
+/// }
+///
+/// let pod = Pod {
+///   name: "web".into(),
+///   namespace: "default".into(),
+///   containers: vec!["nginx".into(), "envoy".into()],
+///   uid: "1234".into(),
+///   cached: true,
+/// };
+///
+/// assert_eq!(Pod::headers(), ["NAMESPACE", "NAME", "containers"]);
+/// assert_eq!(pod.fields(), ["default", "web", "envoy\nnginx"]);
+/// assert_eq!(
+///   serde_json::to_string(&pod).unwrap(),
+///   r#"{"name":"web","namespace":"default","containers":["nginx","envoy"],"uid":"1234"}"#,
+/// );
+///
+/// cata::output::Format::Pretty.item(&pod).unwrap();
+/// ```
+#[proc_macro_derive(Row, attributes(row))]
+pub fn derive_row(input: TokenStream) -> TokenStream {
+    row::derive(&syn::parse_macro_input!(input))
+        .unwrap_or_else(|err| err.to_compile_error())
+        .into()
 }
@@ -0,0 +1,186 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+use syn::ext::IdentExt;
+
+/// The `#[row(...)]` attributes of a field.
+#[derive(Default)]
+struct Attrs {
+    /// The header in `#[row(title = "...")]`, if there is one.
+    title: Option<String>,
+    /// The column in `#[row(order = N)]`, if there is one.
+    order: Option<syn::LitInt>,
+    /// The function in `#[row(display_with = ...)]` that formats the cell, if
+    /// there is one.
+    display_with: Option<syn::ExprPath>,
+    /// Whether the field has `#[row(skip_pretty)]`, it is only serialized.
+    skip_pretty: bool,
+    /// Whether the field has `#[row(skip)]`, it is left out everywhere.
+    skip: bool,
+}
+
+fn attrs(field: &syn::Field) -> syn::Result<Attrs> {
+    let mut parsed = Attrs::default();
+
+    for attr in &field.attrs {
+        if !attr.path().is_ident("row") {
+            continue;
+        }
+
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("title") {
+                parsed.title = Some(meta.value()?.parse::<syn::LitStr>()?.value());
+            } else if meta.path.is_ident("order") {
+                parsed.order = Some(meta.value()?.parse()?);
+            } else if meta.path.is_ident("display_with") {
+                parsed.display_with = Some(meta.value()?.parse()?);
+            } else if meta.path.is_ident("skip_pretty") {
+                parsed.skip_pretty = true;
+            } else if meta.path.is_ident("skip") {
+                parsed.skip = true;
+            } else {
+                return Err(meta.error("unsupported row attribute"));
+            }
+
+            Ok(())
+        })?;
+    }
+
+    Ok(parsed)
+}
+
+/// A column of the table, with its header and cell.
+struct Column {
+    order: Option<(usize, syn::LitInt)>,
+    header: String,
+    cell: TokenStream,
+}
+
+/// Put columns with an `order` at that position, the others fill the gaps in
+/// the order they were declared in.
+fn arrange(columns: Vec<Column>) -> syn::Result<Vec<Column>> {
+    let mut slots: Vec<Option<Column>> = columns.iter().map(|_| None).collect();
+    let mut rest = Vec::new();
+
+    for column in columns {
+        let Some((position, lit)) = &column.order else {
+            rest.push(column);
+            continue;
+        };
+
+        let Some(slot) = slots.get_mut(*position) else {
+            return Err(syn::Error::new_spanned(
+                lit,
+                format!("there are only {} columns", slots.len()),
+            ));
+        };
+
+        if slot.is_some() {
+            return Err(syn::Error::new_spanned(
+                lit,
+                "another column already has this order",
+            ));
+        }
+
+        *slot = Some(column);
+    }
+
+    let mut rest = rest.into_iter();
+
+    Ok(slots
+        .into_iter()
+        .filter_map(|slot| slot.or_else(|| rest.next()))
+        .collect())
+}
+
+/// Generate `Serialize` and `Tabled` implementations for a struct with named
+/// fields, from one set of `#[row(...)]` attributes.
+pub fn derive(input: &syn::DeriveInput) -> syn::Result<TokenStream> {
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    let syn::Data::Struct(syn::DataStruct {
+        fields: syn::Fields::Named(fields),
+        ..
+    }) = &input.data
+    else {
+        return Err(syn::Error::new_spanned(
+            input,
+            "Row can only be derived for structs with named fields",
+        ));
+    };
+
+    let mut serialized = Vec::new();
+    let mut columns = Vec::new();
+
+    for field in &fields.named {
+        let attrs = attrs(field)?;
+        if attrs.skip {
+            continue;
+        }
+
+        let Some(ident) = &field.ident else {
+            continue;
+        };
+        let key = ident.unraw().to_string();
+        serialized.push((key.clone(), ident));
+
+        if attrs.skip_pretty {
+            continue;
+        }
+
+        let order = attrs
+            .order
+            .map(|lit| Ok::<_, syn::Error>((lit.base10_parse()?, lit)))
+            .transpose()?;
+        let cell = attrs.display_with.map_or_else(
+            || quote! { ::std::string::ToString::to_string(&self.#ident) },
+            |display_with| quote! { #display_with(&self.#ident) },
+        );
+
+        columns.push(Column {
+            order,
+            header: attrs.title.unwrap_or(key),
+            cell,
+        });
+    }
+
+    let columns = arrange(columns)?;
+    let length = columns.len();
+    let headers = columns.iter().map(|column| &column.header);
+    let cells = columns.iter().map(|column| &column.cell);
+
+    let count = serialized.len();
+    let keys = serialized.iter().map(|(key, _)| key);
+    let idents = serialized.iter().map(|(_, ident)| ident);
+    let struct_name = name.unraw().to_string();
+
+    Ok(quote! {
+        #[automatically_derived]
+        impl #impl_generics ::cata::__private::serde::Serialize for #name #ty_generics #where_clause {
+            fn serialize<S>(&self, serializer: S) -> ::std::result::Result<S::Ok, S::Error>
+            where
+                S: ::cata::__private::serde::Serializer,
+            {
+                use ::cata::__private::serde::ser::SerializeStruct as _;
+
+                let mut state = serializer.serialize_struct(#struct_name, #count)?;
+                #(state.serialize_field(#keys, &self.#idents)?;)*
+
+                state.end()
+            }
+        }
+
+        #[automatically_derived]
+        impl #impl_generics ::cata::__private::tabled::Tabled for #name #ty_generics #where_clause {
+            const LENGTH: usize = #length;
+
+            fn fields(&self) -> ::std::vec::Vec<::std::borrow::Cow<'_, str>> {
+                ::std::vec![#(::std::borrow::Cow::Owned(#cells)),*]
+            }
+
+            fn headers() -> ::std::vec::Vec<::std::borrow::Cow<'static, str>> {
+                ::std::vec![#(::std::borrow::Cow::Borrowed(#headers)),*]
+            }
+        }
+    })
+}
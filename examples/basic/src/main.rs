@@ -25,7 +25,7 @@ struct Child {}
 
 #[async_trait::async_trait]
 impl Command for Child {
-    async fn run(&self) -> Result<()> {
+    async fn run(&self, _: &mut cata::Context) -> Result<()> {
         println!("Hello");
 
         Ok(())
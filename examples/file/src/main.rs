@@ -9,6 +9,7 @@ async fn main() -> Result<()> {
 }
 
 #[derive(Clone, Debug, Deserialize, File)]
+#[allow(dead_code)]
 struct Thing {
     single: String,
 }
@@ -20,7 +21,7 @@ struct Root {
 
 #[async_trait::async_trait]
 impl Command for Root {
-    async fn run(&self) -> Result<()> {
+    async fn run(&self, _: &mut cata::Context) -> Result<()> {
         println!("input: {:#?}", self.input);
 
         Ok(())
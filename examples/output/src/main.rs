@@ -29,7 +29,7 @@ struct Root {
 
 #[async_trait::async_trait]
 impl Command for Root {
-    async fn run(&self) -> Result<()> {
+    async fn run(&self, _: &mut cata::Context) -> Result<()> {
         let things = &[
             Thing {
                 single: "single".into(),
@@ -0,0 +1,410 @@
+//! Shortcuts that users define for the commands they type the most.
+//!
+//! Users of git and kubectl expect to be able to type `my-cli ls` instead of
+//! `my-cli projects list --all`. [`Aliases`] reads those shortcuts from
+//! `aliases.toml` in the platform config directory, see [`crate::dirs`], and
+//! expands them before clap ever sees the arguments.
+//!
+//! ```toml
+//! ls = "projects list --all"
+//! prod = ["deploy", "--env", "production"]
+//! ```
+//!
+//! Some things to note:
+//! - Only the first argument after the binary is expanded, anything after it
+//!   is appended to the expansion.
+//! - Aliases can expand to other aliases. An alias that refers back to itself
+//!   stops expanding instead of looping.
+//! - Aliases never shadow a real subcommand, the subcommand wins.
+//! - Strings are split like a shell would, use an array for arguments that
+//!   would need quoting.
+//! - [`Aliases::augment`] lists the aliases at the end of the root's help.
+//!   [`crate::App::with_aliases`] expands and lists them for you.
+//!
+//! # Examples
+//!
+//! ```
+//! use cata::{aliases::Aliases, Command, Container};
+//! use clap::{CommandFactory, Parser, Subcommand};
+//!
+//! #[derive(Parser, Container)]
+//! struct Root {
+//!   #[command(subcommand)]
+//!   cmd: RootCmd,
+//! }
+//!
+//! impl Command for Root {}
+//!
+//! #[derive(Subcommand, Container)]
+//! enum RootCmd {
+//!   List(List),
+//! }
+//!
+//! #[derive(Parser, Container)]
+//! struct List {
+//!   #[arg(long)]
+//!   all: bool,
+//! }
+//!
+//! impl Command for List {}
+//!
+//! # let dir = std::env::temp_dir().join("cata-aliases-example");
+//! # std::fs::create_dir_all(&dir).unwrap();
+//! # let path = dir.join("aliases.toml");
+//! # std::fs::write(&path, "ls = \"list --all\"\n").unwrap();
+//! let aliases = Aliases::new("my-cli")
+//! #   .with_path(&path)
+//!   .load()
+//!   .unwrap();
+//!
+//! let args = aliases.expand(&Root::command(), ["my-cli", "ls"]);
+//! assert_eq!(args, ["my-cli", "list", "--all"]);
+//!
+//! let root = Root::parse_from(args);
+//! let RootCmd::List(list) = root.cmd;
+//! assert!(list.all);
+//! # std::fs::remove_dir_all(dir).unwrap();
+//! ```
+use std::{
+    ffi::OsString,
+    fmt::Write as _,
+    path::{Path, PathBuf},
+};
+
+use eyre::{eyre, Result, WrapErr};
+
+use crate::dirs::Dirs;
+
+static ALIASES_FILE: &str = "aliases.toml";
+
+/// The aliases of a CLI and the file they come from.
+///
+/// See the module documentation for usage.
+#[derive(Clone, Debug, Default)]
+pub struct Aliases {
+    path: Option<PathBuf>,
+    entries: Vec<(String, Vec<String>)>,
+}
+
+impl Aliases {
+    /// Create the aliases for the CLI `name`.
+    ///
+    /// Nothing is read until [`Aliases::load`] is called.
+    pub fn new(name: impl AsRef<str>) -> Self {
+        Self {
+            path: Dirs::new(name).config().map(|dir| dir.join(ALIASES_FILE)),
+            entries: Vec::new(),
+        }
+    }
+
+    /// Set the path of the aliases file.
+    ///
+    /// By default, this is `aliases.toml` in the platform config directory.
+    #[must_use]
+    pub fn with_path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.path = Some(path.into());
+        self
+    }
+
+    /// Add the alias `name`, expanding to `args`.
+    ///
+    /// These are defaults, aliases from the file with the same name replace
+    /// them.
+    #[must_use]
+    pub fn with_alias<I, S>(mut self, name: impl Into<String>, args: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.insert(name.into(), args.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Read the aliases file.
+    ///
+    /// A missing file isn't an error, there just aren't any aliases in it.
+    pub fn load(mut self) -> Result<Self> {
+        let Some(path) = self.path.clone() else {
+            return Ok(self);
+        };
+
+        let content = match std::fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(self),
+            Err(e) => return Err(e).wrap_err_with(|| format!("unable to read {}", path.display())),
+        };
+
+        for (name, args) in
+            parse(&content).wrap_err_with(|| format!("invalid {}", path.display()))?
+        {
+            self.insert(name, args);
+        }
+
+        Ok(self)
+    }
+
+    /// The path of the aliases file, if there is one.
+    #[must_use]
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    /// The arguments `name` expands to, if it is an alias.
+    #[must_use]
+    pub fn get(&self, name: &str) -> Option<&[String]> {
+        self.entries
+            .iter()
+            .find(|(alias, _)| alias == name)
+            .map(|(_, args)| args.as_slice())
+    }
+
+    fn insert(&mut self, name: String, args: Vec<String>) {
+        self.entries.retain(|(alias, _)| *alias != name);
+        self.entries.push((name, args));
+    }
+
+    /// Expand the alias in `args`, which start with the binary like
+    /// [`std::env::args_os`] does.
+    ///
+    /// `cmd` is the root, aliases named after one of its subcommands are
+    /// ignored.
+    pub fn expand<I, S>(&self, cmd: &clap::Command, args: I) -> Vec<OsString>
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<OsString>,
+    {
+        let mut args = args.into_iter().map(Into::into).collect::<Vec<_>>();
+        let mut seen = Vec::new();
+
+        while let Some(name) = args.get(1).and_then(|arg| arg.to_str()).map(String::from) {
+            if seen.contains(&name) || cmd.find_subcommand(&name).is_some() {
+                break;
+            }
+
+            let Some(expansion) = self.get(&name) else {
+                break;
+            };
+
+            args.splice(1..2, expansion.iter().map(OsString::from));
+            seen.push(name);
+        }
+
+        args
+    }
+
+    /// List the aliases at the end of the help for `cmd`.
+    #[must_use]
+    pub fn augment(&self, cmd: clap::Command) -> clap::Command {
+        let aliases = self
+            .entries
+            .iter()
+            .filter(|(name, _)| cmd.find_subcommand(name).is_none())
+            .collect::<Vec<_>>();
+
+        if aliases.is_empty() {
+            return cmd;
+        }
+
+        let width = aliases
+            .iter()
+            .map(|(name, _)| name.len())
+            .max()
+            .unwrap_or(0);
+        let mut section = String::from("Aliases:");
+        for (name, args) in aliases {
+            let expansion =
+                shlex::try_join(args.iter().map(String::as_str)).unwrap_or_else(|_| args.join(" "));
+
+            write!(section, "\n  {name:width$}  {expansion}").ok();
+        }
+
+        let help = match cmd.get_after_help() {
+            Some(existing) => format!("{existing}\n\n{section}"),
+            None => section,
+        };
+
+        cmd.after_help(help)
+    }
+}
+
+/// The aliases in the content of an aliases file.
+fn parse(content: &str) -> Result<Vec<(String, Vec<String>)>> {
+    let document = content.parse::<toml_edit::Document>()?;
+
+    document
+        .iter()
+        .map(|(name, item)| {
+            let args = if let Some(line) = item.as_str() {
+                shlex::split(line).ok_or_else(|| eyre!("`{name}` has unbalanced quotes"))?
+            } else if let Some(array) = item.as_array() {
+                array
+                    .iter()
+                    .map(|arg| {
+                        arg.as_str()
+                            .map(String::from)
+                            .ok_or_else(|| eyre!("`{name}` can only contain strings"))
+                    })
+                    .collect::<Result<_>>()?
+            } else {
+                return Err(eyre!("`{name}` must be a string or an array of strings"));
+            };
+
+            if args.is_empty() {
+                return Err(eyre!("`{name}` doesn't expand to anything"));
+            }
+
+            Ok((name.to_string(), args))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::OsString;
+
+    use super::{parse, Aliases};
+
+    fn command() -> clap::Command {
+        clap::Command::new("my-cli")
+            .subcommand(clap::Command::new("list"))
+            .subcommand(clap::Command::new("deploy"))
+    }
+
+    fn expand(aliases: &Aliases, args: &[&str]) -> Vec<OsString> {
+        aliases.expand(&command(), args.iter().copied())
+    }
+
+    #[test]
+    fn chains() {
+        let aliases = Aliases::default()
+            .with_alias("a", ["b", "--one"])
+            .with_alias("b", ["c", "--two"])
+            .with_alias("c", ["list"]);
+
+        assert_eq!(
+            expand(&aliases, &["my-cli", "a", "--three"]),
+            ["my-cli", "list", "--two", "--one", "--three"]
+        );
+    }
+
+    #[test]
+    fn refers_to_itself() {
+        let aliases = Aliases::default().with_alias("ls", ["ls", "--all"]);
+
+        assert_eq!(
+            expand(&aliases, &["my-cli", "ls"]),
+            ["my-cli", "ls", "--all"]
+        );
+    }
+
+    #[test]
+    fn cycles() {
+        let aliases = Aliases::default()
+            .with_alias("a", ["b", "--one"])
+            .with_alias("b", ["a", "--two"]);
+
+        assert_eq!(
+            expand(&aliases, &["my-cli", "a"]),
+            ["my-cli", "a", "--two", "--one"]
+        );
+        assert_eq!(
+            expand(&aliases, &["my-cli", "b"]),
+            ["my-cli", "b", "--one", "--two"]
+        );
+    }
+
+    #[test]
+    fn long_chains_end() {
+        let names = (0..=64).map(|i| format!("alias-{i}")).collect::<Vec<_>>();
+        let aliases = names
+            .windows(2)
+            .fold(Aliases::default(), |aliases, pair| {
+                aliases.with_alias(pair[0].clone(), [pair[1].clone()])
+            })
+            .with_alias("alias-64", ["alias-0"]);
+
+        assert_eq!(
+            expand(&aliases, &["my-cli", "alias-0"]),
+            ["my-cli", "alias-0"]
+        );
+    }
+
+    #[test]
+    fn subcommands_win() {
+        let aliases = Aliases::default()
+            .with_alias("list", ["deploy"])
+            .with_alias("ls", ["list", "--all"]);
+
+        assert_eq!(expand(&aliases, &["my-cli", "list"]), ["my-cli", "list"]);
+        assert_eq!(
+            expand(&aliases, &["my-cli", "ls"]),
+            ["my-cli", "list", "--all"]
+        );
+    }
+
+    #[test]
+    fn only_the_first_argument() {
+        let aliases = Aliases::default().with_alias("ls", ["list"]);
+
+        assert_eq!(
+            expand(&aliases, &["my-cli", "deploy", "ls"]),
+            ["my-cli", "deploy", "ls"]
+        );
+        assert_eq!(expand(&aliases, &["my-cli"]), ["my-cli"]);
+    }
+
+    #[test]
+    fn file_replaces_defaults() {
+        let path = std::env::temp_dir().join(format!("cata-aliases-{}.toml", std::process::id()));
+        std::fs::write(&path, "ls = \"list --all\"\n").unwrap();
+
+        let aliases = Aliases::default()
+            .with_alias("ls", ["list"])
+            .with_alias("prod", ["deploy"])
+            .with_path(&path)
+            .load()
+            .unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(
+            aliases.get("ls"),
+            Some(["list".to_string(), "--all".to_string()].as_slice())
+        );
+        assert_eq!(aliases.get("prod"), Some(["deploy".to_string()].as_slice()));
+    }
+
+    #[test]
+    fn parses_strings_and_arrays() {
+        let parsed = parse(
+            "ls = \"list --name 'my project'\"\nprod = [\"deploy\", \"--env\", \"production\"]\n",
+        )
+        .unwrap();
+
+        assert_eq!(
+            parsed,
+            [
+                (
+                    "ls".to_string(),
+                    vec!["list".into(), "--name".into(), "my project".into()]
+                ),
+                (
+                    "prod".to_string(),
+                    vec!["deploy".into(), "--env".into(), "production".into()]
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_aliases() {
+        for (content, err) in [
+            ("ls = \"list 'all\"", "`ls` has unbalanced quotes"),
+            ("ls = [\"list\", 1]", "`ls` can only contain strings"),
+            ("ls = 1", "`ls` must be a string or an array of strings"),
+            ("ls = \"\"", "`ls` doesn't expand to anything"),
+            ("ls = []", "`ls` doesn't expand to anything"),
+        ] {
+            assert_eq!(parse(content).unwrap_err().to_string(), err, "{content}");
+        }
+    }
+}
@@ -0,0 +1,238 @@
+//! Everything `main` usually does, in one place.
+//!
+//! Most CLIs start with the same boilerplate: install the error hook, set up
+//! crash reports, parse the root, hand the telemetry layer to tracing and then
+//! [`crate::execute`]. [`App`] does all of that with sensible defaults, each
+//! step can be changed or turned off.
+//!
+//! Some things to note:
+//! - The [`crate::error::Hook`] is installed first, before anything can fail.
+//! - Crash reports are opt-in, see [`App::with_crash`].
+//! - The matches are always passed on, see [`crate::Context::matches`].
+//! - Help lists the examples of every command, see [`crate::examples`].
+//! - Tracing is initialized by the first command with a
+//!   [`crate::Command::verbosity`], layers added with
+//!   [`App::with_tracing_layer`] are part of that subscriber.
+//! - Signals are handled as described by [`crate::execute`], unless the
+//!   [`ExecuteOptions`] say otherwise.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use cata::{App, Command, Container};
+//! use clap::Parser;
+//!
+//! #[derive(Parser, Container)]
+//! struct Root {}
+//!
+//! impl Command for Root {}
+//!
+//! #[tokio::main]
+//! async fn main() {
+//!   let result = App::new(env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"))
+//!     .with_crash()
+//!     .run::<Root>()
+//!     .await;
+//!
+//!   cata::error::exit(result)
+//! }
+//! ```
+use std::fmt;
+
+use eyre::Result;
+
+use crate::{crash::Crash, error::Hook, flags::Flags, prompt::Prompt, Command, ExecuteOptions};
+
+type Augment = Box<dyn FnOnce(clap::Command) -> clap::Command + Send>;
+
+/// The setup for a CLI's `main`.
+///
+/// See the module documentation for usage.
+pub struct App {
+    name: String,
+    version: String,
+    hook: Option<Hook>,
+    crash: bool,
+    augment: Option<Augment>,
+    flags: Flags,
+    prompts: bool,
+    #[cfg(feature = "aliases")]
+    aliases: Option<crate::aliases::Aliases>,
+    options: ExecuteOptions,
+}
+
+impl fmt::Debug for App {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut f = f.debug_struct("App");
+        f.field("name", &self.name)
+            .field("version", &self.version)
+            .field("hook", &self.hook)
+            .field("crash", &self.crash)
+            .field("flags", &self.flags)
+            .field("prompts", &self.prompts);
+
+        #[cfg(feature = "aliases")]
+        f.field("aliases", &self.aliases);
+
+        f.field("options", &self.options).finish_non_exhaustive()
+    }
+}
+
+impl App {
+    /// Set up the CLI `name` running `version`.
+    pub fn new(name: impl AsRef<str>, version: impl AsRef<str>) -> Self {
+        Self {
+            name: name.as_ref().into(),
+            version: version.as_ref().into(),
+            hook: Some(Hook::default()),
+            crash: false,
+            augment: None,
+            flags: Flags::default(),
+            prompts: false,
+            #[cfg(feature = "aliases")]
+            aliases: None,
+            options: ExecuteOptions::default(),
+        }
+    }
+
+    /// Install `hook` instead of the default error hook, or none at all.
+    #[must_use]
+    pub fn with_error_hook(mut self, hook: Option<Hook>) -> Self {
+        self.hook = hook;
+        self
+    }
+
+    /// Write crash reports for panics and errors, see [`crate::crash`].
+    ///
+    /// By default, no reports are written.
+    #[must_use]
+    pub fn with_crash(mut self) -> Self {
+        self.crash = true;
+        self
+    }
+
+    /// Change the root's [`clap::Command`] before parsing, see
+    /// [`crate::parse_with`].
+    #[must_use]
+    pub fn with_augment(
+        mut self,
+        augment: impl FnOnce(clap::Command) -> clap::Command + Send + 'static,
+    ) -> Self {
+        self.augment = Some(Box::new(augment));
+        self
+    }
+
+    /// Hide and refuse the commands whose feature flag isn't enabled in
+    /// `flags`, see [`crate::flags`].
+    ///
+    /// By default, no flags are enabled.
+    #[must_use]
+    pub fn with_flags(mut self, flags: Flags) -> Self {
+        self.flags = flags;
+        self
+    }
+
+    /// Ask for required arguments that weren't passed when running in a
+    /// terminal, see [`crate::prompt`].
+    ///
+    /// By default, clap reports them as missing.
+    #[must_use]
+    pub fn with_prompts(mut self) -> Self {
+        self.prompts = true;
+        self
+    }
+
+    /// Expand the user's aliases before parsing and list them in the root's
+    /// help, see [`crate::aliases`].
+    ///
+    /// By default, there are no aliases.
+    #[cfg(feature = "aliases")]
+    #[must_use]
+    pub fn with_aliases(mut self, aliases: crate::aliases::Aliases) -> Self {
+        self.aliases = Some(aliases);
+        self
+    }
+
+    /// Add `layer`, such as [`crate::telemetry::Telemetry`], to the tracing
+    /// subscriber, see [`ExecuteOptions::with_tracing_layer`].
+    #[cfg(feature = "verbosity")]
+    #[must_use]
+    pub fn with_tracing_layer<L>(mut self, layer: L) -> Self
+    where
+        L: tracing_subscriber::Layer<tracing_subscriber::Registry> + Send + Sync + 'static,
+    {
+        self.options = self.options.with_tracing_layer(layer);
+        self
+    }
+
+    /// Execute with `options`, see [`ExecuteOptions`].
+    ///
+    /// Tracing layers that have already been added are kept, as are the
+    /// [`App::with_flags`].
+    #[must_use]
+    pub fn with_options(mut self, options: ExecuteOptions) -> Self {
+        #[cfg(feature = "verbosity")]
+        let options = ExecuteOptions {
+            tracing: self.options.tracing.clone(),
+            ..options
+        };
+
+        self.options = options;
+        self
+    }
+
+    /// Parse `T` from the command line and execute it.
+    ///
+    /// Pass the result to [`crate::error::exit`] to print it and exit with the
+    /// right code.
+    pub async fn run<T: clap::Parser + Command>(self) -> Result<()> {
+        if let Some(hook) = self.hook {
+            hook.install()?;
+        }
+
+        let crash = self.crash.then(|| {
+            let crash = Crash::new(&self.name, &self.version).with_command(T::command());
+            crash.clone().install();
+
+            crash
+        });
+
+        let args = std::env::args_os();
+        #[cfg(feature = "aliases")]
+        let args = match &self.aliases {
+            Some(aliases) => aliases.expand(&T::command(), args),
+            None => args.collect(),
+        };
+
+        let args = if self.prompts {
+            Prompt::default().fill(&T::command(), args)?
+        } else {
+            args.into_iter().collect()
+        };
+
+        let flags = self.flags;
+        let (root, matches) = crate::parse_from_with::<T, _, _, _>(args, |cmd| {
+            let cmd = match self.augment {
+                Some(augment) => augment(cmd),
+                None => cmd,
+            };
+
+            #[cfg(feature = "aliases")]
+            let cmd = match &self.aliases {
+                Some(aliases) => aliases.augment(cmd),
+                None => cmd,
+            };
+
+            crate::examples::augment::<T>(flags.hide::<T>(cmd))
+        });
+
+        let options = self.options.with_matches(matches).with_flags(flags);
+        let result = crate::execute_with(&root, options).await;
+
+        if let (Some(crash), Err(err)) = (&crash, &result) {
+            crash.error(err);
+        }
+
+        result
+    }
+}
@@ -0,0 +1,292 @@
+//! Tasks that run alongside the command tree.
+//!
+//! Commands often kick off work that shouldn't block them, such as refreshing
+//! a cache or sending a heartbeat. Tasks started with [`spawn`] belong to the
+//! current [`crate::execute`] and are torn down with it, they can't outlive
+//! the command that started them.
+//!
+//! Some things to note:
+//! - Once every `post_run` has been called, tasks get a short grace period
+//!   (250ms) to finish before they are cancelled.
+//! - When `execute` itself is cancelled, for example by dropping it from a
+//!   `tokio::select!`, tasks are cancelled immediately.
+//! - Tasks spawned outside of `execute`, or from inside another task, are
+//!   detached tasks on the runtime, see [`crate::runtime`].
+//!
+//! Work that belongs to a single `run`, such as a pool of workers, goes in the
+//! [`TaskGroup`] from [`crate::Context::tasks`] instead. Its tasks are handed a
+//! token that is cancelled as soon as `run` returns or the user hits ctrl-c.
+//! They then have a drain timeout (1s by default, see
+//! [`crate::ExecuteOptions::with_drain`]) to wind down before the next hook is
+//! called.
+//!
+//! # Examples
+//!
+//! ```
+//! use cata::{background, Command, Container};
+//! use clap::Parser;
+//!
+//! #[derive(Parser, Container)]
+//! struct Root {}
+//!
+//! #[async_trait::async_trait]
+//! impl Command for Root {
+//!   async fn run(&self, _: &mut cata::Context) -> eyre::Result<()> {
+//!     background::spawn(async {
+//!       loop {
+//!         tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+//!       }
+//!     });
+//!
+//!     Ok(())
+//!   }
+//! }
+//!
+//! # #[tokio::main]
+//! # async fn main() -> eyre::Result<()> {
+//! // Returns once the grace period is over, instead of waiting on the loop.
+//! cata::execute(&Root::parse_from(["root"])).await
+//! # }
+//! ```
+//!
+//! With a [`TaskGroup`], tasks see the cancellation and can clean up:
+//!
+//! ```
+//! use std::sync::atomic::{AtomicBool, Ordering};
+//!
+//! use cata::{Command, Container};
+//! use clap::Parser;
+//!
+//! static FLUSHED: AtomicBool = AtomicBool::new(false);
+//!
+//! #[derive(Parser, Container)]
+//! struct Root {}
+//!
+//! #[async_trait::async_trait]
+//! impl Command for Root {
+//!   async fn run(&self, ctx: &mut cata::Context) -> eyre::Result<()> {
+//!     ctx.tasks().spawn(|token| async move {
+//!       token.cancelled().await;
+//!       FLUSHED.store(true, Ordering::SeqCst);
+//!     });
+//!
+//!     Ok(())
+//!   }
+//!
+//!   async fn post_run(&self, _: &mut cata::Context, _: &eyre::Result<()>) -> eyre::Result<()> {
+//!     assert!(FLUSHED.load(Ordering::SeqCst));
+//!
+//!     Ok(())
+//!   }
+//! }
+//!
+//! # #[tokio::main]
+//! # async fn main() -> eyre::Result<()> {
+//! cata::execute(&Root::parse_from(["root"])).await
+//! # }
+//! ```
+use std::{
+    future::Future,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex, PoisonError,
+    },
+    time::Duration,
+};
+
+use tokio::sync::mpsc;
+
+use crate::{context::CancellationToken, runtime};
+
+pub(crate) static GRACE: Duration = Duration::from_millis(250);
+pub(crate) static DRAIN: Duration = Duration::from_secs(1);
+
+tokio::task_local! {
+    static TASKS: Mutex<Tasks>;
+}
+
+/// Cancels a task started with [`spawn`] or [`TaskGroup::spawn`].
+#[derive(Clone, Debug)]
+pub struct AbortHandle {
+    token: CancellationToken,
+    finished: Arc<AtomicBool>,
+}
+
+impl AbortHandle {
+    /// Cancel the task, it is dropped the next time it is polled.
+    pub fn abort(&self) {
+        self.token.cancel();
+    }
+
+    /// Whether the task has completed or was cancelled.
+    #[must_use]
+    pub fn is_finished(&self) -> bool {
+        self.finished.load(Ordering::SeqCst)
+    }
+}
+
+/// Tasks that are cancelled together.
+///
+/// Every task holds on to a sender of `finished`, it closes once all of them
+/// are done.
+#[derive(Debug)]
+struct Tasks {
+    abort: CancellationToken,
+    running: Option<mpsc::Sender<()>>,
+    finished: mpsc::Receiver<()>,
+}
+
+impl Default for Tasks {
+    fn default() -> Self {
+        let (running, finished) = mpsc::channel(1);
+
+        Self {
+            abort: CancellationToken::new(),
+            running: Some(running),
+            finished,
+        }
+    }
+}
+
+impl Tasks {
+    fn spawn<F>(&self, fut: F) -> AbortHandle
+    where
+        F: Future<Output = ()> + Send + 'static,
+    {
+        start(self.abort.child_token(), self.running.clone(), fut)
+    }
+
+    /// Wait for every task to complete.
+    async fn join(&mut self) {
+        self.running = None;
+
+        while self.finished.recv().await.is_some() {}
+    }
+
+    /// Wait up to `timeout` for every task to complete, then cancel the rest.
+    async fn shutdown(mut self, timeout: Duration) {
+        let _ = runtime::timeout(timeout, self.join()).await;
+
+        self.abort.cancel();
+        self.join().await;
+    }
+}
+
+impl Drop for Tasks {
+    fn drop(&mut self) {
+        self.abort.cancel();
+    }
+}
+
+/// Start `fut` on the current runtime, until `token` is cancelled.
+fn start<F>(token: CancellationToken, running: Option<mpsc::Sender<()>>, fut: F) -> AbortHandle
+where
+    F: Future<Output = ()> + Send + 'static,
+{
+    let handle = AbortHandle {
+        token,
+        finished: Arc::default(),
+    };
+
+    let task = handle.clone();
+    runtime::current().spawn(Box::pin(async move {
+        runtime::race(task.token.cancelled(), fut).await;
+
+        task.finished.store(true, Ordering::SeqCst);
+        drop(running);
+    }));
+
+    handle
+}
+
+/// Run `fut` in the background until the current command tree is torn down.
+///
+/// The returned handle can be used to cancel the task early.
+pub fn spawn<F>(fut: F) -> AbortHandle
+where
+    F: Future<Output = ()> + Send + 'static,
+{
+    if TASKS.try_with(|_| ()).is_err() {
+        return start(CancellationToken::new(), None, fut);
+    }
+
+    TASKS.with(|tasks| {
+        tasks
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .spawn(fut)
+    })
+}
+
+/// Run `fut` with its own set of background tasks.
+///
+/// Anything still running when `fut` completes or is dropped is cancelled.
+pub(crate) async fn scope<F: Future>(fut: F) -> F::Output {
+    TASKS.scope(Mutex::default(), fut).await
+}
+
+/// Wait up to `grace` for the tasks of the current scope to finish, then
+/// cancel the rest.
+pub(crate) async fn shutdown(grace: Duration) {
+    let Ok(tasks) = TASKS.try_with(|tasks| {
+        std::mem::take(&mut *tasks.lock().unwrap_or_else(PoisonError::into_inner))
+    }) else {
+        return;
+    };
+
+    tasks.shutdown(grace).await;
+}
+
+/// Tasks that belong to a single `run`, see [`crate::Context::tasks`].
+///
+/// See the module documentation for usage.
+#[derive(Debug, Default)]
+pub struct TaskGroup {
+    token: CancellationToken,
+    tasks: Mutex<Tasks>,
+}
+
+impl TaskGroup {
+    pub(crate) fn new(token: CancellationToken) -> Self {
+        Self {
+            token,
+            tasks: Mutex::default(),
+        }
+    }
+
+    /// Run the future returned by `task` until `run` has returned and the
+    /// drain timeout is over.
+    ///
+    /// `task` is handed the token that is cancelled once the task should wind
+    /// down. The returned handle can be used to cancel the task early.
+    pub fn spawn<F, Fut>(&self, task: F) -> AbortHandle
+    where
+        F: FnOnce(CancellationToken) -> Fut,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let fut = task(self.token.clone());
+
+        self.tasks
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .spawn(fut)
+    }
+
+    /// The token that is handed to every task.
+    #[must_use]
+    pub fn token(&self) -> &CancellationToken {
+        &self.token
+    }
+
+    /// Cancel the tasks and wait up to `timeout` for them to finish, then
+    /// abort the rest.
+    pub(crate) async fn drain(self, timeout: Duration) {
+        self.token.cancel();
+
+        self.tasks
+            .into_inner()
+            .unwrap_or_else(PoisonError::into_inner)
+            .shutdown(timeout)
+            .await;
+    }
+}
@@ -0,0 +1,376 @@
+//! Run a script of commands without a shell loop.
+//!
+//! Automating a CLI usually means a shell script that calls it over and over,
+//! paying for startup every time and stopping at whatever `set -e` decides.
+//! [`Batch`] reads a [`Script`] of invocations instead and runs each one
+//! through [`crate::execute_with`], collecting the outcomes into a
+//! [`Summary`].
+//!
+//! Some things to note:
+//! - Scripts have one invocation per line, without the name of the binary.
+//!   Lines are split like a shell would, blank lines and lines starting with
+//!   `#` are ignored.
+//! - With the `yaml` feature, scripts can also be a YAML list. Each item is
+//!   either a line as above or a list of arguments.
+//! - By default, the first failure stops the script. Use
+//!   [`Batch::with_keep_going`] to run everything and look at the [`Summary`]
+//!   afterwards.
+//! - Arguments that fail to parse are failures of that step, they don't stop a
+//!   script that keeps going.
+//!
+//! # Examples
+//!
+//! ```
+//! use cata::{
+//!   batch::{Batch, Script},
+//!   Command, Container,
+//! };
+//! use clap::{Parser, Subcommand};
+//!
+//! #[derive(Parser, Container)]
+//! struct Root {
+//!   #[command(subcommand)]
+//!   cmd: RootCmd,
+//! }
+//!
+//! impl Command for Root {}
+//!
+//! #[derive(Subcommand, Container)]
+//! enum RootCmd {
+//!   Greet(Greet),
+//! }
+//!
+//! #[derive(Parser, Container)]
+//! struct Greet {
+//!   name: String,
+//! }
+//!
+//! #[async_trait::async_trait]
+//! impl Command for Greet {
+//!   async fn run(&self, _: &mut cata::Context) -> eyre::Result<()> {
+//!     println!("hello {}", self.name);
+//!
+//!     Ok(())
+//!   }
+//! }
+//!
+//! # #[tokio::main]
+//! # async fn main() -> eyre::Result<()> {
+//! let script = Script::parse(
+//!   r#"
+//!   ## Say hello to everyone.
+//!   greet alice
+//!   greet "bob smith"
+//!   wave carol
+//!   "#,
+//! );
+//!
+//! let summary = Batch::<Root>::default()
+//!   .with_keep_going(true)
+//!   .run(script)
+//!   .await;
+//!
+//! assert_eq!(summary.outcomes().len(), 3);
+//! assert_eq!(summary.failures().count(), 1);
+//! assert!(summary.into_result().is_err());
+//! # Ok(())
+//! # }
+//! ```
+use std::{fmt, io::Read, marker::PhantomData, path::Path};
+
+use clap::Parser;
+use eyre::{eyre, Report, Result};
+
+use crate::{Command, ExecuteOptions};
+
+/// A single invocation in a [`Script`].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Step {
+    line: usize,
+    args: Vec<String>,
+}
+
+impl Step {
+    /// The line of the script this step came from, starting at 1. For YAML
+    /// scripts, this is the position in the list.
+    #[must_use]
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    /// The arguments, without the name of the binary.
+    #[must_use]
+    pub fn args(&self) -> &[String] {
+        &self.args
+    }
+}
+
+impl fmt::Display for Step {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&shlex::try_join(self.args.iter().map(String::as_str)).unwrap_or_default())
+    }
+}
+
+/// The invocations to run, in order.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Script {
+    steps: Vec<Step>,
+}
+
+impl Script {
+    /// Parse a script with one invocation per line.
+    #[must_use]
+    pub fn parse(raw: &str) -> Self {
+        let steps = raw
+            .lines()
+            .enumerate()
+            .filter_map(|(i, line)| {
+                let line = line.trim();
+
+                (!line.is_empty() && !line.starts_with('#')).then(|| Step {
+                    line: i + 1,
+                    args: split(line),
+                })
+            })
+            .collect();
+
+        Self { steps }
+    }
+
+    /// Parse a script that is a YAML list of lines or lists of arguments.
+    #[cfg(feature = "yaml")]
+    pub fn from_yaml(raw: &str) -> Result<Self> {
+        #[derive(serde::Deserialize)]
+        #[serde(untagged)]
+        enum Item {
+            Line(String),
+            Args(Vec<String>),
+        }
+
+        let items: Vec<Item> = serde_yaml::from_str(raw)?;
+
+        let steps = items
+            .into_iter()
+            .enumerate()
+            .map(|(i, item)| Step {
+                line: i + 1,
+                args: match item {
+                    Item::Line(line) => split(&line),
+                    Item::Args(args) => args,
+                },
+            })
+            .collect();
+
+        Ok(Self { steps })
+    }
+
+    /// Read a script from `path`, or from stdin when it is `-`.
+    ///
+    /// Files ending in `.yaml` or `.yml` are parsed with [`Script::from_yaml`]
+    /// when the `yaml` feature is enabled.
+    pub fn read(path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref();
+
+        let raw = if path == Path::new("-") {
+            let mut raw = String::new();
+            std::io::stdin().read_to_string(&mut raw)?;
+
+            raw
+        } else {
+            std::fs::read_to_string(path)
+                .map_err(|e| eyre!("unable to read {}: {e}", path.display()))?
+        };
+
+        #[cfg(feature = "yaml")]
+        if matches!(
+            path.extension().and_then(|ext| ext.to_str()),
+            Some("yaml" | "yml")
+        ) {
+            return Self::from_yaml(&raw);
+        }
+
+        Ok(Self::parse(&raw))
+    }
+
+    /// The invocations in the script.
+    #[must_use]
+    pub fn steps(&self) -> &[Step] {
+        &self.steps
+    }
+}
+
+/// The result of running a single [`Step`].
+///
+/// `T` is what was run, [`crate::fanout`] reuses this for its invocations.
+#[derive(Debug)]
+pub struct Outcome<T = Step> {
+    pub(crate) run: T,
+    pub(crate) result: Result<()>,
+}
+
+impl<T> Outcome<T> {
+    /// How the step went.
+    pub fn result(&self) -> &Result<()> {
+        &self.result
+    }
+}
+
+impl Outcome {
+    /// The step that was run.
+    #[must_use]
+    pub fn step(&self) -> &Step {
+        &self.run
+    }
+}
+
+/// Every step that ran, in order.
+///
+/// Steps that were never reached because of an earlier failure aren't
+/// included. Displaying the summary lists each step and its outcome.
+#[derive(Debug)]
+pub struct Summary<T = Step> {
+    pub(crate) outcomes: Vec<Outcome<T>>,
+}
+
+impl<T> Default for Summary<T> {
+    fn default() -> Self {
+        Self {
+            outcomes: Vec::new(),
+        }
+    }
+}
+
+impl<T> Summary<T> {
+    /// The outcome of every step that ran.
+    #[must_use]
+    pub fn outcomes(&self) -> &[Outcome<T>] {
+        &self.outcomes
+    }
+
+    /// The outcomes of the steps that failed.
+    pub fn failures(&self) -> impl Iterator<Item = &Outcome<T>> {
+        self.outcomes
+            .iter()
+            .filter(|outcome| outcome.result.is_err())
+    }
+
+    /// Whether every step that ran succeeded.
+    #[must_use]
+    pub fn is_success(&self) -> bool {
+        self.failures().next().is_none()
+    }
+}
+
+impl Summary {
+    /// Fail when any step did.
+    ///
+    /// A single failure is returned as is, with the step it came from attached.
+    /// Multiple failures are counted instead, display the summary for the
+    /// details.
+    pub fn into_result(self) -> Result<()> {
+        let total = self.outcomes.len();
+        let mut failures = self
+            .outcomes
+            .into_iter()
+            .filter_map(|outcome| Some((outcome.run, outcome.result.err()?)))
+            .collect::<Vec<_>>();
+
+        match failures.len() {
+            0 => Ok(()),
+            1 => {
+                let (step, err) = failures.remove(0);
+
+                Err(err.wrap_err(format!("line {}: `{step}` failed", step.line)))
+            }
+            count => Err(eyre!("{count} of {total} commands failed")),
+        }
+    }
+}
+
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for Outcome { run: step, result } in &self.outcomes {
+            match result {
+                Ok(()) => writeln!(f, "ok    line {}: {step}", step.line)?,
+                Err(err) => writeln!(f, "error line {}: {step}: {err}", step.line)?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Runs every step of a [`Script`] as the command `C`.
+///
+/// See the module documentation for usage.
+pub struct Batch<C> {
+    options: ExecuteOptions,
+    keep_going: bool,
+    cmd: PhantomData<fn() -> C>,
+}
+
+impl<C> Default for Batch<C> {
+    fn default() -> Self {
+        Self {
+            options: ExecuteOptions::default(),
+            keep_going: false,
+            cmd: PhantomData,
+        }
+    }
+}
+
+impl<C> fmt::Debug for Batch<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Batch")
+            .field("options", &self.options)
+            .field("keep_going", &self.keep_going)
+            .finish()
+    }
+}
+
+impl<C> Batch<C>
+where
+    C: Parser + Command + 'static,
+{
+    /// Set the options every step is executed with.
+    #[must_use]
+    pub fn with_options(mut self, options: ExecuteOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Keep running after a step fails instead of stopping.
+    #[must_use]
+    pub fn with_keep_going(mut self, keep_going: bool) -> Self {
+        self.keep_going = keep_going;
+        self
+    }
+
+    /// Run the steps of `script` in order.
+    pub async fn run(self, script: Script) -> Summary {
+        let name = C::command().get_name().to_string();
+        let mut summary = Summary::default();
+
+        for step in script.steps {
+            let result = match C::try_parse_from(std::iter::once(&name).chain(&step.args)) {
+                Ok(cmd) => crate::execute_with(&cmd, self.options.clone()).await,
+                Err(e) => Err(Report::new(e)),
+            };
+
+            let failed = result.is_err();
+            summary.outcomes.push(Outcome { run: step, result });
+
+            if failed && !self.keep_going {
+                break;
+            }
+        }
+
+        summary
+    }
+}
+
+/// Split `line` into words, falling back to whitespace when quotes aren't
+/// balanced.
+pub(crate) fn split(line: &str) -> Vec<String> {
+    shlex::split(line).unwrap_or_else(|| line.split_whitespace().map(ToString::to_string).collect())
+}
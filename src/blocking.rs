@@ -0,0 +1,274 @@
+//! Commands with synchronous hooks, for CLIs that don't want to be async.
+//!
+//! Small CLIs often have nothing to `await`. Implementing [`SyncCommand`]
+//! instead of [`Command`] keeps `async_trait` out of them, and
+//! [`crate::execute_sync`] runs the tree from a plain `fn main` without
+//! `#[tokio::main]`. The lifecycle is the same as [`crate::execute`], every
+//! [`SyncCommand`] is also a [`Command`] and the two can be mixed in a tree.
+//!
+//! Some things to note:
+//! - [`crate::execute_sync`] runs the lifecycle on a single threaded runtime
+//!   that it creates. It panics when called from inside of a tokio runtime.
+//! - Hooks run on that runtime's only thread. Timeouts, ctrl-c and signals are
+//!   noticed once the hook that is running returns, long running hooks should
+//!   check [`Context::token`] themselves. The same goes for tasks started with
+//!   [`crate::background::spawn`], they make progress between hooks.
+//! - `#[derive(Container)]` works as usual, subcommands can be either kind of
+//!   command.
+//!
+//! # Examples
+//!
+//! ```
+//! use cata::{blocking::SyncCommand, command::PreRun, Container, Context};
+//! use clap::{Parser, Subcommand};
+//!
+//! #[derive(Parser, Container)]
+//! struct Root {
+//!   #[command(subcommand)]
+//!   cmd: RootCmd,
+//! }
+//!
+//! impl SyncCommand for Root {
+//!   fn pre_run(&self, ctx: &mut Context) -> eyre::Result<PreRun> {
+//!     ctx.provide(String::from("world"));
+//!
+//!     Ok(PreRun::Continue)
+//!   }
+//! }
+//!
+//! #[derive(Subcommand, Container)]
+//! enum RootCmd {
+//!   Greet(Greet),
+//! }
+//!
+//! #[derive(Parser, Container)]
+//! struct Greet {}
+//!
+//! impl SyncCommand for Greet {
+//!   fn run(&self, ctx: &mut Context) -> eyre::Result<()> {
+//!     println!("hello {}", *ctx.require::<String>()?);
+//!
+//!     Ok(())
+//!   }
+//! }
+//!
+//! fn main() -> eyre::Result<()> {
+//!   cata::execute_sync(&Root::parse_from(["root", "greet"]))
+//! }
+//! ```
+use std::time::Duration;
+
+use eyre::{eyre, Report, Result};
+
+#[cfg(feature = "lock")]
+use crate::lock::Lock;
+#[cfg(feature = "requirements")]
+use crate::requirements::Requirement;
+use crate::{
+    command::{
+        Confirmation, Container, Deprecation, Overrides, PreRun, RetryPolicy, RunPolicy, Signal,
+    },
+    Command, Context,
+};
+
+/// A [`Command`] whose hooks are synchronous.
+///
+/// Every hook means the same as its counterpart on [`Command`] and has the
+/// same default.
+pub trait SyncCommand: Send + Sync + Container {
+    /// See [`Command::pre_run`].
+    fn pre_run(&self, _: &mut Context) -> Result<PreRun> {
+        Ok(PreRun::Continue)
+    }
+
+    /// See [`Command::on_first_run`].
+    #[cfg(feature = "first-run")]
+    fn on_first_run(&self, _: &mut Context) -> Result<()> {
+        Ok(())
+    }
+
+    /// See [`Command::run`].
+    fn run(&self, _: &mut Context) -> Result<()> {
+        Ok(())
+    }
+
+    /// See [`Command::run_mut`].
+    fn run_mut(&mut self, ctx: &mut Context) -> Result<()> {
+        self.run(ctx)
+    }
+
+    /// See [`Command::timeout`].
+    fn timeout(&self) -> Option<Duration> {
+        None
+    }
+
+    /// See [`Command::deprecated`].
+    fn deprecated(&self) -> Option<Deprecation> {
+        None
+    }
+
+    /// See [`Command::confirm`].
+    fn confirm(&self) -> Option<Confirmation> {
+        None
+    }
+
+    /// See [`Command::retry`].
+    fn retry(&self) -> Option<RetryPolicy> {
+        None
+    }
+
+    /// See [`Command::lock`].
+    #[cfg(feature = "lock")]
+    fn lock(&self) -> Option<Lock> {
+        None
+    }
+
+    /// See [`Command::overrides`].
+    fn overrides(&self) -> Option<Overrides> {
+        None
+    }
+
+    /// See [`Command::run_policy`].
+    fn run_policy(&self) -> Option<RunPolicy> {
+        None
+    }
+
+    /// See [`Command::requirements`].
+    #[cfg(feature = "requirements")]
+    fn requirements(&self) -> Vec<Requirement> {
+        Vec::new()
+    }
+
+    /// See [`Command::verbosity`].
+    #[cfg(feature = "verbosity")]
+    fn verbosity(&self) -> Option<tracing::level_filters::LevelFilter> {
+        None
+    }
+
+    /// See [`Command::activity`].
+    fn activity(&self) -> Option<&'static str> {
+        None
+    }
+
+    /// See [`Command::telemetry`].
+    fn telemetry(&self) -> bool {
+        self.activity().is_some()
+    }
+
+    /// See [`Command::dry_run`].
+    fn dry_run(&self, _: &mut Context) -> Result<()> {
+        Ok(())
+    }
+
+    /// See [`Command::on_signal`].
+    fn on_signal(&self, _: Signal) -> Result<()> {
+        Ok(())
+    }
+
+    /// See [`Command::on_error`].
+    fn on_error(&self, _: &mut Context, err: Report) -> Report {
+        err
+    }
+
+    /// See [`Command::post_run`].
+    fn post_run(&self, _: &mut Context, _: &Result<()>) -> Result<()> {
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl<T: SyncCommand> Command for T {
+    async fn pre_run(&self, ctx: &mut Context) -> Result<PreRun> {
+        SyncCommand::pre_run(self, ctx)
+    }
+
+    #[cfg(feature = "first-run")]
+    async fn on_first_run(&self, ctx: &mut Context) -> Result<()> {
+        SyncCommand::on_first_run(self, ctx)
+    }
+
+    async fn run(&self, ctx: &mut Context) -> Result<()> {
+        SyncCommand::run(self, ctx)
+    }
+
+    async fn run_mut(&mut self, ctx: &mut Context) -> Result<()> {
+        SyncCommand::run_mut(self, ctx)
+    }
+
+    fn timeout(&self) -> Option<Duration> {
+        SyncCommand::timeout(self)
+    }
+
+    fn deprecated(&self) -> Option<Deprecation> {
+        SyncCommand::deprecated(self)
+    }
+
+    fn confirm(&self) -> Option<Confirmation> {
+        SyncCommand::confirm(self)
+    }
+
+    fn retry(&self) -> Option<RetryPolicy> {
+        SyncCommand::retry(self)
+    }
+
+    #[cfg(feature = "lock")]
+    fn lock(&self) -> Option<Lock> {
+        SyncCommand::lock(self)
+    }
+
+    fn overrides(&self) -> Option<Overrides> {
+        SyncCommand::overrides(self)
+    }
+
+    fn run_policy(&self) -> Option<RunPolicy> {
+        SyncCommand::run_policy(self)
+    }
+
+    #[cfg(feature = "requirements")]
+    fn requirements(&self) -> Vec<Requirement> {
+        SyncCommand::requirements(self)
+    }
+
+    #[cfg(feature = "verbosity")]
+    fn verbosity(&self) -> Option<tracing::level_filters::LevelFilter> {
+        SyncCommand::verbosity(self)
+    }
+
+    fn activity(&self) -> Option<&'static str> {
+        SyncCommand::activity(self)
+    }
+
+    fn telemetry(&self) -> bool {
+        SyncCommand::telemetry(self)
+    }
+
+    async fn dry_run(&self, ctx: &mut Context) -> Result<()> {
+        SyncCommand::dry_run(self, ctx)
+    }
+
+    async fn on_signal(&self, signal: Signal) -> Result<()> {
+        SyncCommand::on_signal(self, signal)
+    }
+
+    async fn on_error(&self, ctx: &mut Context, err: Report) -> Report {
+        SyncCommand::on_error(self, ctx, err)
+    }
+
+    async fn post_run(&self, ctx: &mut Context, result: &Result<()>) -> Result<()> {
+        SyncCommand::post_run(self, ctx, result)
+    }
+}
+
+/// The runtime that [`crate::execute_sync`] runs the lifecycle on.
+pub(crate) fn runtime() -> Result<tokio::runtime::Runtime> {
+    // `block_on` panics inside of another runtime.
+    if tokio::runtime::Handle::try_current().is_ok() {
+        return Err(eyre!(
+            "`execute_sync` can't run inside of an async runtime, use `execute` instead"
+        ));
+    }
+
+    Ok(tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()?)
+}
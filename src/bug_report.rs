@@ -0,0 +1,255 @@
+//! A `bug-report` subcommand that gathers everything needed for an issue.
+//!
+//! Users run `my-cli bug-report` and paste the output into an issue instead of
+//! being asked for their version, OS and logs one at a time. The report
+//! contains:
+//! - The name, version and build information of the CLI.
+//! - The OS and architecture.
+//! - The most recent lines of the CLI's log file, when one has been configured
+//!   with [`Reporter::with_log`].
+//! - The most recent crash report, when a [`Crash`] has been configured.
+//!
+//! Some things to note:
+//! - The home directory is replaced with `~` everywhere. Crash reports already
+//!   have their argument values redacted, see [`crate::crash::redact`].
+//! - Pretty output is Markdown, JSON and YAML are available via
+//!   [`crate::output::Format`].
+//! - [`Reporter::install`] must be called before the subcommand runs.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use cata::{
+//!   bug_report::{BugReport, Reporter},
+//!   crash::Crash,
+//!   Command, Container,
+//! };
+//! use clap::{Parser, Subcommand};
+//!
+//! #[derive(Parser, Container)]
+//! struct Root {
+//!   #[command(subcommand)]
+//!   cmd: RootCmd,
+//! }
+//!
+//! impl Command for Root {}
+//!
+//! #[derive(Subcommand, Container)]
+//! enum RootCmd {
+//!   BugReport(BugReport),
+//! }
+//!
+//! #[tokio::main]
+//! async fn main() -> eyre::Result<()> {
+//!   let name = env!("CARGO_PKG_NAME");
+//!   let version = env!("CARGO_PKG_VERSION");
+//!
+//!   Reporter::new(name, version)
+//!     .with_crash(Crash::new(name, version))
+//!     .install()?;
+//!
+//!   cata::execute(&Root::parse()).await
+//! }
+//! ```
+use std::{
+    fmt::{self, Display, Write as _},
+    path::PathBuf,
+    sync::OnceLock,
+};
+
+use eyre::{eyre, Result};
+use serde::Serialize;
+
+use crate::{
+    command::Container,
+    crash::Crash,
+    describe::{ArgDescriptor, Describe, Descriptor},
+    output::Format,
+    Command, Context,
+};
+
+static DEFAULT_LINES: usize = 50;
+static GLOBAL: OnceLock<Reporter> = OnceLock::new();
+
+/// Collects the contents of a bug report.
+///
+/// See the module documentation for usage.
+#[derive(Clone, Debug)]
+pub struct Reporter {
+    name: String,
+    version: String,
+    build: Option<String>,
+    crash: Option<Crash>,
+    log: Option<PathBuf>,
+    lines: usize,
+}
+
+impl Reporter {
+    /// Create a reporter for the CLI `name` running `version`.
+    pub fn new(name: impl AsRef<str>, version: impl AsRef<str>) -> Self {
+        Self {
+            name: name.as_ref().into(),
+            version: version.as_ref().into(),
+            build: None,
+            crash: None,
+            log: None,
+            lines: DEFAULT_LINES,
+        }
+    }
+
+    /// Add build information, such as the git SHA or build date.
+    #[must_use]
+    pub fn with_build(mut self, build: impl Into<String>) -> Self {
+        self.build = Some(build.into());
+        self
+    }
+
+    /// Include the latest crash report written by `crash`.
+    #[must_use]
+    pub fn with_crash(mut self, crash: Crash) -> Self {
+        self.crash = Some(crash);
+        self
+    }
+
+    /// Include the end of the log file at `path`.
+    #[must_use]
+    pub fn with_log(mut self, path: impl Into<PathBuf>) -> Self {
+        self.log = Some(path.into());
+        self
+    }
+
+    /// Set how many lines of the log file are included.
+    ///
+    /// By default, this is 50.
+    #[must_use]
+    pub fn with_lines(mut self, lines: usize) -> Self {
+        self.lines = lines;
+        self
+    }
+
+    /// Gather the report.
+    ///
+    /// Missing log files and crash reports are skipped, anything else that
+    /// can't be read is an error.
+    pub fn collect(&self) -> Result<Report> {
+        let log = match self.log.as_ref().map(std::fs::read_to_string) {
+            Some(Ok(raw)) => {
+                let lines = raw.lines().collect::<Vec<_>>();
+
+                lines[lines.len().saturating_sub(self.lines)..]
+                    .iter()
+                    .map(|line| redact(line))
+                    .collect()
+            }
+            Some(Err(e)) if e.kind() != std::io::ErrorKind::NotFound => return Err(e.into()),
+            _ => Vec::new(),
+        };
+
+        let crash = self
+            .crash
+            .as_ref()
+            .and_then(Crash::latest)
+            .map(std::fs::read_to_string)
+            .transpose()?
+            .map(|raw| redact(&raw));
+
+        Ok(Report {
+            name: self.name.clone(),
+            version: self.version.clone(),
+            build: self.build.clone(),
+            os: std::env::consts::OS.into(),
+            arch: std::env::consts::ARCH.into(),
+            log,
+            crash,
+        })
+    }
+
+    /// Make this the reporter used by the [`BugReport`] subcommand.
+    pub fn install(self) -> Result<()> {
+        GLOBAL
+            .set(self)
+            .map_err(|_| eyre!("a bug reporter has already been installed"))
+    }
+}
+
+/// Replace the home directory with `~`.
+fn redact(text: &str) -> String {
+    match ::dirs::home_dir() {
+        Some(home) if !home.as_os_str().is_empty() => {
+            text.replace(home.to_string_lossy().as_ref(), "~")
+        }
+        _ => text.into(),
+    }
+}
+
+/// A bug report, ready to be attached to an issue.
+#[derive(Clone, Debug, Serialize)]
+pub struct Report {
+    /// Name of the CLI.
+    pub name: String,
+    /// Version of the CLI.
+    pub version: String,
+    /// Build information, if provided.
+    pub build: Option<String>,
+    /// Operating system.
+    pub os: String,
+    /// CPU architecture.
+    pub arch: String,
+    /// The most recent lines of the log file.
+    pub log: Vec<String>,
+    /// Contents of the latest crash report.
+    pub crash: Option<String>,
+}
+
+impl Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut out = String::new();
+
+        writeln!(out, "## Environment\n")?;
+        writeln!(out, "- {}: {}", self.name, self.version)?;
+        if let Some(build) = &self.build {
+            writeln!(out, "- build: {build}")?;
+        }
+        writeln!(out, "- os: {} ({})", self.os, self.arch)?;
+
+        if !self.log.is_empty() {
+            writeln!(out, "\n## Log\n\n```\n{}\n```", self.log.join("\n"))?;
+        }
+
+        if let Some(crash) = &self.crash {
+            writeln!(out, "\n## Crash\n\n```\n{}\n```", crash.trim_end())?;
+        }
+
+        write!(f, "{}", out.trim_end())
+    }
+}
+
+/// Print a bug report to paste into an issue.
+#[derive(clap::Args, Clone, Debug)]
+pub struct BugReport {
+    /// Format of the report, pretty is Markdown.
+    #[arg(short, long, value_enum, default_value_t)]
+    output: Format,
+}
+
+impl Container for BugReport {}
+
+impl Describe for BugReport {
+    const DESCRIPTOR: Descriptor = Descriptor::new("bug-report")
+        .with_about("Print a bug report to paste into an issue")
+        .with_args(&[ArgDescriptor::new("output")
+            .with_long("output")
+            .with_short('o')
+            .with_help("Format of the report, pretty is Markdown")]);
+}
+
+#[async_trait::async_trait]
+impl Command for BugReport {
+    async fn run(&self, _: &mut Context) -> Result<()> {
+        let reporter = GLOBAL
+            .get()
+            .ok_or_else(|| eyre!("a bug reporter must be installed to create reports"))?;
+
+        self.output.document(&reporter.collect()?)
+    }
+}
@@ -0,0 +1,365 @@
+//! Cache expensive lookups on disk.
+//!
+//! Commands that repeatedly hit slow APIs can keep the results around between
+//! runs. Values are anything that implements [`serde::Serialize`] and expire
+//! after a TTL. The cache lives in the platform cache directory (see
+//! [`crate::dirs`]).
+//!
+//! Some things to note:
+//! - Keys are hashed before being used as file names, so any string works.
+//! - With [`Cache::with_max_size`], the least recently written entries are
+//!   evicted once the cache grows past the limit.
+//! - A disabled cache never returns anything and never writes. Wire this up to
+//!   a `--no-cache` flag with [`Cache::with_enabled`].
+//!
+//! # Examples
+//!
+//! ```
+//! use std::time::Duration;
+//!
+//! use cata::{cache::Cache, Command, Container};
+//! use clap::Parser;
+//!
+//! #[derive(Parser, Container)]
+//! struct Root {
+//!   /// Always fetch fresh results.
+//!   #[arg(long, global = true)]
+//!   no_cache: bool,
+//! }
+//!
+//! #[async_trait::async_trait]
+//! impl Command for Root {
+//!   async fn run(&self, _: &mut cata::Context) -> eyre::Result<()> {
+//!     let cache = Cache::new("my-cli")
+//!       .with_ttl(Duration::from_secs(60))
+//!       .with_enabled(!self.no_cache);
+//!
+//!     let clusters: Vec<String> = cache
+//!       .get_or_insert_with("clusters", || async { Ok(vec!["production".to_string()]) })
+//!       .await?;
+//!
+//!     println!("{clusters:?}");
+//!
+//!     Ok(())
+//!   }
+//! }
+//! ```
+use std::{
+    future::Future,
+    path::PathBuf,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use eyre::{eyre, Result};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::{dirs::Dirs, hex};
+
+static DEFAULT_TTL: Duration = Duration::from_secs(60 * 60);
+
+#[derive(Serialize, Deserialize)]
+struct Entry<T> {
+    expires_at: u64,
+    value: T,
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs())
+}
+
+/// A TTL based cache on disk.
+///
+/// See the module documentation for usage.
+#[derive(Clone, Debug)]
+pub struct Cache {
+    dir: Option<PathBuf>,
+    ttl: Duration,
+    max_size: Option<u64>,
+    enabled: bool,
+}
+
+impl Cache {
+    /// Create a cache for the CLI `name`.
+    pub fn new(name: impl AsRef<str>) -> Self {
+        Self {
+            dir: Dirs::new(name).cache().map(|dir| dir.join("cache")),
+            ttl: DEFAULT_TTL,
+            max_size: None,
+            enabled: true,
+        }
+    }
+
+    /// Set the directory entries are stored in.
+    #[must_use]
+    pub fn with_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.dir = Some(dir.into());
+        self
+    }
+
+    /// Set how long entries are valid for.
+    ///
+    /// By default, this is one hour.
+    #[must_use]
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    /// Cap the total size of the cache in bytes.
+    #[must_use]
+    pub fn with_max_size(mut self, bytes: u64) -> Self {
+        self.max_size = Some(bytes);
+        self
+    }
+
+    /// Enable or disable the cache, typically from a `--no-cache` flag.
+    #[must_use]
+    pub fn with_enabled(mut self, enabled: bool) -> Self {
+        self.enabled = enabled;
+        self
+    }
+
+    /// Whether entries can be stored at all, the cache is enabled and has a
+    /// directory.
+    #[cfg(feature = "update")]
+    pub(crate) fn is_available(&self) -> bool {
+        self.enabled && self.dir.is_some()
+    }
+
+    fn path(&self, key: &str) -> Option<PathBuf> {
+        if !self.enabled {
+            return None;
+        }
+
+        let digest = ring::digest::digest(&ring::digest::SHA256, key.as_bytes());
+
+        self.dir
+            .as_ref()
+            .map(|dir| dir.join(hex::encode(digest.as_ref())))
+    }
+
+    /// Retrieve the value stored under `key` if it hasn't expired.
+    ///
+    /// Entries that can't be read or deserialized are treated as missing.
+    #[must_use]
+    pub fn get<T: DeserializeOwned>(&self, key: &str) -> Option<T> {
+        let raw = std::fs::read_to_string(self.path(key)?).ok()?;
+        let entry: Entry<T> = serde_json::from_str(&raw).ok()?;
+
+        (entry.expires_at > now()).then_some(entry.value)
+    }
+
+    /// Store `value` under `key`.
+    ///
+    /// This is a no-op when the cache is disabled.
+    pub fn set<T: Serialize>(&self, key: &str, value: &T) -> Result<()> {
+        let Some(path) = self.path(key) else {
+            return Ok(());
+        };
+
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        let entry = Entry {
+            expires_at: now() + self.ttl.as_secs(),
+            value,
+        };
+        std::fs::write(&path, serde_json::to_string(&entry)?)?;
+
+        self.evict()
+    }
+
+    /// Return the cached value or compute, store and return a fresh one.
+    pub async fn get_or_insert_with<T, F, Fut>(&self, key: &str, f: F) -> Result<T>
+    where
+        T: Serialize + DeserializeOwned,
+        F: FnOnce() -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        if let Some(value) = self.get(key) {
+            return Ok(value);
+        }
+
+        let value = f().await?;
+        self.set(key, &value)?;
+
+        Ok(value)
+    }
+
+    /// Remove the value stored under `key`.
+    pub fn remove(&self, key: &str) -> Result<()> {
+        match self.path(key).map(std::fs::remove_file) {
+            Some(Err(e)) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Remove every entry.
+    pub fn clear(&self) -> Result<()> {
+        let dir = self
+            .dir
+            .as_ref()
+            .ok_or_else(|| eyre!("no cache directory available"))?;
+
+        match std::fs::remove_dir_all(dir) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Remove the oldest entries until the cache is under `max_size`.
+    fn evict(&self) -> Result<()> {
+        let (Some(max), Some(dir)) = (self.max_size, self.dir.as_ref()) else {
+            return Ok(());
+        };
+
+        let mut entries = std::fs::read_dir(dir)?
+            .filter_map(|entry| {
+                let entry = entry.ok()?;
+                let meta = entry.metadata().ok()?;
+
+                Some((meta.modified().ok()?, meta.len(), entry.path()))
+            })
+            .collect::<Vec<_>>();
+        entries.sort();
+
+        let mut total: u64 = entries.iter().map(|(_, len, _)| len).sum();
+
+        for (_, len, path) in entries {
+            if total <= max {
+                break;
+            }
+
+            std::fs::remove_file(path)?;
+            total -= len;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{now, Cache, Entry};
+
+    /// A cache of its own for `name`, starting out empty.
+    fn cache(name: &str) -> Cache {
+        let dir = std::env::temp_dir().join(format!("cata-cache-{name}-{}", std::process::id()));
+        std::fs::remove_dir_all(&dir).ok();
+
+        Cache::new("cata").with_dir(dir)
+    }
+
+    /// Write `value` under `key`, expiring at `expires_at`.
+    fn write(cache: &Cache, key: &str, value: u32, expires_at: u64) {
+        let path = cache.path(key).unwrap();
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+
+        let entry = Entry { expires_at, value };
+        std::fs::write(path, serde_json::to_string(&entry).unwrap()).unwrap();
+    }
+
+    #[test]
+    fn fresh() {
+        let cache = cache("fresh");
+
+        cache.set("key", &1u32).unwrap();
+        assert_eq!(cache.get::<u32>("key"), Some(1));
+
+        write(&cache, "later", 2, now() + 60);
+        assert_eq!(cache.get::<u32>("later"), Some(2));
+
+        cache.clear().unwrap();
+    }
+
+    #[test]
+    fn expired() {
+        let cache = cache("expired");
+
+        write(&cache, "earlier", 1, now() - 1);
+        assert_eq!(cache.get::<u32>("earlier"), None);
+
+        // Expiring now is already too late.
+        write(&cache, "now", 2, now());
+        assert_eq!(cache.get::<u32>("now"), None);
+
+        cache.clear().unwrap();
+    }
+
+    #[test]
+    fn no_ttl_expires_immediately() {
+        let cache = cache("no-ttl").with_ttl(Duration::ZERO);
+
+        cache.set("key", &1u32).unwrap();
+        assert_eq!(cache.get::<u32>("key"), None);
+
+        cache.clear().unwrap();
+    }
+
+    #[tokio::test]
+    async fn expired_entries_are_replaced() {
+        let cache = cache("replaced");
+        write(&cache, "key", 1, now() - 1);
+
+        let value = cache
+            .get_or_insert_with("key", || async { Ok(2u32) })
+            .await
+            .unwrap();
+        assert_eq!(value, 2);
+
+        let value = cache
+            .get_or_insert_with("key", || async { Ok(3u32) })
+            .await
+            .unwrap();
+        assert_eq!(value, 2);
+
+        cache.clear().unwrap();
+    }
+
+    #[test]
+    fn unreadable_entries_are_missing() {
+        let cache = cache("unreadable");
+
+        cache.set("key", &"value").unwrap();
+        assert_eq!(cache.get::<u32>("key"), None);
+
+        std::fs::write(cache.path("key").unwrap(), "not json").unwrap();
+        assert_eq!(cache.get::<String>("key"), None);
+
+        cache.clear().unwrap();
+    }
+
+    #[test]
+    fn disabled() {
+        let cache = cache("disabled");
+        let disabled = cache.clone().with_enabled(false);
+
+        disabled.set("key", &1u32).unwrap();
+        assert_eq!(cache.get::<u32>("key"), None);
+
+        cache.set("key", &1u32).unwrap();
+        assert_eq!(disabled.get::<u32>("key"), None);
+
+        cache.clear().unwrap();
+    }
+
+    #[test]
+    fn evicts_the_oldest_entries() {
+        let cache = cache("evict");
+
+        cache.set("first", &"a".repeat(64)).unwrap();
+        std::thread::sleep(Duration::from_millis(20));
+        let cache = cache.with_max_size(100);
+        cache.set("second", &"b".repeat(64)).unwrap();
+
+        assert_eq!(cache.get::<String>("first"), None);
+        assert_eq!(cache.get::<String>("second"), Some("b".repeat(64)));
+
+        cache.clear().unwrap();
+    }
+}
@@ -38,7 +38,40 @@
 //! ```
 //!
 //! [examples/basic]: https://github.com/grampelberg/cata/blob/main/examples/basic/src/main.rs
-use eyre::Result;
+use std::{
+    ffi::{OsStr, OsString},
+    fmt,
+    io::{BufRead, IsTerminal, Write},
+    marker::PhantomData,
+    path::{Path, PathBuf},
+    sync::Arc,
+    time::Duration,
+};
+
+use eyre::{eyre, Report, Result};
+
+#[cfg(feature = "background")]
+use crate::background;
+#[cfg(feature = "events")]
+use crate::events::Subscriber;
+#[cfg(feature = "first-run")]
+use crate::first_run::FirstRun;
+#[cfg(feature = "layer")]
+use crate::layer::Layer;
+#[cfg(feature = "lock")]
+use crate::lock::Lock;
+#[cfg(feature = "requirements")]
+use crate::requirements::Requirement;
+#[cfg(feature = "timings")]
+use crate::timings::Recorder;
+use crate::{
+    flags::Flags,
+    lifecycle::Layers,
+    runtime::{self, Runtime},
+    Context,
+};
+
+static MAX_DEPTH: usize = 64;
 
 /// The base structure for commands.
 ///
@@ -50,27 +83,287 @@ use eyre::Result;
 /// only what they need. This primarily results in parent commands implementing
 /// pre/post run and child commands implementing run.
 ///
+/// Every hook receives the [`Context`] for the current execution. Parents can
+/// provide services on it, such as API clients, that their children retrieve.
+///
 /// Commands are called recursively, starting at the root command and traversing
 /// through all the subcommands that were successfully parsed. The `pre-run` and
 /// `run` hooks are called first on the parent before recursing into the child.
 /// Subsequently, `post-run` is called first on the child as it recurses up to
-/// the parent.
+/// the parent. See [`RunPolicy`] and [`PostRunOrder`] to change this.
+///
+/// # Migrating from synchronous hooks
+///
+/// `pre_run` and `post_run` used to be synchronous. Existing implementations
+/// keep working by marking them `async`, adding the new arguments and
+/// adding `#[async_trait]` to the impl. `pre_run` now returns [`PreRun`],
+/// otherwise their bodies don't need to change:
+///
+/// ```
+/// use cata::{command::PreRun, Command, Container};
+///
+/// #[derive(clap::Parser, Container)]
+/// struct Root {}
+///
+/// #[async_trait::async_trait]
+/// impl Command for Root {
+///   async fn pre_run(&self, _: &mut cata::Context) -> eyre::Result<PreRun> {
+///     println!("setting up");
+///
+///     Ok(PreRun::Continue)
+///   }
+/// }
+/// ```
+///
+/// Anything that blocks for a long time should move to
+/// [`tokio::task::spawn_blocking`].
 ///
 /// [`Parser`]: clap::Parser
+#[diagnostic::on_unimplemented(
+    message = "`{Self}` is not a command",
+    label = "not a command",
+    note = "implement `cata::Command` for `{Self}`, or add `#[derive(cata::Command)]` to it"
+)]
 #[async_trait::async_trait]
 pub trait Command: Send + Sync + Container {
     /// Performs any setup required before the command is run.
-    fn pre_run(&self) -> Result<()> {
+    ///
+    /// Returning [`PreRun::Skip`] decides there is nothing to do without it
+    /// being an error, see [`PreRun`].
+    async fn pre_run(&self, _: &mut Context) -> Result<PreRun> {
+        Ok(PreRun::Continue)
+    }
+
+    /// Performs one-time setup the first time the CLI runs on a machine.
+    ///
+    /// This is only called when a [`crate::first_run::FirstRun`] has been
+    /// passed to [`ExecuteOptions::with_first_run`], right before `pre_run`.
+    /// Returning an error stops execution and the next run is treated as the
+    /// first one again. By default, nothing happens.
+    #[cfg(feature = "first-run")]
+    async fn on_first_run(&self, _: &mut Context) -> Result<()> {
         Ok(())
     }
 
     /// Execution of the command.
-    async fn run(&self) -> Result<()> {
+    async fn run(&self, _: &mut Context) -> Result<()> {
+        Ok(())
+    }
+
+    /// Execution of the command when the tree is run with
+    /// [`crate::execute_mut`].
+    ///
+    /// By default, this calls [`Command::run`].
+    async fn run_mut(&mut self, ctx: &mut Context) -> Result<()> {
+        self.run(ctx).await
+    }
+
+    /// How long `run` is allowed to take before it is abandoned.
+    ///
+    /// Once the timeout expires, `run` is dropped and [`crate::execute`]
+    /// returns [`TimedOut`]. Teardown still happens, so every `post_run` sees
+    /// the error. The default, `None`, waits forever.
+    ///
+    /// ```
+    /// use std::time::Duration;
+    ///
+    /// use cata::{command::TimedOut, Command, Container};
+    /// use clap::Parser;
+    ///
+    /// #[derive(Parser, Container)]
+    /// struct Root {}
+    ///
+    /// #[async_trait::async_trait]
+    /// impl Command for Root {
+    ///   fn timeout(&self) -> Option<Duration> {
+    ///     Some(Duration::from_millis(10))
+    ///   }
+    ///
+    ///   async fn run(&self, _: &mut cata::Context) -> eyre::Result<()> {
+    ///     std::future::pending().await
+    ///   }
+    /// }
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let err = cata::execute(&Root::parse_from(["root"])).await.unwrap_err();
+    /// assert!(err.is::<TimedOut>());
+    /// # }
+    /// ```
+    fn timeout(&self) -> Option<Duration> {
+        None
+    }
+
+    /// Marks the command as deprecated.
+    ///
+    /// A warning built from the [`Deprecation`] is printed to stderr when the
+    /// command is reached, before its `pre_run`. By default, commands aren't
+    /// deprecated.
+    fn deprecated(&self) -> Option<Deprecation> {
+        None
+    }
+
+    /// Asks the user to confirm before `run` is called.
+    ///
+    /// Destructive commands return a [`Confirmation`] here. The user is
+    /// prompted on a terminal and has to pass `--yes` otherwise, see [`Yes`].
+    /// Declining skips `run` and every subcommand without an error. There is
+    /// nothing to confirm for dry runs. By default, nothing is asked.
+    fn confirm(&self) -> Option<Confirmation> {
+        None
+    }
+
+    /// Retries `run` when it fails.
+    ///
+    /// Hooks that talk to flaky or rate-limited services return a
+    /// [`RetryPolicy`] here instead of looping themselves. Only `run` is
+    /// retried, [`Command::timeout`] covers every attempt and cancellation
+    /// stops retrying. By default, failures are final.
+    fn retry(&self) -> Option<RetryPolicy> {
+        None
+    }
+
+    /// Keeps other invocations from running at the same time, see
+    /// [`crate::lock`].
+    ///
+    /// The lock is taken before `pre_run` and released after `post_run`, even
+    /// when the command fails. By default, nothing is locked.
+    #[cfg(feature = "lock")]
+    fn lock(&self) -> Option<Lock> {
+        None
+    }
+
+    /// The working directory and environment that `run` sees, see
+    /// [`Overrides`].
+    ///
+    /// Wrappers that shell out to other tools return the directory and
+    /// variables those tools expect, and start them with [`Context::command`].
+    /// The process's own are never changed. By default, there are none.
+    fn overrides(&self) -> Option<Overrides> {
+        None
+    }
+
+    /// Whether `run` is called on this command, see [`RunPolicy`].
+    ///
+    /// This wins over [`ExecuteOptions::with_run_policy`]. By default, the
+    /// policy of the execution is used.
+    fn run_policy(&self) -> Option<RunPolicy> {
+        None
+    }
+
+    /// Prerequisites that have to be met before anything runs, see
+    /// [`crate::requirements`].
+    ///
+    /// The requirements of every command that was parsed are checked before
+    /// the root's `pre_run`. By default, there are none.
+    #[cfg(feature = "requirements")]
+    fn requirements(&self) -> Vec<Requirement> {
+        Vec::new()
+    }
+
+    /// The level to log at, see [`crate::verbosity`].
+    ///
+    /// By default, the command has no opinion and the next one down the tree
+    /// is asked. When no command has a level, no subscriber is installed.
+    #[cfg(feature = "verbosity")]
+    fn verbosity(&self) -> Option<tracing::level_filters::LevelFilter> {
+        None
+    }
+
+    /// The `activity` of the span around `run`, which is what
+    /// [`crate::telemetry`] reports it as.
+    ///
+    /// By default, this is [`Context::path`], such as `my-cli.deploy`.
+    /// `#[cata(activity = "...")]` sets it with `#[derive(Command)]`, which
+    /// also opts the command into [`Command::telemetry`].
+    fn activity(&self) -> Option<&'static str> {
+        None
+    }
+
+    /// Whether the span around `run` has an `activity`, which is what makes
+    /// [`crate::telemetry`] report it.
+    ///
+    /// By default, only commands with an [`Command::activity`] are reported,
+    /// nothing is sent for commands that never asked for it. A bare
+    /// `#[cata(telemetry)]` opts in with `#[derive(Command)]`, reporting the
+    /// command as its [`Context::path`].
+    fn telemetry(&self) -> bool {
+        self.activity().is_some()
+    }
+
+    /// Describes what `run` would do, without doing it.
+    ///
+    /// This is called instead of `run` when dry-run is enabled, see
+    /// [`DryRun`]. `pre_run` and `post_run` are called as usual and can check
+    /// [`Context::dry_run`] themselves. By default, nothing happens.
+    async fn dry_run(&self, _: &mut Context) -> Result<()> {
+        Ok(())
+    }
+
+    /// Reacts to a signal sent to the process while the command tree is
+    /// running.
+    ///
+    /// Every command that has started is notified, child first, before
+    /// teardown. [`Signal::Terminate`] also cancels [`Context::token`], so that
+    /// the running command winds down. Returning an error stops execution with
+    /// that error. Signals are only delivered on unix.
+    ///
+    /// With [`crate::execute_mut`], the running command is borrowed mutably and
+    /// signals are delivered once its `run_mut` returns. The same goes for
+    /// signals that arrive during `pre_run` or while asking for confirmation.
+    /// Once teardown has started, [`Signal::Terminate`] only cancels the token.
+    /// Outside of an execution, signals end the process like they would without
+    /// cata, see [`crate::runtime`].
+    async fn on_signal(&self, _: Signal) -> Result<()> {
         Ok(())
     }
 
+    /// Handles an error from this command or one of its subcommands.
+    ///
+    /// This is called child first while unwinding, on every command whose
+    /// `pre_run` succeeded and right before its `post_run`. The error that is
+    /// returned replaces the original, which makes this the place to add
+    /// context or suggestions that only this command knows about. By default,
+    /// the error is returned untouched.
+    ///
+    /// ```
+    /// use cata::{Command, Container};
+    /// use clap::Parser;
+    /// use eyre::Report;
+    ///
+    /// #[derive(Parser, Container)]
+    /// struct Root {}
+    ///
+    /// #[async_trait::async_trait]
+    /// impl Command for Root {
+    ///   async fn run(&self, _: &mut cata::Context) -> eyre::Result<()> {
+    ///     Err(eyre::eyre!("not logged in"))
+    ///   }
+    ///
+    ///   async fn on_error(&self, _: &mut cata::Context, err: Report) -> Report {
+    ///     err.wrap_err("could not list projects")
+    ///   }
+    /// }
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() {
+    /// let err = cata::execute(&Root::parse_from(["root"])).await.unwrap_err();
+    /// assert_eq!(err.to_string(), "could not list projects");
+    /// # }
+    /// ```
+    async fn on_error(&self, _: &mut Context, err: Report) -> Report {
+        err
+    }
+
     /// Performs any cleanup required after the command is run.
-    fn post_run(&self) -> Result<()> {
+    ///
+    /// This is called whenever `pre_run` succeeded, even if `run` or a
+    /// subcommand failed or panicked, or execution was cancelled. `result` is
+    /// the outcome of everything below and including this command, so cleanup
+    /// can differ between success and failure. The original error is still
+    /// returned from [`crate::execute`], returning it again here is not
+    /// required.
+    async fn post_run(&self, _: &mut Context, _: &Result<()>) -> Result<()> {
         Ok(())
     }
 }
@@ -81,9 +374,1642 @@ pub trait Command: Send + Sync + Container {
 /// if it needs to recurse into a subcommand. While it is possible to implement
 /// this yourself, it is recommended that `#[derive(Command)]` is used to
 /// automatically generate the code required to switch between subcommand enums.
+#[diagnostic::on_unimplemented(
+    message = "`{Self}` is not a container of commands",
+    label = "not a container",
+    note = "add `#[derive(cata::Container)]` to `{Self}`"
+)]
 pub trait Container {
     /// Optionally returns the next command to be run.
     fn next(&self) -> Option<&dyn Command> {
         None
     }
+
+    /// Optionally returns the next command to be run, mutably.
+    ///
+    /// This must return the same command as `next()`, it is used by
+    /// [`crate::execute_mut`].
+    fn next_mut(&mut self) -> Option<&mut dyn Command> {
+        None
+    }
+
+    /// The command to run next when this is the `#[command(subcommand)]` of
+    /// its parent, which the parent's `next()` returns.
+    ///
+    /// By default, this is `next()`. Enums with variants that have named fields,
+    /// or none at all, are the command themselves for those variants.
+    fn subcommand(&self) -> Option<&dyn Command> {
+        self.next()
+    }
+
+    /// The command to run next when this is the `#[command(subcommand)]` of
+    /// its parent, mutably.
+    ///
+    /// This must return the same command as `subcommand()`.
+    fn subcommand_mut(&mut self) -> Option<&mut dyn Command> {
+        self.next_mut()
+    }
+
+    /// The name of the command returned by `next()`.
+    ///
+    /// This is the kebab-case name that clap matches on the command line, or
+    /// the `name` provided via `#[command(name = "...")]` on the variant.
+    fn path_segment(&self) -> Option<&'static str> {
+        None
+    }
+
+    /// The name clap has for this command when it is the root, which starts
+    /// [`Context::path`].
+    ///
+    /// `#[derive(Container)]` on a struct returns its `#[command(name =
+    /// "...")]` or, like clap, the name of the package.
+    fn command_name(&self) -> Option<&'static str> {
+        None
+    }
+
+    /// The names of every subcommand that could be returned by `next()`.
+    fn children(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    /// The feature flag that gates the command returned by `next()`, see
+    /// [`crate::flags`].
+    ///
+    /// This comes from `#[cata(flag = "...")]` on the variant.
+    fn flag(&self) -> Option<&'static str> {
+        None
+    }
+
+    /// Provide the fields marked with `#[cata(global)]` to the commands below
+    /// this one, see [`Context::global`].
+    ///
+    /// This is called right before the command's `pre_run`.
+    fn provide_globals(&self, _: &mut Context) {}
+
+    /// Every gated subcommand below this one, as the path from here and the
+    /// name of its flag.
+    #[must_use]
+    fn flags() -> Vec<(Vec<&'static str>, &'static str)>
+    where
+        Self: Sized,
+    {
+        Vec::new()
+    }
+
+    /// Example invocations of this command, see [`crate::examples`].
+    ///
+    /// This comes from `#[cata(example = "...")]` on the struct.
+    fn examples(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    /// The examples of this command and every subcommand below it that has
+    /// some, along with the path from here.
+    #[must_use]
+    fn all_examples() -> Vec<(Vec<&'static str>, &'static [&'static str])>
+    where
+        Self: Sized,
+    {
+        Vec::new()
+    }
+
+    /// The subcommand to run when this is an optional `#[command(subcommand)]`
+    /// that wasn't passed, freshly built.
+    ///
+    /// This comes from `#[cata(default)]` on the variant. It is an error when
+    /// the variant can't be parsed without arguments of its own.
+    #[must_use]
+    fn default_subcommand() -> Option<Result<Self, clap::Error>>
+    where
+        Self: Sized,
+    {
+        None
+    }
+
+    /// The same subcommand as `default_subcommand()`, shared.
+    ///
+    /// It is built the first time it is needed and kept around for the rest of
+    /// the process.
+    #[must_use]
+    fn shared_default_subcommand() -> Option<Result<&'static dyn Container, &'static clap::Error>>
+    where
+        Self: Sized,
+    {
+        None
+    }
+}
+
+/// The command held by a variant of a `#[derive(Container)]` enum.
+///
+/// Variants usually hold the command itself. Subcommands that are built at
+/// runtime or loaded lazily, such as from a plugin manifest, can be held as a
+/// `Box<dyn Command>` or an `Arc<dyn Command>` instead and are dispatched to in
+/// the same way. Their gated subcommands aren't known until then, they are
+/// missing from [`Container::flags`] but still refused when reached. The same
+/// goes for [`Container::all_examples`].
+///
+/// ```
+/// use std::sync::Arc;
+///
+/// use cata::{command::Container as _, Command, Container};
+/// use clap::Parser;
+///
+/// #[derive(Container)]
+/// enum RootCmd {
+///   Built(Box<dyn Command>),
+///   Shared(Arc<dyn Command>),
+/// }
+///
+/// #[derive(Parser, Container)]
+/// struct Hello {}
+///
+/// impl Command for Hello {}
+///
+/// # #[tokio::main]
+/// # async fn main() -> eyre::Result<()> {
+/// let built = RootCmd::Built(Box::new(Hello::parse_from(["hello"])));
+/// assert_eq!(built.path_segment(), Some("built"));
+/// cata::execute(built.next().unwrap()).await?;
+///
+/// let mut shared = RootCmd::Shared(Arc::new(Hello::parse_from(["hello"])));
+/// assert!(shared.next_mut().is_some());
+/// cata::execute(shared.next().unwrap()).await?;
+/// # Ok(())
+/// # }
+/// ```
+#[diagnostic::on_unimplemented(
+    message = "`{Self}` is not a command that can be dispatched to",
+    label = "not a command",
+    note = "implement `cata::Command` for `{Self}`, or add `#[cata(skip)]` to variants that \
+            aren't commands"
+)]
+pub trait Dispatch {
+    /// The command to run.
+    fn command(&self) -> &dyn Command;
+
+    /// The command to run, mutably.
+    ///
+    /// An `Arc<dyn Command>` only has one while it isn't shared, otherwise
+    /// [`crate::execute_mut`] stops before it.
+    fn command_mut(&mut self) -> Option<&mut dyn Command>;
+
+    /// Every gated subcommand below the command, see [`Container::flags`].
+    #[must_use]
+    fn flags() -> Vec<(Vec<&'static str>, &'static str)>
+    where
+        Self: Sized,
+    {
+        Vec::new()
+    }
+
+    /// The examples of the command and its subcommands, see
+    /// [`Container::all_examples`].
+    #[must_use]
+    fn all_examples() -> Vec<(Vec<&'static str>, &'static [&'static str])>
+    where
+        Self: Sized,
+    {
+        Vec::new()
+    }
+}
+
+impl<T: Command> Dispatch for T {
+    fn command(&self) -> &dyn Command {
+        self
+    }
+
+    fn command_mut(&mut self) -> Option<&mut dyn Command> {
+        Some(self)
+    }
+
+    fn flags() -> Vec<(Vec<&'static str>, &'static str)> {
+        <T as Container>::flags()
+    }
+
+    fn all_examples() -> Vec<(Vec<&'static str>, &'static [&'static str])> {
+        <T as Container>::all_examples()
+    }
+}
+
+impl Dispatch for Box<dyn Command> {
+    fn command(&self) -> &dyn Command {
+        &**self
+    }
+
+    fn command_mut(&mut self) -> Option<&mut dyn Command> {
+        Some(&mut **self)
+    }
+}
+
+impl Dispatch for Arc<dyn Command> {
+    fn command(&self) -> &dyn Command {
+        &**self
+    }
+
+    fn command_mut(&mut self) -> Option<&mut dyn Command> {
+        Arc::get_mut(self).map(|cmd| cmd as &mut dyn Command)
+    }
+}
+
+/// Runs the arguments of an enum's `#[command(external_subcommand)]` variant.
+///
+/// `#[cata(external = path::to::function)]` on the variant implements this for
+/// the enum with `#[derive(Container)]`. The function takes the arguments, the
+/// name of the subcommand first, and `&mut cata::Context`. Usually this is an
+/// associated `async fn` of the enum, see [`crate::plugin::Plugins::run`] for
+/// running them as a plugin.
+#[async_trait::async_trait]
+pub trait ExternalHandler<T> {
+    /// Run the external subcommand `args`.
+    async fn handle(args: &[T], ctx: &mut Context) -> Result<()>;
+}
+
+/// The arguments of an external subcommand, as a command that hands them to
+/// the [`ExternalHandler`] of `E`.
+///
+/// This is what `next()` returns for a variant with `#[cata(external = ...)]`.
+#[repr(transparent)]
+pub struct ExternalArgs<T, E: ?Sized> {
+    args: Vec<T>,
+    _handler: std::marker::PhantomData<fn() -> E>,
+}
+
+impl<T, E: ?Sized> ExternalArgs<T, E> {
+    /// View the arguments of a variant as a command.
+    #[must_use]
+    pub fn new(args: &Vec<T>) -> &Self {
+        // SAFETY: `ExternalArgs` is a transparent wrapper around `Vec<T>`.
+        unsafe { &*std::ptr::from_ref(args).cast::<Self>() }
+    }
+
+    /// View the arguments of a variant as a command, mutably.
+    #[must_use]
+    pub fn new_mut(args: &mut Vec<T>) -> &mut Self {
+        // SAFETY: `ExternalArgs` is a transparent wrapper around `Vec<T>`.
+        unsafe { &mut *std::ptr::from_mut(args).cast::<Self>() }
+    }
+
+    /// The arguments, starting with the name of the subcommand.
+    #[must_use]
+    pub fn args(&self) -> &[T] {
+        &self.args
+    }
+}
+
+impl<T, E: ?Sized> Container for ExternalArgs<T, E> {}
+
+#[async_trait::async_trait]
+impl<T, E> Command for ExternalArgs<T, E>
+where
+    T: Send + Sync,
+    E: ExternalHandler<T> + ?Sized,
+{
+    async fn run(&self, ctx: &mut Context) -> Result<()> {
+        E::handle(&self.args, ctx).await
+    }
+}
+
+/// An optional subcommand, for `#[command(subcommand)] cmd: Option<RootCmd>`.
+///
+/// Without a subcommand, the parent is the last command run. Unless a variant
+/// has `#[cata(default)]`, it is then run as if its name had been passed.
+/// [`crate::execute_mut`] puts it into the `Option` before running it. When
+/// the default can't be parsed without arguments, running it returns clap's
+/// error instead.
+///
+/// ```
+/// use cata::{Command, Container};
+/// use clap::{Parser, Subcommand};
+///
+/// #[derive(Parser, Container)]
+/// struct Root {
+///   #[command(subcommand)]
+///   cmd: Option<RootCmd>,
+/// }
+///
+/// impl Command for Root {}
+///
+/// #[derive(Subcommand, Container)]
+/// enum RootCmd {
+///   Child(Child),
+/// }
+///
+/// #[derive(Parser, Container)]
+/// struct Child {}
+///
+/// #[async_trait::async_trait]
+/// impl Command for Child {
+///   async fn run(&self, _: &mut cata::Context) -> eyre::Result<()> {
+///     Err(eyre::eyre!("child ran"))
+///   }
+/// }
+///
+/// # #[tokio::main]
+/// # async fn main() {
+/// cata::execute(&Root::parse_from(["root"])).await.unwrap();
+///
+/// let err = cata::execute(&Root::parse_from(["root", "child"]))
+///   .await
+///   .unwrap_err();
+/// assert_eq!(err.to_string(), "child ran");
+/// # }
+/// ```
+impl<T: Container> Container for Option<T> {
+    fn next(&self) -> Option<&dyn Command> {
+        match or_default(self.as_ref())? {
+            Ok(cmd) => cmd.next(),
+            Err(_) => Some(&Unparsable::<T>(PhantomData)),
+        }
+    }
+
+    fn next_mut(&mut self) -> Option<&mut dyn Command> {
+        if self.is_none() {
+            match T::default_subcommand()? {
+                Ok(cmd) => *self = Some(cmd),
+                Err(_) => return Some(Box::leak(Box::new(Unparsable::<T>(PhantomData)))),
+            }
+        }
+
+        self.as_mut().and_then(Container::next_mut)
+    }
+
+    fn subcommand(&self) -> Option<&dyn Command> {
+        match or_default(self.as_ref())? {
+            Ok(cmd) => cmd.subcommand(),
+            Err(_) => Some(&Unparsable::<T>(PhantomData)),
+        }
+    }
+
+    fn subcommand_mut(&mut self) -> Option<&mut dyn Command> {
+        if self.is_none() {
+            match T::default_subcommand()? {
+                Ok(cmd) => *self = Some(cmd),
+                Err(_) => return Some(Box::leak(Box::new(Unparsable::<T>(PhantomData)))),
+            }
+        }
+
+        self.as_mut().and_then(Container::subcommand_mut)
+    }
+
+    fn path_segment(&self) -> Option<&'static str> {
+        or_default(self.as_ref())?.ok()?.path_segment()
+    }
+
+    /// Without a subcommand, or a default one, there are no children to list.
+    fn children(&self) -> &'static [&'static str] {
+        or_default(self.as_ref())
+            .and_then(Result::ok)
+            .map_or(&[], |cmd| cmd.children())
+    }
+
+    fn flag(&self) -> Option<&'static str> {
+        or_default(self.as_ref())?.ok()?.flag()
+    }
+
+    fn provide_globals(&self, ctx: &mut Context) {
+        if let Some(Ok(inner)) = or_default(self.as_ref()) {
+            inner.provide_globals(ctx);
+        }
+    }
+
+    fn flags() -> Vec<(Vec<&'static str>, &'static str)> {
+        T::flags()
+    }
+
+    fn examples(&self) -> &'static [&'static str] {
+        or_default(self.as_ref())
+            .and_then(Result::ok)
+            .map_or(&[], |cmd| cmd.examples())
+    }
+
+    fn all_examples() -> Vec<(Vec<&'static str>, &'static [&'static str])> {
+        T::all_examples()
+    }
+}
+
+/// The subcommand that was passed, or the default one when there wasn't any.
+fn or_default<'a, T: Container + 'a>(
+    cmd: Option<&'a T>,
+) -> Option<Result<&'a (dyn Container + 'a), &'static clap::Error>> {
+    match cmd {
+        Some(cmd) => Some(Ok(cmd)),
+        None => T::shared_default_subcommand(),
+    }
+}
+
+/// Stands in for a `#[cata(default)]` subcommand of `T` that can't be parsed
+/// without arguments, it returns clap's error before anything below it runs.
+struct Unparsable<T>(PhantomData<fn() -> T>);
+
+impl<T> Container for Unparsable<T> {}
+
+#[async_trait::async_trait]
+impl<T: Container> Command for Unparsable<T> {
+    async fn pre_run(&self, _: &mut Context) -> Result<PreRun> {
+        match T::default_subcommand() {
+            Some(Err(e)) => Err(e.into()),
+            _ => Ok(PreRun::Continue),
+        }
+    }
+}
+
+/// Puts a service on the [`Context`], see [`ExecuteOptions::with_service`].
+pub(crate) type Provide = Arc<dyn Fn(&mut Context) + Send + Sync>;
+
+/// Configuration for [`crate::execute_with`] and [`crate::execute_mut_with`].
+///
+/// The defaults match [`crate::execute`].
+///
+/// ```
+/// use std::time::Duration;
+///
+/// use cata::{Command, Container, ExecuteOptions};
+/// use clap::Parser;
+///
+/// #[derive(Parser, Container)]
+/// struct Root {}
+///
+/// impl Command for Root {}
+///
+/// # #[tokio::main]
+/// # async fn main() -> eyre::Result<()> {
+/// let options = ExecuteOptions::default()
+///   .with_timeout(Duration::from_secs(30))
+///   .with_signals(false);
+///
+/// cata::execute_with(&Root::parse_from(["root"]), options).await
+/// # }
+/// ```
+#[derive(Clone)]
+#[allow(clippy::struct_excessive_bools)]
+pub struct ExecuteOptions {
+    #[cfg(feature = "background")]
+    pub(crate) grace: Duration,
+    #[cfg(feature = "background")]
+    pub(crate) drain: Duration,
+    pub(crate) timeout: Option<Duration>,
+    pub(crate) signals: bool,
+    pub(crate) recursive: bool,
+    pub(crate) max_depth: usize,
+    pub(crate) run_policy: RunPolicy,
+    pub(crate) post_run_order: PostRunOrder,
+    pub(crate) dry_run: bool,
+    pub(crate) yes: bool,
+    pub(crate) layers: Layers,
+    pub(crate) services: Vec<Provide>,
+    pub(crate) matches: Option<Arc<clap::ArgMatches>>,
+    #[cfg(feature = "events")]
+    pub(crate) subscribers: Vec<Arc<dyn Subscriber>>,
+    #[cfg(feature = "first-run")]
+    pub(crate) first_run: Option<FirstRun>,
+    pub(crate) flags: Flags,
+    pub(crate) runtime: Arc<dyn Runtime>,
+    #[cfg(feature = "update")]
+    pub(crate) update: Option<crate::update::Check>,
+    #[cfg(feature = "verbosity")]
+    pub(crate) tracing: Arc<std::sync::Mutex<Vec<crate::verbosity::BoxLayer>>>,
+}
+
+impl fmt::Debug for ExecuteOptions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut f = f.debug_struct("ExecuteOptions");
+
+        #[cfg(feature = "background")]
+        f.field("grace", &self.grace).field("drain", &self.drain);
+
+        f.field("timeout", &self.timeout)
+            .field("signals", &self.signals)
+            .field("recursive", &self.recursive)
+            .field("max_depth", &self.max_depth)
+            .field("run_policy", &self.run_policy)
+            .field("post_run_order", &self.post_run_order)
+            .field("dry_run", &self.dry_run)
+            .field("yes", &self.yes)
+            .field("services", &self.services.len())
+            .field("matches", &self.matches.is_some())
+            .field("flags", &self.flags);
+
+        #[cfg(feature = "layer")]
+        f.field("layers", &self.layers.layers.len());
+
+        #[cfg(feature = "events")]
+        f.field("subscribers", &self.subscribers.len());
+
+        #[cfg(feature = "first-run")]
+        f.field("first_run", &self.first_run);
+
+        #[cfg(feature = "update")]
+        f.field("update", &self.update);
+
+        f.finish_non_exhaustive()
+    }
+}
+
+impl Default for ExecuteOptions {
+    fn default() -> Self {
+        Self {
+            #[cfg(feature = "background")]
+            grace: background::GRACE,
+            #[cfg(feature = "background")]
+            drain: background::DRAIN,
+            timeout: None,
+            signals: true,
+            recursive: true,
+            max_depth: MAX_DEPTH,
+            run_policy: RunPolicy::default(),
+            post_run_order: PostRunOrder::default(),
+            dry_run: false,
+            yes: false,
+            layers: Layers::default(),
+            services: Vec::new(),
+            matches: None,
+            #[cfg(feature = "events")]
+            subscribers: Vec::new(),
+            #[cfg(feature = "first-run")]
+            first_run: None,
+            flags: Flags::default(),
+            runtime: runtime::default(),
+            #[cfg(feature = "update")]
+            update: None,
+            #[cfg(feature = "verbosity")]
+            tracing: Arc::default(),
+        }
+    }
+}
+
+impl ExecuteOptions {
+    /// Set how long tasks started with [`background::spawn`] have to finish
+    /// once every `post_run` has been called.
+    ///
+    /// By default, this is 250ms.
+    #[cfg(feature = "background")]
+    #[must_use]
+    pub fn with_grace(mut self, grace: Duration) -> Self {
+        self.grace = grace;
+        self
+    }
+
+    /// Set how long the tasks of a [`crate::background::TaskGroup`] have to
+    /// finish once `run` has returned.
+    ///
+    /// By default, this is 1s.
+    #[cfg(feature = "background")]
+    #[must_use]
+    pub fn with_drain(mut self, drain: Duration) -> Self {
+        self.drain = drain;
+        self
+    }
+
+    /// Set the timeout for commands that don't have one of their own, see
+    /// [`Command::timeout`].
+    ///
+    /// By default, there is no timeout.
+    #[must_use]
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Whether ctrl-c and the signals forwarded to [`Command::on_signal`]
+    /// are handled.
+    ///
+    /// Turn this off when the caller handles signals itself. By default,
+    /// signals are handled.
+    #[must_use]
+    pub fn with_signals(mut self, signals: bool) -> Self {
+        self.signals = signals;
+        self
+    }
+
+    /// Whether subcommands are descended into.
+    ///
+    /// When this is off, only the root command is run. By default,
+    /// subcommands are run.
+    #[must_use]
+    pub fn with_recursive(mut self, recursive: bool) -> Self {
+        self.recursive = recursive;
+        self
+    }
+
+    /// Set how many commands deep the tree can be, counting the root.
+    ///
+    /// Deeper trees are refused with [`TooDeep`] before anything runs, which
+    /// also stops a [`Container`] that never returns `None`. By default, this
+    /// is 64.
+    #[must_use]
+    pub fn with_max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    /// Set which commands have `run` called on them, see [`RunPolicy`].
+    ///
+    /// Commands can override this with [`Command::run_policy`]. By default,
+    /// every command is run.
+    #[must_use]
+    pub fn with_run_policy(mut self, run_policy: RunPolicy) -> Self {
+        self.run_policy = run_policy;
+        self
+    }
+
+    /// Set the order `post_run` is called in, see [`PostRunOrder`].
+    ///
+    /// By default, children are torn down before their parents.
+    #[must_use]
+    pub fn with_post_run_order(mut self, post_run_order: PostRunOrder) -> Self {
+        self.post_run_order = post_run_order;
+        self
+    }
+
+    /// Call [`Command::dry_run`] instead of `run`, see [`DryRun`].
+    ///
+    /// By default, commands are run.
+    #[must_use]
+    pub fn with_dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    /// Treat every [`Command::confirm`] as confirmed, see [`Yes`].
+    ///
+    /// By default, the user is asked.
+    #[must_use]
+    pub fn with_yes(mut self, yes: bool) -> Self {
+        self.yes = yes;
+        self
+    }
+
+    /// Add `layer` to the subscriber installed for [`Command::verbosity`].
+    ///
+    /// There is only one global subscriber, the layers are handed to the first
+    /// execution that installs it.
+    #[cfg(feature = "verbosity")]
+    #[must_use]
+    pub fn with_tracing_layer<L>(self, layer: L) -> Self
+    where
+        L: tracing_subscriber::Layer<tracing_subscriber::Registry> + Send + Sync + 'static,
+    {
+        self.tracing
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+            .push(Box::new(layer));
+        self
+    }
+
+    /// Wrap every hook in the tree with `layer`, see [`crate::layer`].
+    ///
+    /// Layers added first are the outermost.
+    #[cfg(feature = "layer")]
+    #[must_use]
+    pub fn with_layer(mut self, layer: impl Layer + 'static) -> Self {
+        self.layers.layers.push(Arc::new(layer));
+        self
+    }
+
+    /// Provide `value` on the [`Context`] before the root's `pre_run`.
+    ///
+    /// This is how a service built outside of the tree, such as a fake API
+    /// client in tests, gets injected. Commands that provide the same type
+    /// replace it.
+    #[must_use]
+    pub fn with_service<T: Clone + Send + Sync + 'static>(mut self, value: T) -> Self {
+        self.services.push(Arc::new(move |ctx: &mut Context| {
+            ctx.provide(value.clone());
+        }));
+        self
+    }
+
+    /// Make `matches` available to every hook, see [`Context::matches`].
+    ///
+    /// These should be the matches the root was parsed from, which
+    /// [`crate::parse`] returns.
+    #[must_use]
+    pub fn with_matches(mut self, matches: clap::ArgMatches) -> Self {
+        self.matches = Some(Arc::new(matches));
+        self
+    }
+
+    /// Tell `subscriber` about everything that happens, see [`crate::events`].
+    #[cfg(feature = "events")]
+    #[must_use]
+    pub fn with_subscriber(mut self, subscriber: impl Subscriber + 'static) -> Self {
+        self.subscribers.push(Arc::new(subscriber));
+        self
+    }
+
+    /// Run on `runtime` instead of tokio, see [`crate::runtime`].
+    #[must_use]
+    pub fn with_runtime(mut self, runtime: impl Runtime + 'static) -> Self {
+        self.runtime = Arc::new(runtime);
+        self
+    }
+
+    /// Record what happens into `recorder`, see [`crate::timings`].
+    ///
+    /// The recorder is the outermost layer, no matter when it was added.
+    #[cfg(feature = "timings")]
+    #[must_use]
+    pub fn with_report(mut self, recorder: Recorder) -> Self {
+        self.layers.layers.insert(0, Arc::new(recorder.clone()));
+        self.subscribers.push(Arc::new(recorder));
+        self
+    }
+
+    /// Call [`Command::on_first_run`] when `first_run` says the CLI has never
+    /// run before, see [`crate::first_run`].
+    ///
+    /// By default, there is no first run.
+    #[cfg(feature = "first-run")]
+    #[must_use]
+    pub fn with_first_run(mut self, first_run: FirstRun) -> Self {
+        self.first_run = Some(first_run);
+        self
+    }
+
+    /// Refuse to run commands whose feature flag isn't enabled in `flags`,
+    /// see [`crate::flags`].
+    ///
+    /// By default, no flags are enabled.
+    #[must_use]
+    pub fn with_flags(mut self, flags: Flags) -> Self {
+        self.flags = flags;
+        self
+    }
+
+    /// Check for a newer version while the tree runs and print a notice after
+    /// the last `post_run`, see [`crate::update`].
+    ///
+    /// By default, there is no check.
+    #[cfg(feature = "update")]
+    #[must_use]
+    pub fn with_update_check(mut self, check: crate::update::Check) -> Self {
+        self.update = Some(check);
+        self
+    }
+}
+
+/// A `--dry-run` flag for the root command.
+///
+/// Flatten it into the root and pass it on with
+/// [`ExecuteOptions::with_dry_run`]. The flag is global, so it can be passed
+/// after any subcommand. Every hook can check [`Context::dry_run`], and
+/// [`Command::dry_run`] is called instead of `run`.
+///
+/// ```
+/// use cata::{command::DryRun, Command, Container, ExecuteOptions};
+/// use clap::{Parser, Subcommand};
+///
+/// #[derive(Parser, Container)]
+/// struct Root {
+///   #[command(subcommand)]
+///   cmd: RootCmd,
+///
+///   #[command(flatten)]
+///   dry_run: DryRun,
+/// }
+///
+/// impl Command for Root {}
+///
+/// #[derive(Subcommand, Container)]
+/// enum RootCmd {
+///   Delete(Delete),
+/// }
+///
+/// #[derive(Parser, Container)]
+/// struct Delete {
+///   name: String,
+/// }
+///
+/// #[async_trait::async_trait]
+/// impl Command for Delete {
+///   async fn run(&self, _: &mut cata::Context) -> eyre::Result<()> {
+///     unreachable!("would have deleted {}", self.name)
+///   }
+///
+///   async fn dry_run(&self, _: &mut cata::Context) -> eyre::Result<()> {
+///     println!("would delete {}", self.name);
+///
+///     Ok(())
+///   }
+/// }
+///
+/// # #[tokio::main]
+/// # async fn main() -> eyre::Result<()> {
+/// let root = Root::parse_from(["root", "delete", "prod", "--dry-run"]);
+/// let options = ExecuteOptions::default().with_dry_run(root.dry_run.enabled);
+///
+/// cata::execute_with(&root, options).await
+/// # }
+/// ```
+#[derive(clap::Args, Clone, Debug, Default)]
+pub struct DryRun {
+    /// Show what would be done without doing it.
+    #[arg(long = "dry-run", global = true)]
+    pub enabled: bool,
+}
+
+/// Details about a deprecated command, see [`Command::deprecated`].
+///
+/// ```
+/// use cata::command::Deprecation;
+///
+/// let deprecation = Deprecation::default()
+///   .with_replacement("my-cli projects list")
+///   .with_removal("2.0.0");
+///
+/// assert_eq!(
+///   deprecation.message("my-cli ls"),
+///   "`my-cli ls` is deprecated and will be removed in 2.0.0, use `my-cli projects list` instead",
+/// );
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct Deprecation {
+    replacement: Option<String>,
+    removal: Option<String>,
+}
+
+impl Deprecation {
+    /// Suggest `replacement`, typically the command to use instead.
+    #[must_use]
+    pub fn with_replacement(mut self, replacement: impl Into<String>) -> Self {
+        self.replacement = Some(replacement.into());
+        self
+    }
+
+    /// Set the version that the command will be removed in.
+    #[must_use]
+    pub fn with_removal(mut self, version: impl Into<String>) -> Self {
+        self.removal = Some(version.into());
+        self
+    }
+
+    /// The warning for the command called `name`.
+    #[must_use]
+    pub fn message(&self, name: &str) -> String {
+        let mut message = format!("`{name}` is deprecated");
+
+        if let Some(version) = &self.removal {
+            message.push_str(&format!(" and will be removed in {version}"));
+        }
+
+        if let Some(replacement) = &self.replacement {
+            message.push_str(&format!(", use `{replacement}` instead"));
+        }
+
+        message
+    }
+}
+
+/// A question that has to be answered before a command runs, see
+/// [`Command::confirm`].
+///
+/// ```
+/// use cata::{
+///   command::{Confirmation, Yes},
+///   Command, Container, ExecuteOptions,
+/// };
+/// use clap::Parser;
+///
+/// #[derive(Parser, Container)]
+/// struct Delete {
+///   name: String,
+///
+///   #[command(flatten)]
+///   yes: Yes,
+/// }
+///
+/// #[async_trait::async_trait]
+/// impl Command for Delete {
+///   fn confirm(&self) -> Option<Confirmation> {
+///     Some(Confirmation::new(format!("Delete {}?", self.name)))
+///   }
+///
+///   async fn run(&self, _: &mut cata::Context) -> eyre::Result<()> {
+///     println!("deleted {}", self.name);
+///
+///     Ok(())
+///   }
+/// }
+///
+/// # #[tokio::main]
+/// # async fn main() -> eyre::Result<()> {
+/// let cmd = Delete::parse_from(["delete", "prod", "--yes"]);
+/// let options = ExecuteOptions::default().with_yes(cmd.yes.enabled);
+///
+/// cata::execute_with(&cmd, options).await
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct Confirmation {
+    message: String,
+}
+
+impl Confirmation {
+    /// Ask `message`, which should be a yes or no question.
+    pub fn new(message: impl Into<String>) -> Self {
+        Self {
+            message: message.into(),
+        }
+    }
+
+    /// Prompt for an answer, failing when stdin isn't a terminal.
+    pub(crate) fn ask(&self) -> Result<bool> {
+        let stdin = std::io::stdin();
+
+        if !stdin.is_terminal() {
+            return Err(eyre!("unable to ask for confirmation, pass --yes"));
+        }
+
+        eprint!("{} [y/N] ", self.message);
+        std::io::stderr().flush()?;
+
+        let mut line = String::new();
+        stdin.lock().read_line(&mut line)?;
+
+        Ok(matches!(line.trim(), "y" | "Y" | "yes"))
+    }
+}
+
+/// A `--yes` flag for the root command.
+///
+/// Flatten it into the root and pass it on with [`ExecuteOptions::with_yes`].
+/// Like [`DryRun`], the flag is global.
+#[derive(clap::Args, Clone, Debug, Default)]
+pub struct Yes {
+    /// Answer yes to every confirmation.
+    #[arg(short = 'y', long = "yes", global = true)]
+    pub enabled: bool,
+}
+
+type Retryable = Arc<dyn Fn(&Report) -> bool + Send + Sync>;
+
+/// How `run` is retried when it fails, see [`Command::retry`].
+///
+/// Attempts are spaced out with exponential backoff, starting at
+/// [`RetryPolicy::with_backoff`] and doubling up to
+/// [`RetryPolicy::with_max_backoff`].
+///
+/// ```
+/// use std::{
+///   sync::atomic::{AtomicU32, Ordering},
+///   time::Duration,
+/// };
+///
+/// use cata::{command::RetryPolicy, Command, Container};
+/// use clap::Parser;
+///
+/// static ATTEMPTS: AtomicU32 = AtomicU32::new(0);
+///
+/// #[derive(Parser, Container)]
+/// struct Fetch {}
+///
+/// #[async_trait::async_trait]
+/// impl Command for Fetch {
+///   fn retry(&self) -> Option<RetryPolicy> {
+///     Some(
+///       RetryPolicy::new(3)
+///         .with_backoff(Duration::from_millis(1))
+///         .with_retryable(|err| err.to_string().contains("rate limited")),
+///     )
+///   }
+///
+///   async fn run(&self, _: &mut cata::Context) -> eyre::Result<()> {
+///     if ATTEMPTS.fetch_add(1, Ordering::SeqCst) < 2 {
+///       return Err(eyre::eyre!("rate limited"));
+///     }
+///
+///     Ok(())
+///   }
+/// }
+///
+/// # #[tokio::main]
+/// # async fn main() -> eyre::Result<()> {
+/// cata::execute(&Fetch::parse_from(["fetch"])).await?;
+/// assert_eq!(ATTEMPTS.load(Ordering::SeqCst), 3);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone)]
+pub struct RetryPolicy {
+    attempts: u32,
+    backoff: Duration,
+    max_backoff: Duration,
+    retryable: Option<Retryable>,
+}
+
+impl fmt::Debug for RetryPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RetryPolicy")
+            .field("attempts", &self.attempts)
+            .field("backoff", &self.backoff)
+            .field("max_backoff", &self.max_backoff)
+            .finish_non_exhaustive()
+    }
+}
+
+impl RetryPolicy {
+    /// Call `run` up to `attempts` times, including the first one.
+    ///
+    /// By default, every error is retried after waiting 100ms at first and at
+    /// most 10s.
+    #[must_use]
+    pub fn new(attempts: u32) -> Self {
+        Self {
+            attempts,
+            backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(10),
+            retryable: None,
+        }
+    }
+
+    /// Set how long to wait before the first retry.
+    #[must_use]
+    pub fn with_backoff(mut self, backoff: Duration) -> Self {
+        self.backoff = backoff;
+        self
+    }
+
+    /// Set the longest wait between two attempts.
+    #[must_use]
+    pub fn with_max_backoff(mut self, max_backoff: Duration) -> Self {
+        self.max_backoff = max_backoff;
+        self
+    }
+
+    /// Only retry errors that `retryable` returns `true` for.
+    #[must_use]
+    pub fn with_retryable(
+        mut self,
+        retryable: impl Fn(&Report) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        self.retryable = Some(Arc::new(retryable));
+        self
+    }
+
+    /// How long to wait after `attempt` failed, or `None` if `err` shouldn't
+    /// be retried. Attempts start at 1.
+    pub(crate) fn delay(&self, attempt: u32, err: &Report) -> Option<Duration> {
+        if attempt >= self.attempts || err.is::<Interrupted>() || err.is::<TimedOut>() {
+            return None;
+        }
+
+        if !self
+            .retryable
+            .as_ref()
+            .map_or(true, |retryable| retryable(err))
+        {
+            return None;
+        }
+
+        let factor = 2u32.saturating_pow(attempt - 1);
+
+        Some(self.backoff.saturating_mul(factor).min(self.max_backoff))
+    }
+}
+
+/// The working directory and environment variables for a command's `run`, see
+/// [`Command::overrides`].
+///
+/// The working directory and environment of the process are left alone, they
+/// are shared with everything else running in it. Instead, `run` sees the
+/// overrides through [`Context::cwd`] and [`Context::var`], and programs
+/// started with [`Context::command`] get them. For tokio, wrap that in a
+/// `tokio::process::Command`.
+///
+/// ```
+/// use cata::{command::Overrides, Command, Container};
+/// use clap::Parser;
+///
+/// #[derive(Parser, Container)]
+/// struct Build {}
+///
+/// #[async_trait::async_trait]
+/// impl Command for Build {
+///   fn overrides(&self) -> Option<Overrides> {
+///     Some(
+///       Overrides::default()
+///         .with_dir(std::env::temp_dir())
+///         .with_var("CATA_EXAMPLE_PROFILE", "release"),
+///     )
+///   }
+///
+///   async fn run(&self, ctx: &mut cata::Context) -> eyre::Result<()> {
+///     let dir = std::env::temp_dir().canonicalize()?;
+///     assert_eq!(ctx.cwd()?, dir);
+///     assert_eq!(ctx.var("CATA_EXAMPLE_PROFILE").unwrap(), "release");
+///
+///     let cargo = ctx.command("cargo");
+///     assert_eq!(cargo.get_current_dir(), Some(dir.as_path()));
+///     assert!(cargo
+///       .get_envs()
+///       .any(|(key, value)| key == "CATA_EXAMPLE_PROFILE" && value.unwrap() == "release"));
+///
+///     // The process itself is untouched.
+///     assert!(std::env::var_os("CATA_EXAMPLE_PROFILE").is_none());
+///
+///     Ok(())
+///   }
+/// }
+///
+/// # #[tokio::main]
+/// # async fn main() -> eyre::Result<()> {
+/// let dir = std::env::current_dir()?;
+///
+/// cata::execute(&Build::parse_from(["build"])).await?;
+///
+/// assert_eq!(std::env::current_dir()?, dir);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct Overrides {
+    dir: Option<PathBuf>,
+    vars: Vec<(OsString, Option<OsString>)>,
+}
+
+impl Overrides {
+    /// Run in `dir`, relative paths are resolved against the current
+    /// directory.
+    #[must_use]
+    pub fn with_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.dir = Some(dir.into());
+        self
+    }
+
+    /// Set the environment variable `key` to `value`.
+    #[must_use]
+    pub fn with_var(mut self, key: impl Into<OsString>, value: impl Into<OsString>) -> Self {
+        self.vars.push((key.into(), Some(value.into())));
+        self
+    }
+
+    /// Remove the environment variable `key`.
+    #[must_use]
+    pub fn without_var(mut self, key: impl Into<OsString>) -> Self {
+        self.vars.push((key.into(), None));
+        self
+    }
+
+    /// Resolve the directory against the current one, failing when it isn't
+    /// one.
+    pub(crate) fn resolve(mut self) -> Result<Self> {
+        if let Some(dir) = &self.dir {
+            let resolved = dir
+                .canonicalize()
+                .map_err(|e| eyre!("unable to change to {}: {e}", dir.display()))?;
+
+            if !resolved.is_dir() {
+                return Err(eyre!(
+                    "unable to change to {}: not a directory",
+                    dir.display()
+                ));
+            }
+
+            self.dir = Some(resolved);
+        }
+
+        Ok(self)
+    }
+
+    /// The directory to run in, if there is one.
+    pub(crate) fn dir(&self) -> Option<&Path> {
+        self.dir.as_deref()
+    }
+
+    /// The value of the environment variable `key` with the overrides
+    /// applied.
+    pub(crate) fn var(&self, key: &OsStr) -> Option<OsString> {
+        match self
+            .vars
+            .iter()
+            .rev()
+            .find(|(candidate, _)| candidate == key)
+        {
+            Some((_, value)) => value.clone(),
+            None => std::env::var_os(key),
+        }
+    }
+
+    /// Apply the overrides to a program that is about to be started.
+    pub(crate) fn apply(&self, cmd: &mut std::process::Command) {
+        if let Some(dir) = &self.dir {
+            cmd.current_dir(dir);
+        }
+
+        for (key, value) in &self.vars {
+            match value {
+                Some(value) => cmd.env(key, value),
+                None => cmd.env_remove(key),
+            };
+        }
+    }
+}
+
+/// What should happen after [`Command::pre_run`].
+///
+/// ```
+/// use cata::{command::PreRun, Command, Container};
+/// use clap::Parser;
+///
+/// #[derive(Parser, Container)]
+/// struct Sync {
+///   #[arg(long)]
+///   fresh: bool,
+/// }
+///
+/// #[async_trait::async_trait]
+/// impl Command for Sync {
+///   async fn pre_run(&self, _: &mut cata::Context) -> eyre::Result<PreRun> {
+///     Ok(if self.fresh { PreRun::Skip } else { PreRun::Continue })
+///   }
+///
+///   async fn run(&self, _: &mut cata::Context) -> eyre::Result<()> {
+///     unreachable!("the cache is already fresh")
+///   }
+/// }
+///
+/// # #[tokio::main]
+/// # async fn main() -> eyre::Result<()> {
+/// cata::execute(&Sync::parse_from(["sync", "--fresh"])).await
+/// # }
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PreRun {
+    /// Run the command and then its subcommands.
+    #[default]
+    Continue,
+    /// Skip `run` and every subcommand. `post_run` is still called, on this
+    /// command and its parents, with a successful outcome.
+    Skip,
+}
+
+/// Which commands in the tree have `run` called on them.
+///
+/// Parents that only set things up for their subcommands, such as `my-cli
+/// projects` in `my-cli projects list`, often shouldn't do anything of their
+/// own when a subcommand was given.
+///
+/// ```
+/// use std::sync::Mutex;
+///
+/// use cata::{command::RunPolicy, Command, Container, ExecuteOptions};
+/// use clap::{Parser, Subcommand};
+///
+/// static RUNS: Mutex<Vec<&str>> = Mutex::new(Vec::new());
+///
+/// #[derive(Parser, Container)]
+/// struct Root {
+///   #[command(subcommand)]
+///   cmd: RootCmd,
+/// }
+///
+/// #[async_trait::async_trait]
+/// impl Command for Root {
+///   async fn run(&self, _: &mut cata::Context) -> eyre::Result<()> {
+///     RUNS.lock().unwrap().push("root");
+///
+///     Ok(())
+///   }
+/// }
+///
+/// #[derive(Subcommand, Container)]
+/// enum RootCmd {
+///   List(List),
+/// }
+///
+/// #[derive(Parser, Container)]
+/// struct List {}
+///
+/// #[async_trait::async_trait]
+/// impl Command for List {
+///   async fn run(&self, _: &mut cata::Context) -> eyre::Result<()> {
+///     RUNS.lock().unwrap().push("list");
+///
+///     Ok(())
+///   }
+/// }
+///
+/// # #[tokio::main]
+/// # async fn main() -> eyre::Result<()> {
+/// cata::execute_with(
+///   &Root::parse_from(["root", "list"]),
+///   ExecuteOptions::default().with_run_policy(RunPolicy::LeafOnly),
+/// )
+/// .await?;
+///
+/// assert_eq!(*RUNS.lock().unwrap(), ["list"]);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum RunPolicy {
+    /// Call `run` on every command, parents before their children.
+    #[default]
+    Every,
+    /// Only call `run` on the last command that is reached. `pre_run` and
+    /// `post_run` are still called on every command.
+    LeafOnly,
+}
+
+/// The order `post_run` is called in.
+///
+/// Either way, each command sees the outcome so far, including failures from
+/// the `post_run` of the commands that were torn down before it. Teardown from
+/// a cancelled execution is always child first.
+///
+/// ```
+/// use std::sync::Mutex;
+///
+/// use cata::{command::PostRunOrder, Command, Container, ExecuteOptions};
+/// use clap::{Parser, Subcommand};
+///
+/// static CALLS: Mutex<Vec<&str>> = Mutex::new(Vec::new());
+///
+/// #[derive(Parser, Container)]
+/// struct Root {
+///   #[command(subcommand)]
+///   cmd: RootCmd,
+/// }
+///
+/// #[async_trait::async_trait]
+/// impl Command for Root {
+///   async fn post_run(&self, _: &mut cata::Context, _: &eyre::Result<()>) -> eyre::Result<()> {
+///     CALLS.lock().unwrap().push("root");
+///
+///     Ok(())
+///   }
+/// }
+///
+/// #[derive(Subcommand, Container)]
+/// enum RootCmd {
+///   List(List),
+/// }
+///
+/// #[derive(Parser, Container)]
+/// struct List {}
+///
+/// #[async_trait::async_trait]
+/// impl Command for List {
+///   async fn post_run(&self, _: &mut cata::Context, _: &eyre::Result<()>) -> eyre::Result<()> {
+///     CALLS.lock().unwrap().push("list");
+///
+///     Ok(())
+///   }
+/// }
+///
+/// # #[tokio::main]
+/// # async fn main() -> eyre::Result<()> {
+/// cata::execute_with(
+///   &Root::parse_from(["root", "list"]),
+///   ExecuteOptions::default().with_post_run_order(PostRunOrder::ParentFirst),
+/// )
+/// .await?;
+///
+/// assert_eq!(*CALLS.lock().unwrap(), ["root", "list"]);
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum PostRunOrder {
+    /// Children before their parents, the reverse of `pre_run`.
+    #[default]
+    ChildFirst,
+    /// Parents before their children, the same order as `pre_run`.
+    ParentFirst,
+}
+
+/// A signal forwarded to [`Command::on_signal`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Signal {
+    /// `SIGTERM`, the process is being asked to exit.
+    Terminate,
+    /// `SIGHUP`, the terminal went away or configuration should be reloaded.
+    Hangup,
+}
+
+/// Returned by [`crate::execute`] when the user interrupted execution, for
+/// example with ctrl-c.
+#[derive(Debug)]
+pub struct Interrupted;
+
+impl fmt::Display for Interrupted {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "interrupted")
+    }
+}
+
+impl std::error::Error for Interrupted {}
+
+/// Returned by [`crate::execute`] when a hook panicked.
+///
+/// The panic is caught so that teardown still happens, every `on_error` and
+/// `post_run` sees this error instead. It contains the panic's message.
+///
+/// ```
+/// use cata::{command::Panicked, Command, Container};
+/// use clap::Parser;
+///
+/// #[derive(Parser, Container)]
+/// struct Root {}
+///
+/// #[async_trait::async_trait]
+/// impl Command for Root {
+///   async fn run(&self, _: &mut cata::Context) -> eyre::Result<()> {
+///     panic!("oops");
+///   }
+///
+///   async fn post_run(&self, _: &mut cata::Context, result: &eyre::Result<()>) -> eyre::Result<()> {
+///     assert!(result.as_ref().unwrap_err().is::<Panicked>());
+///
+///     Ok(())
+///   }
+/// }
+///
+/// # #[tokio::main]
+/// # async fn main() {
+/// let err = cata::execute(&Root::parse_from(["root"])).await.unwrap_err();
+///
+/// assert_eq!(err.to_string(), "panicked: oops");
+/// assert_eq!(cata::error::exit_code(&err), 101);
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct Panicked(pub String);
+
+impl Panicked {
+    pub(crate) fn new(payload: &(dyn std::any::Any + Send)) -> Self {
+        let message = payload
+            .downcast_ref::<&str>()
+            .map(ToString::to_string)
+            .or_else(|| payload.downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "Box<dyn Any>".to_string());
+
+        Self(message)
+    }
+}
+
+impl fmt::Display for Panicked {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "panicked: {}", self.0)
+    }
+}
+
+impl std::error::Error for Panicked {}
+
+/// Returned by [`crate::execute`] when `run` took longer than
+/// [`Command::timeout`].
+#[derive(Debug)]
+pub struct TimedOut(pub Duration);
+
+impl fmt::Display for TimedOut {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "timed out after {:?}", self.0)
+    }
+}
+
+impl std::error::Error for TimedOut {}
+
+/// Returned by [`crate::execute`] when a [`Container`] returns a command that
+/// was already reached, such as itself or one of its parents.
+///
+/// Derived containers can't do this, it comes from an implementation by hand.
+///
+/// ```
+/// use cata::{
+///   command::{Container, Cycle},
+///   Command,
+/// };
+///
+/// struct Root {}
+///
+/// impl Container for Root {
+///   fn next(&self) -> Option<&dyn Command> {
+///     Some(self)
+///   }
+/// }
+///
+/// impl Command for Root {}
+///
+/// # #[tokio::main]
+/// # async fn main() {
+/// let err = cata::execute(&Root {}).await.unwrap_err();
+/// assert!(err.is::<Cycle>());
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct Cycle {
+    /// The command whose container returned it, as it was typed.
+    pub command: String,
+}
+
+impl fmt::Display for Cycle {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "`{}` returns a command that was already reached from `Container::next`",
+            self.command
+        )
+    }
+}
+
+impl std::error::Error for Cycle {}
+
+/// Returned by [`crate::execute`] when the tree is deeper than
+/// [`ExecuteOptions::with_max_depth`] allows.
+#[derive(Debug)]
+pub struct TooDeep(pub usize);
+
+impl fmt::Display for TooDeep {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "the command tree is more than {} commands deep, raise the limit with \
+             `ExecuteOptions::with_max_depth`",
+            self.0
+        )
+    }
+}
+
+impl std::error::Error for TooDeep {}
+
+/// Continue in the background, using the daemon registered with
+/// [`crate::daemon::Daemon::install`].
+///
+/// This is [`crate::daemon::Daemon::detach`] for commands that don't want to
+/// hold on to the daemon themselves. With `foreground`, usually from a
+/// [`crate::daemon::Foreground`] flag, the command keeps running in the current
+/// process and only claims the pidfile.
+///
+/// ```
+/// use cata::daemon::{Daemon, Role};
+///
+/// # fn main() -> eyre::Result<()> {
+/// # let dir = std::env::temp_dir().join("cata-detach-example");
+/// Daemon::new("my-cli")
+/// #   .with_dir(&dir)
+///   .install()?;
+///
+/// let Role::Daemon(guard) = cata::command::detach(true)? else {
+///   unreachable!("foreground runs stay in this process");
+/// };
+///
+/// assert!(Daemon::new("my-cli")
+/// #   .with_dir(&dir)
+///   .status()?
+///   .is_some());
+///
+/// drop(guard);
+/// # Ok(())
+/// # }
+/// ```
+#[cfg(all(feature = "daemon", any(unix, windows)))]
+pub fn detach(foreground: bool) -> Result<crate::daemon::Role> {
+    crate::daemon::installed()?
+        .clone()
+        .with_foreground(foreground)
+        .detach()
+}
+
+/// Returns the path segments of every command that was parsed.
+///
+/// The root command does not have a segment of its own, so the path starts at
+/// the first subcommand. This is useful for breadcrumbs or naming telemetry.
+///
+/// ```
+/// use cata::{command::path, Command, Container};
+/// use clap::{Parser, Subcommand};
+/// # use cata::command::Container as _;
+///
+/// #[derive(Parser, Container)]
+/// struct Root {
+///   #[command(subcommand)]
+///   cmd: RootCmd,
+/// }
+///
+/// #[derive(Subcommand, Container)]
+/// enum RootCmd {
+///   ChildCmd(Child),
+/// }
+///
+/// #[derive(Parser, Container)]
+/// struct Child {}
+///
+/// impl Command for Root {}
+/// impl Command for Child {}
+///
+/// let root = Root::parse_from(["root", "child-cmd"]);
+///
+/// assert_eq!(path(&root), vec!["child-cmd"]);
+/// assert_eq!(root.children(), &["child-cmd"]);
+/// ```
+pub fn path(cmd: &dyn Command) -> Vec<&'static str> {
+    let mut segments = Vec::new();
+    let mut current = cmd;
+
+    while let (Some(segment), Some(next)) = (current.path_segment(), current.next()) {
+        segments.push(segment);
+        current = next;
+    }
+
+    segments
 }
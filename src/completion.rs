@@ -0,0 +1,371 @@
+//! Shell completion with values resolved at runtime.
+//!
+//! Static completion scripts only know about the flags and subcommands
+//! defined in clap. This module wires up clap's dynamic completion so that the
+//! shell calls back into the CLI, letting arguments provide candidates that
+//! are only known at runtime, such as resource names fetched from an API.
+//!
+//! Some things to note:
+//! - [`from_env`] must be called at the start of `main`, before anything is
+//!   written to stdout. When the shell is asking for completions, it responds
+//!   and exits. Otherwise, it returns immediately.
+//! - Users generate the script with `COMPLETE=<shell> my-cli`, for example
+//!   `source <(COMPLETE=bash my-cli)` in `~/.bashrc`. Alternatively, add
+//!   [`Completions`] as a subcommand, which prints the same script with `my-cli
+//!   completions bash`, or [`InstallCompletions`], which writes the script to
+//!   disk and prints the line to add to the shell's rc file.
+//! - Bash, elvish, fish, powershell and zsh are supported. Clap's dynamic
+//!   completion doesn't support nushell yet.
+//! - Expensive lookups can be wrapped in [`Cached`] so that every keypress
+//!   doesn't hit the network. This requires the `cache` feature.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use cata::{
+//!   completion::{self, Candidate, Complete},
+//!   Command, Container,
+//! };
+//! use clap::Parser;
+//!
+//! struct Clusters;
+//!
+//! impl Complete for Clusters {
+//!   fn complete(&self, _: &str) -> eyre::Result<Vec<Candidate>> {
+//!     Ok(vec!["production".into(), "staging".into()])
+//!   }
+//! }
+//!
+//! #[derive(Parser, Container)]
+//! struct Root {
+//!   #[arg(add = completion::values(Clusters))]
+//!   cluster: String,
+//! }
+//!
+//! impl Command for Root {}
+//!
+//! #[tokio::main]
+//! async fn main() -> eyre::Result<()> {
+//!   completion::from_env::<Root>();
+//!
+//!   cata::execute(&Root::parse()).await
+//! }
+//! ```
+#[cfg(feature = "cache")]
+use std::time::Duration;
+
+use clap::CommandFactory;
+use clap_complete::{
+    engine::{ArgValueCompleter, CompletionCandidate},
+    env::Shells,
+};
+use eyre::{eyre, Result};
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "cache")]
+use crate::cache::Cache;
+use crate::{
+    command::{Confirmation, Container},
+    describe::{ArgDescriptor, Describe, Descriptor},
+    dirs::Dirs,
+    Command, Context,
+};
+
+#[cfg(feature = "cache")]
+static DEFAULT_TTL: Duration = Duration::from_secs(60 * 5);
+
+static VAR: &str = "COMPLETE";
+static SHELLS: &[&str] = &["bash", "elvish", "fish", "powershell", "zsh"];
+
+/// A single value that can be completed.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Candidate {
+    /// The value inserted by the shell.
+    pub value: String,
+    /// Optional description shown by shells that support it.
+    pub help: Option<String>,
+}
+
+impl Candidate {
+    /// Create a candidate with a description.
+    pub fn new(value: impl Into<String>, help: impl Into<String>) -> Self {
+        Self {
+            value: value.into(),
+            help: Some(help.into()),
+        }
+    }
+}
+
+impl From<&str> for Candidate {
+    fn from(value: &str) -> Self {
+        Self {
+            value: value.into(),
+            help: None,
+        }
+    }
+}
+
+impl From<String> for Candidate {
+    fn from(value: String) -> Self {
+        Self { value, help: None }
+    }
+}
+
+impl From<Candidate> for CompletionCandidate {
+    fn from(candidate: Candidate) -> Self {
+        CompletionCandidate::new(candidate.value).help(candidate.help.map(Into::into))
+    }
+}
+
+/// Provide completion candidates for an argument at runtime.
+///
+/// Implementations receive what the user has typed so far. Results are
+/// filtered by that prefix afterwards, so it is fine to return everything.
+pub trait Complete: Send + Sync + 'static {
+    /// Returns the candidates for the value currently being typed.
+    fn complete(&self, current: &str) -> Result<Vec<Candidate>>;
+}
+
+impl<F> Complete for F
+where
+    F: Fn(&str) -> Result<Vec<Candidate>> + Send + Sync + 'static,
+{
+    fn complete(&self, current: &str) -> Result<Vec<Candidate>> {
+        self(current)
+    }
+}
+
+/// Attach a [`Complete`] implementation to an argument.
+///
+/// Use with clap's `add` attribute, `#[arg(add = completion::values(...))]`.
+/// Errors from the completer are swallowed, there is nowhere useful to report
+/// them while the shell is completing.
+pub fn values(completer: impl Complete) -> ArgValueCompleter {
+    ArgValueCompleter::new(move |current: &std::ffi::OsStr| {
+        let current = current.to_string_lossy();
+
+        completer
+            .complete(&current)
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|candidate| candidate.value.starts_with(current.as_ref()))
+            .map(Into::into)
+            .collect()
+    })
+}
+
+/// Respond to the shell if it is asking for completions.
+///
+/// This exits the process after responding. It should be the first thing that
+/// runs in `main`.
+pub fn from_env<C: CommandFactory>() {
+    clap_complete::CompleteEnv::with_factory(C::command).complete();
+}
+
+/// Cache the candidates from another completer on disk.
+///
+/// Candidates are fetched once with an empty prefix and then reused until the
+/// TTL expires. Entries are stored with [`crate::cache::Cache`], namespaced by
+/// the application name and a key for the completer.
+#[cfg(feature = "cache")]
+pub struct Cached<C> {
+    inner: C,
+    key: String,
+    cache: Cache,
+}
+
+#[cfg(feature = "cache")]
+impl<C> Cached<C>
+where
+    C: Complete,
+{
+    /// Cache `inner` under `key` for the application `name`.
+    pub fn new(name: impl AsRef<str>, key: impl AsRef<str>, inner: C) -> Self {
+        Self {
+            inner,
+            key: format!("completions/{}", key.as_ref()),
+            cache: Cache::new(name).with_ttl(DEFAULT_TTL),
+        }
+    }
+
+    /// Set how long candidates are reused for.
+    ///
+    /// By default, this is five minutes.
+    #[must_use]
+    pub fn with_ttl(mut self, ttl: Duration) -> Self {
+        self.cache = self.cache.with_ttl(ttl);
+        self
+    }
+}
+
+#[cfg(feature = "cache")]
+impl<C> Complete for Cached<C>
+where
+    C: Complete,
+{
+    fn complete(&self, _: &str) -> Result<Vec<Candidate>> {
+        if let Some(candidates) = self.cache.get(&self.key) {
+            return Ok(candidates);
+        }
+
+        let candidates = self.inner.complete("")?;
+        self.cache.set(&self.key, &candidates)?;
+
+        Ok(candidates)
+    }
+}
+
+/// Print shell completions.
+///
+/// The script calls back into the CLI for candidates, so completions follow
+/// the command tree that is executed, including values resolved at runtime.
+#[derive(clap::Args, Clone, Debug)]
+pub struct Completions {
+    /// Shell to print completions for, detected from `$SHELL` by default.
+    #[arg(value_parser = clap::builder::PossibleValuesParser::new(SHELLS))]
+    shell: Option<String>,
+}
+
+impl Container for Completions {}
+
+impl Describe for Completions {
+    const DESCRIPTOR: Descriptor = Descriptor::new("completions")
+        .with_about("Print shell completions")
+        .with_args(&[ArgDescriptor::new("shell")
+            .with_help("Shell to print completions for, detected from `$SHELL` by default")]);
+}
+
+#[async_trait::async_trait]
+impl Command for Completions {
+    async fn run(&self, _: &mut Context) -> Result<()> {
+        let name = self
+            .shell
+            .clone()
+            .or_else(detect)
+            .ok_or_else(|| eyre!("unable to detect the shell, pass it as an argument"))?;
+        let shells = Shells::builtins();
+        let shell = shells
+            .completer(&name)
+            .ok_or_else(|| eyre!("completions are not supported for {name}"))?;
+
+        let bin = binary()?;
+        shell.write_registration(VAR, &bin, &bin, &bin, &mut std::io::stdout())?;
+
+        Ok(())
+    }
+}
+
+/// Install shell completions.
+///
+/// Writes the completion script for the current shell into the platform data
+/// directory and prints the line that loads it from the shell's rc file.
+#[derive(clap::Args, Clone, Debug)]
+pub struct InstallCompletions {
+    /// Shell to install completions for, detected from `$SHELL` by default.
+    #[arg(long, value_parser = clap::builder::PossibleValuesParser::new(SHELLS))]
+    shell: Option<String>,
+
+    /// Write the script without asking for confirmation.
+    #[arg(short, long)]
+    yes: bool,
+}
+
+impl Container for InstallCompletions {}
+
+impl Describe for InstallCompletions {
+    const DESCRIPTOR: Descriptor = Descriptor::new("install-completions")
+        .with_about("Install shell completions")
+        .with_args(&[
+            ArgDescriptor::new("shell")
+                .with_long("shell")
+                .with_help("Shell to install completions for, detected from `$SHELL` by default"),
+            ArgDescriptor::new("yes")
+                .with_long("yes")
+                .with_short('y')
+                .with_help("Write the script without asking for confirmation"),
+        ]);
+}
+
+#[async_trait::async_trait]
+impl Command for InstallCompletions {
+    async fn run(&self, _: &mut Context) -> Result<()> {
+        let name = self
+            .shell
+            .clone()
+            .or_else(detect)
+            .ok_or_else(|| eyre!("unable to detect the shell, pass --shell"))?;
+        let shells = Shells::builtins();
+        let shell = shells
+            .completer(&name)
+            .ok_or_else(|| eyre!("completions are not supported for {name}"))?;
+
+        let bin = binary()?;
+
+        let path = Dirs::new(&bin)
+            .data()
+            .ok_or_else(|| eyre!("no data directory available"))?
+            .join("completions")
+            .join(format!("{bin}.{}", shell.name()));
+
+        if !self.yes
+            && !Confirmation::new(format!(
+                "Write {} completions to {}?",
+                shell.name(),
+                path.display()
+            ))
+            .ask()?
+        {
+            return Ok(());
+        }
+
+        let mut script = Vec::new();
+        shell.write_registration(VAR, &bin, &bin, &bin, &mut script)?;
+
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(&path, script)?;
+
+        let (rc, line) = rc(shell.name(), &path.display().to_string());
+        println!("Completions written to {}.", path.display());
+        println!("To enable them, add this to {rc}:\n\n  {line}\n");
+
+        Ok(())
+    }
+}
+
+/// The name of the running binary, which the scripts call back into.
+fn binary() -> Result<String> {
+    let exe = std::env::current_exe()?;
+
+    Ok(exe
+        .file_stem()
+        .ok_or_else(|| eyre!("unable to determine the name of the binary"))?
+        .to_string_lossy()
+        .into())
+}
+
+/// The shell the user is running, from `$SHELL`.
+fn detect() -> Option<String> {
+    let shell = std::env::var_os("SHELL").map(std::path::PathBuf::from);
+
+    match shell.as_ref().and_then(|path| path.file_stem()) {
+        Some(name) => Some(name.to_string_lossy().into()),
+        None if cfg!(windows) => Some("powershell".into()),
+        None => None,
+    }
+}
+
+/// The rc file for `shell` and the line that loads the script at `path`.
+fn rc(shell: &str, path: &str) -> (&'static str, String) {
+    match shell {
+        "zsh" => ("~/.zshrc", format!("source \"{path}\"")),
+        "fish" => ("~/.config/fish/config.fish", format!("source \"{path}\"")),
+        "elvish" => (
+            "~/.config/elvish/rc.elv",
+            format!("eval (slurp < \"{path}\")"),
+        ),
+        "powershell" => ("$PROFILE", format!(". \"{path}\"")),
+        _ => ("~/.bashrc", format!("source \"{path}\"")),
+    }
+}
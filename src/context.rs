@@ -0,0 +1,519 @@
+//! Services shared across the command tree.
+//!
+//! Parent commands often build things their subcommands need, such as API
+//! clients or database pools. Instead of reaching for globals, they can be
+//! provided on the [`Context`] that [`crate::execute`] passes to every hook and
+//! consumed by any command further down the tree.
+//!
+//! Some things to note:
+//! - Services are looked up by type, providing a second value of the same type
+//!   replaces the first.
+//! - [`Context::provide_with`] registers a constructor that only runs the first
+//!   time the service is requested.
+//! - Every call to `execute` has its own context, which is dropped once the
+//!   tree has been torn down.
+//! - Services are handed out as an [`Arc`], clone it to use a service from
+//!   background tasks.
+//! - Data flows back up with [`Context::report`], children record results that
+//!   parents summarize in their `post_run`.
+//! - Arguments marked `#[cata(global)]` are available by name with
+//!   [`Context::global`], without threading them through every subcommand.
+//! - The context also carries a [`CancellationToken`], see [`Context::token`],
+//!   whether this is a dry run, see [`Context::dry_run`], the parsed
+//!   arguments, see [`Context::matches`], and the working directory and
+//!   environment that [`crate::Command::overrides`] gives `run`, see
+//!   [`Context::cwd`] and [`Context::var`].
+//!
+//! # Examples
+//!
+//! ```
+//! use cata::{command::PreRun, Command, Container, Context};
+//! use clap::{Parser, Subcommand};
+//!
+//! struct Client {
+//!   url: String,
+//! }
+//!
+//! #[derive(Parser, Container)]
+//! struct Root {
+//!   #[arg(long, default_value = "https://example.com")]
+//!   url: String,
+//!
+//!   #[command(subcommand)]
+//!   cmd: RootCmd,
+//! }
+//!
+//! #[async_trait::async_trait]
+//! impl Command for Root {
+//!   async fn pre_run(&self, ctx: &mut Context) -> eyre::Result<PreRun> {
+//!     let url = self.url.clone();
+//!     ctx.provide_with(move || Client { url });
+//!
+//!     Ok(PreRun::Continue)
+//!   }
+//! }
+//!
+//! #[derive(Subcommand, Container)]
+//! enum RootCmd {
+//!   Fetch(Fetch),
+//! }
+//!
+//! #[derive(Parser, Container)]
+//! struct Fetch {}
+//!
+//! #[async_trait::async_trait]
+//! impl Command for Fetch {
+//!   async fn run(&self, ctx: &mut Context) -> eyre::Result<()> {
+//!     let client = ctx.require::<Client>()?;
+//!     assert_eq!(client.url, "https://example.com");
+//!
+//!     Ok(())
+//!   }
+//! }
+//!
+//! # #[tokio::main]
+//! # async fn main() -> eyre::Result<()> {
+//! cata::execute(&Root::parse_from(["root", "fetch"])).await
+//! # }
+//! ```
+use std::{
+    any::{Any, TypeId},
+    collections::HashMap,
+    ffi::{OsStr, OsString},
+    path::PathBuf,
+    sync::{Arc, Mutex, OnceLock, PoisonError},
+};
+
+use clap::ArgMatches;
+use eyre::{eyre, Result};
+pub use tokio_util::sync::CancellationToken;
+
+#[cfg(feature = "background")]
+use crate::background::TaskGroup;
+use crate::command::Overrides;
+#[cfg(feature = "events")]
+use crate::events::{Event, Subscriber};
+
+type Service = Arc<dyn Any + Send + Sync>;
+type Constructor = Box<dyn FnOnce() -> Service + Send>;
+
+/// A service that is constructed the first time it is requested.
+struct Slot {
+    value: OnceLock<Service>,
+    init: Mutex<Option<Constructor>>,
+}
+
+impl Slot {
+    fn get(&self) -> Option<Service> {
+        if let Some(value) = self.value.get() {
+            return Some(value.clone());
+        }
+
+        // Holding the lock while constructing makes concurrent requests wait
+        // for the value instead of missing it.
+        let mut init = self.init.lock().unwrap_or_else(PoisonError::into_inner);
+
+        if let Some(value) = self.value.get() {
+            return Some(value.clone());
+        }
+
+        Some(self.value.get_or_init(init.take()?).clone())
+    }
+}
+
+/// A typed registry of services for a single run of the command tree.
+///
+/// See the module documentation for usage.
+#[derive(Default)]
+pub struct Context {
+    services: HashMap<TypeId, Slot>,
+    globals: HashMap<&'static str, Service>,
+    reports: Vec<(String, Service)>,
+    token: CancellationToken,
+    pub(crate) dry_run: bool,
+    /// Set when the last `pre_run` returned [`crate::command::PreRun::Skip`].
+    pub(crate) skip: bool,
+    pub(crate) matches: Option<Arc<ArgMatches>>,
+    /// How far below the root the command whose hook is running is.
+    pub(crate) depth: usize,
+    /// The clap name of the root, see [`crate::command::Container::command_name`].
+    pub(crate) root: Option<&'static str>,
+    /// The path segments from the root down to the deepest command entered.
+    pub(crate) segments: Vec<&'static str>,
+    /// How many of `segments` lead to the command whose hook is running.
+    pub(crate) reached: usize,
+    #[cfg(feature = "events")]
+    pub(crate) subscribers: Vec<Arc<dyn Subscriber>>,
+    #[cfg(feature = "first-run")]
+    pub(crate) first_run: bool,
+    #[cfg(feature = "background")]
+    pub(crate) tasks: TaskGroup,
+    /// The overrides of the command whose `run` is executing.
+    pub(crate) overrides: Option<Overrides>,
+}
+
+impl std::fmt::Debug for Context {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Context").finish_non_exhaustive()
+    }
+}
+
+impl Context {
+    /// The token that is cancelled when the user asks the CLI to stop, for
+    /// example with ctrl-c.
+    ///
+    /// Long running commands should watch it and wind down, see
+    /// [`crate::execute`] for the details.
+    #[must_use]
+    pub fn token(&self) -> &CancellationToken {
+        &self.token
+    }
+
+    /// The tasks of the running `run`, see [`TaskGroup`].
+    #[cfg(feature = "background")]
+    #[must_use]
+    pub fn tasks(&self) -> &TaskGroup {
+        &self.tasks
+    }
+
+    /// The working directory of the running command.
+    ///
+    /// This is the one from [`crate::Command::overrides`] while its `run`
+    /// executes, and the process's otherwise.
+    pub fn cwd(&self) -> std::io::Result<PathBuf> {
+        match self.overrides.as_ref().and_then(Overrides::dir) {
+            Some(dir) => Ok(dir.to_path_buf()),
+            None => std::env::current_dir(),
+        }
+    }
+
+    /// The environment variable `key` as the running command sees it, with
+    /// [`crate::Command::overrides`] applied.
+    #[must_use]
+    pub fn var(&self, key: impl AsRef<OsStr>) -> Option<OsString> {
+        let key = key.as_ref();
+
+        match &self.overrides {
+            Some(overrides) => overrides.var(key),
+            None => std::env::var_os(key),
+        }
+    }
+
+    /// A [`std::process::Command`] for `program` that runs in [`Context::cwd`]
+    /// with the environment of [`Context::var`].
+    #[must_use]
+    pub fn command(&self, program: impl AsRef<OsStr>) -> std::process::Command {
+        let mut cmd = std::process::Command::new(program);
+
+        if let Some(overrides) = &self.overrides {
+            overrides.apply(&mut cmd);
+        }
+
+        cmd
+    }
+
+    /// Whether commands should only describe what they would do, see
+    /// [`crate::command::DryRun`].
+    #[must_use]
+    pub fn dry_run(&self) -> bool {
+        self.dry_run
+    }
+
+    /// The output format chosen with [`crate::output::Output`], or the default
+    /// when no command provided one.
+    #[cfg(feature = "output")]
+    #[must_use]
+    pub fn output(&self) -> crate::output::Format {
+        self.get::<crate::output::Format>()
+            .map(|format| (*format).clone())
+            .unwrap_or_default()
+    }
+
+    /// Whether this is the first time the CLI runs on this machine, see
+    /// [`crate::first_run`].
+    #[cfg(feature = "first-run")]
+    #[must_use]
+    pub fn first_run(&self) -> bool {
+        self.first_run
+    }
+
+    /// The [`ArgMatches`] of the command whose hook is running.
+    ///
+    /// This is only available when the matches were passed on with
+    /// [`crate::ExecuteOptions::with_matches`], see [`crate::parse`]. Use it to
+    /// tell flags the user set apart from defaults with
+    /// [`ArgMatches::value_source`]. Global arguments are visible at every
+    /// level.
+    #[must_use]
+    pub fn matches(&self) -> Option<&ArgMatches> {
+        let mut matches = self.matches.as_deref()?;
+        for _ in 0..self.depth {
+            matches = matches.subcommand()?.1;
+        }
+
+        Some(matches)
+    }
+
+    /// The dotted path of the command whose hook is running, such as
+    /// `my-cli.cluster.create`.
+    ///
+    /// The path starts with the name clap has for the root, its
+    /// `#[command(name = "...")]` or the name of the package, and is followed
+    /// by every subcommand down to the running one, see
+    /// [`crate::command::path`]. Unlike the name of the binary, it stays the
+    /// same when the binary is renamed or symlinked, which makes for a stable
+    /// telemetry `activity` or error message prefix.
+    ///
+    /// ```
+    /// use cata::{Command, Container, Context};
+    /// use clap::{Parser, Subcommand};
+    ///
+    /// #[derive(Parser, Container)]
+    /// #[command(name = "my-cli")]
+    /// struct Root {
+    ///   #[command(subcommand)]
+    ///   cmd: RootCmd,
+    /// }
+    ///
+    /// impl Command for Root {}
+    ///
+    /// #[derive(Subcommand, Container)]
+    /// enum RootCmd {
+    ///   Cluster(Cluster),
+    /// }
+    ///
+    /// #[derive(Parser, Container)]
+    /// struct Cluster {
+    ///   #[command(subcommand)]
+    ///   cmd: ClusterCmd,
+    /// }
+    ///
+    /// impl Command for Cluster {}
+    ///
+    /// #[derive(Subcommand, Container)]
+    /// enum ClusterCmd {
+    ///   Create(Create),
+    /// }
+    ///
+    /// #[derive(Parser, Container)]
+    /// struct Create {}
+    ///
+    /// #[async_trait::async_trait]
+    /// impl Command for Create {
+    ///   async fn run(&self, ctx: &mut Context) -> eyre::Result<()> {
+    ///     assert_eq!(ctx.path(), "my-cli.cluster.create");
+    ///
+    ///     Ok(())
+    ///   }
+    /// }
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() -> eyre::Result<()> {
+    /// cata::execute(&Root::parse_from(["root", "cluster", "create"])).await
+    /// # }
+    /// ```
+    #[must_use]
+    pub fn path(&self) -> String {
+        self.root
+            .into_iter()
+            .chain(self.segments[..self.reached].iter().copied())
+            .collect::<Vec<_>>()
+            .join(".")
+    }
+
+    /// The command whose hook is running as it was typed, such as `my-cli
+    /// cluster create`, for messages to the user.
+    pub(crate) fn typed(&self) -> String {
+        crate::lifecycle::typed(&self.segments[..self.reached])
+    }
+
+    /// Tell every subscriber about the event `event` builds from the path of
+    /// the running command.
+    #[cfg(feature = "events")]
+    pub(crate) fn emit<'a>(&self, event: impl FnOnce(String) -> Event<'a>) {
+        if self.subscribers.is_empty() {
+            return;
+        }
+
+        let event = event(self.path());
+
+        for subscriber in &self.subscribers {
+            subscriber.on_event(&event);
+        }
+    }
+
+    fn insert<T: 'static>(&mut self, slot: Slot) {
+        self.services.insert(TypeId::of::<T>(), slot);
+    }
+
+    /// Make `value` available to the rest of the command tree.
+    pub fn provide<T: Send + Sync + 'static>(&mut self, value: T) {
+        self.insert::<T>(Slot {
+            value: OnceLock::from(Arc::new(value) as Service),
+            init: Mutex::new(None),
+        });
+    }
+
+    /// Make the result of `init` available to the rest of the command tree,
+    /// calling it the first time the service is requested.
+    pub fn provide_with<T, F>(&mut self, init: F)
+    where
+        T: Send + Sync + 'static,
+        F: FnOnce() -> T + Send + 'static,
+    {
+        self.insert::<T>(Slot {
+            value: OnceLock::new(),
+            init: Mutex::new(Some(Box::new(move || Arc::new(init()) as Service))),
+        });
+    }
+
+    /// Retrieve the service of type `T`, if one has been provided.
+    #[must_use]
+    pub fn get<T: Send + Sync + 'static>(&self) -> Option<Arc<T>> {
+        self.services
+            .get(&TypeId::of::<T>())?
+            .get()?
+            .downcast()
+            .ok()
+    }
+
+    /// Remove the service of type `T`, returning it if it had been
+    /// constructed.
+    pub fn remove<T: Send + Sync + 'static>(&mut self) -> Option<Arc<T>> {
+        self.services
+            .remove(&TypeId::of::<T>())?
+            .value
+            .into_inner()?
+            .downcast()
+            .ok()
+    }
+
+    /// Retrieve the service of type `T`, failing if it hasn't been provided.
+    pub fn require<T: Send + Sync + 'static>(&self) -> Result<Arc<T>> {
+        self.get()
+            .ok_or_else(|| eyre!("{} has not been provided", std::any::type_name::<T>()))
+    }
+
+    /// Record `value` as a result of the command whose hook is running.
+    ///
+    /// Unlike services, reports of the same type don't replace each other.
+    /// Parents can summarize what their children did from their `post_run`
+    /// with [`Context::reports`].
+    ///
+    /// ```
+    /// use cata::{Command, Container, Context};
+    /// use clap::{Parser, Subcommand};
+    ///
+    /// struct Changed(u32);
+    ///
+    /// #[derive(Parser, Container)]
+    /// struct Root {
+    ///   #[command(subcommand)]
+    ///   cmd: RootCmd,
+    /// }
+    ///
+    /// #[async_trait::async_trait]
+    /// impl Command for Root {
+    ///   async fn post_run(&self, ctx: &mut Context, _: &eyre::Result<()>) -> eyre::Result<()> {
+    ///     let changed: Vec<_> = ctx.reports::<Changed>().collect();
+    ///
+    ///     assert_eq!(changed.len(), 1);
+    ///     assert!(changed[0].0.ends_with(".apply"));
+    ///     assert_eq!(changed[0].1 .0, 3);
+    ///
+    ///     Ok(())
+    ///   }
+    /// }
+    ///
+    /// #[derive(Subcommand, Container)]
+    /// enum RootCmd {
+    ///   Apply(Apply),
+    /// }
+    ///
+    /// #[derive(Parser, Container)]
+    /// struct Apply {}
+    ///
+    /// #[async_trait::async_trait]
+    /// impl Command for Apply {
+    ///   async fn run(&self, ctx: &mut Context) -> eyre::Result<()> {
+    ///     ctx.report(Changed(3));
+    ///
+    ///     Ok(())
+    ///   }
+    /// }
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() -> eyre::Result<()> {
+    /// cata::execute(&Root::parse_from(["root", "apply"])).await
+    /// # }
+    /// ```
+    pub fn report<T: Send + Sync + 'static>(&mut self, value: T) {
+        let path = self.path();
+        self.reports.push((path, Arc::new(value)));
+    }
+
+    /// Every report of type `T` so far, in the order they were made, along
+    /// with the [`Context::path`] of the command that made it.
+    pub fn reports<T: Send + Sync + 'static>(&self) -> impl Iterator<Item = (&str, &T)> {
+        self.reports
+            .iter()
+            .filter_map(|(path, value)| Some((path.as_str(), value.downcast_ref()?)))
+    }
+
+    /// Make the argument `name` available to every command below the one
+    /// whose hook is running.
+    ///
+    /// Fields marked with `#[cata(global)]` are provided this way before the
+    /// `pre_run` of their command, under the name of the field. Providing the
+    /// same name again replaces the value.
+    pub fn provide_global<T: Send + Sync + 'static>(&mut self, name: &'static str, value: T) {
+        self.globals.insert(name, Arc::new(value));
+    }
+
+    /// Retrieve the argument `name` that an ancestor provided, if it has the
+    /// type `T`.
+    ///
+    /// ```
+    /// use cata::{Command, Container, Context};
+    /// use clap::{Parser, Subcommand};
+    ///
+    /// #[derive(Parser, Container)]
+    /// struct Root {
+    ///   #[arg(long, default_value = "default")]
+    ///   #[cata(global)]
+    ///   profile: String,
+    ///
+    ///   #[command(subcommand)]
+    ///   cmd: RootCmd,
+    /// }
+    ///
+    /// impl Command for Root {}
+    ///
+    /// #[derive(Subcommand, Container)]
+    /// enum RootCmd {
+    ///   Deploy(Deploy),
+    /// }
+    ///
+    /// #[derive(Parser, Container)]
+    /// struct Deploy {}
+    ///
+    /// #[async_trait::async_trait]
+    /// impl Command for Deploy {
+    ///   async fn run(&self, ctx: &mut Context) -> eyre::Result<()> {
+    ///     let profile = ctx.global::<String>("profile").expect("root provides it");
+    ///     assert_eq!(*profile, "staging");
+    ///
+    ///     Ok(())
+    ///   }
+    /// }
+    ///
+    /// # #[tokio::main]
+    /// # async fn main() -> eyre::Result<()> {
+    /// cata::execute(&Root::parse_from(["root", "--profile", "staging", "deploy"])).await
+    /// # }
+    /// ```
+    #[must_use]
+    pub fn global<T: Send + Sync + 'static>(&self, name: &str) -> Option<Arc<T>> {
+        self.globals.get(name)?.clone().downcast().ok()
+    }
+}
@@ -0,0 +1,363 @@
+//! Write crash reports for unexpected failures.
+//!
+//! When the CLI panics, or hits an error it can't recover from, a report is
+//! written to the platform cache directory and its path printed to stderr.
+//! Users can then attach the file to bug reports instead of copying output
+//! from their terminal.
+//!
+//! A report contains:
+//! - The name and version of the CLI.
+//! - The OS and architecture.
+//! - The arguments the CLI was invoked with. Values are redacted, only flag
+//!   names and subcommands (when a [`clap::Command`] has been provided) are
+//!   kept.
+//! - The error chain or panic message.
+//! - A backtrace.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use cata::{crash::Crash, Command, Container};
+//! use clap::{CommandFactory, Parser};
+//!
+//! #[derive(Parser, Container)]
+//! struct Root {}
+//!
+//! impl Command for Root {}
+//!
+//! #[tokio::main]
+//! async fn main() -> eyre::Result<()> {
+//!   let crash = Crash::new(env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"))
+//!     .with_command(Root::command());
+//!   crash.clone().install();
+//!
+//!   cata::execute(&Root::parse())
+//!     .await
+//!     .inspect_err(|err| crash.error(err))
+//! }
+//! ```
+use std::{
+    backtrace::Backtrace,
+    fmt::Write as _,
+    path::PathBuf,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use eyre::{eyre, Report, Result};
+
+use crate::dirs::Dirs;
+
+static REDACTED: &str = "<redacted>";
+
+/// Writes crash reports for a CLI.
+///
+/// See the module documentation for usage.
+#[derive(Clone, Debug)]
+pub struct Crash {
+    name: String,
+    version: String,
+    dir: Option<PathBuf>,
+    command: Option<clap::Command>,
+}
+
+impl Crash {
+    /// Create a new crash reporter for the CLI `name` running `version`.
+    pub fn new(name: impl AsRef<str>, version: impl AsRef<str>) -> Self {
+        Self {
+            name: name.as_ref().into(),
+            version: version.as_ref().into(),
+            dir: Dirs::new(&name).cache().map(|dir| dir.join("crashes")),
+            command: None,
+        }
+    }
+
+    /// Use the command definition to keep subcommand names in the arguments.
+    #[must_use]
+    pub fn with_command(mut self, command: clap::Command) -> Self {
+        self.command = Some(command);
+        self
+    }
+
+    /// Set the directory that reports are written to.
+    ///
+    /// By default, this is `crashes` in the platform cache directory.
+    #[must_use]
+    pub fn with_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.dir = Some(dir.into());
+        self
+    }
+
+    /// Write a report whenever the process panics.
+    ///
+    /// The previously installed panic hook still runs, so this can be combined
+    /// with [`crate::error::Hook`]. Install this one last.
+    pub fn install(self) {
+        let previous = std::panic::take_hook();
+
+        std::panic::set_hook(Box::new(move |info| {
+            previous(info);
+
+            self.notify(self.write(&info.to_string(), &Backtrace::force_capture()));
+        }));
+    }
+
+    /// Write a report for an error that could not be recovered from.
+    pub fn error(&self, err: &Report) {
+        let chain = err
+            .chain()
+            .enumerate()
+            .fold(String::new(), |mut out, (i, cause)| {
+                let _ = writeln!(out, "{i}: {cause}");
+                out
+            });
+
+        self.notify(self.write(&chain, &Backtrace::force_capture()));
+    }
+
+    /// The most recently written report, if there is one.
+    #[must_use]
+    pub fn latest(&self) -> Option<PathBuf> {
+        std::fs::read_dir(self.dir.as_ref()?)
+            .ok()?
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == "txt"))
+            .max()
+    }
+
+    fn notify(&self, result: Result<PathBuf>) {
+        match result {
+            Ok(path) => eprintln!(
+                "{} crashed. A report has been written to {}",
+                self.name,
+                path.display()
+            ),
+            Err(e) => eprintln!("Unable to write crash report: {e}"),
+        }
+    }
+
+    fn write(&self, failure: &str, backtrace: &Backtrace) -> Result<PathBuf> {
+        let dir = self
+            .dir
+            .as_ref()
+            .ok_or_else(|| eyre!("no cache directory available"))?;
+        std::fs::create_dir_all(dir)?;
+
+        let now = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+        let path = dir.join(format!("crash-{now}-{}.txt", std::process::id()));
+
+        // `args` panics on arguments that aren't unicode, not what a panic hook
+        // should do.
+        let args = redact(
+            std::env::args_os()
+                .skip(1)
+                .map(|arg| arg.to_string_lossy().into_owned()),
+            self.command.as_ref(),
+        )
+        .join(" ");
+
+        std::fs::write(
+            &path,
+            format!(
+                "name: {}\nversion: {}\nos: {} ({})\nargs: {args}\n\n# Failure\n\n{failure}\n\n# \
+                 Backtrace\n\n{backtrace}\n",
+                self.name,
+                self.version,
+                std::env::consts::OS,
+                std::env::consts::ARCH,
+            ),
+        )?;
+
+        Ok(path)
+    }
+}
+
+/// Replace every argument value with a placeholder.
+///
+/// Flags are kept, without their values. Positional arguments are only kept
+/// when they match the name of a subcommand in `command`. Short flags can have
+/// their value attached, such as `-tsecret`, everything after the first short
+/// flag that takes a value is redacted. Short flags that `command` doesn't know
+/// about are assumed to take one.
+///
+/// ```
+/// use cata::crash::redact;
+///
+/// let cmd = clap::Command::new("root").subcommand(
+///   clap::Command::new("login")
+///     .arg(clap::Arg::new("token").short('t'))
+///     .arg(clap::Arg::new("force").short('f').action(clap::ArgAction::SetTrue)),
+/// );
+/// let args = ["login", "--token=secret", "--user", "me", "-ftsecret"].map(String::from);
+///
+/// assert_eq!(
+///   redact(args, Some(&cmd)),
+///   vec![
+///     "login",
+///     "--token=<redacted>",
+///     "--user",
+///     "<redacted>",
+///     "-ft<redacted>",
+///   ],
+/// );
+/// ```
+pub fn redact(
+    args: impl IntoIterator<Item = String>,
+    command: Option<&clap::Command>,
+) -> Vec<String> {
+    let mut current = command;
+
+    args.into_iter()
+        .map(|arg| {
+            if arg.starts_with('-') && !arg.starts_with("--") {
+                return short(&arg, current);
+            }
+
+            if arg.starts_with('-') {
+                return match arg.split_once('=') {
+                    Some((flag, _)) => format!("{flag}={REDACTED}"),
+                    None => arg,
+                };
+            }
+
+            match current.and_then(|cmd| cmd.find_subcommand(&arg)) {
+                Some(sub) => {
+                    current = Some(sub);
+                    arg
+                }
+                None => REDACTED.to_string(),
+            }
+        })
+        .collect()
+}
+
+/// Redact the value attached to a group of short flags, such as `-ftsecret`.
+fn short(arg: &str, command: Option<&clap::Command>) -> String {
+    let flags = &arg[1..];
+
+    for (i, flag) in flags.char_indices() {
+        let takes_value = command
+            .and_then(|cmd| cmd.get_arguments().find(|a| a.get_short() == Some(flag)))
+            .map_or(true, |a| a.get_action().takes_values());
+
+        let end = i + flag.len_utf8();
+        if !takes_value || end == flags.len() {
+            continue;
+        }
+
+        let separator = if flags[end..].starts_with('=') {
+            "="
+        } else {
+            ""
+        };
+
+        return format!("-{}{separator}{REDACTED}", &flags[..end]);
+    }
+
+    arg.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{redact, short, REDACTED};
+
+    fn command() -> clap::Command {
+        clap::Command::new("root").subcommand(
+            clap::Command::new("login")
+                .arg(clap::Arg::new("token").short('t'))
+                .arg(
+                    clap::Arg::new("force")
+                        .short('f')
+                        .action(clap::ArgAction::SetTrue),
+                )
+                .arg(
+                    clap::Arg::new("verbose")
+                        .short('v')
+                        .action(clap::ArgAction::Count),
+                ),
+        )
+    }
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(ToString::to_string).collect()
+    }
+
+    #[test]
+    fn tokens() {
+        let cmd = command();
+
+        assert_eq!(
+            redact(
+                args(&["login", "--token", "ghp_secret", "--token=ghp_secret"]),
+                Some(&cmd)
+            ),
+            ["login", "--token", REDACTED, "--token=<redacted>"],
+        );
+    }
+
+    #[test]
+    fn paths() {
+        let cmd = command();
+
+        assert_eq!(
+            redact(
+                args(&["login", "/home/me/.ssh/id_ed25519", "--config=./me.toml"]),
+                Some(&cmd)
+            ),
+            ["login", REDACTED, "--config=<redacted>"],
+        );
+    }
+
+    #[test]
+    fn emails() {
+        let cmd = command();
+
+        assert_eq!(
+            redact(
+                args(&["login", "me@example.com", "--user=me@example.com"]),
+                Some(&cmd)
+            ),
+            ["login", REDACTED, "--user=<redacted>"],
+        );
+    }
+
+    #[test]
+    fn subcommands_only_match_where_they_are() {
+        let cmd = command();
+
+        assert_eq!(
+            redact(args(&["login", "login"]), Some(&cmd)),
+            ["login", REDACTED]
+        );
+        assert_eq!(redact(args(&["login"]), None), [REDACTED]);
+    }
+
+    #[test]
+    fn positionals_after_a_separator() {
+        let cmd = command();
+
+        assert_eq!(
+            redact(args(&["login", "--", "login", "-tsecret"]), Some(&cmd)),
+            ["login", "--", REDACTED, "-t<redacted>"],
+        );
+    }
+
+    #[test]
+    fn short_flags() {
+        let cmd = command();
+        let login = cmd.find_subcommand("login");
+
+        assert_eq!(short("-tsecret", login), "-t<redacted>");
+        assert_eq!(short("-t=secret", login), "-t=<redacted>");
+        assert_eq!(short("-fvtsecret", login), "-fvt<redacted>");
+        assert_eq!(short("-fvv", login), "-fvv");
+        assert_eq!(short("-t", login), "-t");
+        assert_eq!(short("-", login), "-");
+    }
+
+    #[test]
+    fn unknown_short_flags_take_a_value() {
+        assert_eq!(short("-xsecret", None), "-x<redacted>");
+        assert_eq!(short("-x", None), "-x");
+        assert_eq!(short("-fé", None), "-f<redacted>");
+    }
+}
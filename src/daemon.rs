@@ -0,0 +1,508 @@
+//! Keep a command running after the terminal closes.
+//!
+//! [`Daemon::detach`] starts a copy of the current process, with the same
+//! arguments, in the background and tells the original that it can exit. The
+//! copy runs through the command tree again, calls `detach` from the same
+//! place and carries on with the actual work.
+//!
+//! Some things to note:
+//! - There is no `fork`, the background process is a fresh one. On unix it is
+//!   in its own process group, on Windows it has no console.
+//! - stdout and stderr of the background process are appended to
+//!   [`Daemon::log`], stdin is closed.
+//! - A pidfile in the platform state directory makes sure only one copy runs at
+//!   a time. It is linked into place, pid and all, by whichever copy gets there
+//!   first and removed when the [`Guard`] is dropped. Pidfiles without a
+//!   running process behind them are stale and replaced.
+//! - The background copy is marked with an environment variable that is read
+//!   once, in [`Daemon::new`], and never changed. Processes started by the
+//!   daemon inherit it, the same CLI detaching again from there finds the
+//!   pidfile taken.
+//! - [`Status`] and [`Stop`] print through [`crate::output`] with the
+//!   `output` feature, in the format from [`Context::output`].
+//! - [`Status`] and [`Stop`] are ready made subcommands, they use the daemon
+//!   registered with [`Daemon::install`]. So does [`crate::command::detach`].
+//! - [`Foreground`] is a `--foreground` flag that skips detaching. The command
+//!   still claims the pidfile but runs in the current process, with output on
+//!   the terminal.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use cata::{
+//!   command::PreRun,
+//!   daemon::{Daemon, Role, Status, Stop},
+//!   Command, Container,
+//! };
+//! use clap::{Parser, Subcommand};
+//!
+//! #[derive(Parser, Container)]
+//! struct Root {
+//!   #[command(subcommand)]
+//!   cmd: RootCmd,
+//! }
+//!
+//! #[async_trait::async_trait]
+//! impl Command for Root {
+//!   async fn pre_run(&self, _: &mut cata::Context) -> eyre::Result<PreRun> {
+//!     Daemon::new("my-cli").install().map(|()| PreRun::Continue)
+//!   }
+//! }
+//!
+//! #[derive(Subcommand, Container)]
+//! enum RootCmd {
+//!   Serve(Serve),
+//!   Status(Status),
+//!   Stop(Stop),
+//! }
+//!
+//! #[derive(Parser, Container)]
+//! struct Serve {}
+//!
+//! #[async_trait::async_trait]
+//! impl Command for Serve {
+//!   async fn run(&self, _: &mut cata::Context) -> eyre::Result<()> {
+//!     let _guard = match Daemon::new("my-cli").detach()? {
+//!       Role::Parent { pid } => {
+//!         println!("started in the background ({pid})");
+//!         return Ok(());
+//!       }
+//!       Role::Daemon(guard) => guard,
+//!     };
+//!
+//!     loop {
+//!       tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+//!     }
+//!   }
+//! }
+//! ```
+use std::{
+    fmt,
+    fs::OpenOptions,
+    path::{Path, PathBuf},
+    process::Stdio,
+    sync::OnceLock,
+    time::Duration,
+};
+
+use eyre::{eyre, Result};
+use serde::Serialize;
+
+use crate::{
+    command::Container,
+    describe::{Describe, Descriptor},
+    dirs::{self, Dirs},
+    runtime, Command, Context,
+};
+
+static GLOBAL: OnceLock<Daemon> = OnceLock::new();
+static STOP_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Runs commands in the background.
+///
+/// See the module documentation for usage.
+#[derive(Clone, Debug)]
+pub struct Daemon {
+    name: String,
+    dir: Option<PathBuf>,
+    foreground: bool,
+    /// Whether this process is the background copy, from the marker.
+    background: bool,
+}
+
+/// Which side of [`Daemon::detach`] the current process is on.
+#[derive(Debug)]
+pub enum Role {
+    /// The original process, which should exit. `pid` is the background
+    /// process.
+    Parent {
+        /// Process ID of the background process.
+        pid: u32,
+    },
+    /// The background process, which should do the work.
+    Daemon(Guard),
+}
+
+/// Removes the pidfile when dropped.
+#[derive(Debug)]
+pub struct Guard {
+    pidfile: PathBuf,
+}
+
+impl Drop for Guard {
+    fn drop(&mut self) {
+        if let Err(e) = std::fs::remove_file(&self.pidfile) {
+            tracing::debug!("unable to remove {}: {e}", self.pidfile.display());
+        }
+    }
+}
+
+impl Daemon {
+    /// Create a daemon for the CLI `name`.
+    pub fn new(name: impl AsRef<str>) -> Self {
+        let name = name.as_ref();
+
+        Self {
+            name: name.into(),
+            dir: Dirs::new(name).state().map(|dir| dir.join("daemon")),
+            foreground: false,
+            background: std::env::var_os(marker(name)).is_some(),
+        }
+    }
+
+    /// Stay in the current process instead of starting a background one, see
+    /// [`Foreground`].
+    ///
+    /// [`Daemon::detach`] then only claims the pidfile. By default, commands
+    /// detach.
+    #[must_use]
+    pub fn with_foreground(mut self, foreground: bool) -> Self {
+        self.foreground = foreground;
+        self
+    }
+
+    /// Set the directory for the pidfile and log.
+    ///
+    /// By default, this is `daemon` in the platform state directory.
+    #[must_use]
+    pub fn with_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.dir = Some(dir.into());
+        self
+    }
+
+    fn dir(&self) -> Result<&PathBuf> {
+        self.dir
+            .as_ref()
+            .ok_or_else(|| eyre!("no state directory available"))
+    }
+
+    fn pidfile(&self) -> Result<PathBuf> {
+        Ok(self.dir()?.join(format!("{}.pid", self.name)))
+    }
+
+    /// Path to the file that output of the background process goes to.
+    pub fn log(&self) -> Result<PathBuf> {
+        Ok(self.dir()?.join(format!("{}.log", self.name)))
+    }
+
+    /// Start the background process, or claim the pidfile when this is it.
+    pub fn detach(&self) -> Result<Role> {
+        if self.background {
+            return self.claim().map(Role::Daemon);
+        }
+
+        if let Some(pid) = self.status()? {
+            return Err(eyre!("{} is already running ({pid})", self.name));
+        }
+
+        std::fs::create_dir_all(self.dir()?)?;
+
+        if self.foreground {
+            return self.claim().map(Role::Daemon);
+        }
+
+        let log = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.log()?)?;
+
+        let mut cmd = std::process::Command::new(std::env::current_exe()?);
+        cmd.args(std::env::args_os().skip(1))
+            .env(marker(&self.name), "1")
+            .stdin(Stdio::null())
+            .stdout(log.try_clone()?)
+            .stderr(log);
+
+        #[cfg(unix)]
+        std::os::unix::process::CommandExt::process_group(&mut cmd, 0);
+
+        #[cfg(windows)]
+        {
+            const DETACHED_PROCESS: u32 = 0x0000_0008;
+            const CREATE_NEW_PROCESS_GROUP: u32 = 0x0000_0200;
+
+            std::os::windows::process::CommandExt::creation_flags(
+                &mut cmd,
+                DETACHED_PROCESS | CREATE_NEW_PROCESS_GROUP,
+            );
+        }
+
+        Ok(Role::Parent {
+            pid: cmd.spawn()?.id(),
+        })
+    }
+
+    /// Create the pidfile for the current process, failing when another one
+    /// already has.
+    ///
+    /// The pid is written to a file of its own first and then linked into
+    /// place, which fails when the pidfile exists. It never shows up empty,
+    /// even when the process dies halfway through.
+    fn claim(&self) -> Result<Guard> {
+        let pidfile = self.pidfile()?;
+        std::fs::create_dir_all(self.dir()?)?;
+
+        let pid = std::process::id();
+        let staged = pidfile.with_extension(format!("pid.{pid}"));
+        std::fs::write(&staged, pid.to_string())?;
+
+        let claimed = self.link(&staged, &pidfile);
+
+        if let Err(e) = std::fs::remove_file(&staged) {
+            tracing::debug!("unable to remove {}: {e}", staged.display());
+        }
+
+        claimed
+    }
+
+    /// Link `staged` to `pidfile`, replacing it only when it is stale.
+    fn link(&self, staged: &Path, pidfile: &Path) -> Result<Guard> {
+        loop {
+            match std::fs::hard_link(staged, pidfile) {
+                Ok(()) => {
+                    return Ok(Guard {
+                        pidfile: pidfile.to_path_buf(),
+                    })
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+                    if let Some(pid) = self.status()? {
+                        return Err(eyre!("{} is already running ({pid})", self.name));
+                    }
+
+                    // `status` removed the stale pidfile, try again.
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+
+    /// The process ID of the background process, if it is running.
+    ///
+    /// Stale pidfiles, left behind by a process that didn't exit cleanly, are
+    /// removed. So are pidfiles that don't hold a pid.
+    pub fn status(&self) -> Result<Option<u32>> {
+        let pidfile = self.pidfile()?;
+
+        let pid = match std::fs::read_to_string(&pidfile) {
+            Ok(raw) => raw.trim().parse::<u32>().ok(),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e.into()),
+        };
+
+        if let Some(pid) = pid.filter(|pid| alive(*pid)) {
+            return Ok(Some(pid));
+        }
+
+        remove(&pidfile)?;
+
+        Ok(None)
+    }
+
+    /// Stop the background process, waiting for it to exit.
+    ///
+    /// Returns the process ID that was stopped, if there was one.
+    pub async fn stop(&self) -> Result<Option<u32>> {
+        let Some(pid) = self.status()? else {
+            return Ok(None);
+        };
+
+        terminate(pid)?;
+
+        runtime::timeout(STOP_TIMEOUT, async {
+            while alive(pid) {
+                runtime::current().sleep(Duration::from_millis(100)).await;
+            }
+        })
+        .await
+        .ok_or_else(|| eyre!("{} ({pid}) did not stop in time", self.name))?;
+
+        remove(&self.pidfile()?)?;
+
+        Ok(Some(pid))
+    }
+
+    /// Make this the daemon used by the [`Status`] and [`Stop`] subcommands.
+    pub fn install(self) -> Result<()> {
+        GLOBAL
+            .set(self)
+            .map_err(|_| eyre!("a daemon has already been installed"))
+    }
+}
+
+/// The variable that marks a process as the background copy of `name`.
+fn marker(name: &str) -> String {
+    dirs::env_var(name, "DAEMON")
+}
+
+fn remove(path: &Path) -> Result<()> {
+    match std::fs::remove_file(path) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+        _ => Ok(()),
+    }
+}
+
+#[cfg(unix)]
+fn alive(pid: u32) -> bool {
+    let Ok(pid) = libc::pid_t::try_from(pid) else {
+        return false;
+    };
+
+    // Signal 0 only checks whether the process exists. EPERM means it exists
+    // but belongs to someone else.
+    let found = unsafe { libc::kill(pid, 0) } == 0;
+
+    found || std::io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+#[cfg(unix)]
+fn terminate(pid: u32) -> Result<()> {
+    let pid = libc::pid_t::try_from(pid)?;
+
+    if unsafe { libc::kill(pid, libc::SIGTERM) } != 0 {
+        return Err(std::io::Error::last_os_error().into());
+    }
+
+    Ok(())
+}
+
+#[cfg(windows)]
+fn alive(pid: u32) -> bool {
+    std::process::Command::new("tasklist")
+        .args(["/FI", &format!("PID eq {pid}"), "/NH"])
+        .output()
+        .is_ok_and(|out| String::from_utf8_lossy(&out.stdout).contains(&pid.to_string()))
+}
+
+#[cfg(windows)]
+fn terminate(pid: u32) -> Result<()> {
+    let status = std::process::Command::new("taskkill")
+        // There is no console to send a close to, it has to be forced.
+        .args(["/PID", &pid.to_string(), "/F"])
+        .status()?;
+
+    if !status.success() {
+        return Err(eyre!("taskkill exited with {status}"));
+    }
+
+    Ok(())
+}
+
+pub(crate) fn installed() -> Result<&'static Daemon> {
+    GLOBAL
+        .get()
+        .ok_or_else(|| eyre!("a daemon must be installed to check on it"))
+}
+
+/// A `--foreground` flag for commands that detach.
+///
+/// Flatten it into the command and pass it on to [`Daemon::with_foreground`]
+/// or [`crate::command::detach`].
+#[derive(clap::Args, Clone, Debug, Default)]
+pub struct Foreground {
+    /// Stay in the foreground instead of running in the background.
+    #[arg(long = "foreground")]
+    pub enabled: bool,
+}
+
+/// Show whether the background process is running.
+#[derive(clap::Args, Clone, Debug)]
+pub struct Status {}
+
+impl Container for Status {}
+
+impl Describe for Status {
+    const DESCRIPTOR: Descriptor =
+        Descriptor::new("status").with_about("Show whether the background process is running");
+}
+
+#[async_trait::async_trait]
+impl Command for Status {
+    async fn run(&self, ctx: &mut Context) -> Result<()> {
+        let daemon = installed()?;
+
+        let state = match daemon.status()? {
+            Some(pid) => State::Running { pid },
+            None => State::NotRunning,
+        };
+
+        Report::new(daemon, state).print(ctx)
+    }
+}
+
+/// Stop the background process.
+#[derive(clap::Args, Clone, Debug)]
+pub struct Stop {}
+
+impl Container for Stop {}
+
+impl Describe for Stop {
+    const DESCRIPTOR: Descriptor =
+        Descriptor::new("stop").with_about("Stop the background process");
+}
+
+#[async_trait::async_trait]
+impl Command for Stop {
+    async fn run(&self, ctx: &mut Context) -> Result<()> {
+        let daemon = installed()?;
+
+        let state = match daemon.stop().await? {
+            Some(pid) => State::Stopped { pid },
+            None => State::NotRunning,
+        };
+
+        Report::new(daemon, state).print(ctx)
+    }
+}
+
+/// What [`Status`] and [`Stop`] found out about the background process.
+#[derive(Serialize)]
+struct Report<'a> {
+    name: &'a str,
+    #[serde(flatten)]
+    state: State,
+}
+
+#[derive(Serialize)]
+#[serde(tag = "state", rename_all = "kebab-case")]
+enum State {
+    Running { pid: u32 },
+    Stopped { pid: u32 },
+    NotRunning,
+}
+
+impl<'a> Report<'a> {
+    fn new(daemon: &'a Daemon, state: State) -> Self {
+        Self {
+            name: &daemon.name,
+            state,
+        }
+    }
+
+    /// Print in the format from [`Context::output`], which needs the `output`
+    /// feature.
+    #[cfg_attr(
+        not(feature = "output"),
+        allow(unused_variables, clippy::unnecessary_wraps)
+    )]
+    fn print(&self, ctx: &Context) -> Result<()> {
+        #[cfg(feature = "output")]
+        return ctx.output().document(self);
+
+        #[cfg(not(feature = "output"))]
+        {
+            println!("{self}");
+
+            Ok(())
+        }
+    }
+}
+
+impl fmt::Display for Report<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.state {
+            State::Running { pid } => write!(f, "{} is running ({pid})", self.name),
+            State::Stopped { pid } => write!(f, "stopped {} ({pid})", self.name),
+            State::NotRunning => write!(f, "{} is not running", self.name),
+        }
+    }
+}
@@ -0,0 +1,307 @@
+//! The command tree as it is known at compile time.
+//!
+//! [`crate::tree::Tree`] is built from clap commands, which have to be
+//! constructed at runtime. Doc generators, completion scripts and permission
+//! audits that only need names, help and arguments can use the [`Descriptor`]
+//! that `#[derive(Container)]` puts together instead. It is a `const`,
+//! assembled from the descriptors of every command below.
+//!
+//! Some things to note:
+//! - Commands without subcommands get a descriptor from the derive. Those with
+//!   subcommands need `#[cata(describe)]`, and every command below them has to
+//!   implement [`Describe`]. Commands that implement `Container` by hand can
+//!   implement it themselves.
+//! - Descriptors come from the attributes the derive sees. Arguments in
+//!   `#[command(flatten)]` groups aren't included, neither are subcommands held
+//!   as a `Box<dyn Command>` or registered with [`crate::registry`], which are
+//!   only known at runtime.
+//! - Paths start with the name of the root command, like the ones of
+//!   [`crate::tree::Tree`]. That is the kebab-case name of the type, or the one
+//!   from `#[command(name = "...")]`.
+//!
+//! # Examples
+//!
+//! ```
+//! use cata::{
+//!   describe::{Describe, Descriptor},
+//!   Command, Container,
+//! };
+//! use clap::{Parser, Subcommand};
+//!
+//! #[derive(Parser, Container)]
+//! #[command(name = "my-cli")]
+//! #[cata(describe)]
+//! struct Root {
+//!   #[command(subcommand)]
+//!   cmd: RootCmd,
+//! }
+//!
+//! impl Command for Root {}
+//!
+//! #[derive(Subcommand, Container)]
+//! #[cata(describe)]
+//! enum RootCmd {
+//!   Deploy(Deploy),
+//! }
+//!
+//! /// Deploy the current project.
+//! #[derive(Parser, Container)]
+//! struct Deploy {
+//!   /// Environment to deploy to.
+//!   #[arg(short, long)]
+//!   env: String,
+//! }
+//!
+//! impl Command for Deploy {}
+//!
+//! const TREE: Descriptor = Root::DESCRIPTOR;
+//!
+//! let deploy = TREE.find(&["my-cli", "deploy"]).unwrap();
+//! assert_eq!(deploy.about(), Some("Deploy the current project"));
+//!
+//! let env = &deploy.args()[0];
+//! assert_eq!(env.long(), Some("env"));
+//! assert_eq!(env.short(), Some('e'));
+//! assert_eq!(env.help(), Some("Environment to deploy to"));
+//!
+//! let mut paths = Vec::new();
+//! TREE.walk(&mut |path, _| paths.push(path.join(" ")));
+//! assert_eq!(paths, ["my-cli", "my-cli deploy"]);
+//! ```
+
+/// Types with a [`Descriptor`], see the module documentation.
+#[diagnostic::on_unimplemented(
+    message = "`{Self}` doesn't have a descriptor",
+    label = "no descriptor",
+    note = "add `#[derive(cata::Container)]` to `{Self}`, along with `#[cata(describe)]` when it has \
+            subcommands"
+)]
+pub trait Describe {
+    /// The command and everything below it.
+    const DESCRIPTOR: Descriptor;
+}
+
+/// A command, its arguments and its subcommands, known at compile time.
+#[derive(Clone, Copy, Debug)]
+pub struct Descriptor {
+    name: &'static str,
+    about: Option<&'static str>,
+    args: &'static [ArgDescriptor],
+    children: &'static [Descriptor],
+    flag: Option<&'static str>,
+    examples: &'static [&'static str],
+}
+
+impl Descriptor {
+    /// Describe the command `name`.
+    #[must_use]
+    pub const fn new(name: &'static str) -> Self {
+        Self {
+            name,
+            about: None,
+            args: &[],
+            children: &[],
+            flag: None,
+            examples: &[],
+        }
+    }
+
+    /// Rename the command, which is what parents do for their subcommands.
+    #[must_use]
+    pub const fn with_name(mut self, name: &'static str) -> Self {
+        self.name = name;
+        self
+    }
+
+    /// Set the short description, the first paragraph of the help.
+    #[must_use]
+    pub const fn with_about(mut self, about: &'static str) -> Self {
+        self.about = Some(about);
+        self
+    }
+
+    /// Set the arguments of the command.
+    #[must_use]
+    pub const fn with_args(mut self, args: &'static [ArgDescriptor]) -> Self {
+        self.args = args;
+        self
+    }
+
+    /// Set the subcommands of the command.
+    #[must_use]
+    pub const fn with_children(mut self, children: &'static [Descriptor]) -> Self {
+        self.children = children;
+        self
+    }
+
+    /// Set the feature flag that gates the command, see [`crate::flags`].
+    #[must_use]
+    pub const fn with_flag(mut self, flag: &'static str) -> Self {
+        self.flag = Some(flag);
+        self
+    }
+
+    /// Set the example invocations of the command, see [`crate::examples`].
+    #[must_use]
+    pub const fn with_examples(mut self, examples: &'static [&'static str]) -> Self {
+        self.examples = examples;
+        self
+    }
+
+    /// The name of the command.
+    #[must_use]
+    pub const fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// The short description of the command, if it has one.
+    #[must_use]
+    pub const fn about(&self) -> Option<&'static str> {
+        self.about
+    }
+
+    /// The arguments of the command.
+    #[must_use]
+    pub const fn args(&self) -> &'static [ArgDescriptor] {
+        self.args
+    }
+
+    /// The subcommands of the command.
+    #[must_use]
+    pub const fn children(&self) -> &'static [Descriptor] {
+        self.children
+    }
+
+    /// The feature flag that gates the command, if there is one.
+    #[must_use]
+    pub const fn flag(&self) -> Option<&'static str> {
+        self.flag
+    }
+
+    /// The example invocations of the command.
+    #[must_use]
+    pub const fn examples(&self) -> &'static [&'static str] {
+        self.examples
+    }
+
+    /// The command at `path`, starting with the name of this one.
+    #[must_use]
+    pub fn find(&self, path: &[&str]) -> Option<&Descriptor> {
+        let (first, mut rest) = path.split_first()?;
+
+        if *first != self.name {
+            return None;
+        }
+
+        let mut descriptor = self;
+        while let Some((name, remaining)) = rest.split_first() {
+            descriptor = descriptor
+                .children
+                .iter()
+                .find(|child| child.name == *name)?;
+            rest = remaining;
+        }
+
+        Some(descriptor)
+    }
+
+    /// Call `f` with the path to every command, depth first and parents
+    /// before their children.
+    pub fn walk(&self, f: &mut impl FnMut(&[&'static str], &Descriptor)) {
+        self.walk_below(&mut Vec::new(), f);
+    }
+
+    fn walk_below(
+        &self,
+        path: &mut Vec<&'static str>,
+        f: &mut impl FnMut(&[&'static str], &Descriptor),
+    ) {
+        path.push(self.name);
+        f(path, self);
+
+        for child in self.children {
+            child.walk_below(path, f);
+        }
+
+        path.pop();
+    }
+}
+
+/// An argument of a [`Descriptor`].
+#[derive(Clone, Copy, Debug)]
+pub struct ArgDescriptor {
+    id: &'static str,
+    long: Option<&'static str>,
+    short: Option<char>,
+    help: Option<&'static str>,
+}
+
+impl ArgDescriptor {
+    /// Describe the argument `id`, the name of its field.
+    #[must_use]
+    pub const fn new(id: &'static str) -> Self {
+        Self {
+            id,
+            long: None,
+            short: None,
+            help: None,
+        }
+    }
+
+    /// Set the long name, `--long`.
+    #[must_use]
+    pub const fn with_long(mut self, long: &'static str) -> Self {
+        self.long = Some(long);
+        self
+    }
+
+    /// Set the short name, `-s`.
+    #[must_use]
+    pub const fn with_short(mut self, short: char) -> Self {
+        self.short = Some(short);
+        self
+    }
+
+    /// Set the help of the argument.
+    #[must_use]
+    pub const fn with_help(mut self, help: &'static str) -> Self {
+        self.help = Some(help);
+        self
+    }
+
+    /// The name of the field the argument is parsed into.
+    #[must_use]
+    pub const fn id(&self) -> &'static str {
+        self.id
+    }
+
+    /// The long name of the argument, without the dashes.
+    #[must_use]
+    pub const fn long(&self) -> Option<&'static str> {
+        self.long
+    }
+
+    /// The short name of the argument.
+    #[must_use]
+    pub const fn short(&self) -> Option<char> {
+        self.short
+    }
+
+    /// The help of the argument, if it has some.
+    #[must_use]
+    pub const fn help(&self) -> Option<&'static str> {
+        self.help
+    }
+
+    /// Whether the argument is positional, it has neither a long nor a short
+    /// name.
+    #[must_use]
+    pub const fn is_positional(&self) -> bool {
+        self.long.is_none() && self.short.is_none()
+    }
+}
+
+/// Optional subcommands have the same descriptor as the required ones.
+impl<T: Describe> Describe for Option<T> {
+    const DESCRIPTOR: Descriptor = T::DESCRIPTOR;
+}
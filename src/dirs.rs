@@ -0,0 +1,112 @@
+//! Platform specific directories for a CLI.
+//!
+//! Resolves where configuration, caches, state and data should live on the
+//! current platform (XDG on Linux, `~/Library` on macOS and `AppData` on
+//! Windows), namespaced by the name of the CLI. Everything in cata that
+//! persists to disk goes through this module so that a CLI's files are all in
+//! predictable places.
+//!
+//! - `config`: `$XDG_CONFIG_HOME`, `~/Library/Application Support` or
+//!   `%APPDATA%`.
+//! - `cache`: `$XDG_CACHE_HOME`, `~/Library/Caches` or `%LOCALAPPDATA%`.
+//! - `state`: `$XDG_STATE_HOME`, `~/Library/Application Support` or
+//!   `%LOCALAPPDATA%`.
+//! - `data`: `$XDG_DATA_HOME`, `~/Library/Application Support` or `%APPDATA%`.
+//!
+//! Each directory can be overridden with an environment variable made from the
+//! upper-cased name and kind, for example `MY_CLI_CACHE_DIR`. Every variable
+//! cata reads for a CLI is named the same way, see [`env_var`].
+//!
+//! # Examples
+//!
+//! ```
+//! use cata::dirs::Dirs;
+//!
+//! let dirs = Dirs::new("my-cli");
+//!
+//! if let Some(cache) = dirs.cache() {
+//!   println!("caching in {}", cache.display());
+//! }
+//! ```
+use std::path::PathBuf;
+
+/// The environment variable `suffix` for the CLI `name`.
+///
+/// This is the upper-cased name and suffix, with dashes turned into
+/// underscores.
+///
+/// ```
+/// assert_eq!(cata::dirs::env_var("my-cli", "cache_dir"), "MY_CLI_CACHE_DIR");
+/// ```
+#[must_use]
+pub fn env_var(name: &str, suffix: &str) -> String {
+    format!("{name}_{suffix}").to_uppercase().replace('-', "_")
+}
+
+/// The directories for a single CLI.
+///
+/// See the module documentation for usage.
+#[derive(Clone, Debug)]
+pub struct Dirs {
+    name: String,
+}
+
+impl Dirs {
+    /// Create the directories for the CLI `name`.
+    pub fn new(name: impl AsRef<str>) -> Self {
+        Self {
+            name: name.as_ref().into(),
+        }
+    }
+
+    /// Directory for user editable configuration.
+    #[must_use]
+    pub fn config(&self) -> Option<PathBuf> {
+        self.resolve("config", ::dirs::config_dir())
+    }
+
+    /// Directory for files that can be deleted at any time.
+    #[must_use]
+    pub fn cache(&self) -> Option<PathBuf> {
+        self.resolve("cache", ::dirs::cache_dir())
+    }
+
+    /// Directory for state that should persist between runs but isn't
+    /// important enough to back up.
+    ///
+    /// Only Linux has a dedicated state directory, the local data directory is
+    /// used everywhere else.
+    #[must_use]
+    pub fn state(&self) -> Option<PathBuf> {
+        self.resolve("state", ::dirs::state_dir().or_else(::dirs::data_local_dir))
+    }
+
+    /// Directory for data the user would expect to keep.
+    #[must_use]
+    pub fn data(&self) -> Option<PathBuf> {
+        self.resolve("data", ::dirs::data_dir())
+    }
+
+    fn resolve(&self, kind: &str, base: Option<PathBuf>) -> Option<PathBuf> {
+        let var = env_var(&self.name, &format!("{kind}_DIR"));
+
+        std::env::var_os(var)
+            .map(PathBuf::from)
+            .or_else(|| base.map(|dir| dir.join(&self.name)))
+    }
+}
+
+/// Whether `path` is a file that can be run, for looking up executables on
+/// `$PATH`.
+#[cfg(all(unix, any(feature = "plugin", feature = "requirements")))]
+pub(crate) fn is_executable(path: &std::path::Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+
+    path.metadata()
+        .is_ok_and(|meta| meta.is_file() && meta.permissions().mode() & 0o111 != 0)
+}
+
+#[cfg(all(windows, any(feature = "plugin", feature = "requirements")))]
+pub(crate) fn is_executable(path: &std::path::Path) -> bool {
+    path.is_file() && path.extension().is_some_and(|ext| ext == "exe")
+}
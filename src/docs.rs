@@ -0,0 +1,68 @@
+//! Documentation generated from the command tree.
+//!
+//! The recursive structure of the CLI already lives in [`crate::tree`], these
+//! generators turn it, along with metadata such as deprecations, into
+//! documentation that can be shipped with the CLI.
+//!
+//! - [`man`]: roff man pages, one per command.
+//! - [`markdown`]: a Markdown reference for docs sites, one page per command.
+use std::path::{Path, PathBuf};
+
+use eyre::Result;
+
+use crate::tree::{Node, Tree, Visitor};
+
+pub mod man;
+pub mod markdown;
+
+/// A rendered page of documentation.
+#[derive(Clone, Debug)]
+pub struct Page {
+    /// The file name of the page.
+    pub name: String,
+    /// The contents of the page.
+    pub content: String,
+}
+
+/// Render a page for every visible command in `tree`, parents first.
+fn pages(tree: &Tree, render: fn(&Node) -> Result<Page>) -> Result<Vec<Page>> {
+    struct Pages {
+        render: fn(&Node) -> Result<Page>,
+        pages: Vec<Result<Page>>,
+    }
+
+    impl Visitor for Pages {
+        fn enter(&mut self, node: &Node) -> bool {
+            if node.is_hidden() {
+                return false;
+            }
+
+            self.pages.push((self.render)(node));
+
+            true
+        }
+    }
+
+    let mut pages = Pages {
+        render,
+        pages: Vec::new(),
+    };
+    tree.walk(&mut pages);
+
+    pages.pages.into_iter().collect()
+}
+
+/// Write `pages` into `dir`, returning the paths that were written.
+fn write(pages: Vec<Page>, dir: &Path) -> Result<Vec<PathBuf>> {
+    std::fs::create_dir_all(dir)?;
+
+    pages
+        .into_iter()
+        .map(|page| {
+            let path = dir.join(&page.name);
+            std::fs::write(&path, page.content)?;
+
+            Ok(path)
+        })
+        .collect()
+}
@@ -0,0 +1,128 @@
+//! Man pages for every command in the tree.
+//!
+//! Each command gets its own page, named after its path, such as
+//! `my-cli-projects-list.1`. Pages are rendered with [`clap_mangen`] and
+//! include a DEPRECATED section for commands marked with
+//! [`Tree::with_deprecation`], along with an EXAMPLES section for the ones
+//! from [`Tree::with_examples`].
+//!
+//! Some things to note:
+//! - Hidden commands don't get a page.
+//! - Pages are typically generated at build time or by a hidden subcommand,
+//!   then installed into `share/man/man1`.
+//!
+//! # Examples
+//!
+//! ```
+//! use cata::{command::Deprecation, docs::man, tree::Tree};
+//! use clap::{CommandFactory, Parser, Subcommand};
+//!
+//! #[derive(Parser)]
+//! #[command(name = "my-cli")]
+//! struct Root {
+//!   #[command(subcommand)]
+//!   cmd: RootCmd,
+//! }
+//!
+//! #[derive(Subcommand)]
+//! enum RootCmd {
+//!   /// List projects.
+//!   List,
+//!   /// List projects, the old way.
+//!   Ls,
+//! }
+//!
+//! let tree = Tree::new(Root::command()).with_deprecation(
+//!   &["my-cli", "ls"],
+//!   Deprecation::default().with_replacement("my-cli list"),
+//! );
+//!
+//! let pages = man::pages(&tree)?;
+//! let names = pages.iter().map(|page| page.name.as_str()).collect::<Vec<_>>();
+//!
+//! assert_eq!(names, ["my-cli.1", "my-cli-list.1", "my-cli-ls.1"]);
+//! assert!(pages[2].content.contains(".SH DEPRECATED"));
+//! # Ok::<(), eyre::Report>(())
+//! ```
+use std::path::{Path, PathBuf};
+
+use clap_mangen::{
+    roff::{roman, Roff},
+    Man,
+};
+use eyre::Result;
+
+use crate::{
+    docs::{self, Page},
+    tree::{Node, Tree},
+};
+
+/// Render the man page for a single command.
+fn render(node: &Node) -> Result<Page> {
+    let cmd = node.command();
+    let man = Man::new(cmd.clone());
+
+    let mut content = Vec::new();
+    man.render_title(&mut content)?;
+    man.render_name_section(&mut content)?;
+    man.render_synopsis_section(&mut content)?;
+    man.render_description_section(&mut content)?;
+
+    if let Some(deprecation) = node.deprecation() {
+        let mut roff = Roff::new();
+        roff.control("SH", ["DEPRECATED"])
+            .text([roman(deprecation.message(&node.path().join(" ")))]);
+        roff.to_writer(&mut content)?;
+    }
+
+    if cmd.get_arguments().any(|arg| !arg.is_hide_set()) {
+        man.render_options_section(&mut content)?;
+    }
+
+    if cmd.get_subcommands().any(|sub| !sub.is_hide_set()) {
+        man.render_subcommands_section(&mut content)?;
+    }
+
+    if !node.examples().is_empty() {
+        let mut roff = Roff::new();
+        roff.control("SH", ["EXAMPLES"]);
+
+        for example in node.examples() {
+            roff.text([roman(*example)]).control("br", []);
+        }
+
+        roff.to_writer(&mut content)?;
+    }
+
+    if cmd.get_after_long_help().is_some() || cmd.get_after_help().is_some() {
+        man.render_extra_section(&mut content)?;
+    }
+
+    if cmd
+        .get_version()
+        .or_else(|| cmd.get_long_version())
+        .is_some()
+    {
+        man.render_version_section(&mut content)?;
+    }
+
+    if cmd.get_author().is_some() {
+        man.render_authors_section(&mut content)?;
+    }
+
+    Ok(Page {
+        name: man.get_filename(),
+        content: String::from_utf8(content)?,
+    })
+}
+
+/// Render a page for every visible command in `tree`, parents first.
+pub fn pages(tree: &Tree) -> Result<Vec<Page>> {
+    docs::pages(tree, render)
+}
+
+/// Write a page for every visible command in `tree` into `dir`, returning the
+/// paths that were written.
+pub fn write(tree: &Tree, dir: impl AsRef<Path>) -> Result<Vec<PathBuf>> {
+    docs::write(pages(tree)?, dir.as_ref())
+}
@@ -0,0 +1,201 @@
+//! A Markdown reference for every command in the tree.
+//!
+//! Each command gets its own page, named after its path, such as
+//! `my-cli-projects-list.md`. Pages link to their subcommands, so the root
+//! page works as the index of the reference.
+//!
+//! Some things to note:
+//! - Hidden commands and arguments are left out.
+//! - Arguments list their possible values, which covers `--output` and any
+//!   other `ValueEnum`, and their defaults.
+//! - Deprecated commands, see [`Tree::with_deprecation`], start with a warning.
+//! - Examples, see [`Tree::with_examples`], follow the options.
+//!
+//! # Examples
+//!
+//! ```
+//! use cata::{docs::markdown, tree::Tree};
+//! use clap::{CommandFactory, Parser, Subcommand};
+//!
+//! #[derive(Parser)]
+//! #[command(name = "my-cli")]
+//! struct Root {
+//!   #[command(subcommand)]
+//!   cmd: RootCmd,
+//! }
+//!
+//! #[derive(Subcommand)]
+//! enum RootCmd {
+//!   /// List projects.
+//!   List {
+//!     /// Include archived projects.
+//!     #[arg(long)]
+//!     all: bool,
+//!   },
+//! }
+//!
+//! let pages = markdown::pages(&Tree::new(Root::command()))?;
+//!
+//! assert_eq!(pages[0].name, "my-cli.md");
+//! assert!(pages[0].content.contains("- [`list`](my-cli-list.md): List projects"));
+//! assert!(pages[1].content.contains("- `--all`: Include archived projects."));
+//! # Ok::<(), eyre::Report>(())
+//! ```
+use std::{
+    fmt::Write as _,
+    path::{Path, PathBuf},
+};
+
+use eyre::Result;
+
+use crate::{
+    docs::{self, Page},
+    tree::{Node, Tree},
+};
+
+/// The file name of the page for the command at `path`.
+fn file_name(path: &[String]) -> String {
+    format!("{}.md", path.join("-"))
+}
+
+/// How an argument is written on the command line.
+fn flag(arg: &clap::Arg) -> String {
+    let value = arg
+        .get_value_names()
+        .and_then(|names| names.first())
+        .map_or_else(|| arg.get_id().as_str().to_uppercase(), ToString::to_string);
+
+    if arg.is_positional() {
+        return format!("<{value}>");
+    }
+
+    let mut flag = [
+        arg.get_short().map(|short| format!("-{short}")),
+        arg.get_long().map(|long| format!("--{long}")),
+    ]
+    .into_iter()
+    .flatten()
+    .collect::<Vec<_>>()
+    .join(", ");
+
+    if arg.get_action().takes_values() {
+        write!(flag, " <{value}>").ok();
+    }
+
+    flag
+}
+
+/// Render the reference for a single command.
+fn render(node: &Node) -> Result<Page> {
+    let cmd = node.command();
+    let mut content = String::new();
+
+    writeln!(content, "# {}\n", node.path().join(" "))?;
+
+    if let Some(deprecation) = node.deprecation() {
+        let message = deprecation.message(&node.path().join(" "));
+        writeln!(content, "> **Warning**: {message}.\n")?;
+    }
+
+    if let Some(about) = cmd.get_long_about().or_else(|| cmd.get_about()) {
+        writeln!(content, "{about}\n")?;
+    }
+
+    let usage = cmd.clone().render_usage().to_string();
+    writeln!(content, "```text\n{}\n```", usage.trim_end())?;
+
+    let args = cmd
+        .get_arguments()
+        .filter(|arg| !arg.is_hide_set())
+        .collect::<Vec<_>>();
+
+    if !args.is_empty() {
+        writeln!(content, "\n## Options\n")?;
+    }
+
+    for arg in args {
+        let mut details = Vec::new();
+
+        if let Some(help) = arg.get_help().or_else(|| arg.get_long_help()) {
+            details.push(help.to_string().trim_end_matches('.').to_string());
+        }
+
+        if arg.get_action().takes_values() {
+            let values = arg
+                .get_possible_values()
+                .into_iter()
+                .filter(|value| !value.is_hide_set())
+                .map(|value| format!("`{}`", value.get_name()))
+                .collect::<Vec<_>>();
+
+            if !values.is_empty() {
+                details.push(format!("Possible values: {}", values.join(", ")));
+            }
+
+            let defaults = arg
+                .get_default_values()
+                .iter()
+                .map(|value| format!("`{}`", value.to_string_lossy()))
+                .collect::<Vec<_>>();
+
+            if !defaults.is_empty() {
+                details.push(format!("Default: {}", defaults.join(", ")));
+            }
+        }
+
+        if details.is_empty() {
+            writeln!(content, "- `{}`", flag(arg))?;
+        } else {
+            writeln!(content, "- `{}`: {}.", flag(arg), details.join(". "))?;
+        }
+    }
+
+    if !node.examples().is_empty() {
+        writeln!(
+            content,
+            "\n## Examples\n\n```text\n{}\n```",
+            node.examples().join("\n")
+        )?;
+    }
+
+    let children = node
+        .children()
+        .iter()
+        .filter(|child| !child.is_hidden())
+        .collect::<Vec<_>>();
+
+    if !children.is_empty() {
+        writeln!(content, "\n## Subcommands\n")?;
+    }
+
+    for child in children {
+        write!(
+            content,
+            "- [`{}`]({})",
+            child.name(),
+            file_name(child.path())
+        )?;
+
+        if let Some(about) = child.command().get_about() {
+            write!(content, ": {about}")?;
+        }
+
+        writeln!(content)?;
+    }
+
+    Ok(Page {
+        name: file_name(node.path()),
+        content,
+    })
+}
+
+/// Render a page for every visible command in `tree`, parents first.
+pub fn pages(tree: &Tree) -> Result<Vec<Page>> {
+    docs::pages(tree, render)
+}
+
+/// Write a page for every visible command in `tree` into `dir`, returning the
+/// paths that were written.
+pub fn write(tree: &Tree, dir: impl AsRef<Path>) -> Result<Vec<PathBuf>> {
+    docs::write(pages(tree)?, dir.as_ref())
+}
@@ -0,0 +1,206 @@
+//! Run the current invocation again with elevated privileges.
+//!
+//! CLIs that manage system services, packages or network settings often need
+//! root for a single subcommand. Instead of failing with a permission error and
+//! leaving users to retype everything with `sudo` in front, call
+//! [`Elevate::ensure`] from that command's `pre_run`. When the process isn't
+//! privileged, the user is asked whether to run the same invocation under
+//! `sudo` and it is run again, this time as root.
+//!
+//! Some things to note:
+//! - Every argument is passed on as it was typed, which includes the output
+//!   format chosen with [`crate::output::Format`]. Environment variables are
+//!   reset by `sudo`, list the ones that matter, such as one that picks the
+//!   output format, with [`Elevate::with_var`].
+//! - The elevated copy goes through the command tree from the root again.
+//!   Output goes straight to the terminal, [`Elevate::ensure`] waits for it
+//!   and the original invocation skips `run` and the rest of the subcommands.
+//!   The elevated copy's exit code is kept, see [`crate::error::exit`].
+//! - `--yes` answers the question, see [`Elevate::with_yes`]. Without a
+//!   terminal to ask on, this fails instead.
+//! - `sudo` can be replaced, for example by `doas`, with
+//!   [`Elevate::with_program`].
+//! - Only available on unix.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use cata::{
+//!   command::{PreRun, Yes},
+//!   elevate::Elevate,
+//!   Command, Container,
+//! };
+//! use clap::Parser;
+//!
+//! #[derive(Parser, Container)]
+//! struct Install {
+//!   #[command(flatten)]
+//!   yes: Yes,
+//! }
+//!
+//! #[async_trait::async_trait]
+//! impl Command for Install {
+//!   async fn pre_run(&self, ctx: &mut cata::Context) -> eyre::Result<PreRun> {
+//!     Elevate::new("my-cli")
+//!       .with_yes(self.yes.enabled)
+//!       .with_var("MY_CLI_OUTPUT")
+//!       .ensure(ctx)
+//!       .await
+//!   }
+//!
+//!   async fn run(&self, _: &mut cata::Context) -> eyre::Result<()> {
+//!     std::fs::write("/etc/my-cli.conf", "installed = true\n")?;
+//!
+//!     Ok(())
+//!   }
+//! }
+//! ```
+use std::{ffi::OsString, fmt};
+
+use eyre::{eyre, Result};
+
+use crate::{
+    command::{Confirmation, PreRun},
+    dirs,
+    error::WithExitCode,
+    runtime, Context,
+};
+
+static DEFAULT_PROGRAM: &str = "sudo";
+
+/// Runs the current invocation again with elevated privileges.
+///
+/// See the module documentation for usage.
+#[derive(Clone, Debug)]
+pub struct Elevate {
+    name: String,
+    program: String,
+    vars: Vec<String>,
+    yes: bool,
+}
+
+impl Elevate {
+    /// Elevate the CLI `name`.
+    pub fn new(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            program: DEFAULT_PROGRAM.into(),
+            vars: Vec::new(),
+            yes: false,
+        }
+    }
+
+    /// Use `program` to elevate instead of `sudo`.
+    ///
+    /// It is called with the command to run as its arguments, like `sudo` and
+    /// `doas` are.
+    #[must_use]
+    pub fn with_program(mut self, program: impl Into<String>) -> Self {
+        self.program = program.into();
+        self
+    }
+
+    /// Pass the environment variable `var` on to the elevated copy, if it is
+    /// set.
+    #[must_use]
+    pub fn with_var(mut self, var: impl Into<String>) -> Self {
+        self.vars.push(var.into());
+        self
+    }
+
+    /// Elevate without asking first, for `--yes`.
+    #[must_use]
+    pub fn with_yes(mut self, yes: bool) -> Self {
+        self.yes = yes;
+        self
+    }
+
+    /// Whether the current process is already running as root.
+    #[must_use]
+    pub fn is_elevated() -> bool {
+        // SAFETY: `geteuid` can't fail and has no side effects.
+        unsafe { libc::geteuid() == 0 }
+    }
+
+    /// The variable that marks a process as the elevated copy.
+    fn marker(&self) -> String {
+        dirs::env_var(&self.name, "ELEVATED")
+    }
+
+    /// Make sure the command at `ctx` runs with elevated privileges.
+    ///
+    /// Returns [`PreRun::Continue`] when the process already is privileged.
+    /// Otherwise, the invocation is run again under `sudo` once the user
+    /// agrees, and [`PreRun::Skip`] is returned after it succeeded. Declining
+    /// fails with [`NotElevated`]. Both the question and the error name the
+    /// command the way it was typed.
+    pub async fn ensure(&self, ctx: &Context) -> Result<PreRun> {
+        if Self::is_elevated() {
+            return Ok(PreRun::Continue);
+        }
+
+        let command = ctx.typed();
+
+        // The elevated copy wasn't privileged either, asking again would loop.
+        if std::env::var_os(self.marker()).is_some() {
+            return Err(NotElevated { command }.into());
+        }
+
+        if !self.yes {
+            let confirmation = Confirmation::new(format!(
+                "`{command}` needs to run as root, run it again with `{}`?",
+                self.program
+            ));
+
+            if !runtime::unblock(move || confirmation.ask()).await?? {
+                return Err(NotElevated { command }.into());
+            }
+        }
+
+        let mut cmd = std::process::Command::new(&self.program);
+        // `env` sets the variables once the program has elevated, `sudo`
+        // would otherwise drop them.
+        cmd.arg("env")
+            .arg(format!("{}=1", self.marker()))
+            .args(self.vars.iter().filter_map(|var| {
+                let value = std::env::var_os(var)?;
+
+                let mut arg = OsString::from(format!("{var}="));
+                arg.push(value);
+
+                Some(arg)
+            }))
+            .arg(std::env::current_exe()?)
+            .args(std::env::args_os().skip(1));
+
+        let program = self.program.clone();
+        let status = runtime::unblock(move || cmd.status())
+            .await?
+            .map_err(|e| eyre!("unable to run `{program}`: {e}"))?;
+
+        if !status.success() {
+            return Err(eyre!(
+                "`{command}` failed with elevated privileges ({status})"
+            ))
+            .with_exit_code(status.code().unwrap_or(1));
+        }
+
+        Ok(PreRun::Skip)
+    }
+}
+
+/// Returned by [`Elevate::ensure`] when the command couldn't be run with
+/// elevated privileges.
+#[derive(Debug)]
+pub struct NotElevated {
+    /// The command as it was typed, such as `my-cli install`.
+    pub command: String,
+}
+
+impl fmt::Display for NotElevated {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "`{}` needs to run as root", self.command)
+    }
+}
+
+impl std::error::Error for NotElevated {}
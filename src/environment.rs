@@ -0,0 +1,234 @@
+//! A reference of every environment variable a CLI honors.
+//!
+//! Variables come from the `env` attribute on clap arguments anywhere in the
+//! command tree, the directory overrides from [`crate::dirs`] and anything
+//! added with [`Reference::with_variable`], such as configuration bindings.
+//! The [`Env`] subcommand prints the reference as a table, JSON, YAML or
+//! Markdown for pasting into documentation.
+//!
+//! Some things to note:
+//! - Hidden arguments and subcommands are left out.
+//! - A variable that is bound in more than one place appears once per place.
+//! - [`Reference::install`] must be called before the subcommand runs.
+//!
+//! # Examples
+//!
+//! ```
+//! use cata::environment::Reference;
+//! use clap::{CommandFactory, Parser};
+//!
+//! #[derive(Parser)]
+//! #[command(name = "my-cli")]
+//! struct Root {
+//!   /// Token used to authenticate.
+//!   #[arg(long, env = "MY_CLI_TOKEN")]
+//!   token: Option<String>,
+//! }
+//!
+//! let reference = Reference::new(Root::command())
+//!   .with_variable("MY_CLI_PROFILE", "Profile to load from the config file.");
+//!
+//! assert!(reference
+//!   .variables()
+//!   .iter()
+//!   .any(|var| var.name == "MY_CLI_TOKEN"));
+//! assert!(reference.to_string().contains("| `MY_CLI_PROFILE` |"));
+//! ```
+use std::{
+    fmt::{self, Display},
+    sync::OnceLock,
+};
+
+use eyre::{eyre, Result};
+use serde::Serialize;
+
+use crate::{
+    command::Container,
+    describe::{ArgDescriptor, Describe, Descriptor},
+    dirs,
+    output::Format,
+    Command, Context,
+};
+
+static DIRS: &[&str] = &["config", "cache", "state", "data"];
+static GLOBAL: OnceLock<Reference> = OnceLock::new();
+
+/// A single environment variable.
+#[derive(Clone, Debug, Serialize, tabled::Tabled)]
+pub struct Variable {
+    /// Name of the variable.
+    pub name: String,
+    /// The command it applies to, empty for the whole CLI.
+    pub command: String,
+    /// The flag or positional argument it sets, if any.
+    pub argument: crate::output::tabled::Option<String>,
+    /// What it does.
+    pub description: String,
+}
+
+/// Every environment variable a CLI honors.
+///
+/// See the module documentation for usage.
+#[derive(Clone, Debug, Serialize)]
+#[serde(transparent)]
+pub struct Reference {
+    variables: Vec<Variable>,
+}
+
+impl Reference {
+    /// Build a reference from the command tree rooted at `cmd`.
+    #[must_use]
+    pub fn new(cmd: clap::Command) -> Self {
+        let name = cmd.get_name().to_string();
+
+        let mut variables = Vec::new();
+        let mut stack = vec![(cmd, name.clone())];
+
+        while let Some((cmd, path)) = stack.pop() {
+            for arg in cmd.get_arguments().filter(|arg| !arg.is_hide_set()) {
+                let Some(env) = arg.get_env() else {
+                    continue;
+                };
+
+                let argument = arg.get_long().map(|long| format!("--{long}")).or_else(|| {
+                    arg.is_positional()
+                        .then(|| format!("<{}>", arg.get_id().as_str().to_uppercase()))
+                });
+
+                variables.push(Variable {
+                    name: env.to_string_lossy().into(),
+                    command: path.clone(),
+                    argument: argument.into(),
+                    description: arg.get_help().map(ToString::to_string).unwrap_or_default(),
+                });
+            }
+
+            for sub in cmd.get_subcommands().filter(|sub| !sub.is_hide_set()) {
+                stack.push((sub.clone(), format!("{path} {}", sub.get_name())));
+            }
+        }
+
+        for kind in DIRS {
+            variables.push(Variable {
+                name: dirs::env_var(&name, &format!("{kind}_DIR")),
+                command: String::new(),
+                argument: None.into(),
+                description: format!("Overrides the {kind} directory."),
+            });
+        }
+
+        variables.sort_by(|a, b| (&a.name, &a.command).cmp(&(&b.name, &b.command)));
+
+        Self { variables }
+    }
+
+    /// Add a variable that isn't bound to an argument, such as a
+    /// configuration binding.
+    #[must_use]
+    pub fn with_variable(
+        mut self,
+        name: impl Into<String>,
+        description: impl Into<String>,
+    ) -> Self {
+        let variable = Variable {
+            name: name.into(),
+            command: String::new(),
+            argument: None.into(),
+            description: description.into(),
+        };
+
+        let idx = self
+            .variables
+            .partition_point(|var| (&var.name, &var.command) < (&variable.name, &variable.command));
+        self.variables.insert(idx, variable);
+
+        self
+    }
+
+    /// The variables, sorted by name.
+    #[must_use]
+    pub fn variables(&self) -> &[Variable] {
+        &self.variables
+    }
+
+    /// Make this the reference used by the [`Env`] subcommand.
+    pub fn install(self) -> Result<()> {
+        GLOBAL
+            .set(self)
+            .map_err(|_| eyre!("an environment reference has already been installed"))
+    }
+}
+
+/// Renders the reference as a Markdown table.
+impl Display for Reference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "| Variable | Command | Argument | Description |")?;
+        write!(f, "| --- | --- | --- | --- |")?;
+
+        for var in &self.variables {
+            let argument = var.argument.to_string();
+
+            write!(
+                f,
+                "\n| `{}` | {} | {} | {} |",
+                var.name,
+                code(&var.command),
+                code(&argument),
+                var.description.replace('|', "\\|").replace('\n', " "),
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+fn code(text: &str) -> String {
+    if text.is_empty() {
+        String::new()
+    } else {
+        format!("`{text}`")
+    }
+}
+
+/// List the environment variables this CLI honors.
+#[derive(clap::Args, Clone, Debug)]
+pub struct Env {
+    /// Format of the list.
+    #[arg(short, long, value_enum, default_value_t)]
+    output: Format,
+
+    /// Print a Markdown table instead.
+    #[arg(long, conflicts_with = "output")]
+    markdown: bool,
+}
+
+impl Container for Env {}
+
+impl Describe for Env {
+    const DESCRIPTOR: Descriptor = Descriptor::new("env")
+        .with_about("List the environment variables this CLI honors")
+        .with_args(&[
+            ArgDescriptor::new("output")
+                .with_long("output")
+                .with_short('o')
+                .with_help("Format of the list"),
+            ArgDescriptor::new("markdown")
+                .with_long("markdown")
+                .with_help("Print a Markdown table instead"),
+        ]);
+}
+
+#[async_trait::async_trait]
+impl Command for Env {
+    async fn run(&self, _: &mut Context) -> Result<()> {
+        let reference = GLOBAL
+            .get()
+            .ok_or_else(|| eyre!("an environment reference must be installed to list it"))?;
+
+        if self.markdown {
+            return Format::Pretty.document(reference);
+        }
+
+        self.output.list(reference.variables())
+    }
+}
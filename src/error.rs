@@ -0,0 +1,227 @@
+//! Human friendly error reports.
+//!
+//! This module installs a [`color_eyre`] hook that has been tuned for CLIs
+//! instead of development. Reports are split into sections, can carry
+//! actionable suggestions and only include backtraces and source locations
+//! when the user asks for them.
+//!
+//! Some things to note:
+//! - The hook must be installed before any errors are created, typically at the
+//!   very start of `main`.
+//! - Backtraces are only captured with `verbose`, unless the user has
+//!   explicitly set `RUST_BACKTRACE` or `RUST_LIB_BACKTRACE`.
+//! - Colors are only used when stderr is a terminal.
+//! - Commands choose the exit code for an error with
+//!   [`WithExitCode::with_exit_code`], [`exit`] then applies it consistently.
+//!
+//! # Examples
+//!
+//! ```
+//! use cata::error::{Hook, Section};
+//!
+//! fn main() -> eyre::Result<()> {
+//!   Hook::default().with_verbose(false).install()?;
+//!
+//!   let err = eyre::eyre!("not logged in").suggestion("try `my-cli login`");
+//!
+//!   println!("{err:?}");
+//!
+//!   Ok(())
+//! }
+//! ```
+use std::{fmt, io::IsTerminal};
+
+use color_eyre::config::{HookBuilder, Theme};
+pub use color_eyre::{Section, SectionExt};
+use eyre::{Report, Result};
+
+use crate::command::{Interrupted, Panicked, TimedOut};
+
+static BACKTRACE_VARS: &[&str] = &["RUST_BACKTRACE", "RUST_LIB_BACKTRACE"];
+
+/// Configuration for the error report hook.
+///
+/// See the module documentation for usage.
+#[derive(Clone, Debug, Default)]
+pub struct Hook {
+    verbose: bool,
+}
+
+impl Hook {
+    /// Include backtraces, source locations and environment details.
+    #[must_use]
+    pub fn with_verbose(mut self, verbose: bool) -> Self {
+        self.verbose = verbose;
+        self
+    }
+
+    /// Install the hook for both errors and panics.
+    pub fn install(self) -> Result<()> {
+        if BACKTRACE_VARS.iter().all(|k| std::env::var_os(k).is_none()) {
+            std::env::set_var("RUST_LIB_BACKTRACE", if self.verbose { "1" } else { "0" });
+        }
+
+        let theme = if std::io::stderr().is_terminal() {
+            Theme::dark()
+        } else {
+            Theme::new()
+        };
+
+        HookBuilder::default()
+            .theme(theme)
+            .display_env_section(self.verbose)
+            .display_location_section(self.verbose)
+            .install()
+    }
+}
+
+/// An error that carries the exit code the process should use for it.
+///
+/// It displays as the error it wraps, which is its source. Create one with
+/// [`WithExitCode::with_exit_code`]. The wrapped report is kept as it is, with
+/// its sections and suggestions, and [`exit`] prints that instead.
+///
+/// ```
+/// use cata::error::{Failure, Hook, Section, WithExitCode};
+///
+/// Hook::default().install().unwrap();
+///
+/// let result: eyre::Result<()> = Err(eyre::eyre!("not logged in"))
+///   .suggestion("try `my-cli login`")
+///   .with_exit_code(2);
+/// let failure = result.unwrap_err().downcast::<Failure>().unwrap();
+///
+/// assert_eq!(failure.exit_code(), 2);
+/// assert_eq!(
+///   std::error::Error::source(&failure).unwrap().to_string(),
+///   "not logged in",
+/// );
+/// assert!(format!("{:?}", failure.into_report()).contains("try `my-cli login`"));
+/// ```
+#[derive(Debug)]
+pub struct Failure {
+    exit_code: i32,
+    source: Report,
+}
+
+impl Failure {
+    /// Wrap `err` so that the process exits with `exit_code`.
+    pub fn new(exit_code: i32, err: impl Into<Report>) -> Self {
+        Self {
+            exit_code,
+            source: err.into(),
+        }
+    }
+
+    /// The exit code for this error.
+    #[must_use]
+    pub fn exit_code(&self) -> i32 {
+        self.exit_code
+    }
+
+    /// The report this wraps, unchanged.
+    pub fn into_report(self) -> Report {
+        self.source
+    }
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.source.fmt(f)
+    }
+}
+
+impl std::error::Error for Failure {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&*self.source)
+    }
+}
+
+/// Attach an exit code to errors.
+///
+/// ```
+/// use cata::error::{exit_code, WithExitCode};
+///
+/// let result: eyre::Result<()> = Err(eyre::eyre!("not found")).with_exit_code(2);
+///
+/// assert_eq!(exit_code(&result.unwrap_err()), 2);
+/// ```
+pub trait WithExitCode<T> {
+    /// Exit with `exit_code` if this is an error.
+    fn with_exit_code(self, exit_code: i32) -> Result<T>;
+}
+
+impl<T, E: Into<Report>> WithExitCode<T> for std::result::Result<T, E> {
+    fn with_exit_code(self, exit_code: i32) -> Result<T> {
+        self.map_err(|e| Failure::new(exit_code, e).into())
+    }
+}
+
+/// The exit code for `err`.
+///
+/// The first [`Failure`] in the chain decides the code. Otherwise,
+/// [`Interrupted`] is 130, matching a shell's ctrl-c, [`TimedOut`] is 124,
+/// matching `timeout(1)`, [`Panicked`] is 101, matching an uncaught panic,
+/// clap errors use clap's code and everything else is 1.
+#[must_use]
+pub fn exit_code(err: &Report) -> i32 {
+    err.chain()
+        .find_map(|e| {
+            if let Some(e) = e.downcast_ref::<Failure>() {
+                Some(e.exit_code())
+            } else if e.is::<Interrupted>() {
+                Some(130)
+            } else if e.is::<TimedOut>() {
+                Some(124)
+            } else if e.is::<Panicked>() {
+                Some(101)
+            } else {
+                e.downcast_ref::<clap::Error>().map(clap::Error::exit_code)
+            }
+        })
+        .unwrap_or(1)
+}
+
+/// Exit the process with the outcome of [`crate::execute`].
+///
+/// Errors are printed to stderr with the installed hook and the process exits
+/// with [`exit_code`]. A [`Failure`] is printed as the report it wraps. Success
+/// exits with 0.
+///
+/// ```no_run
+/// use cata::{Command, Container};
+/// use clap::Parser;
+///
+/// #[derive(Parser, Container)]
+/// struct Root {}
+///
+/// impl Command for Root {}
+///
+/// #[tokio::main]
+/// async fn main() {
+///   cata::error::exit(cata::execute(&Root::parse()).await)
+/// }
+/// ```
+pub fn exit(result: Result<()>) -> ! {
+    use std::io::Write as _;
+
+    let code = match result {
+        Ok(()) => 0,
+        Err(err) => {
+            let code = exit_code(&err);
+            let err = match err.downcast::<Failure>() {
+                Ok(failure) => failure.into_report(),
+                Err(err) => err,
+            };
+
+            eprintln!("Error: {err:?}");
+
+            code
+        }
+    };
+
+    std::io::stdout().flush().ok();
+    std::io::stderr().flush().ok();
+
+    std::process::exit(code)
+}
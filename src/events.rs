@@ -0,0 +1,100 @@
+//! Structured events from the lifecycle, for anything that wants to watch.
+//!
+//! Progress UIs, audit logs and custom telemetry all want to know when
+//! commands start, finish and fail. Instead of every command reporting on
+//! itself, a [`Subscriber`] added with
+//! [`crate::ExecuteOptions::with_subscriber`] is told about every command in
+//! the tree.
+//!
+//! Some things to note:
+//! - Subscribers are synchronous and called inline, anything slow should be
+//!   handed off to a channel or a task.
+//! - Commands are identified by [`crate::Context::path`].
+//! - [`Event::HookFailed`] is emitted for every failed attempt of a retried
+//!   `run`, see [`crate::Command::retry`]. Errors from layers count as the
+//!   hook's.
+//! - Closures taking an [`Event`] are subscribers.
+//!
+//! # Examples
+//!
+//! ```
+//! use std::sync::{Arc, Mutex};
+//!
+//! use cata::{events::Event, Command, Container, ExecuteOptions};
+//! use clap::Parser;
+//!
+//! #[derive(Parser, Container)]
+//! struct Root {}
+//!
+//! #[async_trait::async_trait]
+//! impl Command for Root {
+//!   async fn run(&self, _: &mut cata::Context) -> eyre::Result<()> {
+//!     Err(eyre::eyre!("boom"))
+//!   }
+//! }
+//!
+//! # #[tokio::main]
+//! # async fn main() {
+//! let seen = Arc::new(Mutex::new(Vec::new()));
+//!
+//! let recorder = seen.clone();
+//! let options = ExecuteOptions::default().with_subscriber(move |event: &Event<'_>| {
+//!   recorder.lock().unwrap().push(match event {
+//!     Event::CommandStarted { .. } => "started".to_string(),
+//!     Event::HookFailed { hook, error, .. } => format!("{hook} failed: {error}"),
+//!     Event::CommandFinished { result, .. } => format!("finished: {}", result.is_ok()),
+//!   });
+//! });
+//!
+//! let _ = cata::execute_with(&Root::parse_from(["root"]), options).await;
+//!
+//! assert_eq!(
+//!   *seen.lock().unwrap(),
+//!   ["started", "run failed: boom", "finished: false"],
+//! );
+//! # }
+//! ```
+use eyre::{Report, Result};
+
+/// Something that happened while executing the command tree.
+#[derive(Debug)]
+pub enum Event<'a> {
+    /// The command at `path` was reached, right before its `pre_run`.
+    CommandStarted {
+        /// The command's [`crate::Context::path`].
+        path: String,
+    },
+    /// The command at `path` is done, its `post_run` has returned.
+    CommandFinished {
+        /// The command's [`crate::Context::path`].
+        path: String,
+        /// The outcome of the command and everything below it.
+        result: &'a Result<()>,
+    },
+    /// A hook of the command at `path` returned an error or panicked.
+    HookFailed {
+        /// The command's [`crate::Context::path`].
+        path: String,
+        /// The name of the hook, see [`crate::layer::Hook::name`].
+        hook: &'static str,
+        /// What the hook returned.
+        error: &'a Report,
+    },
+}
+
+/// Receives every [`Event`] of an execution.
+///
+/// See the module documentation for usage.
+pub trait Subscriber: Send + Sync {
+    /// Handle `event`.
+    fn on_event(&self, event: &Event<'_>);
+}
+
+impl<F> Subscriber for F
+where
+    F: Fn(&Event<'_>) + Send + Sync,
+{
+    fn on_event(&self, event: &Event<'_>) {
+        self(event);
+    }
+}
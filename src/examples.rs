@@ -0,0 +1,148 @@
+//! Example invocations that show up in help, docs and errors.
+//!
+//! Examples are often the quickest way to learn a command, but copies of them
+//! in the help, the docs and error messages drift apart. Instead, mark the
+//! command's struct with `#[cata(example = "...")]`, once per example, and
+//! they are used everywhere:
+//!
+//! - [`augment`] lists them at the end of the command's help, which
+//!   [`crate::App::run`] does for you.
+//! - [`crate::tree::Tree::with_examples`] attaches them to the tree, the pages
+//!   in [`crate::docs`] then get an examples section.
+//! - When a command fails, its examples are added to the error report, see
+//!   [`crate::error`].
+//!
+//! Some things to note:
+//! - Examples are written out the way users would type them, including the
+//!   name of the binary.
+//! - Subcommands held as a `Box<dyn Command>` or an `Arc<dyn Command>` aren't
+//!   known until they are parsed, their examples only show up in errors.
+//!
+//! # Examples
+//!
+//! ```
+//! use cata::{docs::markdown, error::Hook, examples, tree::Tree, Command, Container};
+//! use clap::{CommandFactory, Parser, Subcommand};
+//!
+//! #[derive(Parser, Container)]
+//! #[command(name = "my-cli")]
+//! struct Root {
+//!   #[command(subcommand)]
+//!   cmd: RootCmd,
+//! }
+//!
+//! impl Command for Root {}
+//!
+//! #[derive(Subcommand, Container)]
+//! enum RootCmd {
+//!   Deploy(Deploy),
+//! }
+//!
+//! #[derive(Parser, Container)]
+//! #[cata(example = "my-cli deploy --env staging")]
+//! #[cata(example = "my-cli deploy --env production --replicas 3")]
+//! struct Deploy {
+//!   #[arg(long)]
+//!   env: String,
+//!
+//!   #[arg(long, default_value_t = 1)]
+//!   replicas: u32,
+//! }
+//!
+//! #[async_trait::async_trait]
+//! impl Command for Deploy {
+//!   async fn run(&self, _: &mut cata::Context) -> eyre::Result<()> {
+//!     Err(eyre::eyre!("unknown environment `{}`", self.env))
+//!   }
+//! }
+//!
+//! # #[tokio::main]
+//! # async fn main() -> eyre::Result<()> {
+//! Hook::default().install()?;
+//!
+//! let mut cmd = examples::augment::<Root>(Root::command());
+//! let help = cmd
+//!   .find_subcommand_mut("deploy")
+//!   .unwrap()
+//!   .render_help()
+//!   .to_string();
+//! assert!(help.contains("Examples:\n  my-cli deploy --env staging\n"));
+//!
+//! let tree = Tree::new(Root::command()).with_examples::<Root>();
+//! let pages = markdown::pages(&tree)?;
+//! assert!(pages[1].content.contains("## Examples"));
+//!
+//! let err = cata::execute(&Root::parse_from(["my-cli", "deploy", "--env", "prod"]))
+//!   .await
+//!   .unwrap_err();
+//! assert!(format!("{err:?}").contains("my-cli deploy --env production --replicas 3"));
+//! # Ok(())
+//! # }
+//! ```
+use std::fmt::Write as _;
+
+use eyre::Report;
+
+use crate::{
+    command::Container,
+    error::{Section, SectionExt},
+};
+
+static HEADER: &str = "Examples:";
+
+/// List the examples of `T`, usually the root, at the end of the help of
+/// every command in `cmd`, which is usually `T::command()`.
+///
+/// Pass this to [`crate::parse_with`] as the `augment`.
+#[must_use]
+pub fn augment<T: Container>(mut cmd: clap::Command) -> clap::Command {
+    for (path, examples) in T::all_examples() {
+        cmd = append(cmd, &path, examples);
+    }
+
+    cmd
+}
+
+/// Add `examples` to the help of the subcommand at `path` below `cmd`.
+fn append(cmd: clap::Command, path: &[&'static str], examples: &[&str]) -> clap::Command {
+    let Some((name, rest)) = path.split_first() else {
+        let section = format!("{HEADER}\n{}", list(examples));
+
+        let help = match cmd.get_after_help() {
+            Some(existing) => format!("{existing}\n\n{section}"),
+            None => section,
+        };
+
+        return cmd.after_help(help);
+    };
+
+    if cmd.find_subcommand(name).is_none() {
+        return cmd;
+    }
+
+    cmd.mut_subcommand(*name, |sub| append(sub, rest, examples))
+}
+
+/// Every example on a line of its own, indented like clap's help.
+fn list(examples: &[&str]) -> String {
+    let mut list = String::new();
+
+    for example in examples {
+        if !list.is_empty() {
+            list.push('\n');
+        }
+
+        write!(list, "  {example}").ok();
+    }
+
+    list
+}
+
+/// Add `examples` to the report for `err`.
+pub(crate) fn attach(err: Report, examples: &[&str]) -> Report {
+    if examples.is_empty() {
+        return err;
+    }
+
+    err.section(list(examples).header(HEADER))
+}
@@ -0,0 +1,221 @@
+//! Run many invocations of a command at the same time.
+//!
+//! Some work has to happen once per target, such as "run this against 50
+//! clusters". [`Fanout`] takes the parsed invocations, runs each one through
+//! [`crate::execute_with`] with at most a few of them in flight at once, and
+//! collects everything into a [`Summary`] that can be printed with
+//! [`crate::output`]. Outcomes and summaries are the ones from
+//! [`crate::batch`], with an [`Invocation`] instead of a step.
+//!
+//! Some things to note:
+//! - Every invocation has its own lifecycle, context and background tasks.
+//!   Hooks are called in the usual order per invocation, there is no ordering
+//!   between them.
+//! - Invocations are spawned as tasks, they need to be `'static`. Outcomes are
+//!   in the order the invocations were passed in, not the order they finished.
+//! - A panic in one invocation is its failure, the rest keep going.
+//! - Output from concurrent invocations is interleaved. Have them return data
+//!   through their own channel, or keep their output short.
+//!
+//! # Examples
+//!
+//! ```
+//! use cata::{fanout::Fanout, Command, Container, Format};
+//! use clap::Parser;
+//!
+//! #[derive(Parser, Container)]
+//! struct Status {
+//!   cluster: String,
+//! }
+//!
+//! #[async_trait::async_trait]
+//! impl Command for Status {
+//!   async fn run(&self, _: &mut cata::Context) -> eyre::Result<()> {
+//!     if self.cluster == "broken" {
+//!       return Err(eyre::eyre!("unreachable"));
+//!     }
+//!
+//!     Ok(())
+//!   }
+//! }
+//!
+//! # #[tokio::main]
+//! # async fn main() -> eyre::Result<()> {
+//! let invocations = ["east", "west", "broken"]
+//!   .into_iter()
+//!   .map(|cluster| (cluster, Status::parse_from(["status", cluster])));
+//!
+//! let summary = Fanout::default().with_limit(2).run(invocations).await;
+//!
+//! assert_eq!(summary.failures().count(), 1);
+//! Format::Pretty.list(&summary.rows())?;
+//! # Ok(())
+//! # }
+//! ```
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use eyre::{eyre, Result};
+use serde::Serialize;
+use tokio::sync::{oneshot, Semaphore};
+
+use crate::{batch, output::tabled::Option as Cell, runtime, Command, ExecuteOptions};
+
+static DEFAULT_LIMIT: usize = 8;
+
+/// A single invocation that was run.
+#[derive(Debug)]
+pub struct Invocation {
+    name: String,
+    duration: Duration,
+}
+
+/// How a single invocation went, [`Outcome::result`] is what
+/// [`crate::execute_with`] returned.
+pub type Outcome = batch::Outcome<Invocation>;
+
+/// Every invocation, in the order they were passed in.
+pub type Summary = batch::Summary<Invocation>;
+
+impl Outcome {
+    /// The name the invocation was given.
+    #[must_use]
+    pub fn name(&self) -> &str {
+        &self.run.name
+    }
+
+    /// How long the invocation took, from `pre_run` to the last `post_run`.
+    #[must_use]
+    pub fn duration(&self) -> Duration {
+        self.run.duration
+    }
+}
+
+/// A single [`Outcome`], ready for [`crate::output::Format::list`].
+#[derive(Clone, Debug, Serialize, tabled::Tabled)]
+pub struct Row {
+    /// The name the invocation was given.
+    pub name: String,
+    /// `ok` or `error`.
+    pub status: &'static str,
+    /// How long the invocation took, in milliseconds.
+    pub duration_ms: u128,
+    /// The error, if there was one.
+    pub error: Cell<String>,
+}
+
+impl Summary {
+    /// A row per invocation for printing.
+    #[must_use]
+    pub fn rows(&self) -> Vec<Row> {
+        self.outcomes
+            .iter()
+            .map(|outcome| Row {
+                name: outcome.run.name.clone(),
+                status: if outcome.result.is_ok() {
+                    "ok"
+                } else {
+                    "error"
+                },
+                duration_ms: outcome.run.duration.as_millis(),
+                error: outcome
+                    .result
+                    .as_ref()
+                    .err()
+                    .map(ToString::to_string)
+                    .into(),
+            })
+            .collect()
+    }
+
+    /// Fail when any invocation did.
+    ///
+    /// The error counts the failures, print [`Summary::rows`] for the details.
+    pub fn into_result(self) -> Result<()> {
+        let total = self.outcomes.len();
+
+        match self.failures().count() {
+            0 => Ok(()),
+            count => Err(eyre!("{count} of {total} invocations failed")),
+        }
+    }
+}
+
+/// Runs invocations concurrently.
+///
+/// See the module documentation for usage.
+#[derive(Debug)]
+pub struct Fanout {
+    limit: usize,
+    options: ExecuteOptions,
+}
+
+impl Default for Fanout {
+    fn default() -> Self {
+        Self {
+            limit: DEFAULT_LIMIT,
+            options: ExecuteOptions::default(),
+        }
+    }
+}
+
+impl Fanout {
+    /// Set how many invocations run at once, at least one. Defaults to 8.
+    #[must_use]
+    pub fn with_limit(mut self, limit: usize) -> Self {
+        self.limit = limit.max(1);
+        self
+    }
+
+    /// Set the options every invocation is executed with.
+    #[must_use]
+    pub fn with_options(mut self, options: ExecuteOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Run every invocation, each along with the name it is reported under.
+    pub async fn run<C, N>(self, invocations: impl IntoIterator<Item = (N, C)>) -> Summary
+    where
+        C: Command + 'static,
+        N: Into<String>,
+    {
+        let permits = Arc::new(Semaphore::new(self.limit));
+
+        let tasks = invocations
+            .into_iter()
+            .map(|(name, cmd)| {
+                let permits = permits.clone();
+                let options = self.options.clone();
+
+                let (tx, rx) = oneshot::channel();
+
+                runtime::current().spawn(Box::pin(async move {
+                    let _permit = permits.acquire_owned().await;
+                    let start = Instant::now();
+                    let result = crate::execute_with(&cmd, options).await;
+
+                    tx.send((start.elapsed(), result)).ok();
+                }));
+
+                (name.into(), rx)
+            })
+            .collect::<Vec<_>>();
+
+        let mut outcomes = Vec::with_capacity(tasks.len());
+        for (name, task) in tasks {
+            let (duration, result) = task
+                .await
+                .unwrap_or_else(|_| (Duration::ZERO, Err(eyre!("invocation panicked"))));
+
+            outcomes.push(Outcome {
+                run: Invocation { name, duration },
+                result,
+            });
+        }
+
+        Summary { outcomes }
+    }
+}
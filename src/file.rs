@@ -2,7 +2,27 @@
 //!
 //! Takes a user provided path, reads the file and deserializes it into the
 //! provided struct. Does file extension detection to understand the file's
-//! format. Currently supports JSON and YAML.
+//! format. Currently supports JSON and, with the `yaml` and `toml` features,
+//! YAML and TOML.
+//!
+//! A path of `-` reads from stdin instead. Without an extension, input that
+//! starts with `{` or `[` is JSON and anything else is YAML. See
+//! [`crate::pipe`] for chaining commands together this way.
+//!
+//! Paths that don't say what they are, such as `/dev/fd/63` from process
+//! substitution, can be given a fixed format with `#[file(format = "...")]` on
+//! the deriving struct. The format is one of `json`, `yaml` or `toml` and the
+//! extension is then ignored.
+//!
+//! Values that deserialize but make no sense can be refused with
+//! `#[file(validate = "path::to::function")]`. The function takes a reference
+//! to the value and returns a `Result<(), E>`, errors are reported by clap
+//! along with the path.
+//!
+//! Newtypes around a collection, such as `struct Hosts(Vec<Host>)`, read the
+//! whole file as that collection and deref to it. Only the field has to be
+//! deserializable. TOML files are tables, they can be read into maps but not
+//! into lists.
 //!
 //! # Examples
 //!
@@ -23,18 +43,181 @@
 //!
 //! #[async_trait::async_trait]
 //! impl cata::Command for Cmd {
-//!   async fn run(&self) -> eyre::Result<()> {
+//!   async fn run(&self, _: &mut cata::Context) -> eyre::Result<()> {
 //!     println!("input: {:#?}", self.input);
 //!     Ok(())
 //!   }
 //! }
 //! ```
 //!
+//! Always reading YAML:
+//!
+//! ```
+//! use cata::File;
+//! use clap::Parser;
+//!
+//! #[derive(Clone, Debug, serde::Deserialize, File)]
+//! #[file(format = "yaml")]
+//! struct Manifest {
+//!   name: String,
+//! }
+//!
+//! #[derive(Parser)]
+//! struct Cmd {
+//!   manifest: Manifest,
+//! }
+//!
+//! let path = std::env::temp_dir().join("cata-file-manifest");
+//! std::fs::write(&path, "name: web\n").unwrap();
+//!
+//! let cmd = Cmd::parse_from([std::ffi::OsStr::new("cmd"), path.as_os_str()]);
+//! assert_eq!(cmd.manifest.name, "web");
+//! ```
+//!
+//! A list of hosts and a map of variables:
+//!
+//! ```
+//! use std::collections::HashMap;
+//!
+//! use cata::File;
+//! use clap::Parser;
+//!
+//! #[derive(Clone, Debug, serde::Deserialize)]
+//! struct Host {
+//!   name: String,
+//! }
+//!
+//! #[derive(Clone, Debug, File)]
+//! struct Hosts(Vec<Host>);
+//!
+//! #[derive(Clone, Debug, File)]
+//! struct Vars(HashMap<String, String>);
+//!
+//! #[derive(Parser)]
+//! struct Cmd {
+//!   hosts: Hosts,
+//!
+//!   #[arg(long)]
+//!   vars: Vars,
+//! }
+//!
+//! let hosts = std::env::temp_dir().join("cata-file-hosts.yaml");
+//! std::fs::write(&hosts, "- name: web\n- name: db\n").unwrap();
+//! let vars = std::env::temp_dir().join("cata-file-vars.toml");
+//! std::fs::write(&vars, "region = \"eu\"\n").unwrap();
+//!
+//! let cmd = Cmd::parse_from([
+//!   "cmd".as_ref(),
+//!   hosts.as_os_str(),
+//!   "--vars".as_ref(),
+//!   vars.as_os_str(),
+//! ]);
+//!
+//! let names: Vec<_> = cmd.hosts.iter().map(|host| host.name.as_str()).collect();
+//! assert_eq!(names, ["web", "db"]);
+//! assert_eq!(cmd.vars["region"], "eu");
+//! ```
+//!
+//! TOML values keep what TOML says they are, such as infinite floats:
+//!
+//! ```
+//! use cata::File;
+//! use clap::Parser;
+//!
+//! #[derive(Clone, Debug, serde::Deserialize, File)]
+//! struct Limits {
+//!   rate: f64,
+//! }
+//!
+//! #[derive(Parser)]
+//! struct Cmd {
+//!   limits: Limits,
+//! }
+//!
+//! let path = std::env::temp_dir().join("cata-file-limits.toml");
+//! std::fs::write(&path, "rate = inf\n").unwrap();
+//!
+//! let cmd = Cmd::parse_from([std::ffi::OsStr::new("cmd"), path.as_os_str()]);
+//! assert_eq!(cmd.limits.rate, f64::INFINITY);
+//! ```
+//!
 //! [examples/file]: https://github.com/grampelberg/cata/blob/main/examples/file/src/main.rs
+use std::{fmt, io::Read};
+
 use clap::{builder::TypedValueParser, error::ErrorKind};
 use eyre::{eyre, Result};
 use serde::de::DeserializeOwned;
 
+/// The value that reads from stdin instead of a file.
+static STDIN: &str = "-";
+
+/// The formats that files can be deserialized from.
+///
+/// Formats whose feature isn't enabled fail to deserialize.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    /// Files ending in `.json`.
+    Json,
+    /// Files ending in `.yaml` or `.yml`, needs the `yaml` feature.
+    Yaml,
+    /// Files ending in `.toml`, needs the `toml` feature.
+    Toml,
+}
+
+impl Format {
+    /// The format for the MIME `subtype` of a path, such as `x-yaml`.
+    fn from_subtype(subtype: &str) -> Option<Self> {
+        match subtype {
+            "json" => Some(Self::Json),
+            "x-yaml" => Some(Self::Yaml),
+            "x-toml" => Some(Self::Toml),
+            _ => None,
+        }
+    }
+
+    /// Guess the format of `raw`, which has no extension to go by.
+    ///
+    /// JSON always starts with an object or an array, anything else is treated
+    /// as YAML.
+    fn sniff(raw: &str) -> Self {
+        if raw.trim_start().starts_with(['{', '[']) {
+            Self::Json
+        } else {
+            Self::Yaml
+        }
+    }
+
+    /// Deserialize `raw` as this format.
+    fn deserialize<T: DeserializeOwned>(self, raw: &str) -> Result<T> {
+        match self {
+            Self::Json => {
+                serde_path_to_error::deserialize(&mut serde_json::Deserializer::from_str(raw))
+                    .map_err(|e| eyre!(e))
+            }
+            #[cfg(feature = "yaml")]
+            Self::Yaml => serde_path_to_error::deserialize(serde_yaml::Deserializer::from_str(raw))
+                .map_err(|e| eyre!(e)),
+            #[cfg(feature = "toml")]
+            Self::Toml => {
+                serde_path_to_error::deserialize(raw.parse::<toml_edit::de::Deserializer>()?)
+                    .map_err(|e| eyre!(e))
+            }
+            #[allow(unreachable_patterns)]
+            unsupported => Err(eyre!("Unsupported file type: {}", unsupported)),
+        }
+    }
+}
+
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Json => write!(f, "json"),
+            Self::Yaml => write!(f, "yaml"),
+            Self::Toml => write!(f, "toml"),
+        }
+    }
+}
+
 /// Implementation of `TypedValueParser` for deserializing a file into a struct.
 ///
 /// This is not meant to be used directly, see the `File` derive macro for how
@@ -42,20 +225,56 @@ use serde::de::DeserializeOwned;
 /// structs using that macro and the implementation uses this implementation.
 #[derive(Debug, Clone)]
 pub struct File<T> {
+    format: Option<Format>,
+    validate: Option<fn(&T) -> Result<()>>,
+    deserialize: fn(Format, &str) -> Result<T>,
     _p: std::marker::PhantomData<T>,
 }
 
-impl<T> Default for File<T> {
+impl<T: DeserializeOwned> Default for File<T> {
     fn default() -> Self {
         Self {
+            format: None,
+            validate: None,
+            deserialize: Format::deserialize,
+            _p: std::marker::PhantomData,
+        }
+    }
+}
+
+impl<T> File<T> {
+    /// Deserialize the `U` that a newtype wraps and convert it into `T`.
+    #[must_use]
+    pub fn newtype<U: DeserializeOwned>() -> Self
+    where
+        T: From<U>,
+    {
+        Self {
+            format: None,
+            validate: None,
+            deserialize: |format, raw| format.deserialize::<U>(raw).map(T::from),
             _p: std::marker::PhantomData,
         }
     }
+
+    /// Always deserialize as `format`, instead of going by the extension.
+    #[must_use]
+    pub fn with_format(mut self, format: Format) -> Self {
+        self.format = Some(format);
+        self
+    }
+
+    /// Check values with `validate` once they have been deserialized.
+    #[must_use]
+    pub fn with_validate(mut self, validate: fn(&T) -> Result<()>) -> Self {
+        self.validate = Some(validate);
+        self
+    }
 }
 
 impl<T> TypedValueParser for File<T>
 where
-    T: DeserializeOwned + Sync + Send + Clone + 'static,
+    T: Sync + Send + Clone + 'static,
 {
     type Value = T;
 
@@ -65,34 +284,52 @@ where
         arg: Option<&clap::Arg>,
         value: &std::ffi::OsStr,
     ) -> Result<Self::Value, clap::Error> {
-        let path = std::path::PathBuf::from(value);
-        let raw = std::fs::read_to_string(&path).map_err(|e| {
-            cmd.clone().error(
-                ErrorKind::InvalidValue,
-                format!(
-                    "Could not read file {} for {}: {}",
-                    value.to_str().unwrap(),
-                    arg.unwrap(),
-                    e
-                ),
-            )
-        })?;
+        let (raw, format) = if value == STDIN {
+            let mut raw = String::new();
+            std::io::stdin().read_to_string(&mut raw).map_err(|e| {
+                cmd.clone().error(
+                    ErrorKind::InvalidValue,
+                    format!("Could not read stdin for {}: {}", arg.unwrap(), e),
+                )
+            })?;
 
-        let content: Result<T> = match mime_guess::from_path(path.clone())
-            .first_or_text_plain()
-            .subtype()
-            .as_str()
-        {
-            "x-yaml" => serde_path_to_error::deserialize(serde_yaml::Deserializer::from_str(&raw))
-                .map_err(|e| eyre!(e)),
-            "json" => {
-                serde_path_to_error::deserialize(&mut serde_json::Deserializer::from_str(&raw))
-                    .map_err(|e| eyre!(e))
-            }
-            unsupported => Err(eyre!("Unsupported file type: {}", unsupported)),
+            let format = self.format.unwrap_or_else(|| Format::sniff(&raw));
+
+            (raw, Some(format))
+        } else {
+            let path = std::path::PathBuf::from(value);
+            let raw = std::fs::read_to_string(&path).map_err(|e| {
+                cmd.clone().error(
+                    ErrorKind::InvalidValue,
+                    format!(
+                        "Could not read file {} for {}: {}",
+                        value.to_str().unwrap(),
+                        arg.unwrap(),
+                        e
+                    ),
+                )
+            })?;
+
+            let format = self.format.or_else(|| {
+                Format::from_subtype(
+                    mime_guess::from_path(&path)
+                        .first_or_text_plain()
+                        .subtype()
+                        .as_str(),
+                )
+            });
+
+            (raw, format)
         };
 
-        content.map_err(|e| {
+        let content: Result<T> = match format {
+            Some(format) => (self.deserialize)(format, &raw),
+            None => Err(eyre!(
+                "Unsupported file type, use a `.json`, `.yaml` or `.toml` extension"
+            )),
+        };
+
+        let content = content.map_err(|e| {
             cmd.clone().error(
                 ErrorKind::InvalidValue,
                 format!(
@@ -102,6 +339,22 @@ where
                     e
                 ),
             )
-        })
+        })?;
+
+        if let Some(validate) = self.validate {
+            validate(&content).map_err(|e| {
+                cmd.clone().error(
+                    ErrorKind::InvalidValue,
+                    format!(
+                        "Invalid {} for {}: {}",
+                        value.to_str().unwrap(),
+                        arg.unwrap(),
+                        e
+                    ),
+                )
+            })?;
+        }
+
+        Ok(content)
     }
 }
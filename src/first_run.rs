@@ -0,0 +1,107 @@
+//! Notice the first time a CLI runs on a machine.
+//!
+//! Onboarding messages, asking for telemetry consent or one-time setup should
+//! only happen once. A marker file in the platform data directory, see
+//! [`crate::dirs`], remembers that the CLI has run before. Pass a [`FirstRun`]
+//! to [`crate::ExecuteOptions::with_first_run`] and [`Command::on_first_run`] is
+//! called on every command in the tree before its `pre_run`.
+//!
+//! Some things to note:
+//! - The marker is written once execution gets past every `on_first_run`. If
+//!   one fails, the next run is still the first.
+//! - When there is no data directory, or the marker can't be read, the CLI is
+//!   assumed to have run before.
+//! - [`crate::Context::first_run`] tells every hook whether this is the first
+//!   run.
+//!
+//! # Examples
+//!
+//! ```
+//! use cata::{first_run::FirstRun, Command, Container, ExecuteOptions};
+//! use clap::Parser;
+//!
+//! #[derive(Parser, Container)]
+//! struct Root {}
+//!
+//! #[async_trait::async_trait]
+//! impl Command for Root {
+//!   async fn on_first_run(&self, _: &mut cata::Context) -> eyre::Result<()> {
+//!     eprintln!("Welcome! Run `my-cli login` to get started.");
+//!
+//!     Ok(())
+//!   }
+//! }
+//!
+//! # #[tokio::main]
+//! # async fn main() -> eyre::Result<()> {
+//! # let dir = std::env::temp_dir().join("cata-first-run-example");
+//! let first_run = FirstRun::new("my-cli");
+//! # let first_run = first_run.with_path(dir.join("first-run"));
+//!
+//! let options = ExecuteOptions::default().with_first_run(first_run.clone());
+//! cata::execute_with(&Root::parse_from(["root"]), options).await?;
+//!
+//! assert!(!first_run.is_first());
+//! # std::fs::remove_dir_all(dir)?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! [`Command::on_first_run`]: crate::Command::on_first_run
+use std::path::PathBuf;
+
+use eyre::{eyre, Result};
+
+use crate::dirs::Dirs;
+
+static MARKER_FILE: &str = "first-run";
+
+/// Where the CLI remembers that it has run before.
+///
+/// See the module documentation for usage.
+#[derive(Clone, Debug)]
+pub struct FirstRun {
+    path: Option<PathBuf>,
+}
+
+impl FirstRun {
+    /// Create the marker for the CLI `name`.
+    pub fn new(name: impl AsRef<str>) -> Self {
+        Self {
+            path: Dirs::new(name).data().map(|dir| dir.join(MARKER_FILE)),
+        }
+    }
+
+    /// Set the path of the marker file.
+    ///
+    /// By default, this is `first-run` in the platform data directory.
+    #[must_use]
+    pub fn with_path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.path = Some(path.into());
+        self
+    }
+
+    /// Whether the CLI has never run on this machine.
+    #[must_use]
+    pub fn is_first(&self) -> bool {
+        self.path
+            .as_ref()
+            .is_some_and(|path| matches!(path.try_exists(), Ok(false)))
+    }
+
+    /// Remember that the CLI has run.
+    pub fn mark(&self) -> Result<()> {
+        let path = self
+            .path
+            .as_ref()
+            .ok_or_else(|| eyre!("unable to determine the data directory"))?;
+
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        std::fs::write(path, "")?;
+
+        Ok(())
+    }
+}
@@ -0,0 +1,229 @@
+//! Subcommands that only exist once a feature flag turns them on.
+//!
+//! New and experimental commands often ship before they are ready for
+//! everyone. Mark the variant with `#[cata(flag = "...")]` and the command is
+//! hidden from help and refused by [`crate::execute`] unless [`Flags`] has the
+//! flag enabled.
+//!
+//! Some things to note:
+//! - Flags come from an environment variable, a list such as one read from a
+//!   config file, or a provider such as a telemetry service's feature flags.
+//!   A flag is enabled when any of them says so.
+//! - Hiding happens while parsing, see [`Flags::hide`] and
+//!   [`crate::App::with_flags`]. Hidden commands can still be typed, they are
+//!   only refused once [`crate::ExecuteOptions::with_flags`] sees them.
+//! - Gated commands are refused with [`Disabled`] before any requirement is
+//!   checked or hook is called.
+//! - [`crate::tree::Tree::with_flags`] attaches the flags to the full command
+//!   tree, for documentation and audits.
+//!
+//! # Examples
+//!
+//! ```
+//! use cata::{flags::{Disabled, Flags}, Command, Container, ExecuteOptions};
+//! use clap::{CommandFactory, Parser, Subcommand};
+//!
+//! #[derive(Parser, Container)]
+//! struct Root {
+//!   #[command(subcommand)]
+//!   cmd: RootCmd,
+//! }
+//!
+//! impl Command for Root {}
+//!
+//! #[derive(Subcommand, Container)]
+//! enum RootCmd {
+//!   List(List),
+//!   #[cata(flag = "sync")]
+//!   Sync(Sync),
+//! }
+//!
+//! #[derive(Parser, Container)]
+//! struct List {}
+//!
+//! impl Command for List {}
+//!
+//! #[derive(Parser, Container)]
+//! struct Sync {}
+//!
+//! impl Command for Sync {}
+//!
+//! # #[tokio::main]
+//! # async fn main() {
+//! let flags = Flags::new().with_env("CATA_EXAMPLE_FEATURES");
+//!
+//! let cmd = flags.hide::<Root>(Root::command());
+//! assert!(cmd.find_subcommand("sync").unwrap().is_hide_set());
+//!
+//! let err = cata::execute_with(
+//!   &Root::parse_from(["root", "sync"]),
+//!   ExecuteOptions::default().with_flags(flags),
+//! )
+//! .await
+//! .unwrap_err();
+//!
+//! assert_eq!(err.downcast_ref::<Disabled>().unwrap().flag, "sync");
+//!
+//! let flags = Flags::new().with_enabled(["sync"]);
+//! cata::execute_with(
+//!   &Root::parse_from(["root", "sync"]),
+//!   ExecuteOptions::default().with_flags(flags),
+//! )
+//! .await
+//! .unwrap();
+//! # }
+//! ```
+use std::{fmt, sync::Arc};
+
+use crate::command::Container;
+
+type Provider = Arc<dyn Fn(&str) -> bool + Send + Sync>;
+
+/// The feature flags that are enabled.
+///
+/// By default, nothing is enabled. See the module documentation for usage.
+#[derive(Clone, Default)]
+pub struct Flags {
+    enabled: Vec<String>,
+    providers: Vec<Provider>,
+    var: Option<String>,
+}
+
+impl fmt::Debug for Flags {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Flags")
+            .field("enabled", &self.enabled)
+            .field("providers", &self.providers.len())
+            .field("var", &self.var)
+            .finish()
+    }
+}
+
+impl Flags {
+    /// No flags enabled.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Enable the comma separated flags in the environment variable `var`,
+    /// such as `MY_CLI_FEATURES=sync,beta`.
+    ///
+    /// The variable is also what [`Disabled`] tells users to set.
+    #[must_use]
+    pub fn with_env(mut self, var: impl Into<String>) -> Self {
+        let var = var.into();
+
+        if let Some(value) = std::env::var_os(&var) {
+            self.enabled.extend(
+                value
+                    .to_string_lossy()
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|flag| !flag.is_empty())
+                    .map(String::from),
+            );
+        }
+
+        self.var = Some(var);
+        self
+    }
+
+    /// Enable `flags`, for example the ones listed in a config file.
+    #[must_use]
+    pub fn with_enabled<I, S>(mut self, flags: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.enabled.extend(flags.into_iter().map(Into::into));
+        self
+    }
+
+    /// Ask `provider` about every flag, such as a telemetry service that
+    /// rolls flags out to some users.
+    ///
+    /// Providers are called synchronously while parsing and executing,
+    /// anything that hits the network should be resolved beforehand.
+    #[must_use]
+    pub fn with_provider(
+        mut self,
+        provider: impl Fn(&str) -> bool + Send + Sync + 'static,
+    ) -> Self {
+        self.providers.push(Arc::new(provider));
+        self
+    }
+
+    /// Whether `flag` is enabled.
+    #[must_use]
+    pub fn is_enabled(&self, flag: &str) -> bool {
+        self.enabled.iter().any(|enabled| enabled == flag)
+            || self.providers.iter().any(|provider| provider(flag))
+    }
+
+    /// Hide every subcommand of `T` whose flag isn't enabled from the help of
+    /// `cmd`, which is usually `T::command()`.
+    ///
+    /// Pass this to [`crate::parse_with`] as the `augment`.
+    #[must_use]
+    pub fn hide<T: Container>(&self, mut cmd: clap::Command) -> clap::Command {
+        for (path, flag) in T::flags() {
+            if !self.is_enabled(flag) {
+                cmd = hide(cmd, &path);
+            }
+        }
+
+        cmd
+    }
+}
+
+/// Hide the subcommand at `path` below `cmd`.
+fn hide(cmd: clap::Command, path: &[&'static str]) -> clap::Command {
+    let Some((name, rest)) = path.split_first() else {
+        return cmd.hide(true);
+    };
+
+    if cmd.find_subcommand(name).is_none() {
+        return cmd;
+    }
+
+    cmd.mut_subcommand(*name, |sub| hide(sub, rest))
+}
+
+/// Returned by [`crate::execute`] when a command's flag isn't enabled.
+#[derive(Debug)]
+pub struct Disabled {
+    /// The command as it was typed, such as `my-cli sync`.
+    pub command: String,
+    /// The flag that gates it.
+    pub flag: &'static str,
+    var: Option<String>,
+}
+
+impl Disabled {
+    pub(crate) fn new(command: String, flag: &'static str, flags: &Flags) -> Self {
+        Self {
+            command,
+            flag,
+            var: flags.var.clone(),
+        }
+    }
+}
+
+impl fmt::Display for Disabled {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "`{}` is behind the `{}` feature flag",
+            self.command, self.flag
+        )?;
+
+        if let Some(var) = &self.var {
+            write!(f, ", set `{var}={}` to enable it", self.flag)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl std::error::Error for Disabled {}
@@ -0,0 +1,24 @@
+//! Lowercase hex for digests, IDs and encrypted values.
+use std::fmt::Write as _;
+
+#[cfg(feature = "secrets")]
+use eyre::{eyre, Result};
+
+pub(crate) fn encode(bytes: &[u8]) -> String {
+    bytes.iter().fold(String::new(), |mut out, b| {
+        write!(out, "{b:02x}").ok();
+        out
+    })
+}
+
+#[cfg(feature = "secrets")]
+pub(crate) fn decode(raw: &str) -> Result<Vec<u8>> {
+    (0..raw.len())
+        .step_by(2)
+        .map(|i| {
+            raw.get(i..i + 2)
+                .and_then(|b| u8::from_str_radix(b, 16).ok())
+                .ok_or_else(|| eyre!("invalid hex at {i}"))
+        })
+        .collect()
+}
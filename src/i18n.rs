@@ -0,0 +1,265 @@
+//! Localized text for help, prompts and output.
+//!
+//! Messages are written in [Fluent] and bundled per locale. The locale is
+//! picked from an explicit value, typically a `--locale` flag, and otherwise
+//! from the `LC_ALL`, `LC_MESSAGES` and `LANG` environment variables. Lookups
+//! fall back to the default locale and then to the message ID itself, so
+//! missing translations never break the CLI.
+//!
+//! Some things to note:
+//! - Once [`Localizer::install`] has been called, the headers of
+//!   [`Format::Pretty`] tables are translated whenever there is a message with
+//!   the same ID as the header.
+//! - [`Localizer::command`] translates the help text of a [`clap::Command`],
+//!   help strings are treated as message IDs.
+//! - Anything else, such as prompts, can use [`text`] or [`Localizer::text`].
+//!
+//! # Examples
+//!
+//! ```
+//! use cata::i18n::{self, Localizer};
+//!
+//! let localizer = Localizer::new("en-US")?
+//!   .with_resource("en-US", "greeting = Hello { $name }")?
+//!   .with_resource("fr", "greeting = Bonjour { $name }")?
+//!   .with_locale(Some("fr-FR"))?;
+//!
+//! let mut args = i18n::Args::new();
+//! args.set("name", "Thomas");
+//!
+//! assert_eq!(localizer.format("greeting", &args), "Bonjour Thomas");
+//! assert_eq!(localizer.text("missing"), "missing");
+//! # Ok::<(), eyre::Report>(())
+//! ```
+//!
+//! [Fluent]: https://projectfluent.org
+//! [`Format::Pretty`]: crate::output::Format::Pretty
+use std::sync::OnceLock;
+
+use eyre::{eyre, Result};
+pub use fluent_bundle::FluentArgs as Args;
+use fluent_bundle::{concurrent::FluentBundle, FluentResource};
+use unic_langid::LanguageIdentifier;
+
+static LOCALE_VARS: &[&str] = &["LC_ALL", "LC_MESSAGES", "LANG"];
+static GLOBAL: OnceLock<Localizer> = OnceLock::new();
+
+type Bundle = FluentBundle<FluentResource>;
+
+/// A set of translations and the locale currently selected.
+///
+/// See the module documentation for usage.
+pub struct Localizer {
+    default: LanguageIdentifier,
+    selected: LanguageIdentifier,
+    bundles: Vec<Bundle>,
+}
+
+impl std::fmt::Debug for Localizer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Localizer")
+            .field("default", &self.default)
+            .field("selected", &self.selected)
+            .finish_non_exhaustive()
+    }
+}
+
+fn parse(locale: &str) -> Result<LanguageIdentifier> {
+    // POSIX locales look like `en_US.UTF-8`, strip the encoding and modifier.
+    let locale = locale
+        .split(['.', '@'])
+        .next()
+        .unwrap_or_default()
+        .replace('_', "-");
+
+    locale
+        .parse()
+        .map_err(|e| eyre!("invalid locale {locale}: {e}"))
+}
+
+impl Localizer {
+    /// Create a localizer whose fallback is `default`.
+    pub fn new(default: &str) -> Result<Self> {
+        let default = parse(default)?;
+
+        Ok(Self {
+            selected: default.clone(),
+            default,
+            bundles: Vec::new(),
+        })
+    }
+
+    /// Add messages in Fluent syntax for `locale`.
+    pub fn with_resource(mut self, locale: &str, source: impl Into<String>) -> Result<Self> {
+        let locale = parse(locale)?;
+
+        let resource = FluentResource::try_new(source.into())
+            .map_err(|(_, errs)| eyre!("invalid messages for {locale}: {errs:?}"))?;
+
+        let idx = if let Some(idx) = self.bundles.iter().position(|b| b.locales[0] == locale) {
+            idx
+        } else {
+            let mut bundle = Bundle::new_concurrent(vec![locale]);
+            // Isolation marks are for bidirectional text in UIs and only show up as
+            // garbage in terminals.
+            bundle.set_use_isolating(false);
+            self.bundles.push(bundle);
+
+            self.bundles.len() - 1
+        };
+
+        self.bundles[idx]
+            .add_resource(resource)
+            .map_err(|errs| eyre!("conflicting messages: {errs:?}"))?;
+
+        Ok(self)
+    }
+
+    /// Select the locale to use.
+    ///
+    /// An explicit locale, such as one from a `--locale` flag, wins and fails
+    /// when it isn't valid. Otherwise the environment is consulted. When
+    /// nothing is set, or the environment has a locale that isn't a language
+    /// such as `C.UTF-8`, the default is used.
+    ///
+    /// ```
+    /// use cata::i18n::Localizer;
+    ///
+    /// std::env::set_var("LC_ALL", "C.UTF-8");
+    ///
+    /// let localizer = Localizer::new("en-US")?.with_locale(None)?;
+    /// assert_eq!(localizer.locale().to_string(), "en-US");
+    ///
+    /// assert!(Localizer::new("en-US")?.with_locale(Some("C")).is_err());
+    /// # Ok::<(), eyre::Report>(())
+    /// ```
+    pub fn with_locale(mut self, locale: Option<&str>) -> Result<Self> {
+        self.selected = match locale {
+            Some(locale) => parse(locale)?,
+            None => LOCALE_VARS
+                .iter()
+                .filter_map(|k| std::env::var(k).ok())
+                .find(|v| !v.is_empty() && v != "C" && v != "POSIX")
+                .and_then(|v| parse(&v).ok())
+                .unwrap_or_else(|| self.default.clone()),
+        };
+
+        Ok(self)
+    }
+
+    /// The locale that was selected.
+    #[must_use]
+    pub fn locale(&self) -> &LanguageIdentifier {
+        &self.selected
+    }
+
+    /// Bundles in the order they should be searched.
+    ///
+    /// This is an exact match on the selected locale, then the selected
+    /// language and finally the default.
+    fn chain(&self) -> impl Iterator<Item = &Bundle> {
+        let exact = self
+            .bundles
+            .iter()
+            .filter(|b| b.locales[0] == self.selected);
+        let language = self.bundles.iter().filter(|b| {
+            b.locales[0] != self.selected && b.locales[0].language == self.selected.language
+        });
+        let default = self.bundles.iter().filter(|b| b.locales[0] == self.default);
+
+        exact.chain(language).chain(default)
+    }
+
+    /// Look up and format the message `id`, if there is one.
+    #[must_use]
+    pub fn lookup(&self, id: &str, args: Option<&Args>) -> Option<String> {
+        self.chain().find_map(|bundle| {
+            let pattern = bundle.get_message(id)?.value()?;
+            let mut errors = Vec::new();
+
+            Some(
+                bundle
+                    .format_pattern(pattern, args, &mut errors)
+                    .into_owned(),
+            )
+        })
+    }
+
+    /// Translate the message `id`, falling back to the ID itself.
+    #[must_use]
+    pub fn text(&self, id: &str) -> String {
+        self.lookup(id, None).unwrap_or_else(|| id.into())
+    }
+
+    /// Translate the message `id` with arguments, falling back to the ID
+    /// itself.
+    #[must_use]
+    pub fn format(&self, id: &str, args: &Args) -> String {
+        self.lookup(id, Some(args)).unwrap_or_else(|| id.into())
+    }
+
+    /// Translate the help text for a command and all of its subcommands.
+    ///
+    /// `about`, `long_about` and the help on every argument are treated as
+    /// message IDs. Any that don't have a message are left as is.
+    #[must_use]
+    pub fn command(&self, cmd: clap::Command) -> clap::Command {
+        let translate = |text: Option<&clap::builder::StyledStr>| {
+            text.and_then(|text| self.lookup(&text.to_string(), None))
+        };
+
+        let mut cmd = cmd;
+
+        if let Some(about) = translate(cmd.get_about()) {
+            cmd = cmd.about(about);
+        }
+
+        if let Some(about) = translate(cmd.get_long_about()) {
+            cmd = cmd.long_about(about);
+        }
+
+        let ids = cmd
+            .get_arguments()
+            .map(|arg| arg.get_id().clone())
+            .collect::<Vec<_>>();
+
+        for id in ids {
+            cmd = cmd.mut_arg(id, |arg| match translate(arg.get_help()) {
+                Some(help) => arg.help(help),
+                None => arg,
+            });
+        }
+
+        let names = cmd
+            .get_subcommands()
+            .map(|sub| sub.get_name().to_string())
+            .collect::<Vec<_>>();
+
+        for name in names {
+            cmd = cmd.mut_subcommand(name, |sub| self.command(sub));
+        }
+
+        cmd
+    }
+
+    /// Make this the localizer used by [`text`] and the output module.
+    pub fn install(self) -> Result<()> {
+        GLOBAL
+            .set(self)
+            .map_err(|_| eyre!("a localizer has already been installed"))
+    }
+}
+
+/// The installed localizer, if there is one.
+#[must_use]
+pub fn global() -> Option<&'static Localizer> {
+    GLOBAL.get()
+}
+
+/// Translate `id` with the installed localizer.
+///
+/// Without an installed localizer, or a message for `id`, this returns `id`.
+#[must_use]
+pub fn text(id: &str) -> String {
+    global().map_or_else(|| id.into(), |l| l.text(id))
+}
@@ -0,0 +1,129 @@
+//! Middleware that wraps every lifecycle hook in the command tree.
+//!
+//! Cross-cutting behavior such as logging, metrics, refreshing credentials or
+//! gating features shouldn't have to be repeated in every command. A [`Layer`]
+//! is handed each `pre_run`, `run` and `post_run` call for the whole tree and
+//! decides what happens around it, including whether it happens at all.
+//!
+//! Some things to note:
+//! - Layers are added with [`crate::ExecuteOptions::with_layer`]. The first
+//!   layer added is the outermost one, it sees every call first and its result
+//!   last.
+//! - Not calling [`Next::run`] skips the hook and everything inside of the
+//!   layer. Whatever the layer returns is used as the hook's result.
+//! - `on_signal` and `on_error` are not wrapped, they aren't part of the
+//!   regular lifecycle.
+//!
+//! # Examples
+//!
+//! ```
+//! use std::sync::Mutex;
+//!
+//! use cata::{
+//!   layer::{Hook, Layer, Next},
+//!   Command, Container, Context, ExecuteOptions,
+//! };
+//! use clap::Parser;
+//!
+//! static CALLS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+//!
+//! struct Log;
+//!
+//! #[async_trait::async_trait]
+//! impl Layer for Log {
+//!   async fn call(&self, ctx: &mut Context, next: Next<'_>) -> eyre::Result<()> {
+//!     let hook = next.hook().name();
+//!     let result = next.run(ctx).await;
+//!
+//!     CALLS.lock().unwrap().push(format!("{hook}: {}", result.is_ok()));
+//!
+//!     result
+//!   }
+//! }
+//!
+//! #[derive(Parser, Container)]
+//! struct Root {}
+//!
+//! impl Command for Root {}
+//!
+//! # #[tokio::main]
+//! # async fn main() -> eyre::Result<()> {
+//! let options = ExecuteOptions::default().with_layer(Log);
+//! cata::execute_with(&Root::parse_from(["root"]), options).await?;
+//!
+//! assert_eq!(
+//!   *CALLS.lock().unwrap(),
+//!   ["pre_run: true", "run: true", "post_run: true"],
+//! );
+//! # Ok(())
+//! # }
+//! ```
+use std::sync::Arc;
+
+use eyre::Result;
+
+pub use crate::lifecycle::Hook;
+use crate::{lifecycle::Node, Command, Context};
+
+/// Wraps the lifecycle hooks of every command in the tree.
+///
+/// See the module documentation for usage.
+#[async_trait::async_trait]
+pub trait Layer: Send + Sync {
+    /// Handle a single hook, calling [`Next::run`] to continue on to the rest
+    /// of the layers and the hook itself.
+    async fn call(&self, ctx: &mut Context, next: Next<'_>) -> Result<()>;
+}
+
+/// The rest of the layers, followed by the hook itself.
+pub struct Next<'a> {
+    layers: &'a [Arc<dyn Layer>],
+    hook: Hook<'a>,
+    node: Node<'a>,
+}
+
+impl<'a> Next<'a> {
+    pub(crate) fn new(layers: &'a [Arc<dyn Layer>], hook: Hook<'a>, node: Node<'a>) -> Self {
+        Self { layers, hook, node }
+    }
+
+    /// The hook that is being called.
+    #[must_use]
+    pub fn hook(&self) -> Hook<'a> {
+        self.hook
+    }
+
+    /// The command whose hook is being called.
+    #[must_use]
+    pub fn command(&self) -> &dyn Command {
+        self.node.command()
+    }
+
+    /// Continue on to the next layer, or call the hook if this is the last
+    /// one.
+    pub async fn run(self, ctx: &mut Context) -> Result<()> {
+        let Some((layer, layers)) = self.layers.split_first() else {
+            return self.node.call(self.hook, ctx).await;
+        };
+
+        layer
+            .call(
+                ctx,
+                Next {
+                    layers,
+                    hook: self.hook,
+                    node: self.node,
+                },
+            )
+            .await
+    }
+}
+
+impl std::fmt::Debug for Next<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Next")
+            .field("layers", &self.layers.len())
+            .field("hook", &self.hook)
+            .finish_non_exhaustive()
+    }
+}
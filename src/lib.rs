@@ -3,40 +3,778 @@
 //! This crate provides a collection of utilities that make it easier to build
 //! CLI tools.
 //!
+//! - [`aliases`]: user defined shortcuts for commands, read from the config
+//!   directory.
+//! - [`app`]: the usual setup for `main`, parsing and executing the root in
+//!   one call.
+//! - [`background`]: tasks that run alongside commands and are cancelled with
+//!   them.
+//! - [`batch`]: run a script of commands from a file or stdin without a shell
+//!   loop.
+//! - [`blocking`]: commands with synchronous hooks, run without an async
+//!   runtime of your own.
+//! - [`bug_report`]: a `bug-report` subcommand that collects everything needed
+//!   for an issue.
+//! - [`cache`]: a TTL based disk cache for expensive lookups.
 //! - [`command`]: recursively traverse a tree of clap commands and subcommands
 //!   calling lifecycle hooks at each level.
+//! - [`completion`]: shell completion with candidates resolved at runtime,
+//!   needs the `completion` feature.
+//! - [`context`]: share services such as API clients across the command tree.
+//! - [`crash`]: write crash reports that users can attach to bug reports.
+//! - [`daemon`]: run commands in the background with `status` and `stop`
+//!   subcommands to manage them.
+//! - [`describe`]: the command tree as a `const`, for tools that shouldn't have
+//!   to build clap commands.
+//! - [`dirs`]: platform specific config, cache, state and data directories.
+//! - [`docs`]: man pages and a Markdown reference generated from the command
+//!   tree.
+//! - [`elevate`]: run the current invocation again under `sudo` when it needs
+//!   root.
+//! - [`environment`]: a reference of every environment variable the CLI honors.
+//! - [`error`]: error reports with suggestions that hide backtraces unless
+//!   asked for, and consistent exit codes.
+//! - [`events`]: structured events for every command that starts, finishes or
+//!   fails.
+//! - [`examples`]: example invocations that show up in help, docs and
+//!   errors.
+//! - [`fanout`]: run many invocations of a command at the same time and report
+//!   on all of them.
 //! - [`mod@file`]: derive `clap::value_parser` for deserializing values from
 //!   files. Detects the file format from the extension and currently supports
-//!   JSON in addition to YAML.
+//!   JSON in addition to YAML and TOML.
+//! - [`first_run`]: one-time setup the first time the CLI runs on a machine.
+//! - [`flags`]: subcommands that are hidden and refused unless a feature flag
+//!   turns them on.
+//! - [`i18n`]: localized help text, prompts and output headers.
+//! - [`layer`]: middleware that wraps every lifecycle hook in the command tree.
+//! - [`licenses`]: a `licenses` subcommand listing third-party attributions
+//!   generated at build time.
+//! - [`lock`]: keep more than one invocation of a command from running at a
+//!   time.
+//! - [`machine`]: a stable ID for the current machine, with fallbacks when the
+//!   OS doesn't provide one.
 //! - [`output`]: structured output for commands. Users can choose the output
 //!   format they would like, currently supporting JSON, YAML and pretty.
+//! - [`pipe`]: chain commands together, one's output becomes the next one's
+//!   input.
+//! - [`plugin`]: external `my-cli-<name>` executables on `$PATH` as
+//!   subcommands.
+//! - [`prompt`]: ask for missing required arguments instead of failing.
+//! - [`registry`]: subcommands that plugins and optional crates register at
+//!   startup.
+//! - [`repl`]: an interactive shell that runs commands through the usual
+//!   lifecycle, with history and completion.
+//! - [`requirements`]: prerequisites such as environment variables and
+//!   binaries, checked before anything runs.
+//! - [`runtime`]: run executions on tokio or any other async runtime.
+//! - [`secrets`]: store API tokens in the OS keychain with a file based
+//!   fallback.
+//! - [`state`]: a key-value store for remembering things between runs.
 //! - [`telemetry`]: a simple way to track activity and errors for your CLI.
+//! - [`testing`]: run command trees in tests, assert on their output and
+//!   snapshot it.
+//! - [`timings`]: a report of the commands that ran and how long each hook
+//!   took.
+//! - [`tree`]: walk every command the CLI knows about, not just the ones that
+//!   were parsed.
+//! - [`update`]: let users know when a new version of the CLI is available.
+//! - [`verbosity`]: `-v` flags that control what gets logged.
+//! - [`whats_new`]: show release notes the first time a new version runs.
+//!
+//! # Features
+//!
+//! Everything except `blocking` and `completion` is enabled by default.
+//! Minimal CLIs can turn off the subsystems they don't use with
+//! `default-features = false`:
+//!
+//! - `aliases`: the [`aliases`] module, pulls in `toml_edit`.
+//! - `app`: the [`app`] module and [`App`], turns on `crash` and `prompt`.
+//! - `background`: the [`background`] module and [`Context::tasks`].
+//! - `batch`: the [`batch`] module, along with `output` the [`fanout`] module.
+//! - `blocking`: the [`blocking`] module and [`execute_sync`].
+//! - `cache`: the [`cache`] module and cached completions.
+//! - `completion`: the [`completion`] module and tab completion in the
+//!   [`repl`], turns on `describe` and the unstable dynamic completion of clap
+//!   and `clap_complete`.
+//! - `crash`: the [`crash`] module, along with `describe` and `output` the
+//!   [`bug_report`] module.
+//! - `daemon`: the [`daemon`] module and [`command::detach`], turns on
+//!   `describe`.
+//! - `describe`: the [`describe`] module and the descriptors from
+//!   `#[derive(Container)]`, along with `output` the [`environment`] and
+//!   [`licenses`] modules.
+//! - `docs`: the [`docs`] module, turns on `tree` and pulls in `clap_mangen`.
+//! - `elevate`: the [`elevate`] module.
+//! - `events`: the [`events`] module and [`ExecuteOptions::with_subscriber`].
+//! - `file`: the [`mod@file`] module and `File` derive, along with `output` the
+//!   [`pipe`] module.
+//! - `first-run`: the [`first_run`] module and [`Command::on_first_run`].
+//! - `i18n`: the [`i18n`] module.
+//! - `layer`: the [`layer`] module and [`ExecuteOptions::with_layer`].
+//! - `lock`: the [`lock`] module and [`Command::lock`].
+//! - `output`: the [`output`] and [`testing`] modules and the `Row` derive,
+//!   pulls in `tabled`.
+//! - `plugin`: the [`plugin`] module, turns on `registry`.
+//! - `prompt`: the [`prompt`] module.
+//! - `registry`: the [`registry`] module, turns on `describe`.
+//! - `repl`: the [`repl`] module, turns on `batch` and pulls in `rustyline`.
+//! - `requirements`: the [`requirements`] module and
+//!   [`Command::requirements`].
+//! - `secrets`: the [`secrets`] module, turns on `lock` and pulls in the OS
+//!   keychain.
+//! - `state`: the [`state`] module.
+//! - `telemetry`: the [`telemetry`] and [`machine`] modules, pulls in posthog.
+//! - `timings`: the [`timings`] module and [`ExecuteOptions::with_report`],
+//!   turns on `events` and `layer`.
+//! - `tokio`: the [`runtime::Tokio`] runtime, which executions default to.
+//! - `toml`: TOML support for [`mod@file`], which it turns on, pulls in
+//!   `toml_edit`.
+//! - `tree`: the [`tree`] module.
+//! - `update`: the [`update`] module, which turns on `cache`, pulls in an HTTP
+//!   client.
+//! - `verbosity`: the [`verbosity`] module and [`Command::verbosity`], pulls in
+//!   `tracing-subscriber`.
+//! - `whats-new`: the [`whats_new`] module, turns on `state`.
+//! - `yaml`: YAML support for [`mod@file`], [`output`] and [`batch`] scripts.
+//!
+//! # WebAssembly
+//!
+//! CLIs can target `wasm32-wasi` by only enabling the `file`, `i18n`, `output`,
+//! `verbosity` and `yaml` features. The other features rely on native code or
+//! networking that isn't available in a sandbox. The `daemon`, `elevate` and
+//! `plugin` modules are not available, [`state`] does not lock its file and
+//! [`lock`] always succeeds.
+#[cfg(feature = "aliases")]
+pub mod aliases;
+#[cfg(feature = "app")]
+pub mod app;
+#[cfg(feature = "background")]
+pub mod background;
+#[cfg(feature = "batch")]
+pub mod batch;
+#[cfg(feature = "blocking")]
+pub mod blocking;
+#[cfg(all(feature = "output", feature = "crash", feature = "describe"))]
+pub mod bug_report;
+#[cfg(feature = "cache")]
+pub mod cache;
 pub mod command;
+#[cfg(feature = "completion")]
+pub mod completion;
+pub mod context;
+#[cfg(feature = "crash")]
+pub mod crash;
+#[cfg(all(feature = "daemon", any(unix, windows)))]
+pub mod daemon;
+#[cfg(feature = "describe")]
+pub mod describe;
+pub mod dirs;
+#[cfg(feature = "docs")]
+pub mod docs;
+#[cfg(all(feature = "elevate", unix))]
+pub mod elevate;
+#[cfg(all(feature = "output", feature = "describe"))]
+pub mod environment;
+pub mod error;
+#[cfg(feature = "events")]
+pub mod events;
+pub mod examples;
+#[cfg(all(feature = "output", feature = "batch"))]
+pub mod fanout;
+#[cfg(feature = "file")]
 pub mod file;
+#[cfg(feature = "first-run")]
+pub mod first_run;
+pub mod flags;
+#[cfg(any(feature = "cache", feature = "secrets", feature = "telemetry"))]
+mod hex;
+#[cfg(feature = "i18n")]
+pub mod i18n;
+#[cfg(feature = "layer")]
+pub mod layer;
+#[cfg(all(feature = "output", feature = "describe"))]
+pub mod licenses;
+mod lifecycle;
+#[cfg(feature = "lock")]
+pub mod lock;
+#[cfg(feature = "telemetry")]
+pub mod machine;
+#[cfg(feature = "output")]
 pub mod output;
+#[cfg(all(feature = "file", feature = "output"))]
+pub mod pipe;
+#[cfg(all(feature = "plugin", any(unix, windows)))]
+pub mod plugin;
+#[cfg(feature = "prompt")]
+pub mod prompt;
+#[cfg(feature = "registry")]
+pub mod registry;
+#[cfg(feature = "repl")]
+pub mod repl;
+#[cfg(feature = "requirements")]
+pub mod requirements;
+pub mod runtime;
+#[cfg(feature = "secrets")]
+pub mod secrets;
+#[cfg(feature = "state")]
+pub mod state;
+#[cfg(feature = "telemetry")]
 pub mod telemetry;
+#[cfg(feature = "output")]
+pub mod testing;
+#[cfg(feature = "timings")]
+pub mod timings;
+#[cfg(feature = "tree")]
+pub mod tree;
+#[cfg(feature = "update")]
+pub mod update;
+#[cfg(feature = "verbosity")]
+pub mod verbosity;
+#[cfg(feature = "whats-new")]
+pub mod whats_new;
 
-pub use cata_derive::{Container, File};
+#[cfg(feature = "file")]
+pub use cata_derive::File;
+#[cfg(feature = "output")]
+pub use cata_derive::Row;
+pub use cata_derive::{Command, Container};
+#[cfg(test)]
+use criterion as _;
+// YAML is only read and written by the modules that support it.
 use eyre::Result;
-use futures::future::{BoxFuture, FutureExt};
+#[cfg(all(
+    feature = "yaml",
+    not(any(feature = "batch", feature = "file", feature = "output"))
+))]
+use serde_yaml as _;
 
-pub use crate::{command::Command, output::Format};
+#[cfg(feature = "app")]
+pub use crate::app::App;
+#[cfg(feature = "output")]
+pub use crate::output::Format;
+pub use crate::{
+    command::{Command, ExecuteOptions},
+    context::Context,
+};
 
-/// Executes a command and all of its subcommands.
-///
-/// Recursively calls `pre_run`, `run`, and `post_run` on the command and all of
-/// its subcommands.
-pub fn execute(cmd: &dyn Command) -> BoxFuture<Result<()>> {
-    async move {
-        cmd.pre_run()?;
+/// Used by the code that the derives generate.
+#[doc(hidden)]
+pub mod __private {
+    pub use async_trait::async_trait;
+    pub use eyre::Result;
+    pub use serde::{self, de::DeserializeOwned};
+    #[cfg(feature = "output")]
+    pub use tabled;
+
+    pub use crate::__describe as describe;
+
+    /// Keep the [`crate::describe::Describe`] implementations from
+    /// `#[derive(Container)]`, or drop them without the `describe` feature.
+    #[cfg(feature = "describe")]
+    #[macro_export]
+    #[doc(hidden)]
+    macro_rules! __describe {
+        ($($item:tt)*) => {
+            $($item)*
+        };
+    }
+
+    #[cfg(not(feature = "describe"))]
+    #[macro_export]
+    #[doc(hidden)]
+    macro_rules! __describe {
+        ($($item:tt)*) => {};
+    }
+
+    /// Parse `T` as if only the subcommand `name` had been passed, for
+    /// `#[cata(default)]`.
+    ///
+    /// # Errors
+    ///
+    /// When the subcommand can't be run without arguments of its own.
+    pub fn parse_subcommand<T: clap::Subcommand>(name: &'static str) -> Result<T, clap::Error> {
+        let cmd = T::augment_subcommands(clap::Command::new(name).no_binary_name(true));
+
+        cmd.try_get_matches_from([name])
+            .and_then(|matches| T::from_arg_matches(&matches))
+    }
+
+    /// Build `T` with `init` the first time it is asked for and keep it around
+    /// for the rest of the process.
+    pub fn leak_once<T: Send + Sync + 'static>(init: impl FnOnce() -> T) -> &'static T {
+        use std::{
+            any::{Any, TypeId},
+            collections::HashMap,
+            sync::{Mutex, OnceLock, PoisonError},
+        };
+
+        type Leaked = HashMap<TypeId, &'static (dyn Any + Send + Sync)>;
+        static LEAKED: OnceLock<Mutex<Leaked>> = OnceLock::new();
 
-        cmd.run().await?;
+        let mut leaked = LEAKED
+            .get_or_init(Mutex::default)
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        let value = *leaked
+            .entry(TypeId::of::<T>())
+            .or_insert_with(|| Box::leak(Box::new(init())));
 
-        if let Some(next) = cmd.next() {
-            execute(next).await?;
+        value.downcast_ref().expect("values are kept by their type")
+    }
+
+    /// The length of `segment` joined onto `parent`, see [`join_path`].
+    #[must_use]
+    pub const fn path_len(parent: &str, segment: &str) -> usize {
+        if parent.is_empty() {
+            segment.len()
+        } else {
+            parent.len() + 1 + segment.len()
+        }
+    }
+
+    /// Join `segment` onto `parent` with a space, at compile time.
+    ///
+    /// `N` has to be [`path_len`], the result goes through [`path_str`].
+    #[must_use]
+    pub const fn join_path<const N: usize>(parent: &str, segment: &str) -> [u8; N] {
+        let mut joined = [0; N];
+        let (parent, segment) = (parent.as_bytes(), segment.as_bytes());
+
+        let mut i = 0;
+        while i < parent.len() {
+            joined[i] = parent[i];
+            i += 1;
+        }
+
+        if !parent.is_empty() {
+            joined[i] = b' ';
+            i += 1;
         }
 
-        cmd.post_run()
+        let mut j = 0;
+        while j < segment.len() {
+            joined[i + j] = segment[j];
+            j += 1;
+        }
+
+        joined
+    }
+
+    /// The path that [`join_path`] put together.
+    #[must_use]
+    pub const fn path_str(joined: &'static [u8]) -> &'static str {
+        match std::str::from_utf8(joined) {
+            Ok(path) => path,
+            Err(_) => panic!("paths are joined from strings"),
+        }
     }
-    .boxed()
+}
+
+/// Parses `T` from the command line, keeping the [`clap::ArgMatches`] around.
+///
+/// This is [`clap::Parser::parse`] for hooks that need more than the parsed
+/// struct, such as which flags the user actually set. Pass the matches on with
+/// [`ExecuteOptions::with_matches`] and hooks can get them from
+/// [`Context::matches`]. Errors are printed and exit like they do with clap.
+///
+/// ```
+/// use cata::{Command, Container, ExecuteOptions};
+/// use clap::{parser::ValueSource, Parser, Subcommand};
+///
+/// #[derive(Parser, Container)]
+/// struct Root {
+///   #[arg(long, global = true, default_value = "us-east-1")]
+///   region: String,
+///
+///   #[command(subcommand)]
+///   cmd: RootCmd,
+/// }
+///
+/// impl Command for Root {}
+///
+/// #[derive(Subcommand, Container)]
+/// enum RootCmd {
+///   Deploy(Deploy),
+/// }
+///
+/// #[derive(Parser, Container)]
+/// struct Deploy {}
+///
+/// #[async_trait::async_trait]
+/// impl Command for Deploy {
+///   async fn run(&self, ctx: &mut cata::Context) -> eyre::Result<()> {
+///     let matches = ctx.matches().expect("matches were passed on");
+///
+///     assert_eq!(matches.value_source("region"), Some(ValueSource::DefaultValue));
+///
+///     Ok(())
+///   }
+/// }
+///
+/// # #[tokio::main]
+/// # async fn main() -> eyre::Result<()> {
+/// let (root, matches) = cata::parse_from::<Root, _, _>(["root", "deploy"]);
+///
+/// cata::execute_with(&root, ExecuteOptions::default().with_matches(matches)).await
+/// # }
+/// ```
+#[must_use]
+pub fn parse<T: clap::Parser>() -> (T, clap::ArgMatches) {
+    parse_from(std::env::args_os())
+}
+
+/// Parses `T` from `args`, keeping the [`clap::ArgMatches`] around.
+///
+/// See [`parse`].
+#[must_use]
+pub fn parse_from<T, I, S>(args: I) -> (T, clap::ArgMatches)
+where
+    T: clap::Parser,
+    I: IntoIterator<Item = S>,
+    S: Into<std::ffi::OsString> + Clone,
+{
+    parse_from_with(args, std::convert::identity)
+}
+
+/// Parses `T` from the command line after `augment` has changed its
+/// [`clap::Command`].
+///
+/// Defaults that are only known at runtime, such as ones from a config file or
+/// the environment, can be filled in here with [`clap::Command::mut_arg`].
+/// See [`parse`] and [`parse_and_execute`].
+#[must_use]
+pub fn parse_with<T, F>(augment: F) -> (T, clap::ArgMatches)
+where
+    T: clap::Parser,
+    F: FnOnce(clap::Command) -> clap::Command,
+{
+    parse_from_with(std::env::args_os(), augment)
+}
+
+/// Parses `T` from `args` after `augment` has changed its [`clap::Command`].
+///
+/// See [`parse_with`].
+///
+/// ```
+/// use cata::{Command, Container};
+/// use clap::Parser;
+///
+/// #[derive(Parser, Container)]
+/// struct Root {
+///   #[arg(long, default_value = "us-east-1")]
+///   region: String,
+/// }
+///
+/// impl Command for Root {}
+///
+/// // Usually read from a config file.
+/// let region = "eu-west-1";
+///
+/// let (root, _) = cata::parse_from_with::<Root, _, _, _>(["root"], |cmd| {
+///   cmd.mut_arg("region", |arg| arg.default_value(region))
+/// });
+///
+/// assert_eq!(root.region, "eu-west-1");
+/// ```
+#[must_use]
+pub fn parse_from_with<T, I, S, F>(args: I, augment: F) -> (T, clap::ArgMatches)
+where
+    T: clap::Parser,
+    I: IntoIterator<Item = S>,
+    S: Into<std::ffi::OsString> + Clone,
+    F: FnOnce(clap::Command) -> clap::Command,
+{
+    let mut cmd = augment(T::command());
+    let matches = cmd.clone().get_matches_from(args);
+
+    match T::from_arg_matches(&matches) {
+        Ok(root) => (root, matches),
+        Err(e) => e.format(&mut cmd).exit(),
+    }
+}
+
+/// Parses `T` from the command line and executes it.
+///
+/// The [`clap::Command`] goes through `augment` first, see [`parse_with`], and
+/// the matches are passed on to the hooks, see [`Context::matches`]. This is
+/// the whole of `main` for most CLIs.
+///
+/// ```no_run
+/// use cata::{Command, Container, ExecuteOptions};
+/// use clap::Parser;
+///
+/// #[derive(Parser, Container)]
+/// struct Root {
+///   #[arg(long, default_value = "us-east-1")]
+///   region: String,
+/// }
+///
+/// impl Command for Root {}
+///
+/// #[tokio::main]
+/// async fn main() -> eyre::Result<()> {
+///   let region: &'static str = match std::env::var("MY_CLI_REGION") {
+///     Ok(region) => region.leak(),
+///     Err(_) => "us-east-1",
+///   };
+///
+///   cata::parse_and_execute::<Root, _>(
+///     |cmd| cmd.mut_arg("region", |arg| arg.default_value(region)),
+///     ExecuteOptions::default(),
+///   )
+///   .await
+/// }
+/// ```
+pub async fn parse_and_execute<T, F>(augment: F, options: ExecuteOptions) -> Result<()>
+where
+    T: clap::Parser + Command,
+    F: FnOnce(clap::Command) -> clap::Command,
+{
+    let (root, matches) = parse_with::<T, F>(augment);
+
+    execute_with(&root, options.with_matches(matches)).await
+}
+
+/// Executes a command and all of its subcommands.
+///
+/// Calls `pre_run` and `run` on the command and each of its subcommands in
+/// turn, then `post_run` from the deepest subcommand back up to the root. The
+/// tree is walked iteratively, so deep trees don't allocate a future per
+/// level.
+///
+/// Teardown is the same no matter how execution ends:
+/// 1. `post_run` is called, child first, on every command whose `pre_run`
+///    succeeded, with the outcome so far. When that is an error, `on_error` is
+///    called right before and can add to it. A failing `pre_run` or `run` stops
+///    the descent but not the teardown. When execution is cancelled by dropping
+///    the future, `post_run` still happens as part of the drop, but each one
+///    only runs until its first `await` that isn't ready.
+/// 2. Tasks started with [`background::spawn`] get a grace period to finish and
+///    are then cancelled. On cancellation they are cancelled immediately.
+/// 3. stdout and stderr are flushed.
+///
+/// The first error wins, errors from `post_run` are only returned when
+/// everything before them succeeded. Each call gets its own [`Context`], which
+/// is dropped after teardown.
+///
+/// ctrl-c is handled from the start of execution until it returns. The first
+/// one cancels [`Context::token`] so that the command can wind down, the second
+/// one stops waiting for it. Either way, nothing else is descended into,
+/// teardown happens as usual and [`command::Interrupted`] is returned. A second
+/// ctrl-c during `post_run` stops waiting for that hook too. The same goes for
+/// a `run` that takes longer than [`Command::timeout`], which returns
+/// [`command::TimedOut`]. Outside of an execution, ctrl-c ends the process like
+/// it would without cata, see [`runtime`].
+///
+/// Each hook runs inside of a [`tracing`] span named after it, `pre_run`, `run`
+/// or `post_run`, with [`Context::path`] as the `command` field. Subscribers
+/// see how long every hook took and how they nest without any
+/// `#[instrument]`. `run` spans also set `activity`, so [`telemetry`] reports
+/// them.
+///
+/// This is [`execute_with`] using the default [`ExecuteOptions`].
+///
+/// ```
+/// use std::sync::Mutex;
+///
+/// use cata::{command::PreRun, Command, Container};
+/// use clap::{Parser, Subcommand};
+///
+/// static CALLS: Mutex<Vec<&str>> = Mutex::new(Vec::new());
+///
+/// fn call(name: &'static str) -> eyre::Result<()> {
+///   CALLS.lock().unwrap().push(name);
+///   Ok(())
+/// }
+///
+/// #[derive(Parser, Container)]
+/// struct Root {
+///   #[command(subcommand)]
+///   cmd: RootCmd,
+/// }
+///
+/// #[derive(Subcommand, Container)]
+/// enum RootCmd {
+///   Child(Child),
+/// }
+///
+/// #[derive(Parser, Container)]
+/// struct Child {
+///   #[arg(long)]
+///   hang: bool,
+/// }
+///
+/// #[async_trait::async_trait]
+/// impl Command for Root {
+///   async fn pre_run(&self, _: &mut cata::Context) -> eyre::Result<PreRun> {
+///     call("root.pre_run").map(|()| PreRun::Continue)
+///   }
+///
+///   async fn post_run(
+///     &self,
+///     _: &mut cata::Context,
+///     result: &eyre::Result<()>,
+///   ) -> eyre::Result<()> {
+///     call(if result.is_ok() { "root.post_run" } else { "root.post_run(err)" })
+///   }
+/// }
+///
+/// #[async_trait::async_trait]
+/// impl Command for Child {
+///   async fn pre_run(&self, _: &mut cata::Context) -> eyre::Result<PreRun> {
+///     call("child.pre_run").map(|()| PreRun::Continue)
+///   }
+///
+///   async fn run(&self, _: &mut cata::Context) -> eyre::Result<()> {
+///     call("child.run")?;
+///
+///     if self.hang {
+///       std::future::pending::<()>().await;
+///     }
+///
+///     Err(eyre::eyre!("child failed"))
+///   }
+///
+///   async fn post_run(
+///     &self,
+///     _: &mut cata::Context,
+///     result: &eyre::Result<()>,
+///   ) -> eyre::Result<()> {
+///     call(if result.is_ok() { "child.post_run" } else { "child.post_run(err)" })
+///   }
+/// }
+///
+/// # #[tokio::main]
+/// # async fn main() -> eyre::Result<()> {
+/// let order = [
+///   "root.pre_run",
+///   "child.pre_run",
+///   "child.run",
+///   "child.post_run(err)",
+///   "root.post_run(err)",
+/// ];
+///
+/// let result = cata::execute(&Root::parse_from(["root", "child"])).await;
+///
+/// assert_eq!(result.unwrap_err().to_string(), "child failed");
+/// assert_eq!(*CALLS.lock().unwrap(), order);
+///
+/// CALLS.lock().unwrap().clear();
+///
+/// // Cancelling in the middle of `run` tears the tree down in the same order.
+/// let cmd = Root::parse_from(["root", "child", "--hang"]);
+/// let _ = tokio::time::timeout(
+///   std::time::Duration::from_millis(10),
+///   cata::execute(&cmd),
+/// )
+/// .await;
+///
+/// assert_eq!(*CALLS.lock().unwrap(), order);
+/// # Ok(())
+/// # }
+/// ```
+pub async fn execute(cmd: &dyn Command) -> Result<()> {
+    execute_with(cmd, ExecuteOptions::default()).await
+}
+
+/// Executes a command and all of its subcommands, configured by `options`.
+///
+/// See [`execute`] for the lifecycle and [`ExecuteOptions`] for what can be
+/// changed.
+pub async fn execute_with(cmd: &dyn Command, options: ExecuteOptions) -> Result<()> {
+    lifecycle::execute(lifecycle::Tree::Shared(cmd), options).await
+}
+
+/// Executes a command and all of its subcommands, with mutable access.
+///
+/// This is the same lifecycle as [`execute`], except that [`Command::run_mut`]
+/// is called instead of [`Command::run`] and subcommands are reached through
+/// [`Container::next_mut`]. State set up in `pre_run` can then be stored on the
+/// command and consumed by `run_mut` without interior mutability.
+///
+/// ```
+/// use cata::{Command, Container};
+/// use clap::Parser;
+///
+/// #[derive(Parser, Container)]
+/// struct Root {
+///   #[arg(skip)]
+///   runs: usize,
+/// }
+///
+/// #[async_trait::async_trait]
+/// impl Command for Root {
+///   async fn run_mut(&mut self, _: &mut cata::Context) -> eyre::Result<()> {
+///     self.runs += 1;
+///
+///     Ok(())
+///   }
+/// }
+///
+/// # #[tokio::main]
+/// # async fn main() -> eyre::Result<()> {
+/// let mut root = Root::parse_from(["root"]);
+/// cata::execute_mut(&mut root).await?;
+///
+/// assert_eq!(root.runs, 1);
+/// # Ok(())
+/// # }
+/// ```
+///
+/// [`Container::next_mut`]: crate::command::Container::next_mut
+pub async fn execute_mut(cmd: &mut dyn Command) -> Result<()> {
+    execute_mut_with(cmd, ExecuteOptions::default()).await
+}
+
+/// Executes a command and all of its subcommands with mutable access,
+/// configured by `options`.
+///
+/// See [`execute_mut`] and [`ExecuteOptions`].
+pub async fn execute_mut_with(cmd: &mut dyn Command, options: ExecuteOptions) -> Result<()> {
+    lifecycle::execute(lifecycle::Tree::Exclusive(cmd), options).await
+}
+
+/// Executes a command and all of its subcommands without an async runtime.
+///
+/// This is [`execute`] for a synchronous `main`, using a runtime of its own.
+/// Commands in the tree can implement either [`Command`] or
+/// [`blocking::SyncCommand`].
+///
+/// # Errors
+///
+/// When called from inside of a tokio runtime, which can't be blocked on.
+/// Use [`execute`] there instead.
+///
+/// ```
+/// use cata::{Command, Container};
+/// use clap::Parser;
+///
+/// #[derive(Parser, Container)]
+/// struct Root {}
+///
+/// impl Command for Root {}
+///
+/// # #[tokio::main]
+/// # async fn main() {
+/// let err = cata::execute_sync(&Root::parse_from(["root"])).unwrap_err();
+/// assert!(err.to_string().contains("use `execute`"));
+/// # }
+/// ```
+#[cfg(feature = "blocking")]
+pub fn execute_sync(cmd: &dyn Command) -> Result<()> {
+    execute_sync_with(cmd, ExecuteOptions::default())
+}
+
+/// Executes a command and all of its subcommands without an async runtime,
+/// configured by `options`.
+///
+/// See [`execute_sync`] and [`ExecuteOptions`].
+///
+/// # Errors
+///
+/// When called from inside of a tokio runtime, see [`execute_sync`].
+#[cfg(feature = "blocking")]
+pub fn execute_sync_with(cmd: &dyn Command, options: ExecuteOptions) -> Result<()> {
+    blocking::runtime()?.block_on(execute_with(cmd, options))
 }
@@ -0,0 +1,243 @@
+//! Ship attributions for third-party dependencies.
+//!
+//! Many organizations require CLIs to list the licenses of everything they
+//! bundle. The list is generated at build time from `cargo metadata` and
+//! embedded in the binary, a [`Licenses`] subcommand then prints it through
+//! [`crate::output`].
+//!
+//! Some things to note:
+//! - Only dependencies that end up in the binary are included. Dev
+//!   dependencies, build dependencies and crates for other platforms are
+//!   skipped.
+//! - Crates that don't declare a license are listed as `UNKNOWN` so that they
+//!   stand out.
+//! - `cata` has to be a build dependency as well as a normal dependency.
+//!
+//! # Examples
+//!
+//! In the `main` of `build.rs`:
+//!
+//! ```no_run
+//! cata::licenses::generate().expect("licenses can be generated");
+//! ```
+//!
+//! And then in the CLI:
+//!
+//! ```no_run
+//! use cata::{licenses::{self, Licenses}, Command, Container};
+//! use clap::{Parser, Subcommand};
+//!
+//! #[derive(Parser, Container)]
+//! struct Root {
+//!   #[command(subcommand)]
+//!   cmd: RootCmd,
+//! }
+//!
+//! impl Command for Root {}
+//!
+//! #[derive(Subcommand, Container)]
+//! enum RootCmd {
+//!   Licenses(Licenses),
+//! }
+//!
+//! # static LICENSES: &str = "[]";
+//! // static LICENSES: &str = include_str!(concat!(env!("OUT_DIR"), "/licenses.json"));
+//!
+//! #[tokio::main]
+//! async fn main() -> eyre::Result<()> {
+//!   licenses::install(LICENSES)?;
+//!
+//!   cata::execute(&Root::parse()).await
+//! }
+//! ```
+use std::{
+    collections::{HashMap, HashSet},
+    path::PathBuf,
+    sync::OnceLock,
+};
+
+use eyre::{eyre, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::{
+    command::Container,
+    describe::{ArgDescriptor, Describe, Descriptor},
+    output::Format,
+    Command, Context,
+};
+
+static FILE: &str = "licenses.json";
+static UNKNOWN: &str = "UNKNOWN";
+static GLOBAL: OnceLock<Vec<License>> = OnceLock::new();
+
+/// Attribution for a single dependency.
+#[derive(Clone, Debug, Serialize, Deserialize, tabled::Tabled)]
+pub struct License {
+    /// Name of the crate.
+    pub name: String,
+    /// Version of the crate.
+    pub version: String,
+    /// SPDX license expression.
+    pub license: String,
+    /// Where the source lives.
+    pub repository: crate::output::tabled::Option<String>,
+}
+
+/// Write the licenses of every dependency to `$OUT_DIR/licenses.json`.
+///
+/// This is meant to be called from `build.rs`.
+pub fn generate() -> Result<()> {
+    let out = std::env::var_os("OUT_DIR").ok_or_else(|| eyre!("OUT_DIR is not set"))?;
+
+    generate_to(PathBuf::from(out).join(FILE))
+}
+
+/// Write the licenses of every dependency to `path`.
+pub fn generate_to(path: impl Into<PathBuf>) -> Result<()> {
+    let cargo = std::env::var_os("CARGO").unwrap_or_else(|| "cargo".into());
+    let manifest = PathBuf::from(
+        std::env::var_os("CARGO_MANIFEST_DIR")
+            .ok_or_else(|| eyre!("CARGO_MANIFEST_DIR is not set"))?,
+    )
+    .join("Cargo.toml");
+
+    let mut cmd = std::process::Command::new(cargo);
+    cmd.args(["metadata", "--format-version", "1", "--manifest-path"])
+        .arg(&manifest);
+
+    if let Some(target) = std::env::var_os("TARGET") {
+        cmd.arg("--filter-platform").arg(target);
+    }
+
+    let output = cmd.output()?;
+    if !output.status.success() {
+        return Err(eyre!(
+            "cargo metadata failed: {}",
+            String::from_utf8_lossy(&output.stderr)
+        ));
+    }
+
+    let metadata: Value = serde_json::from_slice(&output.stdout)?;
+    let licenses = collect(&metadata, std::env::var("CARGO_PKG_NAME").ok().as_deref())?;
+
+    std::fs::write(path.into(), serde_json::to_string(&licenses)?)?;
+
+    println!("cargo:rerun-if-changed={}", manifest.display());
+    println!(
+        "cargo:rerun-if-changed={}",
+        manifest.with_file_name("Cargo.lock").display()
+    );
+
+    Ok(())
+}
+
+/// Find every package reachable from the root through normal dependencies.
+fn collect(metadata: &Value, root: Option<&str>) -> Result<Vec<License>> {
+    let packages = metadata["packages"]
+        .as_array()
+        .ok_or_else(|| eyre!("metadata is missing packages"))?
+        .iter()
+        .filter_map(|pkg| Some((pkg["id"].as_str()?, pkg)))
+        .collect::<HashMap<_, _>>();
+
+    let nodes = metadata["resolve"]["nodes"]
+        .as_array()
+        .ok_or_else(|| eyre!("metadata is missing the dependency graph"))?
+        .iter()
+        .filter_map(|node| Some((node["id"].as_str()?, node)))
+        .collect::<HashMap<_, _>>();
+
+    // `resolve.root` is the workspace root, which isn't necessarily the package
+    // being built.
+    let root = metadata["workspace_members"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(Value::as_str)
+        .find(|id| {
+            packages
+                .get(id)
+                .is_some_and(|pkg| root.is_some_and(|root| pkg["name"] == root))
+        })
+        .or_else(|| metadata["resolve"]["root"].as_str())
+        .ok_or_else(|| eyre!("unable to find the root package"))?;
+
+    let mut seen = HashSet::from([root]);
+    let mut queue = vec![root];
+
+    while let Some(id) = queue.pop() {
+        let deps = nodes
+            .get(id)
+            .and_then(|node| node["deps"].as_array())
+            .into_iter()
+            .flatten();
+
+        for dep in deps {
+            let normal = dep["dep_kinds"]
+                .as_array()
+                .is_some_and(|kinds| kinds.iter().any(|kind| kind["kind"].is_null()));
+
+            if let (true, Some(pkg)) = (normal, dep["pkg"].as_str()) {
+                if seen.insert(pkg) {
+                    queue.push(pkg);
+                }
+            }
+        }
+    }
+
+    let mut licenses = seen
+        .into_iter()
+        .filter(|id| *id != root)
+        .filter_map(|id| packages.get(id))
+        .map(|pkg| License {
+            name: pkg["name"].as_str().unwrap_or_default().into(),
+            version: pkg["version"].as_str().unwrap_or_default().into(),
+            license: pkg["license"].as_str().unwrap_or(UNKNOWN).into(),
+            repository: pkg["repository"].as_str().map(String::from).into(),
+        })
+        .collect::<Vec<_>>();
+    licenses.sort_by(|a, b| (&a.name, &a.version).cmp(&(&b.name, &b.version)));
+
+    Ok(licenses)
+}
+
+/// Make the generated licenses available to the [`Licenses`] subcommand.
+///
+/// `raw` is the contents of the file written by [`generate`], usually
+/// embedded with `include_str!`.
+pub fn install(raw: &str) -> Result<()> {
+    GLOBAL
+        .set(serde_json::from_str(raw)?)
+        .map_err(|_| eyre!("licenses have already been installed"))
+}
+
+/// Show the licenses of third-party dependencies.
+#[derive(clap::Args, Clone, Debug)]
+pub struct Licenses {
+    /// Format of the list.
+    #[arg(short, long, value_enum, default_value_t)]
+    output: Format,
+}
+
+impl Container for Licenses {}
+
+impl Describe for Licenses {
+    const DESCRIPTOR: Descriptor = Descriptor::new("licenses")
+        .with_about("Show the licenses of third-party dependencies")
+        .with_args(&[ArgDescriptor::new("output")
+            .with_long("output")
+            .with_short('o')
+            .with_help("Format of the list")]);
+}
+
+#[async_trait::async_trait]
+impl Command for Licenses {
+    async fn run(&self, _: &mut Context) -> Result<()> {
+        let licenses = GLOBAL
+            .get()
+            .ok_or_else(|| eyre!("licenses must be installed to list them"))?;
+
+        self.output.list(licenses)
+    }
+}
@@ -0,0 +1,1136 @@
+//! The machinery behind [`crate::execute`] and [`crate::execute_mut`].
+//!
+//! Parents and children can't be borrowed mutably at the same time, so instead
+//! of keeping a stack of commands, the tree is re-walked from the root to
+//! reach the command at a given depth. Trees are shallow, this is cheaper than
+//! it sounds.
+use std::{
+    future::Future,
+    panic::AssertUnwindSafe,
+    pin::pin,
+    sync::Arc,
+    task::{self, Poll, Wake, Waker},
+    time::Duration,
+};
+
+use eyre::{eyre, Report, Result};
+use tokio::sync::mpsc::UnboundedReceiver;
+use tracing::Instrument;
+
+#[cfg(feature = "background")]
+use crate::background::{self, TaskGroup};
+#[cfg(feature = "events")]
+use crate::events::Event;
+#[cfg(feature = "first-run")]
+use crate::first_run::FirstRun;
+#[cfg(feature = "layer")]
+use crate::layer::{Layer, Next};
+#[cfg(feature = "lock")]
+use crate::lock::Guard;
+use crate::{
+    command::{
+        Cycle, Deprecation, ExecuteOptions, Interrupted, Overrides, Panicked, PostRunOrder, PreRun,
+        RetryPolicy, RunPolicy, Signal, TimedOut, TooDeep,
+    },
+    context::CancellationToken,
+    examples,
+    flags::{Disabled, Flags},
+    runtime::{self, race, BoxFuture, Either, Listening, Runtime},
+    Command, Context,
+};
+
+/// The root of a parsed command tree.
+pub(crate) enum Tree<'a> {
+    Shared(&'a dyn Command),
+    Exclusive(&'a mut dyn Command),
+}
+
+/// The lifecycle hook a [`crate::layer::Layer`] is wrapping.
+#[derive(Clone, Copy, Debug)]
+pub enum Hook<'a> {
+    /// [`Command::pre_run`].
+    PreRun,
+    /// [`Command::run`], [`Command::run_mut`] for [`crate::execute_mut`] or
+    /// [`Command::dry_run`] for dry runs.
+    Run,
+    /// [`Command::post_run`] with the outcome so far.
+    PostRun(&'a Result<()>),
+}
+
+impl Hook<'_> {
+    /// The name of the hook, as it appears on [`Command`].
+    #[must_use]
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::PreRun => "pre_run",
+            Self::Run => "run",
+            Self::PostRun(_) => "post_run",
+        }
+    }
+}
+
+/// Calls hooks through the layers from [`ExecuteOptions::with_layer`].
+#[derive(Clone, Default)]
+pub(crate) struct Layers {
+    #[cfg(feature = "layer")]
+    pub(crate) layers: Vec<Arc<dyn Layer>>,
+}
+
+impl Layers {
+    /// Call `hook` on `node`, inside of every layer.
+    async fn call<'a>(&'a self, hook: Hook<'a>, node: Node<'a>, ctx: &mut Context) -> Result<()> {
+        #[cfg(feature = "layer")]
+        return Next::new(&self.layers, hook, node).run(ctx).await;
+
+        #[cfg(not(feature = "layer"))]
+        node.call(hook, ctx).await
+    }
+}
+
+/// A single command in the tree.
+pub(crate) enum Node<'a> {
+    Shared(&'a dyn Command),
+    Exclusive(&'a mut dyn Command),
+}
+
+impl Tree<'_> {
+    /// The root command, shared.
+    fn root(&self) -> &dyn Command {
+        match self {
+            Self::Shared(root) => *root,
+            Self::Exclusive(root) => &**root,
+        }
+    }
+
+    /// Every command from the root down, shared.
+    fn commands(&self) -> impl Iterator<Item = &dyn Command> {
+        std::iter::successors(Some(self.root()), |cmd| cmd.next())
+    }
+
+    /// The command `depth` levels below the root, if the tree is that deep.
+    fn at(&mut self, depth: usize) -> Option<Node<'_>> {
+        match self {
+            Self::Shared(root) => {
+                let mut current = &**root;
+                for _ in 0..depth {
+                    current = current.next()?;
+                }
+
+                Some(Node::Shared(current))
+            }
+            Self::Exclusive(root) => {
+                let mut current: &mut dyn Command = &mut **root;
+                for _ in 0..depth {
+                    current = current.next_mut()?;
+                }
+
+                Some(Node::Exclusive(current))
+            }
+        }
+    }
+}
+
+impl Node<'_> {
+    pub(crate) fn command(&self) -> &dyn Command {
+        match self {
+            Self::Shared(cmd) => *cmd,
+            Self::Exclusive(cmd) => &**cmd,
+        }
+    }
+
+    /// Call `hook` inside of a span for it, turning a panic into a
+    /// [`Panicked`] error.
+    ///
+    /// Spans are named after the hook and have the command's path as
+    /// `command`. Commands that opted into telemetry also have an `activity`
+    /// on `run`, their own or the path, which makes it show up in
+    /// [`crate::telemetry`]. Every other command doesn't have one.
+    pub(crate) async fn call(self, hook: Hook<'_>, ctx: &mut Context) -> Result<()> {
+        let name = hook.name();
+        let span = match hook {
+            Hook::PreRun => tracing::info_span!("pre_run", command = %ctx.path()),
+            Hook::Run if !self.command().telemetry() => {
+                tracing::info_span!("run", command = %ctx.path())
+            }
+            Hook::Run => {
+                let path = ctx.path();
+                let activity = self.command().activity().unwrap_or(&path);
+
+                tracing::info_span!("run", command = %path, activity = %activity)
+            }
+            Hook::PostRun(_) => tracing::info_span!("post_run", command = %ctx.path()),
+        };
+
+        let result = {
+            let mut call = pin!(self.dispatch(hook, ctx));
+
+            std::future::poll_fn(|cx| {
+                std::panic::catch_unwind(AssertUnwindSafe(|| call.as_mut().poll(cx)))
+                    .unwrap_or_else(|payload| {
+                        let err = Panicked::new(&*payload);
+                        tracing::error!(error = %err, "{name} panicked");
+
+                        Poll::Ready(Err(err.into()))
+                    })
+            })
+            .instrument(span)
+            .await
+        };
+
+        #[cfg(feature = "events")]
+        if let Err(error) = &result {
+            ctx.emit(|path| Event::HookFailed {
+                path,
+                hook: name,
+                error,
+            });
+        }
+
+        result
+    }
+
+    async fn dispatch(self, hook: Hook<'_>, ctx: &mut Context) -> Result<()> {
+        match (hook, self) {
+            (Hook::PreRun, node) => {
+                ctx.skip = node.command().pre_run(ctx).await? == PreRun::Skip;
+
+                Ok(())
+            }
+            (Hook::Run, node) if ctx.dry_run => node.command().dry_run(ctx).await,
+            (Hook::Run, Self::Shared(cmd)) => cmd.run(ctx).await,
+            (Hook::Run, Self::Exclusive(cmd)) => cmd.run_mut(ctx).await,
+            (Hook::PostRun(result), node) => node.command().post_run(ctx, result).await,
+        }
+    }
+}
+
+/// Run the lifecycle of every command in `tree`.
+pub(crate) async fn execute(tree: Tree<'_>, options: ExecuteOptions) -> Result<()> {
+    let runtime = options.runtime.clone();
+
+    let execution = async {
+        let mut ctx = Context::default();
+        ctx.dry_run = options.dry_run;
+        ctx.root = tree.root().command_name();
+        ctx.matches.clone_from(&options.matches);
+        #[cfg(feature = "events")]
+        ctx.subscribers.clone_from(&options.subscribers);
+
+        for provide in &options.services {
+            provide(&mut ctx);
+        }
+
+        #[cfg(feature = "first-run")]
+        let first_run = options.first_run.clone().filter(FirstRun::is_first);
+        #[cfg(feature = "first-run")]
+        {
+            ctx.first_run = first_run.is_some();
+        }
+
+        let mut teardown = Teardown {
+            tree,
+            depth: 0,
+            top: 0,
+            ctx,
+            layers: options.layers.clone(),
+            #[cfg(feature = "first-run")]
+            first_run,
+            #[cfg(feature = "lock")]
+            locks: Vec::new(),
+            marks: vec![0],
+        };
+
+        let mut listener = Listener::new(
+            runtime::current(),
+            teardown.ctx.token().clone(),
+            options.signals,
+        );
+        let guard = teardown.guard(options.max_depth);
+
+        #[cfg(feature = "verbosity")]
+        if guard.is_ok() {
+            teardown.trace(&options);
+        }
+
+        #[cfg(feature = "update")]
+        let update = options.update.clone().map(crate::update::Check::spawn);
+
+        let result = match guard {
+            Ok(()) => teardown
+                .descend(&options, &mut listener)
+                .await
+                .map_err(|e| teardown.examples(e)),
+            Err(e) => Err(e),
+        };
+
+        #[cfg(feature = "first-run")]
+        if let Some(first_run) = teardown.first_run.take() {
+            if let Err(e) = first_run.mark() {
+                tracing::debug!("unable to remember the first run: {e}");
+            }
+        }
+
+        let result = teardown
+            .post_run(result, options.post_run_order, &mut listener)
+            .await;
+
+        #[cfg(feature = "update")]
+        if let Some(update) = update {
+            listener.guard(update.notify()).await.ok();
+        }
+
+        // Interrupting stops waiting for the grace period, dropping the
+        // shutdown cancels what is left.
+        #[cfg(feature = "background")]
+        listener
+            .guard(background::shutdown(options.grace))
+            .await
+            .ok();
+
+        result
+    };
+
+    #[cfg(feature = "background")]
+    let execution = background::scope(execution);
+
+    // Boxed, the whole execution would otherwise live in the caller's future.
+    runtime::scope(runtime, Box::pin(execution)).await
+}
+
+/// The commands that need `post_run` called on them.
+///
+/// Dropping this runs whatever teardown hasn't happened yet, which is what
+/// makes cancellation deterministic, and flushes output.
+struct Teardown<'a> {
+    tree: Tree<'a>,
+    /// How many commands, starting at the root, had `pre_run` succeed.
+    depth: usize,
+    /// How many commands, starting at the root, have been torn down parent
+    /// first.
+    top: usize,
+    ctx: Context,
+    layers: Layers,
+    /// Set on the first run, until an `on_first_run` fails.
+    #[cfg(feature = "first-run")]
+    first_run: Option<FirstRun>,
+    /// The locks that are held and the depth of the command that holds them.
+    #[cfg(feature = "lock")]
+    locks: Vec<(usize, Guard)>,
+    /// How many of the context's segments lead to each command that was
+    /// entered, by depth.
+    marks: Vec<usize>,
+}
+
+impl Teardown<'_> {
+    async fn descend(&mut self, options: &ExecuteOptions, listener: &mut Listener) -> Result<()> {
+        let token = self.ctx.token().clone();
+        let runtime = runtime::current();
+
+        // Shared trees can be notified while `run` is in flight, exclusive ones
+        // have to wait until it returns.
+        let shared = match self.tree {
+            Tree::Shared(root) => Some(root),
+            Tree::Exclusive(_) => None,
+        };
+
+        self.gates(&options.flags)?;
+        #[cfg(feature = "requirements")]
+        listener
+            .guard(self.requirements(options.recursive))
+            .await??;
+
+        loop {
+            self.focus(self.depth);
+
+            let Some(node) = self.tree.at(self.depth) else {
+                break;
+            };
+
+            let timeout = node.command().timeout().or(options.timeout);
+            let confirmation = node.command().confirm();
+            let retry = node.command().retry();
+            let overrides = node.command().overrides();
+            let segment = node.command().path_segment();
+            let leaf = !options.recursive || node.command().next().is_none();
+            let run = leaf
+                || node.command().run_policy().unwrap_or(options.run_policy) == RunPolicy::Every;
+
+            if let Some(deprecation) = node.command().deprecated() {
+                self.deprecated(&deprecation);
+            }
+
+            listener.guard(self.enter()).await??;
+            self.depth += 1;
+            self.ctx.segments.extend(segment);
+            self.marks.push(self.ctx.segments.len());
+            self.deliver(listener).await?;
+
+            if token.is_cancelled() {
+                return Err(Interrupted.into());
+            }
+
+            if std::mem::take(&mut self.ctx.skip) {
+                break;
+            }
+
+            if !run {
+                continue;
+            }
+
+            if let Some(confirmation) = confirmation.filter(|_| !options.yes && !self.ctx.dry_run) {
+                let answer = listener
+                    .guard(runtime::unblock(move || confirmation.ask()))
+                    .await??;
+
+                if !answer? {
+                    break;
+                }
+            }
+
+            #[cfg(feature = "background")]
+            {
+                self.ctx.tasks = TaskGroup::new(token.child_token());
+            }
+            self.ctx.overrides = overrides.map(Overrides::resolve).transpose()?;
+            let outcome = {
+                let mut run = pin!(attempt(
+                    &mut self.tree,
+                    self.depth - 1,
+                    &self.layers,
+                    &mut self.ctx,
+                    retry,
+                ));
+                let mut expired = pin!(expire(&*runtime, timeout));
+
+                loop {
+                    let heard = match race(&mut run, race(&mut expired, listener.heard())).await {
+                        Either::Left(result) => break result,
+                        Either::Right(Either::Left(timeout)) => break Err(TimedOut(timeout).into()),
+                        Either::Right(Either::Right(heard)) => heard,
+                    };
+
+                    let signal = match heard {
+                        Heard::Interrupt => break Err(Interrupted.into()),
+                        Heard::Signal(signal) => signal,
+                    };
+
+                    if let Some(root) = shared {
+                        if let Err(e) = notify(&mut Tree::Shared(root), self.depth, signal).await {
+                            break Err(e);
+                        }
+                    } else {
+                        listener.pending.push(signal);
+                    }
+                }
+            };
+
+            self.ctx.overrides = None;
+            #[cfg(feature = "background")]
+            std::mem::take(&mut self.ctx.tasks)
+                .drain(options.drain)
+                .await;
+            outcome?;
+            self.deliver(listener).await?;
+
+            if token.is_cancelled() {
+                return Err(Interrupted.into());
+            }
+
+            if !options.recursive {
+                break;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Notify the commands that have started of the signals that arrived while
+    /// they couldn't be.
+    async fn deliver(&mut self, listener: &mut Listener) -> Result<()> {
+        for signal in std::mem::take(&mut listener.pending) {
+            notify(&mut self.tree, self.depth, signal).await?;
+        }
+
+        Ok(())
+    }
+
+    /// Everything up to and including `pre_run` for the command the context
+    /// points at.
+    async fn enter(&mut self) -> Result<()> {
+        let Some(node) = self.tree.at(self.depth) else {
+            return Ok(());
+        };
+
+        #[cfg(feature = "events")]
+        self.ctx.emit(|path| Event::CommandStarted { path });
+        node.command().provide_globals(&mut self.ctx);
+
+        #[cfg(feature = "lock")]
+        if let Some(lock) = node.command().lock() {
+            self.locks.push((self.depth, lock.acquire()?));
+        }
+
+        #[cfg(feature = "first-run")]
+        if self.first_run.is_some() {
+            if let Err(e) = node.command().on_first_run(&mut self.ctx).await {
+                self.first_run = None;
+
+                return Err(e);
+            }
+        }
+
+        self.layers.call(Hook::PreRun, node, &mut self.ctx).await
+    }
+
+    /// Refuse trees that are deeper than `max_depth` or reach the same command
+    /// twice, before anything else walks them.
+    fn guard(&self, max_depth: usize) -> Result<()> {
+        // `next_mut` returns the same commands as `next`, walking the shared
+        // tree is enough for both.
+        let mut seen: Vec<&dyn Command> = Vec::new();
+        let mut current = Some(self.tree.root());
+
+        loop {
+            let Some(cmd) = current else {
+                return Ok(());
+            };
+
+            if seen.len() == max_depth {
+                return Err(TooDeep(max_depth).into());
+            }
+
+            // The vtable is compared along with the address, a command shares
+            // its address with its first field.
+            if seen.iter().any(|other| std::ptr::eq(*other, cmd)) {
+                let segments: Vec<_> = seen[..seen.len() - 1]
+                    .iter()
+                    .filter_map(|cmd| cmd.path_segment())
+                    .collect();
+
+                return Err(Cycle {
+                    command: typed(&segments),
+                }
+                .into());
+            }
+
+            seen.push(cmd);
+            current = cmd.next();
+        }
+    }
+
+    /// Refuse commands that were parsed but whose feature flag is off.
+    fn gates(&self, flags: &Flags) -> Result<()> {
+        let mut segments = Vec::new();
+
+        for cmd in self.tree.commands() {
+            segments.extend(cmd.path_segment());
+
+            if let Some(flag) = cmd.flag().filter(|flag| !flags.is_enabled(flag)) {
+                return Err(Disabled::new(typed(&segments), flag, flags).into());
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Check the requirements of every command that will be reached.
+    #[cfg(feature = "requirements")]
+    async fn requirements(&self, recursive: bool) -> Result<()> {
+        let reached = if recursive { usize::MAX } else { 1 };
+        let requirements = self
+            .tree
+            .commands()
+            .take(reached)
+            .flat_map(Command::requirements)
+            .collect();
+
+        crate::requirements::check(requirements).await
+    }
+
+    /// Warn that the command the context points at is deprecated.
+    fn deprecated(&self, deprecation: &Deprecation) {
+        let name = self.ctx.typed();
+        let warning = format!("warning: {}", deprecation.message(&name));
+
+        #[cfg(feature = "output")]
+        crate::output::warn(warning);
+        #[cfg(not(feature = "output"))]
+        eprintln!("{warning}");
+    }
+
+    /// Point the context at the command `depth` levels below the root, before
+    /// calling its hooks. The command has to have been entered already.
+    fn focus(&mut self, depth: usize) {
+        self.ctx.depth = depth;
+        self.ctx.reached = self.marks[depth];
+    }
+
+    /// Add the examples of the command the context points at to `err`, unless
+    /// it was interrupted.
+    fn examples(&mut self, err: Report) -> Report {
+        if err.is::<Interrupted>() {
+            return err;
+        }
+
+        match self.tree.at(self.ctx.depth) {
+            Some(node) => examples::attach(err, node.command().examples()),
+            None => err,
+        }
+    }
+
+    /// Install a subscriber at the level of the first command that has one.
+    #[cfg(feature = "verbosity")]
+    fn trace(&self, options: &ExecuteOptions) {
+        for cmd in self.tree.commands() {
+            if let Some(level) = cmd.verbosity() {
+                let layers = std::mem::take(
+                    &mut *options
+                        .tracing
+                        .lock()
+                        .unwrap_or_else(std::sync::PoisonError::into_inner),
+                );
+
+                crate::verbosity::init(level, layers);
+
+                return;
+            }
+        }
+    }
+
+    /// Call `on_error` and `post_run` in `order`, returning the first error.
+    ///
+    /// Each command sees the outcome so far, which includes failures from the
+    /// `post_run` of the commands torn down before it. Interrupting stops
+    /// waiting for the hook in flight, the rest happens as part of the drop.
+    async fn post_run(
+        &mut self,
+        mut result: Result<()>,
+        order: PostRunOrder,
+        listener: &mut Listener,
+    ) -> Result<()> {
+        while self.depth > self.top {
+            let depth = match order {
+                PostRunOrder::ChildFirst => {
+                    self.depth -= 1;
+                    self.depth
+                }
+                PostRunOrder::ParentFirst => {
+                    self.top += 1;
+                    self.top - 1
+                }
+            };
+            self.focus(depth);
+
+            if let Err(e) = result {
+                result = Err(match self.tree.at(depth) {
+                    Some(node) => node.command().on_error(&mut self.ctx, e).await,
+                    None => e,
+                });
+            }
+
+            let Some(node) = self.tree.at(depth) else {
+                continue;
+            };
+
+            let hook = self
+                .layers
+                .call(Hook::PostRun(&result), node, &mut self.ctx);
+            if let Err(e) = listener.guard(hook).await? {
+                if result.is_ok() {
+                    result = Err(e);
+                } else {
+                    tracing::debug!("post_run failed during teardown: {e}");
+                }
+            }
+
+            #[cfg(feature = "lock")]
+            self.locks.retain(|(held, _)| *held != depth);
+
+            #[cfg(feature = "events")]
+            self.ctx.emit(|path| Event::CommandFinished {
+                path,
+                result: &result,
+            });
+        }
+
+        result
+    }
+}
+
+/// The name of the binary as it was typed, without an extension.
+pub(crate) fn binary() -> String {
+    std::env::args_os()
+        .next()
+        .and_then(|arg| {
+            std::path::Path::new(&arg)
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().into_owned())
+        })
+        .unwrap_or_default()
+}
+
+/// The command that `segments` lead to, as it was typed.
+pub(crate) fn typed(segments: &[&str]) -> String {
+    let binary = binary();
+
+    std::iter::once(binary.as_str())
+        .chain(segments.iter().copied())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Call `run` on the command at `depth`, retrying as long as `retry` allows.
+async fn attempt(
+    tree: &mut Tree<'_>,
+    depth: usize,
+    layers: &Layers,
+    ctx: &mut Context,
+    retry: Option<RetryPolicy>,
+) -> Result<()> {
+    let mut attempt = 1;
+
+    loop {
+        let Some(node) = tree.at(depth) else {
+            return Ok(());
+        };
+
+        let err = match layers.call(Hook::Run, node, ctx).await {
+            Ok(()) => return Ok(()),
+            Err(err) => err,
+        };
+
+        let delay = retry
+            .as_ref()
+            .filter(|_| !ctx.token().is_cancelled())
+            .and_then(|retry| retry.delay(attempt, &err));
+
+        let Some(delay) = delay else {
+            return Err(err);
+        };
+
+        tracing::debug!("run failed on attempt {attempt}, retrying in {delay:?}: {err}");
+
+        runtime::current().sleep(delay).await;
+        attempt += 1;
+    }
+}
+
+/// Call `on_signal` on the first `depth` commands of `tree`, child first.
+async fn notify(tree: &mut Tree<'_>, depth: usize, signal: Signal) -> Result<()> {
+    for depth in (0..depth).rev() {
+        if let Some(node) = tree.at(depth) {
+            node.command().on_signal(signal).await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// The next signal from `signals`, or never without any.
+async fn recv(signals: Option<&mut UnboundedReceiver<Signal>>) -> Signal {
+    if let Some(signals) = signals {
+        if let Some(signal) = signals.recv().await {
+            return signal;
+        }
+    }
+
+    std::future::pending().await
+}
+
+/// Complete with `timeout` once it has elapsed, or never without one.
+async fn expire(runtime: &dyn Runtime, timeout: Option<Duration>) -> Duration {
+    let Some(timeout) = timeout else {
+        return std::future::pending().await;
+    };
+
+    runtime.sleep(timeout).await;
+
+    timeout
+}
+
+/// What a [`Listener`] heard.
+enum Heard {
+    /// The second ctrl-c, or any after it.
+    Interrupt,
+    /// A signal for [`Command::on_signal`].
+    Signal(Signal),
+}
+
+/// Ctrl-c and the signals for [`Command::on_signal`], listened for from the
+/// start of an execution until it returns.
+struct Listener {
+    runtime: Arc<dyn Runtime>,
+    token: CancellationToken,
+    enabled: bool,
+    interrupt: BoxFuture<()>,
+    signals: Option<UnboundedReceiver<Signal>>,
+    /// Signals that no command has been notified of yet.
+    pending: Vec<Signal>,
+    _listening: Option<Listening>,
+}
+
+impl Listener {
+    fn new(runtime: Arc<dyn Runtime>, token: CancellationToken, enabled: bool) -> Self {
+        Self {
+            interrupt: Box::pin(interrupt(runtime.clone(), token.clone(), enabled)),
+            signals: enabled.then(|| runtime.signals()).flatten(),
+            pending: Vec::new(),
+            _listening: enabled.then(Listening::start),
+            runtime,
+            token,
+            enabled,
+        }
+    }
+
+    /// Complete on the second ctrl-c, the first one cancels the token, or on
+    /// the next signal. Once ctrl-c has completed this, every one does.
+    ///
+    /// [`Signal::Terminate`] cancels the token too.
+    async fn heard(&mut self) -> Heard {
+        let signal = match race(&mut self.interrupt, recv(self.signals.as_mut())).await {
+            Either::Left(()) => None,
+            Either::Right(signal) => Some(signal),
+        };
+
+        let Some(signal) = signal else {
+            self.interrupt = Box::pin(interrupt(
+                self.runtime.clone(),
+                self.token.clone(),
+                self.enabled,
+            ));
+
+            return Heard::Interrupt;
+        };
+
+        if signal == Signal::Terminate {
+            self.token.cancel();
+        }
+
+        Heard::Signal(signal)
+    }
+
+    /// Complete with `fut`'s output, or fail with [`Interrupted`] once it was
+    /// interrupted. Signals that arrive in the meantime are kept in `pending`.
+    async fn guard<F: Future>(&mut self, fut: F) -> Result<F::Output> {
+        let mut fut = pin!(fut);
+
+        loop {
+            let heard = match race(&mut fut, self.heard()).await {
+                Either::Left(output) => return Ok(output),
+                Either::Right(heard) => heard,
+            };
+
+            match heard {
+                Heard::Interrupt => return Err(Interrupted.into()),
+                Heard::Signal(signal) => self.pending.push(signal),
+            }
+        }
+    }
+}
+
+/// Cancel `token` on the first ctrl-c and complete on the second.
+///
+/// The first ctrl-c lets the running command wind down on its own, the second
+/// one stops waiting for it. Without `enabled`, this never completes.
+async fn interrupt(runtime: Arc<dyn Runtime>, token: CancellationToken, enabled: bool) {
+    if enabled {
+        loop {
+            runtime.ctrl_c().await;
+
+            if token.is_cancelled() {
+                return;
+            }
+
+            token.cancel();
+        }
+    }
+
+    std::future::pending::<()>().await;
+}
+
+/// A waker for futures that are only polled once.
+struct Noop;
+
+impl Wake for Noop {
+    fn wake(self: Arc<Self>) {}
+}
+
+impl Drop for Teardown<'_> {
+    fn drop(&mut self) {
+        use std::io::Write as _;
+
+        // There's no way to wait from inside of a drop, each remaining
+        // `post_run` is polled once instead. Hooks that don't need to wait on
+        // anything complete, the rest are abandoned at their first `await`.
+        let waker = Waker::from(Arc::new(Noop));
+        let mut cx = task::Context::from_waker(&waker);
+        let cancelled = Err(eyre!("execution was cancelled"));
+
+        while self.depth > self.top {
+            self.depth -= 1;
+            self.focus(self.depth);
+
+            let Some(node) = self.tree.at(self.depth) else {
+                continue;
+            };
+
+            let hook = self
+                .layers
+                .call(Hook::PostRun(&cancelled), node, &mut self.ctx);
+            if let Poll::Ready(Err(e)) = pin!(hook).poll(&mut cx) {
+                tracing::debug!("post_run failed during cancellation: {e}");
+            }
+
+            #[cfg(feature = "events")]
+            self.ctx.emit(|path| Event::CommandFinished {
+                path,
+                result: &cancelled,
+            });
+        }
+
+        std::io::stdout().flush().ok();
+        std::io::stderr().flush().ok();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        sync::{Arc, Mutex},
+        time::Duration,
+    };
+
+    use eyre::{eyre, Result};
+    use tokio::sync::Semaphore;
+
+    use crate::{
+        command::{Command, Container, Interrupted, Panicked, PostRunOrder},
+        runtime::{BoxFuture, Runtime},
+        Context, ExecuteOptions,
+    };
+
+    /// What `run` does for the last command of a chain.
+    enum Outcome {
+        Ok,
+        Fail,
+        Panic,
+        /// Press ctrl-c twice and wait for it.
+        Interrupt(Arc<Semaphore>),
+        Hang,
+    }
+
+    /// A chain of commands that logs every `post_run` and what it saw.
+    struct Step {
+        name: &'static str,
+        log: Arc<Mutex<Vec<String>>>,
+        outcome: Outcome,
+        next: Option<Box<Step>>,
+    }
+
+    impl Step {
+        /// `names` from the root to the leaf, with `outcome` on the leaf.
+        fn chain(names: &[&'static str], outcome: Outcome) -> (Self, Arc<Mutex<Vec<String>>>) {
+            let log = Arc::default();
+            let mut outcome = Some(outcome);
+            let mut next = None;
+
+            for name in names.iter().rev() {
+                next = Some(Box::new(Self {
+                    name,
+                    log: Arc::clone(&log),
+                    outcome: outcome.take().unwrap_or(Outcome::Ok),
+                    next,
+                }));
+            }
+
+            (*next.expect("at least one name"), log)
+        }
+    }
+
+    impl Container for Step {
+        fn next(&self) -> Option<&dyn Command> {
+            self.next.as_deref().map(|next| next as &dyn Command)
+        }
+
+        fn next_mut(&mut self) -> Option<&mut dyn Command> {
+            self.next
+                .as_deref_mut()
+                .map(|next| next as &mut dyn Command)
+        }
+
+        fn path_segment(&self) -> Option<&'static str> {
+            Some(self.name)
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl Command for Step {
+        async fn run(&self, _: &mut Context) -> Result<()> {
+            if self.next.is_some() {
+                return Ok(());
+            }
+
+            match &self.outcome {
+                Outcome::Ok => Ok(()),
+                Outcome::Fail => Err(eyre!("{} failed", self.name)),
+                Outcome::Panic => panic!("{} panicked", self.name),
+                Outcome::Interrupt(presses) => {
+                    presses.add_permits(2);
+
+                    std::future::pending().await
+                }
+                Outcome::Hang => std::future::pending().await,
+            }
+        }
+
+        async fn post_run(&self, _: &mut Context, result: &Result<()>) -> Result<()> {
+            let outcome = match result {
+                Ok(()) => "ok".to_string(),
+                Err(e) => e.to_string(),
+            };
+            self.log
+                .lock()
+                .unwrap()
+                .push(format!("{}: {outcome}", self.name));
+
+            Ok(())
+        }
+    }
+
+    /// Tokio, with a ctrl-c for every permit of `presses`.
+    struct Presses(Arc<Semaphore>);
+
+    impl Runtime for Presses {
+        fn spawn(&self, fut: BoxFuture<()>) {
+            tokio::spawn(fut);
+        }
+
+        fn sleep(&self, duration: Duration) -> BoxFuture<()> {
+            Box::pin(tokio::time::sleep(duration))
+        }
+
+        fn ctrl_c(&self) -> BoxFuture<()> {
+            let presses = Arc::clone(&self.0);
+
+            Box::pin(async move {
+                presses.acquire_owned().await.unwrap().forget();
+            })
+        }
+    }
+
+    fn log(log: &Mutex<Vec<String>>) -> Vec<String> {
+        log.lock().unwrap().clone()
+    }
+
+    #[tokio::test]
+    async fn post_run_child_first_on_error() {
+        let (root, calls) = Step::chain(&["root", "group", "leaf"], Outcome::Fail);
+
+        let err = crate::execute(&root).await.unwrap_err();
+
+        assert_eq!(err.to_string(), "leaf failed");
+        assert_eq!(
+            log(&calls),
+            [
+                "leaf: leaf failed",
+                "group: leaf failed",
+                "root: leaf failed"
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn post_run_parent_first_on_error() {
+        let (root, calls) = Step::chain(&["root", "group", "leaf"], Outcome::Fail);
+
+        let options = ExecuteOptions::default().with_post_run_order(PostRunOrder::ParentFirst);
+        let err = crate::execute_with(&root, options).await.unwrap_err();
+
+        assert_eq!(err.to_string(), "leaf failed");
+
+        assert_eq!(
+            log(&calls),
+            [
+                "root: leaf failed",
+                "group: leaf failed",
+                "leaf: leaf failed"
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn post_run_after_success() {
+        let (root, calls) = Step::chain(&["root", "leaf"], Outcome::Ok);
+
+        crate::execute(&root).await.unwrap();
+
+        assert_eq!(log(&calls), ["leaf: ok", "root: ok"]);
+    }
+
+    #[tokio::test]
+    async fn post_run_child_first_on_panic() {
+        let (root, calls) = Step::chain(&["root", "group", "leaf"], Outcome::Panic);
+
+        let err = crate::execute(&root).await.unwrap_err();
+
+        assert!(err.is::<Panicked>());
+        let calls = log(&calls);
+        let names = calls
+            .iter()
+            .map(|call| call.split(':').next().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(names, ["leaf", "group", "root"]);
+        assert!(calls.iter().all(|call| call.contains("leaf panicked")));
+    }
+
+    #[tokio::test]
+    async fn post_run_child_first_on_interrupt() {
+        let presses = Arc::new(Semaphore::new(0));
+        let (root, calls) = Step::chain(
+            &["root", "group", "leaf"],
+            Outcome::Interrupt(Arc::clone(&presses)),
+        );
+
+        let options = ExecuteOptions::default().with_runtime(Presses(presses));
+        let err = crate::execute_with(&root, options).await.unwrap_err();
+
+        assert!(err.is::<Interrupted>());
+        let interrupted = Interrupted.to_string();
+        assert_eq!(
+            log(&calls),
+            [
+                format!("leaf: {interrupted}"),
+                format!("group: {interrupted}"),
+                format!("root: {interrupted}"),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn post_run_child_first_when_dropped() {
+        let (root, calls) = Step::chain(&["root", "group", "leaf"], Outcome::Hang);
+
+        tokio::time::timeout(Duration::from_millis(10), crate::execute(&root))
+            .await
+            .unwrap_err();
+
+        assert_eq!(
+            log(&calls),
+            [
+                "leaf: execution was cancelled",
+                "group: execution was cancelled",
+                "root: execution was cancelled",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn post_run_only_for_entered_commands() {
+        let (root, calls) = Step::chain(&["root", "leaf"], Outcome::Ok);
+
+        let options = ExecuteOptions::default().with_max_depth(1);
+        assert!(crate::execute_with(&root, options).await.is_err());
+
+        assert!(log(&calls).is_empty());
+    }
+}
@@ -0,0 +1,243 @@
+//! Make sure only one invocation of a command runs at a time.
+//!
+//! Commands that migrate a database or rewrite a config file shouldn't run
+//! twice in parallel. Returning a [`Lock`] from [`Command::lock`] takes an
+//! advisory file lock before the command's `pre_run` and releases it after its
+//! `post_run`, whether the command succeeded or not. A second invocation fails
+//! straight away with [`Locked`] instead of waiting.
+//!
+//! Some things to note:
+//! - Locks live in the platform state directory, see [`crate::dirs`]. The CLI
+//!   as a whole has one lock, [`Lock::with_scope`] gives a subcommand its own.
+//! - The lock file contains the process ID of the holder, which shows up in
+//!   the error.
+//! - Locks are released by the OS when the process exits, a crash never leaves
+//!   a stale lock behind.
+//! - Commands that only need the lock some of the time can call
+//!   [`Lock::acquire`] themselves, for example from `pre_run`, and keep the
+//!   [`Guard`] on the [`crate::Context`].
+//! - There is no locking on wasm, acquiring always succeeds.
+//!
+//! # Examples
+//!
+//! ```
+//! use cata::{lock::{Lock, Locked}, Command, Container};
+//! use clap::Parser;
+//!
+//! #[derive(Parser, Container)]
+//! struct Migrate {}
+//!
+//! #[async_trait::async_trait]
+//! impl Command for Migrate {
+//!   fn lock(&self) -> Option<Lock> {
+//! #   let dir = std::env::temp_dir().join("cata-lock-example");
+//!     Some(Lock::new("my-cli").with_scope("migrate"))
+//! #     .map(|lock| lock.with_path(dir.join("migrate.lock")))
+//!   }
+//!
+//!   async fn run(&self, _: &mut cata::Context) -> eyre::Result<()> {
+//!     // Someone else tries to migrate at the same time.
+//! #   let dir = std::env::temp_dir().join("cata-lock-example");
+//!     let err = Lock::new("my-cli")
+//!       .with_scope("migrate")
+//! #     .with_path(dir.join("migrate.lock"))
+//!       .acquire()
+//!       .unwrap_err();
+//!
+//!     assert!(err.is::<Locked>());
+//!
+//!     Ok(())
+//!   }
+//! }
+//!
+//! # #[tokio::main]
+//! # async fn main() -> eyre::Result<()> {
+//! cata::execute(&Migrate::parse_from(["migrate"])).await?;
+//!
+//! // Released after `post_run`.
+//! # let dir = std::env::temp_dir().join("cata-lock-example");
+//! Lock::new("my-cli")
+//!   .with_scope("migrate")
+//! # .with_path(dir.join("migrate.lock"))
+//!   .acquire()?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! [`Command::lock`]: crate::Command::lock
+use std::{
+    fmt,
+    fs::{File, OpenOptions},
+    io::{Read, Seek, Write},
+    path::PathBuf,
+};
+
+use eyre::{eyre, Result};
+
+use crate::dirs::Dirs;
+
+static LOCKS_DIR: &str = "locks";
+
+/// An advisory lock for a CLI or one of its subcommands.
+///
+/// See the module documentation for usage.
+#[derive(Clone, Debug)]
+pub struct Lock {
+    label: String,
+    path: Option<PathBuf>,
+}
+
+impl Lock {
+    /// Create the lock for the CLI `name`.
+    pub fn new(name: impl AsRef<str>) -> Self {
+        let name = name.as_ref();
+
+        Self {
+            label: name.to_string(),
+            path: Dirs::new(name)
+                .state()
+                .map(|dir| dir.join(LOCKS_DIR).join(format!("{name}.lock"))),
+        }
+    }
+
+    /// Only keep other invocations of `scope`, such as the name of a
+    /// subcommand, from running.
+    #[must_use]
+    pub fn with_scope(mut self, scope: impl AsRef<str>) -> Self {
+        let scope = scope.as_ref();
+
+        self.label = format!("{} {scope}", self.label);
+        self.path = self
+            .path
+            .map(|path| path.with_file_name(format!("{scope}.lock")));
+        self
+    }
+
+    /// Set the path of the lock file.
+    ///
+    /// By default, this is in `locks` of the platform state directory.
+    #[must_use]
+    pub fn with_path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.path = Some(path.into());
+        self
+    }
+
+    /// Take the lock, failing with [`Locked`] if another invocation holds it.
+    ///
+    /// The lock is held until the returned guard is dropped.
+    pub fn acquire(&self) -> Result<Guard> {
+        let mut file = self.open()?;
+
+        if !try_acquire(&file)? {
+            let mut holder = String::new();
+            file.read_to_string(&mut holder).ok();
+
+            return Err(Locked {
+                command: self.label.clone(),
+                pid: holder.trim().parse().ok(),
+            }
+            .into());
+        }
+
+        Guard::hold(file)
+    }
+
+    /// Take the lock, waiting for another invocation to release it first.
+    #[cfg(feature = "secrets")]
+    pub(crate) fn wait(&self) -> Result<Guard> {
+        let file = self.open()?;
+        acquire(&file)?;
+
+        Guard::hold(file)
+    }
+
+    fn open(&self) -> Result<File> {
+        let path = self
+            .path
+            .as_ref()
+            .ok_or_else(|| eyre!("unable to determine the state directory"))?;
+
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        Ok(OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .read(true)
+            .write(true)
+            .open(path)?)
+    }
+}
+
+/// Holds a [`Lock`] until it is dropped.
+#[derive(Debug)]
+pub struct Guard {
+    _file: File,
+}
+
+impl Guard {
+    /// Record the process ID in the locked `file`.
+    fn hold(mut file: File) -> Result<Self> {
+        file.set_len(0)?;
+        file.rewind()?;
+        write!(file, "{}", std::process::id())?;
+
+        Ok(Self { _file: file })
+    }
+}
+
+/// Returned by [`Lock::acquire`] when another invocation holds the lock.
+#[derive(Debug)]
+pub struct Locked {
+    /// What is locked, such as `my-cli migrate`.
+    pub command: String,
+    /// The process ID of the invocation that holds the lock, if it is known.
+    pub pid: Option<u32>,
+}
+
+impl fmt::Display for Locked {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "another invocation of `{}` is running", self.command)?;
+
+        if let Some(pid) = self.pid {
+            write!(f, " (pid {pid})")?;
+        }
+
+        write!(f, ", wait for it to finish and try again")
+    }
+}
+
+impl std::error::Error for Locked {}
+
+/// Take the lock on `file` without waiting, `false` when someone else has it.
+#[cfg(not(target_family = "wasm"))]
+fn try_acquire(file: &File) -> Result<bool> {
+    use fs4::fs_std::FileExt;
+
+    match file.try_lock_exclusive() {
+        Ok(()) => Ok(true),
+        Err(e) if e.kind() == fs4::lock_contended_error().kind() => Ok(false),
+        Err(e) => Err(e.into()),
+    }
+}
+
+#[cfg(target_family = "wasm")]
+#[allow(clippy::unnecessary_wraps)]
+fn try_acquire(_: &File) -> Result<bool> {
+    Ok(true)
+}
+
+/// Take the lock on `file`, waiting for someone else to release it.
+#[cfg(all(feature = "secrets", not(target_family = "wasm")))]
+fn acquire(file: &File) -> Result<()> {
+    use fs4::fs_std::FileExt;
+
+    Ok(file.lock_exclusive()?)
+}
+
+#[cfg(all(feature = "secrets", target_family = "wasm"))]
+#[allow(clippy::unnecessary_wraps)]
+fn acquire(_: &File) -> Result<()> {
+    Ok(())
+}
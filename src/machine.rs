@@ -0,0 +1,162 @@
+//! Identify the machine a CLI is running on.
+//!
+//! The OS provided machine ID is not always available. Containers, minimal
+//! distros and sandboxed macOS apps often don't have one, or need extra
+//! entitlements to read it. Instead of collapsing every one of those machines
+//! into the same ID, detection falls back through:
+//!
+//! 1. [`Source::MachineUid`]: the OS machine ID, via [`machine_uid`].
+//! 2. [`Source::Persisted`]: a random ID generated on first use and stored in
+//!    the platform state directory.
+//! 3. [`Source::Hostname`]: a hash of the hostname.
+//! 4. [`Source::Unknown`]: nothing worked, the ID is `unknown`.
+//!
+//! The source is kept alongside the ID, so that backends can segment users
+//! whose IDs are less stable.
+//!
+//! # Examples
+//!
+//! ```
+//! use cata::machine::{Id, Source};
+//!
+//! let id = Id::detect();
+//!
+//! if id.source() == Source::Hostname {
+//!   println!("this machine may not be told apart from others");
+//! }
+//! ```
+use std::{path::Path, sync::OnceLock};
+
+use ring::rand::{SecureRandom, SystemRandom};
+
+use crate::{dirs::Dirs, hex};
+
+static NAME: &str = env!("CARGO_PKG_NAME");
+static ID_FILE: &str = "machine-id";
+static UNKNOWN: &str = "unknown";
+static DETECTED: OnceLock<Id> = OnceLock::new();
+
+/// How an [`Id`] was found.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Source {
+    /// The ID provided by the OS.
+    MachineUid,
+    /// A random ID stored on disk.
+    Persisted,
+    /// A hash of the hostname.
+    Hostname,
+    /// No ID could be found.
+    Unknown,
+}
+
+impl Source {
+    /// A short name, suitable for reporting.
+    #[must_use]
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::MachineUid => "machine-uid",
+            Self::Persisted => "persisted",
+            Self::Hostname => "hostname",
+            Self::Unknown => "unknown",
+        }
+    }
+}
+
+/// An ID for the current machine and how it was found.
+///
+/// See the module documentation for usage.
+#[derive(Clone, Debug)]
+pub struct Id {
+    id: String,
+    source: Source,
+}
+
+impl Id {
+    /// Detect the ID of the current machine.
+    ///
+    /// Detection only happens once per process, the result is reused after
+    /// that. The persisted ID is shared by every CLI built with cata.
+    pub fn detect() -> Self {
+        DETECTED
+            .get_or_init(|| {
+                Self::detect_at(
+                    Dirs::new(NAME)
+                        .state()
+                        .map(|dir| dir.join(ID_FILE))
+                        .as_deref(),
+                )
+            })
+            .clone()
+    }
+
+    /// Detect the ID of the current machine, persisting generated IDs at
+    /// `path`.
+    pub fn detect_at(path: Option<&Path>) -> Self {
+        if let Ok(id) = machine_uid::get() {
+            return Self::new(id, Source::MachineUid);
+        }
+
+        if let Some(id) = path.and_then(persisted) {
+            return Self::new(id, Source::Persisted);
+        }
+
+        if let Some(host) = hostname() {
+            let digest = ring::digest::digest(&ring::digest::SHA256, host.as_bytes());
+
+            return Self::new(hex::encode(&digest.as_ref()[..16]), Source::Hostname);
+        }
+
+        Self::new(UNKNOWN.into(), Source::Unknown)
+    }
+
+    fn new(id: String, source: Source) -> Self {
+        Self { id, source }
+    }
+
+    /// The ID itself.
+    ///
+    /// This is not hashed, hash it before sending it anywhere.
+    #[must_use]
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// How the ID was found.
+    #[must_use]
+    pub fn source(&self) -> Source {
+        self.source
+    }
+}
+
+/// Read the ID stored at `path`, generating it first if there isn't one.
+fn persisted(path: &Path) -> Option<String> {
+    if let Ok(id) = std::fs::read_to_string(path) {
+        let id = id.trim();
+
+        if !id.is_empty() {
+            return Some(id.into());
+        }
+    }
+
+    let mut bytes = [0u8; 16];
+    SystemRandom::new().fill(&mut bytes).ok()?;
+    let id = hex::encode(&bytes);
+
+    std::fs::create_dir_all(path.parent()?).ok()?;
+    std::fs::write(path, &id).ok()?;
+
+    Some(id)
+}
+
+fn hostname() -> Option<String> {
+    ["HOSTNAME", "COMPUTERNAME"]
+        .iter()
+        .filter_map(|var| std::env::var(var).ok())
+        .chain(
+            ["/etc/hostname", "/proc/sys/kernel/hostname"]
+                .iter()
+                .filter_map(|path| std::fs::read_to_string(path).ok()),
+        )
+        .map(|host| host.trim().to_string())
+        .find(|host| !host.is_empty())
+}
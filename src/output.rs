@@ -1,14 +1,21 @@
 //! Structured output for commands.
 //!
 //! When implemented, users of a CLI can choose what type of structured output
-//! they would like from the CLI. JSON, YAML and pretty are currently supported.
+//! they would like from the CLI. JSON, YAML (with the `yaml` feature) and
+//! pretty are currently supported.
 //! This can be added as part of a root command and then any subcommands are
-//! able to output correctly.
+//! able to output correctly. Flatten [`Output`] into the root with
+//! `#[cata(output)]` for a global `--output` flag that every command can read
+//! from [`crate::Context::output`].
 //!
 //! Any type being output is required to implement [`serde::Serialize`] in
 //! addition to [`tabled::Tabled`]. `Tabled` requires that every field
 //! implements `Display`. The [`cata::output::tabled`] module provides some
-//! helpers.
+//! helpers. The `Row` derive implements both from one set of attributes, see
+//! [`crate::Row`].
+//!
+//! When a localizer has been installed with [`crate::i18n`], table headers in
+//! pretty output are translated.
 //!
 //! # Examples
 //! For a more complete example, see [examples/output].
@@ -29,7 +36,7 @@
 //!
 //! #[async_trait::async_trait]
 //! impl Command for Cmd {
-//!   async fn run(&self) -> eyre::Result<()> {
+//!   async fn run(&self, _: &mut cata::Context) -> eyre::Result<()> {
 //!     self.output.item(&MyType { field: "value".into() })
 //!   }
 //! }
@@ -38,15 +45,76 @@
 //! [examples/output]: https://github.com/grampelberg/cata/blob/main/examples/output/src/main.rs
 pub mod tabled;
 
+use std::{
+    fmt::{Display, Write as _},
+    future::Future,
+    sync::{Arc, Mutex},
+};
+
+#[cfg(feature = "i18n")]
+use ::tabled::settings::{format::Format as CellFormat, object::Rows, Modify};
 use ::tabled::{Table, Tabled};
 use clap::ValueEnum;
 use eyre::Result;
 use serde::Serialize;
 
+// Task locals are only set while the future in `scope` is polled, which works
+// the same on every `Runtime` without needing tokio's.
+tokio::task_local! {
+    static CAPTURE: Arc<Captured>;
+}
+
+/// What was printed while running inside of [`capture`].
+#[derive(Default)]
+struct Captured {
+    stdout: Mutex<String>,
+    stderr: Mutex<String>,
+}
+
+/// Print to stdout, or to the buffer when running inside of [`capture`].
+fn emit(text: impl Display) {
+    let captured = CAPTURE.try_with(|captured| {
+        if let Ok(mut buf) = captured.stdout.lock() {
+            let _ = writeln!(buf, "{text}");
+        }
+    });
+
+    if captured.is_err() {
+        println!("{text}");
+    }
+}
+
+/// Print to stderr, or to the buffer when running inside of [`capture`].
+pub(crate) fn warn(text: impl Display) {
+    let captured = CAPTURE.try_with(|captured| {
+        if let Ok(mut buf) = captured.stderr.lock() {
+            let _ = writeln!(buf, "{text}");
+        }
+    });
+
+    if captured.is_err() {
+        eprintln!("{text}");
+    }
+}
+
+/// Run `fut`, collecting everything printed by [`Format`] and cata's own
+/// warnings instead of writing them to stdout and stderr.
+///
+/// Only output from the current task is captured, anything printed from tasks
+/// spawned by `fut` still goes to stdout.
+pub(crate) async fn capture<F: Future>(fut: F) -> (F::Output, String, String) {
+    let captured = Arc::new(Captured::default());
+    let result = CAPTURE.scope(captured.clone(), fut).await;
+
+    let read = |buf: &Mutex<String>| buf.lock().map(|buf| buf.clone()).unwrap_or_default();
+
+    (result, read(&captured.stdout), read(&captured.stderr))
+}
+
 /// Argument for specifying the output format of structured data.
 ///
 /// See the module documentation for usage.
-#[derive(ValueEnum, Debug, Default, Clone, Serialize)]
+#[derive(ValueEnum, Debug, Default, Clone, PartialEq, Eq, Serialize)]
 #[serde(rename_all = "kebab-case")]
 pub enum Format {
     #[default]
@@ -56,16 +124,93 @@ pub enum Format {
     /// Prints the output as JSON.
     Json,
     /// Prints the output as YAML.
+    #[cfg(feature = "yaml")]
     Yaml,
 }
 
+/// A global `-o`/`--output` flag for the root command.
+///
+/// Flatten it into the root with `#[cata(output)]` and the chosen format is
+/// provided to every command, see [`crate::Context::output`].
+///
+/// ```
+/// use cata::{output::Output, Command, Container};
+/// use clap::{Parser, Subcommand};
+///
+/// #[derive(Parser, Container)]
+/// struct Root {
+///   #[command(flatten)]
+///   #[cata(output)]
+///   output: Output,
+///
+///   #[command(subcommand)]
+///   cmd: RootCmd,
+/// }
+///
+/// impl Command for Root {}
+///
+/// #[derive(Subcommand, Container)]
+/// enum RootCmd {
+///   List(List),
+/// }
+///
+/// #[derive(Parser, Container)]
+/// struct List {}
+///
+/// #[async_trait::async_trait]
+/// impl Command for List {
+///   async fn run(&self, ctx: &mut cata::Context) -> eyre::Result<()> {
+///     assert_eq!(ctx.output(), cata::output::Format::Json);
+///
+///     Ok(())
+///   }
+/// }
+///
+/// # #[tokio::main]
+/// # async fn main() -> eyre::Result<()> {
+/// cata::execute(&Root::parse_from(["root", "list", "-o", "json"])).await
+/// # }
+/// ```
+#[derive(clap::Args, Clone, Debug, Default)]
+pub struct Output {
+    /// The format to print structured output in.
+    #[arg(
+        short = 'o',
+        long = "output",
+        value_enum,
+        default_value_t,
+        global = true
+    )]
+    pub format: Format,
+}
+
+impl From<Output> for Format {
+    fn from(output: Output) -> Self {
+        output.format
+    }
+}
+
 impl Format {
     /// Print a list of items to the console.
     pub fn list(&self, data: &[impl Serialize + Tabled]) -> Result<()> {
         match self {
-            Format::Pretty => println!("{}", Table::new(data)),
-            Format::Json => println!("{}", serde_json::to_string_pretty(&data)?),
-            Format::Yaml => println!("{}", serde_yaml::to_string(&data)?),
+            Format::Pretty => {
+                #[cfg_attr(not(feature = "i18n"), allow(unused_mut))]
+                let mut table = Table::new(data);
+
+                #[cfg(feature = "i18n")]
+                if let Some(localizer) = crate::i18n::global() {
+                    table.with(
+                        Modify::new(Rows::first())
+                            .with(CellFormat::content(|header| localizer.text(header))),
+                    );
+                }
+
+                emit(table);
+            }
+            Format::Json => emit(serde_json::to_string_pretty(&data)?),
+            #[cfg(feature = "yaml")]
+            Format::Yaml => emit(serde_yaml::to_string(&data)?),
         }
 
         Ok(())
@@ -78,8 +223,24 @@ impl Format {
     pub fn item(&self, data: &(impl Serialize + Tabled)) -> Result<()> {
         match self {
             Format::Pretty => self.list(&[data])?,
-            Format::Json => println!("{}", serde_json::to_string_pretty(data)?),
-            Format::Yaml => println!("{}", serde_yaml::to_string(data)?),
+            Format::Json => emit(serde_json::to_string_pretty(data)?),
+            #[cfg(feature = "yaml")]
+            Format::Yaml => emit(serde_yaml::to_string(data)?),
+        }
+
+        Ok(())
+    }
+
+    /// Print a document to the console.
+    ///
+    /// This is for data that doesn't fit in a table, such as a report. Pretty
+    /// output uses the `Display` implementation.
+    pub fn document(&self, data: &(impl Serialize + Display)) -> Result<()> {
+        match self {
+            Format::Pretty => emit(data),
+            Format::Json => emit(serde_json::to_string_pretty(data)?),
+            #[cfg(feature = "yaml")]
+            Format::Yaml => emit(serde_yaml::to_string(data)?),
         }
 
         Ok(())
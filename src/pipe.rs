@@ -0,0 +1,191 @@
+//! Chain commands together with pipes.
+//!
+//! Structured output from one command can be the input of another, such as
+//! `my-cli projects list | my-cli projects delete -`. Commands declare what
+//! they print with [`Output`] and what they read with [`Items`], cata handles
+//! the serialization in between.
+//!
+//! Some things to note:
+//! - [`Output`] is a replacement for [`Format`] that defaults to JSON when
+//!   stdout isn't a terminal and pretty output when it is. An explicit
+//!   `--output` always wins.
+//! - [`Items`] is parsed with [`crate::file::File`], so it reads from a path or
+//!   from stdin when given `-`. Both a single item and a list of items are
+//!   accepted.
+//! - The types on either side of the pipe don't have to match. Fields the
+//!   reading command doesn't know about are ignored, unless it uses
+//!   `#[serde(deny_unknown_fields)]`.
+//!
+//! # Examples
+//!
+//! ```
+//! use cata::{
+//!   command,
+//!   pipe::{Items, Output},
+//!   Command, Container,
+//! };
+//! use clap::{Parser, Subcommand};
+//!
+//! #[derive(Clone, Debug, serde::Serialize, serde::Deserialize, tabled::Tabled)]
+//! struct Project {
+//!   name: String,
+//! }
+//!
+//! #[derive(Parser, Container)]
+//! struct Root {
+//!   #[command(subcommand)]
+//!   cmd: RootCmd,
+//! }
+//!
+//! impl Command for Root {}
+//!
+//! #[derive(Subcommand, Container)]
+//! enum RootCmd {
+//!   List(List),
+//!   Delete(Delete),
+//! }
+//!
+//! #[derive(Parser)]
+//! struct List {
+//!   #[command(flatten)]
+//!   output: Output,
+//! }
+//!
+//! impl command::Container for List {}
+//!
+//! #[async_trait::async_trait]
+//! impl Command for List {
+//!   async fn run(&self, _: &mut cata::Context) -> eyre::Result<()> {
+//!     self.output.list(&[Project { name: "cata".into() }])
+//!   }
+//! }
+//!
+//! #[derive(Parser, Container)]
+//! struct Delete {
+//!   /// Projects to delete, `-` reads them from stdin.
+//!   projects: Items<Project>,
+//! }
+//!
+//! #[async_trait::async_trait]
+//! impl Command for Delete {
+//!   async fn run(&self, _: &mut cata::Context) -> eyre::Result<()> {
+//!     for project in &self.projects {
+//!       println!("deleting {}", project.name);
+//!     }
+//!
+//!     Ok(())
+//!   }
+//! }
+//!
+//! let input = std::env::temp_dir().join("cata-pipe-doctest.json");
+//! std::fs::write(&input, r#"[{"name": "cata"}, {"name": "other"}]"#).unwrap();
+//!
+//! let root = Root::parse_from(["root", "delete", input.to_str().unwrap()]);
+//! let RootCmd::Delete(delete) = &root.cmd else {
+//!   unreachable!()
+//! };
+//!
+//! assert_eq!(delete.projects.len(), 2);
+//! ```
+use std::{io::IsTerminal, ops::Deref};
+
+use clap::builder::ValueParserFactory;
+use eyre::Result;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use tabled::Tabled;
+
+use crate::{file::File, output::Format};
+
+/// The output format, defaulting to JSON when stdout is piped.
+///
+/// Flatten this into a command in place of a [`Format`] argument.
+#[derive(clap::Args, Clone, Debug, Default)]
+pub struct Output {
+    /// Format of the output, defaults to JSON when piped and pretty otherwise.
+    #[arg(short, long, value_enum)]
+    pub output: Option<Format>,
+}
+
+impl Output {
+    /// The format that was chosen, or the default for where stdout goes.
+    #[must_use]
+    pub fn format(&self) -> Format {
+        self.output.clone().unwrap_or_else(|| {
+            if std::io::stdout().is_terminal() {
+                Format::Pretty
+            } else {
+                Format::Json
+            }
+        })
+    }
+
+    /// Print a list of items, see [`Format::list`].
+    pub fn list(&self, data: &[impl Serialize + Tabled]) -> Result<()> {
+        self.format().list(data)
+    }
+
+    /// Print a single item, see [`Format::item`].
+    pub fn item(&self, data: &(impl Serialize + Tabled)) -> Result<()> {
+        self.format().item(data)
+    }
+}
+
+/// One or more items read from a file or stdin.
+///
+/// This accepts the output of both [`Output::list`] and [`Output::item`].
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize)]
+#[serde(from = "OneOrMany<T>", bound = "T: Deserialize<'de>")]
+pub struct Items<T>(pub Vec<T>);
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum OneOrMany<T> {
+    Many(Vec<T>),
+    One(T),
+}
+
+impl<T> From<OneOrMany<T>> for Items<T> {
+    fn from(items: OneOrMany<T>) -> Self {
+        match items {
+            OneOrMany::Many(items) => Self(items),
+            OneOrMany::One(item) => Self(vec![item]),
+        }
+    }
+}
+
+impl<T> Deref for Items<T> {
+    type Target = [T];
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl<T> IntoIterator for Items<T> {
+    type Item = T;
+    type IntoIter = std::vec::IntoIter<T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
+    }
+}
+
+impl<'a, T> IntoIterator for &'a Items<T> {
+    type Item = &'a T;
+    type IntoIter = std::slice::Iter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.iter()
+    }
+}
+
+impl<T> ValueParserFactory for Items<T>
+where
+    T: DeserializeOwned + Clone + Send + Sync + 'static,
+{
+    type Parser = File<Items<T>>;
+
+    fn value_parser() -> Self::Parser {
+        File::default()
+    }
+}
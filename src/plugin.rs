@@ -0,0 +1,333 @@
+//! External plugins discovered on `$PATH`, in the style of git and kubectl.
+//!
+//! Any executable named `my-cli-<name>` on `$PATH` becomes the subcommand
+//! `my-cli <name>`. [`Plugins::discover`] finds them and [`Plugins::register`]
+//! adds them to a [`Registry`], so that they're parsed, listed in `--help` and
+//! run through [`crate::execute`] like the rest of the tree.
+//!
+//! Some things to note:
+//! - Everything after the plugin's name is passed to it untouched, including
+//!   `--help`. The environment is inherited.
+//! - When the root has provided a [`crate::output::Format`] on the [`Context`],
+//!   it is forwarded as `MY_CLI_OUTPUT`. Plugins built with cata can pick it up
+//!   with `#[arg(env = "MY_CLI_OUTPUT")]`.
+//! - A plugin that exits with a non-zero code fails with that same code, see
+//!   [`crate::error::exit_code`].
+//! - Plugins call [`about`] at the start of `main` to describe themselves in
+//!   `--help`. Descriptions are only looked up when help was asked for, other
+//!   plugins are asked for `--help` and the first line is used.
+//! - When more than one directory has the same plugin, the first one on `$PATH`
+//!   wins. Subcommands defined in the CLI itself always win.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use cata::{
+//!   plugin::Plugins,
+//!   registry::{Dynamic, Registry},
+//!   Command, Container,
+//! };
+//! use clap::{Parser, Subcommand};
+//!
+//! #[derive(Parser, Container)]
+//! #[command(name = "my-cli")]
+//! struct Root {
+//!   #[command(subcommand)]
+//!   cmd: Dynamic<RootCmd>,
+//! }
+//!
+//! impl Command for Root {}
+//!
+//! #[derive(Subcommand, Container)]
+//! enum RootCmd {
+//!   Version(Version),
+//! }
+//!
+//! #[derive(Parser, Container)]
+//! struct Version {}
+//!
+//! impl Command for Version {}
+//!
+//! #[tokio::main]
+//! async fn main() -> eyre::Result<()> {
+//!   Plugins::discover("my-cli")
+//!     .register(Registry::default())
+//!     .install()?;
+//!
+//!   cata::execute(&Root::parse()).await
+//! }
+//! ```
+//!
+//! [`Registry`]: crate::registry::Registry
+use std::{
+    ffi::{OsStr, OsString},
+    path::{Path, PathBuf},
+    process::Stdio,
+};
+
+use clap::{Arg, ArgMatches, CommandFactory};
+use eyre::{eyre, Result};
+
+use crate::{command::Container, dirs, error::WithExitCode, registry::Registry, Command, Context};
+
+/// Set when a plugin is only being asked to describe itself.
+static ABOUT_VAR: &str = "CATA_PLUGIN_ABOUT";
+static ARGS: &str = "args";
+
+/// Describe this plugin to the CLI that found it, then exit.
+///
+/// This must be called at the start of `main`, before anything is written to
+/// stdout. When the plugin isn't being asked for a description, it returns
+/// immediately.
+pub fn about<C: CommandFactory>() {
+    if std::env::var_os(ABOUT_VAR).is_none() {
+        return;
+    }
+
+    let cmd = C::command();
+    if let Some(about) = cmd.get_about() {
+        println!("{about}");
+    }
+
+    std::process::exit(0);
+}
+
+/// A single executable found on `$PATH`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Plugin {
+    name: String,
+    path: PathBuf,
+}
+
+impl Plugin {
+    /// The subcommand the plugin provides.
+    #[must_use]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Where the executable lives.
+    #[must_use]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Ask the plugin to describe itself.
+    ///
+    /// Plugins that call [`about`] print their description, the rest print
+    /// their help and its first line is used.
+    pub fn about(&self) -> Result<Option<String>> {
+        let output = std::process::Command::new(&self.path)
+            .arg("--help")
+            .env(ABOUT_VAR, "1")
+            .stdin(Stdio::null())
+            .stderr(Stdio::null())
+            .output()?;
+
+        Ok(String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .map(str::trim)
+            .find(|line| !line.is_empty())
+            .map(ToString::to_string))
+    }
+}
+
+/// Every plugin found for a CLI.
+///
+/// See the module documentation for usage.
+#[derive(Clone, Debug)]
+pub struct Plugins {
+    prefix: String,
+    plugins: Vec<Plugin>,
+}
+
+impl Plugins {
+    /// Search `$PATH` for the plugins of the CLI `name`.
+    #[must_use]
+    pub fn discover(name: impl Into<String>) -> Self {
+        let prefix = name.into();
+        let path = std::env::var_os("PATH").unwrap_or_default();
+
+        let mut plugins: Vec<Plugin> = Vec::new();
+        for dir in std::env::split_paths(&path) {
+            let Ok(entries) = std::fs::read_dir(&dir) else {
+                continue;
+            };
+
+            for entry in entries.flatten() {
+                let path = entry.path();
+                let Some(name) = plugin_name(&prefix, &path) else {
+                    continue;
+                };
+
+                if dirs::is_executable(&path) && !plugins.iter().any(|plugin| plugin.name == name) {
+                    plugins.push(Plugin { name, path });
+                }
+            }
+        }
+
+        plugins.sort_by(|a, b| a.name.cmp(&b.name));
+
+        Self { prefix, plugins }
+    }
+
+    /// The plugins that were found, sorted by name.
+    #[must_use]
+    pub fn plugins(&self) -> &[Plugin] {
+        &self.plugins
+    }
+
+    /// The variable the output format is forwarded in.
+    fn output(&self) -> String {
+        dirs::env_var(&self.prefix, "OUTPUT")
+    }
+
+    /// Run the plugin named by the first of `args` with the rest of them.
+    ///
+    /// This is for `#[command(external_subcommand)]` variants, call it from
+    /// their `#[cata(external = ...)]`. Unknown plugins fail.
+    ///
+    /// ```no_run
+    /// use std::ffi::OsString;
+    ///
+    /// use cata::{plugin::Plugins, Container};
+    /// use clap::Subcommand;
+    ///
+    /// #[derive(Subcommand, Container)]
+    /// enum RootCmd {
+    ///   #[command(external_subcommand)]
+    ///   #[cata(external = Self::plugin)]
+    ///   Plugin(Vec<OsString>),
+    /// }
+    ///
+    /// impl RootCmd {
+    ///   async fn plugin(args: &[OsString], ctx: &mut cata::Context) -> eyre::Result<()> {
+    ///     Plugins::discover("my-cli").run(args, ctx).await
+    ///   }
+    /// }
+    /// ```
+    pub async fn run<T: AsRef<OsStr>>(&self, args: &[T], ctx: &mut Context) -> Result<()> {
+        let Some((name, args)) = args.split_first() else {
+            return Err(eyre!("no plugin to run"));
+        };
+
+        let name = name.as_ref();
+        let plugin = self
+            .plugins
+            .iter()
+            .find(|plugin| OsStr::new(&plugin.name) == name)
+            .ok_or_else(|| {
+                eyre!(
+                    "unknown command `{}`, there is no `{}-{}` on $PATH",
+                    name.to_string_lossy(),
+                    self.prefix,
+                    name.to_string_lossy(),
+                )
+            })?;
+
+        External {
+            plugin: plugin.clone(),
+            output: self.output(),
+            args: args.iter().map(|arg| arg.as_ref().to_os_string()).collect(),
+        }
+        .run(ctx)
+        .await
+    }
+
+    /// Add every plugin to `registry` as a subcommand.
+    #[must_use]
+    pub fn register(self, registry: Registry) -> Registry {
+        let describe = help_requested();
+        let output = self.output();
+
+        self.plugins.into_iter().fold(registry, |registry, plugin| {
+            let about = describe
+                .then(|| plugin.about().ok().flatten())
+                .flatten()
+                .unwrap_or_else(|| format!("Plugin at {}", plugin.path.display()));
+
+            let command = clap::Command::new("plugin")
+                .about(about)
+                .disable_help_flag(true)
+                .arg(
+                    Arg::new(ARGS)
+                        .num_args(0..)
+                        .trailing_var_arg(true)
+                        .allow_hyphen_values(true)
+                        .value_parser(clap::value_parser!(OsString)),
+                );
+
+            let output = output.clone();
+            registry.with_command(plugin.name.clone(), command, move |matches| {
+                Ok(Box::new(External {
+                    plugin: plugin.clone(),
+                    output: output.clone(),
+                    args: args(matches),
+                }))
+            })
+        })
+    }
+}
+
+/// The name of the plugin at `path`, if it is one of `prefix`'s.
+fn plugin_name(prefix: &str, path: &Path) -> Option<String> {
+    let file = if cfg!(windows) {
+        path.file_stem()?
+    } else {
+        path.file_name()?
+    };
+
+    let name = file.to_str()?.strip_prefix(prefix)?.strip_prefix('-')?;
+
+    (!name.is_empty()).then(|| name.to_string())
+}
+
+/// Whether the CLI was asked for its help, which is when descriptions matter.
+fn help_requested() -> bool {
+    std::env::args_os()
+        .skip(1)
+        .any(|arg| ["-h", "--help", "help"].iter().any(|help| arg == *help))
+}
+
+fn args(matches: &ArgMatches) -> Vec<OsString> {
+    matches
+        .get_many::<OsString>(ARGS)
+        .map(|args| args.cloned().collect())
+        .unwrap_or_default()
+}
+
+/// Runs a plugin with the arguments it was given.
+#[derive(Debug)]
+struct External {
+    plugin: Plugin,
+    /// The variable the output format is forwarded in.
+    #[cfg_attr(not(feature = "output"), allow(dead_code))]
+    output: String,
+    args: Vec<OsString>,
+}
+
+impl Container for External {}
+
+#[async_trait::async_trait]
+impl Command for External {
+    async fn run(&self, ctx: &mut Context) -> Result<()> {
+        let mut cmd = ctx.command(&self.plugin.path);
+        cmd.args(&self.args);
+
+        #[cfg(feature = "output")]
+        if let Some(value) = ctx
+            .get::<crate::output::Format>()
+            .and_then(|format| clap::ValueEnum::to_possible_value(&*format))
+        {
+            cmd.env(&self.output, value.get_name());
+        }
+
+        let status = crate::runtime::unblock(move || cmd.status()).await??;
+        if status.success() {
+            return Ok(());
+        }
+
+        Err(eyre!("plugin {} failed with {status}", self.plugin.name))
+            .with_exit_code(status.code().unwrap_or(1))
+    }
+}
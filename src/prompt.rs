@@ -0,0 +1,278 @@
+//! Ask for required arguments that weren't passed, instead of failing.
+//!
+//! Someone typing `my-cli deploy` at a terminal would rather be asked which
+//! environment to deploy to than read a usage error. [`Prompt::fill`] looks for
+//! required arguments that are missing from the command line, asks for each of
+//! them and hands back arguments that clap can parse.
+//!
+//! Some things to note:
+//! - This is opt-in, see [`crate::App::with_prompts`]. Nothing is asked unless
+//!   stdin and stderr are terminals.
+//! - Answers go through the argument's value parser, including the one for
+//!   [`crate::file::File`]. Invalid answers print clap's error and ask again.
+//! - Only arguments that take a value are asked for. Required flags and
+//!   argument groups are left for clap to report.
+//! - An empty answer, or the end of input, stops asking and clap reports
+//!   whatever is still missing.
+//!
+//! # Examples
+//!
+//! ```
+//! use cata::{prompt::Prompt, Command, Container};
+//! use clap::{CommandFactory, Parser, Subcommand};
+//!
+//! #[derive(Parser, Container)]
+//! struct Root {
+//!   #[command(subcommand)]
+//!   cmd: RootCmd,
+//! }
+//!
+//! impl Command for Root {}
+//!
+//! #[derive(Subcommand, Container)]
+//! enum RootCmd {
+//!   Deploy(Deploy),
+//! }
+//!
+//! #[derive(Parser, Container)]
+//! struct Deploy {
+//!   /// Where to deploy to.
+//!   #[arg(long)]
+//!   env: String,
+//!
+//!   /// How many replicas to run.
+//!   #[arg(long)]
+//!   replicas: u32,
+//! }
+//!
+//! impl Command for Deploy {}
+//!
+//! // Usually this is the terminal, the first answer for `--replicas` is invalid.
+//! let answers = std::io::Cursor::new("production\nmany\n3\n");
+//!
+//! let args = Prompt::default()
+//!   .with_input(answers)
+//!   .fill(&Root::command(), ["my-cli", "deploy"])
+//!   .unwrap();
+//!
+//! let RootCmd::Deploy(deploy) = Root::parse_from(args).cmd;
+//! assert_eq!(deploy.env, "production");
+//! assert_eq!(deploy.replicas, 3);
+//! ```
+use std::{
+    ffi::OsString,
+    fmt,
+    io::{BufRead, IsTerminal, Write},
+};
+
+use eyre::Result;
+
+type Input = Box<dyn BufRead + Send>;
+
+/// Asks for missing required arguments.
+///
+/// See the module documentation for usage.
+#[derive(Default)]
+pub struct Prompt {
+    input: Option<Input>,
+}
+
+impl fmt::Debug for Prompt {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Prompt")
+            .field("input", &self.input.is_some())
+            .finish()
+    }
+}
+
+/// An argument that was required but not passed.
+struct Missing {
+    arg: clap::Arg,
+    /// The command the argument belongs to, for errors.
+    cmd: clap::Command,
+    /// Where the answer goes in the arguments.
+    at: usize,
+}
+
+impl Missing {
+    /// Run `answer` through the argument's value parser.
+    fn check(&self, answer: &str) -> Result<(), clap::Error> {
+        let ids = self
+            .cmd
+            .get_arguments()
+            .map(|arg| arg.get_id().clone())
+            .collect::<Vec<_>>();
+
+        let cmd = ids.into_iter().fold(
+            self.cmd
+                .clone()
+                .ignore_errors(false)
+                .subcommand_required(false)
+                .arg_required_else_help(false),
+            |cmd, id| cmd.mut_arg(id, |arg| arg.required(false)),
+        );
+
+        let name = OsString::from(cmd.get_name());
+        cmd.try_get_matches_from(std::iter::once(name).chain(value(&self.arg, answer.to_string())))
+            .map(|_| ())
+    }
+}
+
+impl Prompt {
+    /// Read answers from `input` instead of stdin.
+    ///
+    /// Answers are read even when stdin isn't a terminal, which is mostly
+    /// useful for tests.
+    #[must_use]
+    pub fn with_input(mut self, input: impl BufRead + Send + 'static) -> Self {
+        self.input = Some(Box::new(input));
+        self
+    }
+
+    /// Ask for every required argument missing from `args`, which start with
+    /// the binary like [`std::env::args_os`] does, and return them with the
+    /// answers added.
+    ///
+    /// `cmd` is the root, usually `T::command()`. When nothing is missing or
+    /// there is nobody to ask, `args` are returned as they are.
+    pub fn fill<I, S>(self, cmd: &clap::Command, args: I) -> Result<Vec<OsString>>
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<OsString>,
+    {
+        let mut args = args.into_iter().map(Into::into).collect::<Vec<_>>();
+
+        let mut input = match self.input {
+            Some(input) => input,
+            None if std::io::stdin().is_terminal() && std::io::stderr().is_terminal() => {
+                Box::new(std::io::BufReader::new(std::io::stdin()))
+            }
+            None => return Ok(args),
+        };
+
+        let mut answers = Vec::new();
+        for missing in missing(cmd, &args) {
+            let Some(answer) = ask(&mut input, &missing)? else {
+                break;
+            };
+
+            answers.push((missing.at, value(&missing.arg, answer)));
+        }
+
+        // Insert from the back so that earlier positions stay valid, answers
+        // for the same position keep the order they were asked in.
+        answers.reverse();
+        answers.sort_by_key(|(at, _)| std::cmp::Reverse(*at));
+
+        for (at, value) in answers {
+            args.splice(at..at, value);
+        }
+
+        Ok(args)
+    }
+}
+
+/// The required arguments that `args` don't have, on the command that was
+/// parsed and its parents.
+fn missing(cmd: &clap::Command, args: &[OsString]) -> Vec<Missing> {
+    let mut cmd = cmd.clone().ignore_errors(true);
+    cmd.build();
+
+    let Ok(matches) = cmd.clone().try_get_matches_from(args) else {
+        return Vec::new();
+    };
+
+    let mut missing = Vec::new();
+    let mut cmd = &cmd;
+    let mut matches = &matches;
+    // Options go right after the command's name, positionals before the next
+    // subcommand.
+    let mut start = 1;
+
+    loop {
+        let next = matches.subcommand().and_then(|(name, sub)| {
+            let position = args
+                .iter()
+                .skip(start)
+                .position(|arg| arg == name)
+                .map(|position| position + start)?;
+
+            Some((cmd.find_subcommand(name)?, sub, position))
+        });
+        let end = next
+            .as_ref()
+            .map_or(args.len(), |(_, _, position)| *position);
+
+        for arg in cmd.get_arguments() {
+            if !arg.is_required_set()
+                || !arg.get_action().takes_values()
+                || (arg.is_global_set() && start > 1)
+                || matches.contains_id(arg.get_id().as_str())
+            {
+                continue;
+            }
+
+            missing.push(Missing {
+                arg: arg.clone(),
+                cmd: cmd.clone(),
+                at: if arg.is_positional() { end } else { start },
+            });
+        }
+
+        let Some((sub, sub_matches, position)) = next else {
+            break;
+        };
+
+        cmd = sub;
+        matches = sub_matches;
+        start = position + 1;
+    }
+
+    missing
+}
+
+/// Keep asking for `missing` until the answer is valid, `None` once there is
+/// no answer.
+fn ask(input: &mut Input, missing: &Missing) -> Result<Option<String>> {
+    let name = missing.arg.get_id().as_str().replace('_', " ");
+    let help = missing
+        .arg
+        .get_help()
+        .map(|help| format!(" ({})", help.to_string().trim_end_matches('.')))
+        .unwrap_or_default();
+
+    loop {
+        eprint!("{name}{help}: ");
+        std::io::stderr().flush()?;
+
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 {
+            eprintln!();
+
+            return Ok(None);
+        }
+
+        let answer = line.trim();
+        if answer.is_empty() {
+            return Ok(None);
+        }
+
+        match missing.check(answer) {
+            Ok(()) => return Ok(Some(answer.to_string())),
+            Err(e) => eprint!("{}", e.render()),
+        }
+    }
+}
+
+/// The arguments that pass `answer` as the value of `arg`.
+fn value(arg: &clap::Arg, answer: String) -> Vec<OsString> {
+    if let Some(long) = arg.get_long() {
+        return vec![format!("--{long}={answer}").into()];
+    }
+
+    if let Some(short) = arg.get_short() {
+        return vec![format!("-{short}").into(), answer.into()];
+    }
+
+    vec![answer.into()]
+}
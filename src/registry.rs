@@ -0,0 +1,345 @@
+//! Subcommands registered at runtime.
+//!
+//! `#[derive(Container)]` needs every subcommand to be known at compile time.
+//! Plugins and optional crates can't be listed in the root's enum, so instead
+//! they add themselves to a [`Registry`] at startup. Wrapping the root's
+//! subcommands in [`Dynamic`] lets clap parse both and [`crate::execute`]
+//! dispatch to either.
+//!
+//! Some things to note:
+//! - [`Registry::install`] must be called before the root is parsed, clap asks
+//!   for the registered subcommands while building the parser.
+//! - Registered commands are regular [`Command`]s with their own subcommands,
+//!   hooks and help. They show up in `--help`, completion and [`crate::tree`]
+//!   like any other subcommand.
+//! - When a registered command has the same name as a derived one, the derived
+//!   one wins.
+//! - Crates that provide commands can expose a function that takes a
+//!   [`Registry`] and returns it with their commands added.
+//!
+//! # Examples
+//!
+//! ```
+//! use std::sync::atomic::{AtomicBool, Ordering};
+//!
+//! use cata::{
+//!   command::Container as _,
+//!   registry::{Dynamic, Registry},
+//!   Command, Container,
+//! };
+//! use clap::{Parser, Subcommand};
+//!
+//! static GREETED: AtomicBool = AtomicBool::new(false);
+//!
+//! #[derive(Parser, Container)]
+//! struct Root {
+//!   #[command(subcommand)]
+//!   cmd: Dynamic<RootCmd>,
+//! }
+//!
+//! impl Command for Root {}
+//!
+//! #[derive(Subcommand, Container)]
+//! enum RootCmd {
+//!   List(List),
+//! }
+//!
+//! #[derive(Parser, Container)]
+//! struct List {}
+//!
+//! impl Command for List {}
+//!
+//! /// Provided by a plugin crate.
+//! #[derive(Parser, Container)]
+//! struct Greet {}
+//!
+//! #[async_trait::async_trait]
+//! impl Command for Greet {
+//!   async fn run(&self, _: &mut cata::Context) -> eyre::Result<()> {
+//!     GREETED.store(true, Ordering::SeqCst);
+//!
+//!     Ok(())
+//!   }
+//! }
+//!
+//! # #[tokio::main]
+//! # async fn main() -> eyre::Result<()> {
+//! Registry::default().with::<Greet>("greet").install()?;
+//!
+//! let root = Root::parse_from(["root", "greet"]);
+//! assert_eq!(cata::command::path(&root), ["greet"]);
+//! assert_eq!(root.cmd.children(), ["list", "greet"]);
+//!
+//! cata::execute(&root).await?;
+//! assert!(GREETED.load(Ordering::SeqCst));
+//! # Ok(())
+//! # }
+//! ```
+use std::{
+    any::TypeId,
+    fmt,
+    sync::{Mutex, OnceLock, PoisonError},
+};
+
+use clap::{error::ErrorKind, ArgMatches, FromArgMatches, Subcommand};
+use eyre::{eyre, Result};
+
+use crate::{
+    command::Container,
+    describe::{Describe, Descriptor},
+    Command,
+};
+
+static GLOBAL: OnceLock<Registry> = OnceLock::new();
+
+/// The names of every subcommand of each `Dynamic<T>`, built the first time
+/// they're asked for.
+static CHILDREN: Mutex<Vec<(TypeId, &'static [&'static str])>> = Mutex::new(Vec::new());
+
+type Build = Box<dyn Fn(&ArgMatches) -> Result<Box<dyn Command>, clap::Error> + Send + Sync>;
+
+struct Entry {
+    name: &'static str,
+    command: clap::Command,
+    build: Build,
+}
+
+/// Commands that are added to the tree at runtime.
+///
+/// See the module documentation for usage.
+#[derive(Default)]
+pub struct Registry {
+    entries: Vec<Entry>,
+}
+
+impl fmt::Debug for Registry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Registry")
+            .field(
+                "entries",
+                &self
+                    .entries
+                    .iter()
+                    .map(|entry| entry.name)
+                    .collect::<Vec<_>>(),
+            )
+            .finish()
+    }
+}
+
+impl Registry {
+    /// Add `C` as the subcommand `name`.
+    #[must_use]
+    pub fn with<C>(self, name: impl Into<String>) -> Self
+    where
+        C: clap::Parser + Command + 'static,
+    {
+        self.with_command(name, C::command(), |matches| {
+            C::from_arg_matches(matches).map(|cmd| Box::new(cmd) as Box<dyn Command>)
+        })
+    }
+
+    /// Add `command` as the subcommand `name`, calling `build` with its
+    /// matches when it is parsed.
+    #[must_use]
+    pub(crate) fn with_command<F>(
+        mut self,
+        name: impl Into<String>,
+        command: clap::Command,
+        build: F,
+    ) -> Self
+    where
+        F: Fn(&ArgMatches) -> Result<Box<dyn Command>, clap::Error> + Send + Sync + 'static,
+    {
+        // Registries are built once at startup and live for the rest of the
+        // process, this is what lets `Container` hand out static names.
+        let name: &'static str = Box::leak(name.into().into_boxed_str());
+
+        self.entries.push(Entry {
+            name,
+            command: command.name(name),
+            build: Box::new(build),
+        });
+
+        self
+    }
+
+    /// The names of the registered commands, in the order they were added.
+    pub fn names(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.entries.iter().map(|entry| entry.name)
+    }
+
+    /// Make this the registry used by [`Dynamic`].
+    pub fn install(self) -> Result<()> {
+        GLOBAL
+            .set(self)
+            .map_err(|_| eyre!("a registry has already been installed"))
+    }
+
+    fn get(name: &str) -> Option<&'static Entry> {
+        GLOBAL
+            .get()?
+            .entries
+            .iter()
+            .find(|entry| entry.name == name)
+    }
+
+    fn entries() -> &'static [Entry] {
+        GLOBAL.get().map_or(&[], |registry| &registry.entries)
+    }
+}
+
+/// A command from the installed [`Registry`] that was parsed.
+pub struct Registered {
+    name: &'static str,
+    cmd: Box<dyn Command>,
+}
+
+impl Registered {
+    /// The name the command was registered with.
+    #[must_use]
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// The parsed command.
+    #[must_use]
+    pub fn command(&self) -> &dyn Command {
+        &*self.cmd
+    }
+}
+
+impl fmt::Debug for Registered {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Registered")
+            .field("name", &self.name)
+            .finish_non_exhaustive()
+    }
+}
+
+/// The subcommands in `T` along with every registered command.
+///
+/// Use this as the type of a `#[command(subcommand)]` field in place of `T`.
+#[derive(Debug)]
+pub enum Dynamic<T> {
+    /// One of the subcommands known at compile time.
+    Static(T),
+    /// A command from the installed [`Registry`].
+    Registered(Registered),
+}
+
+impl<T: Subcommand> FromArgMatches for Dynamic<T> {
+    fn from_arg_matches(matches: &ArgMatches) -> Result<Self, clap::Error> {
+        let Some((name, sub)) = matches.subcommand() else {
+            return T::from_arg_matches(matches).map(Self::Static);
+        };
+
+        if T::has_subcommand(name) {
+            return T::from_arg_matches(matches).map(Self::Static);
+        }
+
+        let entry = Registry::get(name).ok_or_else(|| {
+            clap::Error::raw(
+                ErrorKind::InvalidSubcommand,
+                format!("the subcommand '{name}' wasn't recognized"),
+            )
+        })?;
+
+        Ok(Self::Registered(Registered {
+            name: entry.name,
+            cmd: (entry.build)(sub)?,
+        }))
+    }
+
+    fn update_from_arg_matches(&mut self, matches: &ArgMatches) -> Result<(), clap::Error> {
+        *self = Self::from_arg_matches(matches)?;
+
+        Ok(())
+    }
+}
+
+impl<T: Subcommand> Subcommand for Dynamic<T> {
+    fn augment_subcommands(cmd: clap::Command) -> clap::Command {
+        T::augment_subcommands(cmd).subcommands(registered::<T>())
+    }
+
+    fn augment_subcommands_for_update(cmd: clap::Command) -> clap::Command {
+        T::augment_subcommands_for_update(cmd).subcommands(registered::<T>())
+    }
+
+    fn has_subcommand(name: &str) -> bool {
+        T::has_subcommand(name) || Registry::get(name).is_some()
+    }
+}
+
+/// The registered commands that don't clash with one in `T`.
+fn registered<T: Subcommand>() -> impl Iterator<Item = clap::Command> {
+    Registry::entries()
+        .iter()
+        .filter(|entry| !T::has_subcommand(entry.name))
+        .map(|entry| entry.command.clone())
+}
+
+/// Registered commands are only known at runtime, the descriptor only has the
+/// subcommands in `T`.
+impl<T: Describe> Describe for Dynamic<T> {
+    const DESCRIPTOR: Descriptor = T::DESCRIPTOR;
+}
+
+impl<T: Subcommand + Container + 'static> Container for Dynamic<T> {
+    fn next(&self) -> Option<&dyn Command> {
+        match self {
+            Self::Static(cmd) => cmd.subcommand(),
+            Self::Registered(registered) => Some(&*registered.cmd),
+        }
+    }
+
+    fn next_mut(&mut self) -> Option<&mut dyn Command> {
+        match self {
+            Self::Static(cmd) => cmd.subcommand_mut(),
+            Self::Registered(registered) => Some(&mut *registered.cmd),
+        }
+    }
+
+    fn path_segment(&self) -> Option<&'static str> {
+        match self {
+            Self::Static(cmd) => cmd.path_segment(),
+            Self::Registered(registered) => Some(registered.name),
+        }
+    }
+
+    fn children(&self) -> &'static [&'static str] {
+        let id = TypeId::of::<T>();
+        let mut cache = CHILDREN.lock().unwrap_or_else(PoisonError::into_inner);
+
+        if let Some((_, children)) = cache.iter().find(|(key, _)| *key == id) {
+            return children;
+        }
+
+        let cmd = Self::augment_subcommands(clap::Command::new("dynamic"));
+        let children: &'static [&'static str] = Box::leak(
+            cmd.get_subcommands()
+                .map(|sub| &*Box::leak(sub.get_name().to_string().into_boxed_str()))
+                .collect(),
+        );
+
+        cache.push((id, children));
+
+        children
+    }
+
+    fn flag(&self) -> Option<&'static str> {
+        match self {
+            Self::Static(cmd) => cmd.flag(),
+            Self::Registered(_) => None,
+        }
+    }
+
+    fn flags() -> Vec<(Vec<&'static str>, &'static str)> {
+        T::flags()
+    }
+
+    fn all_examples() -> Vec<(Vec<&'static str>, &'static [&'static str])> {
+        T::all_examples()
+    }
+}
@@ -0,0 +1,245 @@
+//! An interactive shell over the command tree.
+//!
+//! Exploratory CLIs, such as database or infrastructure tools, are often used
+//! for many commands in a row. [`Repl`] reads lines from the terminal, parses
+//! each of them against the root command and runs the result through the same
+//! lifecycle as [`crate::execute`].
+//!
+//! Some things to note:
+//! - Lines are split like a shell would, quotes work as expected. The name of
+//!   the binary is left out, `projects list` instead of `my-cli projects list`.
+//! - With the `completion` feature, tab completes subcommands, flags and the
+//!   values from `cata::completion::values`.
+//! - History is kept in the platform state directory, see [`crate::dirs`].
+//! - Errors are printed and the shell keeps going. `exit`, `quit` or ctrl-d
+//!   leave, ctrl-c clears the current line.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use cata::{repl::Repl, Command, Container};
+//! use clap::{Parser, Subcommand};
+//!
+//! #[derive(Parser, Container)]
+//! struct Root {
+//!   #[command(subcommand)]
+//!   cmd: RootCmd,
+//! }
+//!
+//! impl Command for Root {}
+//!
+//! #[derive(Subcommand, Container)]
+//! enum RootCmd {
+//!   Tables(Tables),
+//! }
+//!
+//! #[derive(Parser, Container)]
+//! struct Tables {}
+//!
+//! #[async_trait::async_trait]
+//! impl Command for Tables {
+//!   async fn run(&self, _: &mut cata::Context) -> eyre::Result<()> {
+//!     println!("users");
+//!
+//!     Ok(())
+//!   }
+//! }
+//!
+//! #[tokio::main]
+//! async fn main() -> eyre::Result<()> {
+//!   Repl::<Root>::new("my-cli").run().await
+//! }
+//! ```
+#[cfg(feature = "completion")]
+use std::ffi::OsString;
+use std::{marker::PhantomData, path::PathBuf};
+
+use clap::Parser;
+use eyre::Result;
+use rustyline::{
+    completion::{Completer, Pair},
+    error::ReadlineError,
+    history::DefaultHistory,
+    Editor, Helper, Highlighter, Hinter, Validator,
+};
+
+use crate::{batch::split, dirs::Dirs, Command, ExecuteOptions};
+
+static HISTORY_FILE: &str = "history";
+static EXIT: &[&str] = &["exit", "quit"];
+
+/// Completes lines with clap's completion engine.
+#[derive(Helper, Highlighter, Hinter, Validator)]
+struct Completion {
+    #[cfg_attr(not(feature = "completion"), allow(dead_code))]
+    cmd: clap::Command,
+}
+
+/// Without the engine, nothing is completed.
+#[cfg(not(feature = "completion"))]
+impl Completer for Completion {
+    type Candidate = Pair;
+}
+
+#[cfg(feature = "completion")]
+impl Completer for Completion {
+    type Candidate = Pair;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _: &rustyline::Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<Pair>)> {
+        let line = &line[..pos];
+        let mut words = split(line);
+
+        if line.is_empty() || line.ends_with(char::is_whitespace) {
+            words.push(String::new());
+        }
+
+        let current = words.last().map_or(0, String::len);
+        let args = std::iter::once(self.cmd.get_name().to_string())
+            .chain(words)
+            .map(OsString::from)
+            .collect::<Vec<_>>();
+        let index = args.len() - 1;
+
+        let candidates = clap_complete::engine::complete(&mut self.cmd.clone(), args, index, None)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|candidate| {
+                let value = candidate.get_value().to_string_lossy().into_owned();
+
+                Pair {
+                    display: value.clone(),
+                    replacement: value,
+                }
+            })
+            .collect();
+
+        Ok((pos.saturating_sub(current), candidates))
+    }
+}
+
+/// An interactive shell for the command `C`.
+///
+/// See the module documentation for usage.
+pub struct Repl<C> {
+    prompt: String,
+    history: Option<PathBuf>,
+    options: ExecuteOptions,
+    cmd: PhantomData<fn() -> C>,
+}
+
+impl<C> std::fmt::Debug for Repl<C> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Repl")
+            .field("prompt", &self.prompt)
+            .field("history", &self.history)
+            .field("options", &self.options)
+            .finish()
+    }
+}
+
+impl<C> Repl<C>
+where
+    C: Parser + Command + 'static,
+{
+    /// Create a shell for the CLI `name`.
+    pub fn new(name: impl AsRef<str>) -> Self {
+        Self {
+            prompt: format!("{}> ", name.as_ref()),
+            history: Dirs::new(&name).state().map(|dir| dir.join(HISTORY_FILE)),
+            options: ExecuteOptions::default(),
+            cmd: PhantomData,
+        }
+    }
+
+    /// Set the prompt shown before every line.
+    ///
+    /// By default, this is the name of the CLI followed by `> `.
+    #[must_use]
+    pub fn with_prompt(mut self, prompt: impl Into<String>) -> Self {
+        self.prompt = prompt.into();
+        self
+    }
+
+    /// Set where history is kept, `None` turns it off.
+    #[must_use]
+    pub fn with_history(mut self, path: Option<PathBuf>) -> Self {
+        self.history = path;
+        self
+    }
+
+    /// Set the options every line is executed with.
+    #[must_use]
+    pub fn with_options(mut self, options: ExecuteOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Read and execute lines until the user leaves.
+    pub async fn run(self) -> Result<()> {
+        let cmd = C::command();
+        let name = cmd.get_name().to_string();
+
+        let mut editor = Editor::<Completion, DefaultHistory>::new()?;
+        editor.set_helper(Some(Completion { cmd }));
+
+        if let Some(path) = &self.history {
+            editor.load_history(path).ok();
+        }
+
+        loop {
+            let prompt = self.prompt.clone();
+            let (returned, line) = crate::runtime::unblock(move || {
+                let line = editor.readline(&prompt);
+
+                (editor, line)
+            })
+            .await?;
+            editor = returned;
+
+            let line = match line {
+                Ok(line) => line,
+                Err(ReadlineError::Interrupted) => continue,
+                Err(ReadlineError::Eof) => break,
+                Err(e) => return Err(e.into()),
+            };
+
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            editor.add_history_entry(line)?;
+
+            if EXIT.contains(&line) {
+                break;
+            }
+
+            let cmd = match C::try_parse_from(std::iter::once(name.clone()).chain(split(line))) {
+                Ok(cmd) => cmd,
+                Err(e) => {
+                    e.print()?;
+                    continue;
+                }
+            };
+
+            if let Err(e) = crate::execute_with(&cmd, self.options.clone()).await {
+                eprintln!("Error: {e:?}");
+            }
+        }
+
+        if let Some(path) = &self.history {
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+
+            editor.save_history(path)?;
+        }
+
+        Ok(())
+    }
+}
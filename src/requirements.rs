@@ -0,0 +1,280 @@
+//! Prerequisites that commands check before they start.
+//!
+//! A command that shells out to `kubectl` or needs an API token shouldn't find
+//! out halfway through `run`. Commands return the [`Requirement`]s they have
+//! from [`Command::requirements`] and [`crate::execute`] checks every one of
+//! them, for every command that was parsed, before the first `pre_run`. When
+//! any are unmet, nothing runs and a single [`Unmet`] error lists all of them.
+//!
+//! Some things to note:
+//! - Checks are blocking and run on tokio's blocking pool.
+//! - Requirements are deduplicated by their message, parents and children can
+//!   both ask for the same thing.
+//! - Binaries are looked up on `$PATH`, versions are read from the first
+//!   dotted number printed by `<binary> --version`.
+//!
+//! # Examples
+//!
+//! ```
+//! use cata::{
+//!   requirements::{Requirement, Unmet},
+//!   Command, Container,
+//! };
+//! use clap::Parser;
+//!
+//! #[derive(Parser, Container)]
+//! struct Deploy {}
+//!
+//! #[async_trait::async_trait]
+//! impl Command for Deploy {
+//!   fn requirements(&self) -> Vec<Requirement> {
+//!     vec![
+//!       Requirement::env("CATA_EXAMPLE_TOKEN").with_hint("run `my-cli login` first"),
+//!       Requirement::new(|| Err(eyre::eyre!("the cluster is read-only"))),
+//!     ]
+//!   }
+//!
+//!   async fn run(&self, _: &mut cata::Context) -> eyre::Result<()> {
+//!     unreachable!("requirements are checked first")
+//!   }
+//! }
+//!
+//! # #[tokio::main]
+//! # async fn main() {
+//! let err = cata::execute(&Deploy::parse_from(["deploy"])).await.unwrap_err();
+//!
+//! assert_eq!(err.downcast_ref::<Unmet>().unwrap().0.len(), 2);
+//! assert_eq!(
+//!   err.to_string(),
+//!   "unmet requirements:\n  \
+//!    - `CATA_EXAMPLE_TOKEN` is not set, run `my-cli login` first\n  \
+//!    - the cluster is read-only",
+//! );
+//! # }
+//! ```
+//!
+//! [`Command::requirements`]: crate::Command::requirements
+use std::{
+    fmt,
+    net::{TcpStream, ToSocketAddrs},
+    sync::Arc,
+    time::Duration,
+};
+
+use eyre::{eyre, Result};
+
+static CONNECT_TIMEOUT: Duration = Duration::from_secs(3);
+
+type Check = Arc<dyn Fn() -> Result<()> + Send + Sync>;
+
+/// Something that has to be true before a command can run, see
+/// [`crate::Command::requirements`].
+#[derive(Clone)]
+pub struct Requirement {
+    check: Check,
+    hint: Option<String>,
+}
+
+impl fmt::Debug for Requirement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Requirement")
+            .field("hint", &self.hint)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Requirement {
+    /// A requirement that is met when `check` succeeds.
+    ///
+    /// The error's message is what the user sees.
+    pub fn new(check: impl Fn() -> Result<()> + Send + Sync + 'static) -> Self {
+        Self {
+            check: Arc::new(check),
+            hint: None,
+        }
+    }
+
+    /// The environment variable `var` has to be set.
+    pub fn env(var: impl Into<String>) -> Self {
+        let var = var.into();
+
+        Self::new(move || {
+            std::env::var_os(&var)
+                .map(|_| ())
+                .ok_or_else(|| eyre!("`{var}` is not set"))
+        })
+    }
+
+    /// The executable `name` has to be on `$PATH`.
+    #[cfg(any(unix, windows))]
+    pub fn binary(name: impl Into<String>) -> Self {
+        let name = name.into();
+
+        Self::new(move || find(&name).map(|_| ()))
+    }
+
+    /// The executable `name` has to be on `$PATH` and report a version of at
+    /// least `min`, such as `1.25`.
+    #[cfg(any(unix, windows))]
+    pub fn version(name: impl Into<String>, min: impl Into<String>) -> Self {
+        let name = name.into();
+        let min = min.into();
+
+        Self::new(move || {
+            let wanted = parse(&min).ok_or_else(|| eyre!("`{min}` is not a version"))?;
+
+            let output = std::process::Command::new(find(&name)?)
+                .arg("--version")
+                .stdin(std::process::Stdio::null())
+                .output()?;
+
+            let found = String::from_utf8_lossy(&output.stdout)
+                .split_whitespace()
+                .find_map(|word| parse(word.trim_start_matches('v')))
+                .ok_or_else(|| eyre!("unable to determine the version of `{name}`"))?;
+
+            if compare(&found, &wanted).is_lt() {
+                return Err(eyre!(
+                    "`{name}` is {}, at least {min} is required",
+                    join(&found)
+                ));
+            }
+
+            Ok(())
+        })
+    }
+
+    /// A TCP connection to `addr`, such as `api.example.com:443`, has to
+    /// succeed within 3s.
+    pub fn reachable(addr: impl Into<String>) -> Self {
+        let addr = addr.into();
+
+        Self::new(move || {
+            let unreachable = |e: &dyn fmt::Display| eyre!("unable to reach `{addr}`: {e}");
+
+            let target = addr
+                .to_socket_addrs()
+                .map_err(|e| unreachable(&e))?
+                .next()
+                .ok_or_else(|| unreachable(&"no addresses found"))?;
+
+            TcpStream::connect_timeout(&target, CONNECT_TIMEOUT).map_err(|e| unreachable(&e))?;
+
+            Ok(())
+        })
+    }
+
+    /// Tell the user how to fix it when the requirement isn't met.
+    #[must_use]
+    pub fn with_hint(mut self, hint: impl Into<String>) -> Self {
+        self.hint = Some(hint.into());
+        self
+    }
+
+    /// Why the requirement isn't met, if it isn't.
+    fn unmet(&self) -> Option<String> {
+        let err = (self.check)().err()?;
+
+        Some(match &self.hint {
+            Some(hint) => format!("{err}, {hint}"),
+            None => err.to_string(),
+        })
+    }
+}
+
+/// Returned by [`crate::execute`] when requirements weren't met.
+///
+/// It contains a message for each of them.
+#[derive(Debug)]
+pub struct Unmet(pub Vec<String>);
+
+impl fmt::Display for Unmet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "unmet requirements:")?;
+
+        for message in &self.0 {
+            write!(f, "\n  - {message}")?;
+        }
+
+        Ok(())
+    }
+}
+
+impl std::error::Error for Unmet {}
+
+/// Check every requirement, failing with all of the ones that aren't met.
+pub(crate) async fn check(requirements: Vec<Requirement>) -> Result<()> {
+    if requirements.is_empty() {
+        return Ok(());
+    }
+
+    let unmet = crate::runtime::unblock(move || {
+        let mut unmet: Vec<String> = Vec::new();
+
+        for message in requirements.iter().filter_map(Requirement::unmet) {
+            if !unmet.contains(&message) {
+                unmet.push(message);
+            }
+        }
+
+        unmet
+    })
+    .await?;
+
+    if unmet.is_empty() {
+        return Ok(());
+    }
+
+    Err(Unmet(unmet).into())
+}
+
+/// The path of the executable `name` on `$PATH`.
+#[cfg(any(unix, windows))]
+fn find(name: &str) -> Result<std::path::PathBuf> {
+    let path = std::env::var_os("PATH").unwrap_or_default();
+    let file = if cfg!(windows) {
+        format!("{name}.exe")
+    } else {
+        name.to_string()
+    };
+
+    std::env::split_paths(&path)
+        .map(|dir| dir.join(&file))
+        .find(|candidate| crate::dirs::is_executable(candidate))
+        .ok_or_else(|| eyre!("`{name}` was not found on $PATH"))
+}
+
+/// The components of a dotted version, such as `1.25.3`.
+#[cfg(any(unix, windows))]
+fn parse(version: &str) -> Option<Vec<u64>> {
+    let version = version.trim_end_matches(|c: char| !c.is_ascii_digit());
+
+    if !version.contains('.') {
+        return None;
+    }
+
+    version.split('.').map(|part| part.parse().ok()).collect()
+}
+
+/// Compare two versions, treating missing components as 0.
+#[cfg(any(unix, windows))]
+fn compare(a: &[u64], b: &[u64]) -> std::cmp::Ordering {
+    (0..a.len().max(b.len()))
+        .map(|i| {
+            a.get(i)
+                .copied()
+                .unwrap_or_default()
+                .cmp(&b.get(i).copied().unwrap_or_default())
+        })
+        .find(|ordering| ordering.is_ne())
+        .unwrap_or(std::cmp::Ordering::Equal)
+}
+
+#[cfg(any(unix, windows))]
+fn join(version: &[u64]) -> String {
+    version
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join(".")
+}
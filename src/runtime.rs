@@ -0,0 +1,467 @@
+//! The async runtime that executions run on.
+//!
+//! [`crate::execute`] needs very little from a runtime: a way to spawn
+//! background tasks, a timer and, optionally, signals. Those come from a
+//! [`Runtime`]. With the `tokio` feature, the default, that is [`Tokio`].
+//! Libraries that embed cata on another executor, such as async-std or smol,
+//! implement [`Runtime`] for it and pass it to
+//! [`crate::ExecuteOptions::with_runtime`].
+//!
+//! Some things to note:
+//! - cata keeps track of spawned tasks and cancels them itself, runtimes only
+//!   have to run them.
+//! - Blocking work, such as asking for confirmation, happens on a thread of its
+//!   own and doesn't involve the runtime.
+//! - Ctrl-c and the signals forwarded to [`crate::Command::on_signal`] only
+//!   arrive when the runtime provides them.
+//! - Tokio keeps its handlers installed for the life of the process once a
+//!   signal has been listened for. [`Tokio`] keeps listening on a thread of its
+//!   own and, outside of an execution, does what the default handler would
+//!   have for ctrl-c, `SIGTERM` and `SIGHUP`, ending the process.
+//! - Without the `tokio` feature, and without a runtime of your own, tasks and
+//!   timers each get a thread. That works on any executor but isn't meant for
+//!   many tasks.
+//! - [`crate::blocking`], [`crate::telemetry`] and [`crate::update`] always
+//!   need tokio, their features turn on `tokio`. Their tasks and timers still
+//!   go through the current [`Runtime`].
+//!
+//! # Examples
+//!
+//! ```
+//! use std::{
+//!   sync::{
+//!     atomic::{AtomicUsize, Ordering},
+//!     Arc,
+//!   },
+//!   time::Duration,
+//! };
+//!
+//! use cata::{
+//!   command::TimedOut,
+//!   runtime::{BoxFuture, Runtime, Tokio},
+//!   Command, Container, ExecuteOptions,
+//! };
+//! use clap::Parser;
+//!
+//! /// Another executor would go here, this one counts timers.
+//! #[derive(Default)]
+//! struct Counting(Arc<AtomicUsize>);
+//!
+//! impl Runtime for Counting {
+//!   fn spawn(&self, fut: BoxFuture<()>) {
+//!     Tokio.spawn(fut);
+//!   }
+//!
+//!   fn sleep(&self, duration: Duration) -> BoxFuture<()> {
+//!     self.0.fetch_add(1, Ordering::SeqCst);
+//!     Tokio.sleep(duration)
+//!   }
+//! }
+//!
+//! #[derive(Parser, Container)]
+//! struct Root {}
+//!
+//! #[async_trait::async_trait]
+//! impl Command for Root {
+//!   async fn run(&self, _: &mut cata::Context) -> eyre::Result<()> {
+//!     std::future::pending().await
+//!   }
+//! }
+//!
+//! # #[tokio::main]
+//! # async fn main() {
+//! let runtime = Counting::default();
+//! let timers = runtime.0.clone();
+//!
+//! let options = ExecuteOptions::default()
+//!   .with_runtime(runtime)
+//!   .with_timeout(Duration::from_millis(10));
+//!
+//! let err = cata::execute_with(&Root::parse_from(["root"]), options)
+//!   .await
+//!   .unwrap_err();
+//!
+//! assert!(err.is::<TimedOut>());
+//! assert!(timers.load(Ordering::SeqCst) > 0);
+//! # }
+//! ```
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use eyre::{eyre, Result};
+use tokio::sync::{mpsc::UnboundedReceiver, oneshot};
+
+use crate::command::Signal;
+
+/// A future that can be sent to a runtime.
+pub type BoxFuture<T> = Pin<Box<dyn Future<Output = T> + Send + 'static>>;
+
+tokio::task_local! {
+    static RUNTIME: Arc<dyn Runtime>;
+}
+
+/// How many executions are handling signals right now.
+static LISTENING: AtomicUsize = AtomicUsize::new(0);
+
+/// Marks an execution as handling signals, for as long as it is held.
+pub(crate) struct Listening(());
+
+impl Listening {
+    pub(crate) fn start() -> Self {
+        LISTENING.fetch_add(1, Ordering::SeqCst);
+
+        Self(())
+    }
+}
+
+impl Drop for Listening {
+    fn drop(&mut self) {
+        LISTENING.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// What an execution needs from an async runtime.
+///
+/// See the module documentation for usage.
+pub trait Runtime: Send + Sync {
+    /// Run `fut` to completion in the background.
+    fn spawn(&self, fut: BoxFuture<()>);
+
+    /// Complete once `duration` has elapsed.
+    fn sleep(&self, duration: Duration) -> BoxFuture<()>;
+
+    /// Run `f` in the background, on a thread where it is fine to block.
+    ///
+    /// By default, it gets a thread of its own.
+    fn spawn_blocking(&self, f: Box<dyn FnOnce() + Send>) {
+        // There are no threads to spare on wasm.
+        #[cfg(target_family = "wasm")]
+        f();
+
+        #[cfg(not(target_family = "wasm"))]
+        std::thread::spawn(f);
+    }
+
+    /// Complete on the next ctrl-c.
+    ///
+    /// By default, ctrl-c never arrives.
+    fn ctrl_c(&self) -> BoxFuture<()> {
+        Box::pin(std::future::pending())
+    }
+
+    /// Receive the signals that are forwarded to
+    /// [`crate::Command::on_signal`], until the receiver is dropped.
+    ///
+    /// By default, there are none.
+    fn signals(&self) -> Option<UnboundedReceiver<Signal>> {
+        None
+    }
+}
+
+/// The tokio runtime that the current task is running on.
+#[cfg(feature = "tokio")]
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Tokio;
+
+#[cfg(feature = "tokio")]
+impl Runtime for Tokio {
+    fn spawn(&self, fut: BoxFuture<()>) {
+        tokio::spawn(fut);
+    }
+
+    fn sleep(&self, duration: Duration) -> BoxFuture<()> {
+        Box::pin(tokio::time::sleep(duration))
+    }
+
+    /// Tokio waits for blocking work when it shuts down, which makes sure it
+    /// finishes before the process exits. Outside of a runtime, such as after
+    /// it has shut down, a new one is built for `f`.
+    fn spawn_blocking(&self, f: Box<dyn FnOnce() + Send>) {
+        if let Ok(current) = tokio::runtime::Handle::try_current() {
+            current.spawn_blocking(f);
+
+            return;
+        }
+
+        match tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+        {
+            Ok(runtime) => {
+                runtime.spawn_blocking(f);
+            }
+            Err(_) => f(),
+        }
+    }
+
+    #[cfg(not(target_family = "wasm"))]
+    fn ctrl_c(&self) -> BoxFuture<()> {
+        static FALLBACK: std::sync::Once = std::sync::Once::new();
+
+        FALLBACK.call_once(|| {
+            fallback(async {
+                while tokio::signal::ctrl_c().await.is_ok() {
+                    if LISTENING.load(Ordering::SeqCst) == 0 {
+                        interrupt();
+                    }
+                }
+            });
+        });
+
+        Box::pin(async {
+            if tokio::signal::ctrl_c().await.is_err() {
+                std::future::pending::<()>().await;
+            }
+        })
+    }
+
+    #[cfg(unix)]
+    fn signals(&self) -> Option<UnboundedReceiver<Signal>> {
+        use tokio::signal::unix::{signal, SignalKind};
+
+        async fn next(signal: Option<&mut tokio::signal::unix::Signal>) {
+            if let Some(signal) = signal {
+                if signal.recv().await.is_some() {
+                    return;
+                }
+            }
+
+            std::future::pending::<()>().await;
+        }
+
+        static FALLBACK: std::sync::Once = std::sync::Once::new();
+
+        FALLBACK.call_once(|| {
+            for (kind, raised) in [
+                (SignalKind::terminate(), libc::SIGTERM),
+                (SignalKind::hangup(), libc::SIGHUP),
+            ] {
+                fallback(async move {
+                    let Ok(mut listener) = signal(kind) else {
+                        return;
+                    };
+
+                    while listener.recv().await.is_some() {
+                        if LISTENING.load(Ordering::SeqCst) == 0 {
+                            raise(raised);
+                        }
+                    }
+                });
+            }
+        });
+
+        // Signals that can't be registered never arrive.
+        let mut terminate = signal(SignalKind::terminate()).ok();
+        let mut hangup = signal(SignalKind::hangup()).ok();
+        let (tx, rx) = tokio::sync::mpsc::unbounded_channel();
+
+        self.spawn(Box::pin(async move {
+            loop {
+                let signal = tokio::select! {
+                    () = tx.closed() => return,
+                    () = next(terminate.as_mut()) => Signal::Terminate,
+                    () = next(hangup.as_mut()) => Signal::Hangup,
+                };
+
+                if tx.send(signal).is_err() {
+                    return;
+                }
+            }
+        }));
+
+        Some(rx)
+    }
+}
+
+/// Run `listener` on a runtime of its own, which is there for as long as the
+/// process is, unlike the one executions run on.
+#[cfg(all(feature = "tokio", not(target_family = "wasm")))]
+fn fallback(listener: impl Future<Output = ()> + Send + 'static) {
+    static HANDLE: std::sync::OnceLock<Option<tokio::runtime::Handle>> = std::sync::OnceLock::new();
+
+    let handle = HANDLE.get_or_init(|| {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .ok()?;
+        let handle = runtime.handle().clone();
+
+        std::thread::Builder::new()
+            .name("cata-signals".into())
+            .spawn(move || runtime.block_on(std::future::pending::<()>()))
+            .ok()?;
+
+        Some(handle)
+    });
+
+    if let Some(handle) = handle {
+        handle.spawn(listener);
+    }
+}
+
+/// End the process like the default ctrl-c handler does.
+#[cfg(all(feature = "tokio", not(target_family = "wasm")))]
+fn interrupt() -> ! {
+    #[cfg(unix)]
+    raise(libc::SIGINT);
+
+    #[cfg(not(unix))]
+    std::process::exit(130);
+}
+
+/// Raise `signal` with its default handler, which ends the process.
+#[cfg(all(feature = "tokio", unix))]
+fn raise(signal: libc::c_int) -> ! {
+    // SAFETY: this isn't called from a signal handler, both only touch the
+    // disposition of `signal`.
+    unsafe {
+        libc::signal(signal, libc::SIG_DFL);
+        libc::raise(signal);
+    }
+
+    // Like a shell reports it, in case the signal is blocked.
+    std::process::exit(128 + signal)
+}
+
+/// Gives every task and timer a thread, for when there is no runtime to use.
+#[cfg(not(feature = "tokio"))]
+struct Threads;
+
+#[cfg(not(feature = "tokio"))]
+impl Runtime for Threads {
+    fn spawn(&self, fut: BoxFuture<()>) {
+        std::thread::spawn(move || block_on(fut));
+    }
+
+    fn sleep(&self, duration: Duration) -> BoxFuture<()> {
+        let (tx, rx) = oneshot::channel();
+
+        std::thread::spawn(move || {
+            std::thread::sleep(duration);
+            let _ = tx.send(());
+        });
+
+        Box::pin(async move {
+            let _ = rx.await;
+        })
+    }
+}
+
+/// Poll `fut` on the current thread until it completes.
+#[cfg(not(feature = "tokio"))]
+fn block_on<F: Future>(fut: F) -> F::Output {
+    use std::task::{Context, Poll, Wake, Waker};
+
+    struct Unpark(std::thread::Thread);
+
+    impl Wake for Unpark {
+        fn wake(self: Arc<Self>) {
+            self.0.unpark();
+        }
+    }
+
+    let waker = Waker::from(Arc::new(Unpark(std::thread::current())));
+    let mut cx = Context::from_waker(&waker);
+    let mut fut = std::pin::pin!(fut);
+
+    loop {
+        if let Poll::Ready(output) = fut.as_mut().poll(&mut cx) {
+            return output;
+        }
+
+        std::thread::park();
+    }
+}
+
+/// The runtime used when none was configured.
+pub(crate) fn default() -> Arc<dyn Runtime> {
+    #[cfg(feature = "tokio")]
+    return Arc::new(Tokio);
+
+    #[cfg(not(feature = "tokio"))]
+    Arc::new(Threads)
+}
+
+/// The runtime of the current execution, or the default one outside of
+/// [`crate::execute`].
+pub(crate) fn current() -> Arc<dyn Runtime> {
+    RUNTIME.try_with(Arc::clone).unwrap_or_else(|_| default())
+}
+
+/// Run `fut` with `runtime` as the current runtime.
+pub(crate) async fn scope<F: Future>(runtime: Arc<dyn Runtime>, fut: F) -> F::Output {
+    RUNTIME.scope(runtime, fut).await
+}
+
+/// Complete with `fut`'s output, or `None` once `duration` has elapsed.
+#[cfg_attr(
+    not(any(feature = "background", feature = "daemon", feature = "update")),
+    allow(dead_code)
+)]
+pub(crate) async fn timeout<F: Future>(duration: Duration, fut: F) -> Option<F::Output> {
+    match race(fut, current().sleep(duration)).await {
+        Either::Left(output) => Some(output),
+        Either::Right(()) => None,
+    }
+}
+
+/// Which of the futures passed to [`race`] completed first.
+pub(crate) enum Either<L, R> {
+    Left(L),
+    Right(R),
+}
+
+/// Complete with the output of whichever of `left` and `right` completes
+/// first, `left` when both have.
+pub(crate) async fn race<L: Future, R: Future>(left: L, right: R) -> Either<L::Output, R::Output> {
+    #[cfg(feature = "tokio")]
+    return tokio::select! {
+        biased;
+
+        output = left => Either::Left(output),
+        output = right => Either::Right(output),
+    };
+
+    #[cfg(not(feature = "tokio"))]
+    {
+        let (mut left, mut right) = (std::pin::pin!(left), std::pin::pin!(right));
+
+        std::future::poll_fn(|cx| {
+            if let std::task::Poll::Ready(output) = left.as_mut().poll(cx) {
+                return std::task::Poll::Ready(Either::Left(output));
+            }
+
+            right.as_mut().poll(cx).map(Either::Right)
+        })
+        .await
+    }
+}
+
+/// Run `f` on a thread where it is fine to block.
+pub(crate) async fn unblock<F, T>(f: F) -> Result<T>
+where
+    F: FnOnce() -> T + Send + 'static,
+    T: Send + 'static,
+{
+    // There are no threads to spare on wasm.
+    #[cfg(target_family = "wasm")]
+    return Ok(f());
+
+    #[cfg(not(target_family = "wasm"))]
+    {
+        let (tx, rx) = oneshot::channel();
+
+        std::thread::spawn(move || {
+            tx.send(f()).ok();
+        });
+
+        rx.await.map_err(|_| eyre!("blocking work panicked"))
+    }
+}
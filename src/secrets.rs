@@ -0,0 +1,655 @@
+//! Store and retrieve secrets such as API tokens.
+//!
+//! Secrets are kept in the OS keychain (Keychain on macOS, Credential Manager
+//! on Windows and the Secret Service on Linux). When the keychain isn't
+//! available, for example in containers or over SSH, secrets fall back to a
+//! file in the platform data directory.
+//!
+//! Some things to note:
+//! - The fallback file is encrypted with a random key of its own, generated
+//!   the first time a secret is stored and kept next to it, `secrets.key` by
+//!   default. On unix, both files are only readable by the user. This keeps
+//!   tokens from being readable at a glance, but anyone who can read both files
+//!   can decrypt them. It is not a replacement for the keychain.
+//! - Changes to the file take a [`crate::lock::Lock`] on `secrets.lock` next to
+//!   it, concurrent invocations wait on each other instead of losing secrets.
+//! - [`login`] and [`logout`] provide the bodies for the usual `login`/`logout`
+//!   command pair.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use cata::{
+//!   secrets::{self, Secrets},
+//!   Command, Container,
+//! };
+//! use clap::Parser;
+//!
+//! #[derive(Parser, Container)]
+//! struct Login {
+//!   /// Token to store, read from stdin when not provided.
+//!   #[arg(long)]
+//!   token: Option<String>,
+//! }
+//!
+//! #[async_trait::async_trait]
+//! impl Command for Login {
+//!   async fn run(&self, _: &mut cata::Context) -> eyre::Result<()> {
+//!     secrets::login(&Secrets::new("my-cli"), "token", self.token.clone())
+//!   }
+//! }
+//! ```
+use std::{
+    collections::HashMap,
+    io::{BufRead, IsTerminal, Write},
+    path::{Path, PathBuf},
+};
+
+use eyre::{eyre, Result, WrapErr};
+use ring::{
+    aead::{self, Aad, LessSafeKey, Nonce, UnboundKey, NONCE_LEN},
+    rand::{SecureRandom, SystemRandom},
+};
+
+use crate::{
+    dirs::Dirs,
+    hex,
+    lock::{Guard, Lock},
+};
+
+static SECRETS_FILE: &str = "secrets.json";
+
+/// A store for secrets namespaced by a service name.
+///
+/// See the module documentation for usage.
+#[derive(Clone, Debug)]
+pub struct Secrets {
+    service: String,
+    keyring: bool,
+    path: Option<PathBuf>,
+}
+
+impl Secrets {
+    /// Create a new store for `service`, usually the name of the CLI.
+    pub fn new(service: impl AsRef<str>) -> Self {
+        Self {
+            service: service.as_ref().into(),
+            keyring: true,
+            path: Dirs::new(&service).data().map(|dir| dir.join(SECRETS_FILE)),
+        }
+    }
+
+    /// Never use the OS keychain and always store secrets in the file.
+    ///
+    /// ```
+    /// use cata::secrets::Secrets;
+    ///
+    /// let dir = std::env::temp_dir().join("cata-secrets-example");
+    /// # std::fs::remove_dir_all(&dir).ok();
+    /// let secrets = Secrets::new("my-cli")
+    ///   .without_keyring()
+    ///   .with_path(dir.join("secrets.json"));
+    ///
+    /// assert_eq!(secrets.get("token")?, None);
+    /// assert!(!dir.join("secrets.key").exists());
+    ///
+    /// secrets.set("token", "hunter2")?;
+    /// assert_eq!(secrets.get("token")?.as_deref(), Some("hunter2"));
+    /// assert!(!std::fs::read_to_string(dir.join("secrets.json"))?.contains("hunter2"));
+    ///
+    /// #[cfg(unix)]
+    /// for file in ["secrets.json", "secrets.key"] {
+    ///   use std::os::unix::fs::PermissionsExt;
+    ///
+    ///   let mode = std::fs::metadata(dir.join(file))?.permissions().mode();
+    ///   assert_eq!(mode & 0o777, 0o600);
+    /// }
+    ///
+    /// // Without its key, the file is of no use.
+    /// std::fs::remove_file(dir.join("secrets.key"))?;
+    /// assert!(secrets.get("token").is_err());
+    /// # std::fs::remove_dir_all(&dir).ok();
+    /// # Ok::<_, eyre::Report>(())
+    /// ```
+    #[must_use]
+    pub fn without_keyring(mut self) -> Self {
+        self.keyring = false;
+        self
+    }
+
+    /// Set the path of the fallback file.
+    ///
+    /// By default, this is `secrets.json` in the platform data directory.
+    #[must_use]
+    pub fn with_path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.path = Some(path.into());
+        self
+    }
+
+    /// Retrieve the secret stored under `key`, if there is one.
+    pub fn get(&self, key: &str) -> Result<Option<String>> {
+        if self.keyring {
+            match keyring::Entry::new(&self.service, key).and_then(|entry| entry.get_password()) {
+                Ok(value) => return Ok(Some(value)),
+                Err(keyring::Error::NoEntry) => {}
+                Err(e) if unavailable(&e) => {
+                    tracing::debug!("keyring unavailable, using file: {e}");
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+
+        self.file()?.get(key)
+    }
+
+    /// Store `value` under `key`, replacing anything that was there.
+    pub fn set(&self, key: &str, value: &str) -> Result<()> {
+        if self.keyring {
+            match keyring::Entry::new(&self.service, key)
+                .and_then(|entry| entry.set_password(value))
+            {
+                Ok(()) => return Ok(()),
+                Err(e) if unavailable(&e) => {
+                    tracing::debug!("keyring unavailable, using file: {e}");
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+
+        self.file()?.set(key, value)
+    }
+
+    /// Remove the secret stored under `key`.
+    ///
+    /// Removing a secret that doesn't exist is not an error.
+    pub fn delete(&self, key: &str) -> Result<()> {
+        if self.keyring {
+            match keyring::Entry::new(&self.service, key).and_then(|entry| entry.delete_password())
+            {
+                Ok(()) | Err(keyring::Error::NoEntry) => {}
+                Err(e) if unavailable(&e) => {
+                    tracing::debug!("keyring unavailable, using file: {e}");
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+
+        self.file()?.delete(key)
+    }
+
+    fn file(&self) -> Result<SecretsFile> {
+        let path = self
+            .path
+            .clone()
+            .ok_or_else(|| eyre!("no data directory available to store secrets"))?;
+
+        Ok(SecretsFile { path })
+    }
+}
+
+/// Whether the error means the keychain can't be used at all.
+fn unavailable(err: &keyring::Error) -> bool {
+    matches!(
+        err,
+        keyring::Error::NoStorageAccess(_) | keyring::Error::PlatformFailure(_)
+    )
+}
+
+/// Open `path` for writing, only readable by the user on unix.
+///
+/// The permissions are set again on files that already existed.
+fn private(path: &Path, options: &mut std::fs::OpenOptions) -> std::io::Result<std::fs::File> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    options.write(true);
+
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(options, 0o600);
+
+    let file = options.open(path)?;
+
+    #[cfg(unix)]
+    file.set_permissions(std::os::unix::fs::PermissionsExt::from_mode(0o600))?;
+
+    Ok(file)
+}
+
+/// An encrypted file of secrets, used when the keychain is unavailable.
+///
+/// Each value is stored hex encoded as the nonce followed by the ciphertext.
+struct SecretsFile {
+    path: PathBuf,
+}
+
+impl SecretsFile {
+    /// The key the secrets are encrypted with, generated the first time it is
+    /// needed to store one.
+    fn key(&self, generate: bool) -> Result<LessSafeKey> {
+        let path = self.path.with_extension("key");
+
+        let bytes = match std::fs::read(&path) {
+            Ok(bytes) => bytes,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound && generate => {
+                Self::generate(&path)?
+            }
+            Err(e) => return Err(eyre!("unable to read {}: {e}", path.display())),
+        };
+
+        let key = UnboundKey::new(&aead::CHACHA20_POLY1305, &bytes)
+            .map_err(|_| eyre!("{} isn't a valid secrets key", path.display()))?;
+
+        Ok(LessSafeKey::new(key))
+    }
+
+    /// Generate a key at `path`, or read the one another process got to first.
+    ///
+    /// The key is written in full next to `path` and linked into place, which
+    /// never replaces a key that is already there or leaves a partial one
+    /// behind.
+    fn generate(path: &Path) -> Result<Vec<u8>> {
+        let mut key = vec![0u8; aead::CHACHA20_POLY1305.key_len()];
+        SystemRandom::new()
+            .fill(&mut key)
+            .map_err(|_| eyre!("unable to generate secrets key"))?;
+
+        let tmp = path.with_extension(format!("key.{}.tmp", std::process::id()));
+        let linked = private(
+            &tmp,
+            std::fs::OpenOptions::new().create(true).truncate(true),
+        )
+        .and_then(|mut file| {
+            file.write_all(&key)?;
+            file.sync_all()
+        })
+        .and_then(|()| std::fs::hard_link(&tmp, path));
+
+        std::fs::remove_file(&tmp).ok();
+
+        match linked {
+            Ok(()) => Ok(key),
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+                std::fs::read(path).map_err(|e| eyre!("unable to read {}: {e}", path.display()))
+            }
+            Err(e) => Err(eyre!("unable to create {}: {e}", path.display())),
+        }
+    }
+
+    fn load(&self) -> Result<HashMap<String, String>> {
+        match std::fs::read_to_string(&self.path) {
+            Ok(raw) => Ok(serde_json::from_str(&raw)?),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(HashMap::new()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Write the secrets next to the file and move them over it, so that it is
+    /// never left half written.
+    fn store(&self, secrets: &HashMap<String, String>) -> Result<()> {
+        let tmp = self
+            .path
+            .with_extension(format!("{}.tmp", std::process::id()));
+
+        let written = private(
+            &tmp,
+            std::fs::OpenOptions::new().create(true).truncate(true),
+        )
+        .and_then(|mut file| {
+            file.write_all(serde_json::to_string_pretty(secrets)?.as_bytes())?;
+            file.sync_all()
+        })
+        .and_then(|()| std::fs::rename(&tmp, &self.path));
+
+        if let Err(e) = written {
+            std::fs::remove_file(&tmp).ok();
+
+            return Err(eyre!("unable to write {}: {e}", self.path.display()));
+        }
+
+        Ok(())
+    }
+
+    fn get(&self, key: &str) -> Result<Option<String>> {
+        let Some(raw) = self.load()?.remove(key) else {
+            return Ok(None);
+        };
+
+        let mut raw = hex::decode(&raw).wrap_err("invalid secret encoding")?;
+        if raw.len() < NONCE_LEN {
+            return Err(eyre!("secret {key} is corrupt"));
+        }
+
+        let mut ciphertext = raw.split_off(NONCE_LEN);
+        let nonce =
+            Nonce::try_assume_unique_for_key(&raw).map_err(|_| eyre!("secret {key} is corrupt"))?;
+
+        let plaintext = self
+            .key(false)?
+            .open_in_place(nonce, Aad::from(key.as_bytes()), &mut ciphertext)
+            .map_err(|_| eyre!("unable to decrypt secret {key}, store it again"))?;
+
+        Ok(Some(String::from_utf8(plaintext.to_vec())?))
+    }
+
+    /// Keep other invocations from changing the file until the guard is
+    /// dropped.
+    fn lock(&self) -> Result<Guard> {
+        Lock::new(SECRETS_FILE)
+            .with_path(self.path.with_extension("lock"))
+            .wait()
+            .wrap_err_with(|| format!("unable to lock {}", self.path.display()))
+    }
+
+    fn set(&self, key: &str, value: &str) -> Result<()> {
+        let _guard = self.lock()?;
+
+        let mut nonce = [0u8; NONCE_LEN];
+        SystemRandom::new()
+            .fill(&mut nonce)
+            .map_err(|_| eyre!("unable to generate nonce"))?;
+
+        let mut ciphertext = value.as_bytes().to_vec();
+        self.key(true)?
+            .seal_in_place_append_tag(
+                Nonce::assume_unique_for_key(nonce),
+                Aad::from(key.as_bytes()),
+                &mut ciphertext,
+            )
+            .map_err(|_| eyre!("unable to encrypt secret {key}"))?;
+
+        let mut secrets = self.load()?;
+        secrets.insert(
+            key.into(),
+            hex::encode(&[nonce.as_slice(), &ciphertext].concat()),
+        );
+
+        self.store(&secrets)
+    }
+
+    fn delete(&self, key: &str) -> Result<()> {
+        let _guard = self.lock()?;
+        let mut secrets = self.load()?;
+
+        if secrets.remove(key).is_some() {
+            self.store(&secrets)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Store a token for a `login` command.
+///
+/// When `token` is `None`, it is read from stdin, with a prompt that doesn't
+/// echo it when stdin is a terminal.
+pub fn login(secrets: &Secrets, key: &str, token: Option<String>) -> Result<()> {
+    let token = match token {
+        Some(token) => token,
+        None => prompt("Token: ")?,
+    };
+
+    if token.is_empty() {
+        return Err(eyre!("no token provided"));
+    }
+
+    secrets.set(key, &token)
+}
+
+/// Remove a token for a `logout` command.
+pub fn logout(secrets: &Secrets, key: &str) -> Result<()> {
+    secrets.delete(key)
+}
+
+/// Read a line from stdin. On a terminal, `message` is shown first and what is
+/// typed isn't echoed.
+fn prompt(message: &str) -> Result<String> {
+    let stdin = std::io::stdin();
+    let mut line = String::new();
+
+    if stdin.is_terminal() {
+        eprint!("{message}");
+        std::io::stderr().flush()?;
+
+        let echo = Echo::off()?;
+        let read = stdin.lock().read_line(&mut line);
+        drop(echo);
+
+        // The newline wasn't echoed either.
+        eprintln!();
+        read?;
+    } else {
+        stdin.lock().read_line(&mut line)?;
+    }
+
+    Ok(line.trim().to_string())
+}
+
+/// Keeps the terminal on stdin from echoing what is typed, until dropped.
+struct Echo {
+    #[cfg(unix)]
+    previous: libc::termios,
+    #[cfg(windows)]
+    previous: windows_sys::Win32::System::Console::CONSOLE_MODE,
+}
+
+impl Echo {
+    #[cfg(unix)]
+    fn off() -> Result<Self> {
+        use std::os::fd::AsRawFd;
+
+        let fd = std::io::stdin().as_raw_fd();
+
+        // SAFETY: `termios` is plain data, which `tcgetattr` fills in.
+        let mut termios = unsafe { std::mem::zeroed::<libc::termios>() };
+        if unsafe { libc::tcgetattr(fd, &mut termios) } != 0 {
+            return Err(std::io::Error::last_os_error().into());
+        }
+
+        let previous = termios;
+        termios.c_lflag &= !libc::ECHO;
+
+        // SAFETY: `termios` came from `tcgetattr` for the same descriptor.
+        if unsafe { libc::tcsetattr(fd, libc::TCSANOW, &termios) } != 0 {
+            return Err(std::io::Error::last_os_error().into());
+        }
+
+        Ok(Self { previous })
+    }
+
+    #[cfg(windows)]
+    fn off() -> Result<Self> {
+        use std::os::windows::io::AsRawHandle;
+
+        use windows_sys::Win32::System::Console::{
+            GetConsoleMode, SetConsoleMode, ENABLE_ECHO_INPUT,
+        };
+
+        let handle = std::io::stdin().as_raw_handle();
+        let mut previous = 0;
+
+        // SAFETY: stdin's handle stays open for as long as the process runs.
+        if unsafe { GetConsoleMode(handle, &mut previous) } == 0 {
+            return Err(std::io::Error::last_os_error().into());
+        }
+
+        // SAFETY: as above.
+        if unsafe { SetConsoleMode(handle, previous & !ENABLE_ECHO_INPUT) } == 0 {
+            return Err(std::io::Error::last_os_error().into());
+        }
+
+        Ok(Self { previous })
+    }
+
+    #[cfg(not(any(unix, windows)))]
+    fn off() -> Result<Self> {
+        Err(eyre!("unable to hide what is typed on this platform"))
+    }
+}
+
+impl Drop for Echo {
+    fn drop(&mut self) {
+        #[cfg(unix)]
+        {
+            use std::os::fd::AsRawFd;
+
+            // SAFETY: `previous` came from `tcgetattr` for stdin.
+            unsafe {
+                libc::tcsetattr(std::io::stdin().as_raw_fd(), libc::TCSANOW, &self.previous);
+            }
+        }
+
+        #[cfg(windows)]
+        {
+            use std::os::windows::io::AsRawHandle;
+
+            // SAFETY: stdin's handle stays open for as long as the process
+            // runs.
+            unsafe {
+                windows_sys::Win32::System::Console::SetConsoleMode(
+                    std::io::stdin().as_raw_handle(),
+                    self.previous,
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SecretsFile;
+
+    /// A secrets file of its own for `name`, starting out empty.
+    fn file(name: &str) -> SecretsFile {
+        let dir = std::env::temp_dir().join(format!("cata-secrets-{name}-{}", std::process::id()));
+        std::fs::remove_dir_all(&dir).ok();
+
+        SecretsFile {
+            path: dir.join("secrets.json"),
+        }
+    }
+
+    fn cleanup(file: &SecretsFile) {
+        std::fs::remove_dir_all(file.path.parent().unwrap()).ok();
+    }
+
+    /// Change the raw, hex encoded value of `key` on disk.
+    fn tamper(file: &SecretsFile, key: &str, f: impl FnOnce(&mut String)) {
+        let mut secrets = file.load().unwrap();
+        f(secrets.get_mut(key).unwrap());
+        file.store(&secrets).unwrap();
+    }
+
+    #[test]
+    fn round_trip() {
+        let file = file("round-trip");
+
+        assert_eq!(file.get("token").unwrap(), None);
+        file.set("token", "hunter2").unwrap();
+        assert_eq!(file.get("token").unwrap().as_deref(), Some("hunter2"));
+
+        file.delete("token").unwrap();
+        file.delete("token").unwrap();
+        assert_eq!(file.get("token").unwrap(), None);
+
+        cleanup(&file);
+    }
+
+    #[test]
+    fn reading_never_generates_a_key() {
+        let file = file("no-key");
+
+        assert_eq!(file.get("token").unwrap(), None);
+        assert!(!file.path.with_extension("key").exists());
+
+        cleanup(&file);
+    }
+
+    #[test]
+    fn tampered_ciphertext() {
+        let file = file("tampered");
+        file.set("token", "hunter2").unwrap();
+
+        tamper(&file, "token", |raw| {
+            let last = if raw.ends_with('0') { "1" } else { "0" };
+            raw.replace_range(raw.len() - 1.., last);
+        });
+
+        let err = file.get("token").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "unable to decrypt secret token, store it again"
+        );
+
+        cleanup(&file);
+    }
+
+    #[test]
+    fn truncated_ciphertext() {
+        let file = file("truncated");
+        file.set("token", "hunter2").unwrap();
+
+        tamper(&file, "token", |raw| raw.truncate(8));
+
+        let err = file.get("token").unwrap_err();
+        assert_eq!(err.to_string(), "secret token is corrupt");
+
+        cleanup(&file);
+    }
+
+    #[test]
+    fn swapped_secrets() {
+        let file = file("swapped");
+        file.set("token", "hunter2").unwrap();
+        file.set("password", "correct horse").unwrap();
+
+        let mut secrets = file.load().unwrap();
+        let password = secrets["password"].clone();
+        secrets.insert("token".into(), password);
+        file.store(&secrets).unwrap();
+
+        assert!(file.get("token").is_err());
+
+        cleanup(&file);
+    }
+
+    #[test]
+    fn missing_key_file() {
+        let file = file("missing-key");
+        file.set("token", "hunter2").unwrap();
+
+        std::fs::remove_file(file.path.with_extension("key")).unwrap();
+
+        let err = file.get("token").unwrap_err();
+        assert!(err.to_string().starts_with("unable to read "));
+
+        cleanup(&file);
+    }
+
+    #[test]
+    fn invalid_key_file() {
+        let file = file("invalid-key");
+        file.set("token", "hunter2").unwrap();
+
+        std::fs::write(file.path.with_extension("key"), b"short").unwrap();
+
+        let err = file.get("token").unwrap_err();
+        assert!(err.to_string().ends_with("isn't a valid secrets key"));
+
+        cleanup(&file);
+    }
+
+    #[test]
+    fn generate_keeps_the_existing_key() {
+        let file = file("existing-key");
+        file.set("token", "hunter2").unwrap();
+
+        let path = file.path.with_extension("key");
+        let existing = std::fs::read(&path).unwrap();
+
+        assert_eq!(SecretsFile::generate(&path).unwrap(), existing);
+        assert_eq!(file.get("token").unwrap().as_deref(), Some("hunter2"));
+
+        cleanup(&file);
+    }
+}
@@ -0,0 +1,390 @@
+//! Remember things between runs.
+//!
+//! A small key-value store for the bits of state a CLI needs to carry between
+//! invocations, such as "last sync timestamp" or "dismissed hints". Values are
+//! anything that implements [`serde::Serialize`] and are stored as JSON in the
+//! platform state directory.
+//!
+//! Some things to note:
+//! - Every operation takes an advisory lock on the file, so concurrent
+//!   invocations of the CLI don't clobber each other. Use [`State::update`] for
+//!   read-modify-write operations. There is no locking on wasm, where sandboxes
+//!   don't share a filesystem anyway.
+//! - Writes go to a temporary file that is renamed into place, a crash will
+//!   never leave a half written file behind.
+//! - The file carries a schema version. When the version on disk is older than
+//!   the one configured with [`State::with_version`], the migration is called
+//!   before anything is read. Files from a newer version are rejected instead
+//!   of being overwritten.
+//!
+//! # Examples
+//!
+//! ```
+//! use cata::state::State;
+//!
+//! # let dir = std::env::temp_dir().join("cata-state-example");
+//! let state = State::new("my-cli");
+//! # let state = state.with_path(dir.join("state.json"));
+//!
+//! state.set("last_sync", &1_700_000_000u64)?;
+//! assert_eq!(state.get::<u64>("last_sync")?, Some(1_700_000_000));
+//!
+//! let runs = state.update("runs", |runs: Option<u64>| runs.unwrap_or_default() + 1)?;
+//! assert!(runs >= 1);
+//! # std::fs::remove_dir_all(dir)?;
+//! # Ok::<(), eyre::Report>(())
+//! ```
+use std::{
+    fs::{File, OpenOptions},
+    path::PathBuf,
+};
+
+use eyre::{eyre, Result};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::dirs::Dirs;
+
+static STATE_FILE: &str = "state.json";
+
+/// A function that upgrades entries written with an older schema version.
+///
+/// It is called with the version found on disk and can modify the entries in
+/// place. The store is then written back with the current version.
+pub type Migration = fn(u32, &mut Map<String, Value>) -> Result<()>;
+
+#[derive(Default, Serialize, Deserialize)]
+struct Contents {
+    version: u32,
+    entries: Map<String, Value>,
+}
+
+/// A persistent key-value store.
+///
+/// See the module documentation for usage.
+#[derive(Clone, Debug)]
+pub struct State {
+    path: Option<PathBuf>,
+    version: u32,
+    migration: Option<Migration>,
+}
+
+impl State {
+    /// Create a store for the CLI `name`.
+    pub fn new(name: impl AsRef<str>) -> Self {
+        Self {
+            path: Dirs::new(name).state().map(|dir| dir.join(STATE_FILE)),
+            version: 0,
+            migration: None,
+        }
+    }
+
+    /// Set the path of the file backing the store.
+    ///
+    /// By default, this is `state.json` in the platform state directory.
+    #[must_use]
+    pub fn with_path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.path = Some(path.into());
+        self
+    }
+
+    /// Set the current schema version and how to migrate older files.
+    #[must_use]
+    pub fn with_version(mut self, version: u32, migration: Migration) -> Self {
+        self.version = version;
+        self.migration = Some(migration);
+        self
+    }
+
+    /// Retrieve the value stored under `key`.
+    pub fn get<T: DeserializeOwned>(&self, key: &str) -> Result<Option<T>> {
+        let (_lock, contents) = self.read(false)?;
+
+        contents
+            .entries
+            .get(key)
+            .map(|value| serde_json::from_value(value.clone()))
+            .transpose()
+            .map_err(Into::into)
+    }
+
+    /// Store `value` under `key`, replacing anything that was there.
+    pub fn set<T: Serialize>(&self, key: &str, value: &T) -> Result<()> {
+        let value = serde_json::to_value(value)?;
+
+        self.modify(|entries| {
+            entries.insert(key.into(), value);
+
+            Ok(())
+        })
+    }
+
+    /// Remove the value stored under `key`.
+    pub fn remove(&self, key: &str) -> Result<()> {
+        self.modify(|entries| {
+            entries.remove(key);
+
+            Ok(())
+        })
+    }
+
+    /// Atomically replace the value under `key` with the result of `f`.
+    ///
+    /// The file stays locked between reading the current value and writing the
+    /// new one.
+    pub fn update<T, F>(&self, key: &str, f: F) -> Result<T>
+    where
+        T: Serialize + DeserializeOwned,
+        F: FnOnce(Option<T>) -> T,
+    {
+        let mut result = None;
+
+        self.modify(|entries| {
+            let current = entries
+                .get(key)
+                .map(|value| serde_json::from_value(value.clone()))
+                .transpose()?;
+
+            let next = f(current);
+            entries.insert(key.into(), serde_json::to_value(&next)?);
+            result = Some(next);
+
+            Ok(())
+        })?;
+
+        result.ok_or_else(|| eyre!("update did not produce a value"))
+    }
+
+    fn path(&self) -> Result<&PathBuf> {
+        self.path
+            .as_ref()
+            .ok_or_else(|| eyre!("no state directory available"))
+    }
+
+    /// Lock the store and load its contents, migrating them if required.
+    ///
+    /// The returned file holds the lock until it is dropped.
+    fn read(&self, exclusive: bool) -> Result<(File, Contents)> {
+        let path = self.path()?;
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+
+        let lock = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(path.with_extension("lock"))?;
+
+        acquire(&lock, exclusive)?;
+
+        let mut contents = match std::fs::read_to_string(path) {
+            Ok(raw) => serde_json::from_str(&raw)?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Contents {
+                version: self.version,
+                ..Default::default()
+            },
+            Err(e) => return Err(e.into()),
+        };
+
+        if contents.version > self.version {
+            return Err(eyre!(
+                "{} was written by a newer version (schema {} > {})",
+                path.display(),
+                contents.version,
+                self.version
+            ));
+        }
+
+        if contents.version < self.version {
+            if let Some(migration) = self.migration {
+                migration(contents.version, &mut contents.entries)?;
+            }
+
+            contents.version = self.version;
+        }
+
+        Ok((lock, contents))
+    }
+
+    fn modify<F>(&self, f: F) -> Result<()>
+    where
+        F: FnOnce(&mut Map<String, Value>) -> Result<()>,
+    {
+        let (_lock, mut contents) = self.read(true)?;
+
+        f(&mut contents.entries)?;
+
+        let path = self.path()?;
+        let tmp = path.with_extension("tmp");
+        std::fs::write(&tmp, serde_json::to_string_pretty(&contents)?)?;
+        std::fs::rename(tmp, path)?;
+
+        Ok(())
+    }
+}
+
+#[cfg(not(target_family = "wasm"))]
+fn acquire(lock: &File, exclusive: bool) -> Result<()> {
+    use fs4::fs_std::FileExt;
+
+    if exclusive {
+        lock.lock_exclusive()?;
+    } else {
+        lock.lock_shared()?;
+    }
+
+    Ok(())
+}
+
+#[cfg(target_family = "wasm")]
+#[allow(clippy::unnecessary_wraps)]
+fn acquire(_: &File, _: bool) -> Result<()> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Map, Value};
+
+    use super::State;
+
+    /// A state file of its own for `name`, starting out empty.
+    fn state(name: &str) -> State {
+        let dir = std::env::temp_dir().join(format!("cata-state-{name}-{}", std::process::id()));
+        std::fs::remove_dir_all(&dir).ok();
+
+        State::new("cata").with_path(dir.join("state.json"))
+    }
+
+    fn cleanup(state: &State) {
+        std::fs::remove_dir_all(state.path().unwrap().parent().unwrap()).ok();
+    }
+
+    fn write(state: &State, contents: &Value) {
+        let path = state.path().unwrap();
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, contents.to_string()).unwrap();
+    }
+
+    fn read(state: &State) -> Value {
+        serde_json::from_str(&std::fs::read_to_string(state.path().unwrap()).unwrap()).unwrap()
+    }
+
+    #[allow(clippy::unnecessary_wraps)]
+    fn rename(version: u32, entries: &mut Map<String, Value>) -> eyre::Result<()> {
+        if version < 1 {
+            if let Some(value) = entries.remove("old") {
+                entries.insert("new".into(), value);
+            }
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn missing_file() {
+        let state = state("missing");
+
+        assert_eq!(state.get::<u64>("key").unwrap(), None);
+        state.remove("key").unwrap();
+        assert_eq!(state.get::<u64>("key").unwrap(), None);
+
+        cleanup(&state);
+    }
+
+    #[test]
+    fn set_and_remove() {
+        let state = state("set");
+
+        state.set("key", &1u64).unwrap();
+        state.set("other", &"value").unwrap();
+        assert_eq!(state.get::<u64>("key").unwrap(), Some(1));
+
+        state.remove("key").unwrap();
+        assert_eq!(state.get::<u64>("key").unwrap(), None);
+        assert_eq!(
+            state.get::<String>("other").unwrap().as_deref(),
+            Some("value")
+        );
+        assert!(state.get::<u64>("other").is_err());
+
+        cleanup(&state);
+    }
+
+    #[test]
+    fn migrates_older_files() {
+        let state = state("migrate").with_version(1, rename);
+        write(&state, &json!({"version": 0, "entries": {"old": 1}}));
+
+        assert_eq!(state.get::<u64>("new").unwrap(), Some(1));
+        assert_eq!(state.get::<u64>("old").unwrap(), None);
+
+        // Reads don't write the migration back, the next write does.
+        assert_eq!(read(&state)["version"], 0);
+        state.set("other", &2u64).unwrap();
+        assert_eq!(
+            read(&state),
+            json!({"version": 1, "entries": {"new": 1, "other": 2}})
+        );
+
+        cleanup(&state);
+    }
+
+    #[test]
+    fn rejects_newer_files() {
+        let state = state("newer").with_version(1, rename);
+        let newer = json!({"version": 2, "entries": {"key": 1}});
+        write(&state, &newer);
+
+        assert!(state.get::<u64>("key").is_err());
+        assert!(state.set("key", &2u64).is_err());
+        assert_eq!(read(&state), newer);
+
+        cleanup(&state);
+    }
+
+    #[test]
+    fn failed_migrations_leave_the_file() {
+        let state = state("failed").with_version(1, |_, _| Err(eyre::eyre!("unable to migrate")));
+        let older = json!({"version": 0, "entries": {"key": 1}});
+        write(&state, &older);
+
+        assert!(state.set("key", &2u64).is_err());
+        assert_eq!(read(&state), older);
+
+        cleanup(&state);
+    }
+
+    #[test]
+    fn corrupt_files() {
+        let state = state("corrupt");
+        write(&state, &json!("not state"));
+
+        assert!(state.get::<u64>("key").is_err());
+        assert!(state.set("key", &1u64).is_err());
+
+        cleanup(&state);
+    }
+
+    #[test]
+    fn concurrent_updates() {
+        let state = state("concurrent");
+
+        std::thread::scope(|scope| {
+            for _ in 0..8 {
+                scope.spawn(|| {
+                    for _ in 0..8 {
+                        state
+                            .update("runs", |runs: Option<u64>| runs.unwrap_or_default() + 1)
+                            .unwrap();
+                    }
+                });
+            }
+        });
+
+        assert_eq!(state.get::<u64>("runs").unwrap(), Some(64));
+
+        cleanup(&state);
+    }
+}
@@ -7,10 +7,16 @@
 //! Some things to note:
 //! - By default, the layer ignores all events and spans. To opt-in to
 //!   reporting, call `with_activity` or `with_errors` to enable.
-//! - IDs are stable for a single machine and rely on [`machine_uid`]. These are
-//!   hashed before being sent over the network.
+//! - IDs are stable for a single machine and come from [`crate::machine`].
+//!   These are hashed before being sent over the network. How the ID was found
+//!   is sent as the `machine_id_source` property.
 //! - What is actually reported is up to the implementation of the `Handler`.
 //!   Check the documentation to see what is reported.
+//! - [`crate::execute`] sets `activity` on the span around `run` for commands
+//!   that opt in, so they are reported without instrumenting them. Commands
+//!   opt in with `#[cata(telemetry)]`, reported as the path of the command,
+//!   or `#[cata(telemetry = "...")]`, reported as [`crate::Command::activity`].
+//!   Every other command isn't reported, see [`crate::Command::telemetry`].
 //!
 //! For a complete example, see [examples/telemetry].
 //!
@@ -59,6 +65,9 @@
 //!   .init();
 //! ```
 //!
+//! Commands using [`crate::verbosity`] can skip this and hand the layer to
+//! [`crate::ExecuteOptions::with_tracing_layer`] instead.
+//!
 //! # Building
 //!
 //! It is recommended that you use `build.rs` to pull the API keys used for
@@ -96,27 +105,31 @@
 //! `capture` function to publish the event.
 //!
 //! Because tracing requires that layer handlers are synchronous, the `capture`
-//! function is called via. tokio's `spawn_blocking`. This guarantees that any
-//! telemetry completes before the program exits and does not block normal
-//! program flow.
+//! function is called via. [`crate::runtime::Runtime::spawn_blocking`] of the
+//! current runtime. With tokio, this guarantees that any telemetry completes
+//! before the program exits and does not block normal program flow.
 //!
 //! [examples/telemetry]: https://github.com/grampelberg/cata/blob/main/examples/telemetry/src/main.rs
 pub mod posthog;
 
-use std::collections::HashMap;
+use std::{collections::HashMap, sync::Arc};
 
 use eyre::Result;
 use tracing::{error, field::ValueSet, Subscriber};
 use tracing_subscriber::{layer::Layer, registry::LookupSpan};
 
+use crate::{
+    machine::{Id, Source},
+    runtime,
+};
+
 static NAME: &str = env!("CARGO_PKG_NAME");
 static FIELD: &str = "activity";
 
-fn uuid() -> String {
-    let mid = machine_uid::get().unwrap_or_else(|_| "unknown".to_string());
+fn uuid(mid: &Id) -> String {
     let tag = ring::hmac::sign(
         &ring::hmac::Key::new(ring::hmac::HMAC_SHA256, NAME.as_bytes()),
-        mid.as_bytes(),
+        mid.id().as_bytes(),
     );
 
     uuid::Builder::from_bytes(tag.as_ref()[..16].try_into().unwrap())
@@ -138,8 +151,9 @@ where
     H: Handler + 'static,
     Self: 'static,
 {
-    provider: H,
+    provider: Arc<H>,
     user_id: String,
+    source: Source,
     emit_activity: bool,
     emit_errors: bool,
 }
@@ -150,9 +164,12 @@ where
 {
     /// Create a new telemetry layer with the given handler.
     pub fn new(handler: H) -> Telemetry<H> {
+        let mid = Id::detect();
+
         Self {
-            user_id: uuid(),
-            provider: handler,
+            user_id: uuid(&mid),
+            source: mid.source(),
+            provider: Arc::new(handler),
             emit_activity: false,
             emit_errors: false,
         }
@@ -183,8 +200,12 @@ where
             || (self.emit_errors && metadata.fields().field("error").is_some())
     }
 
-    fn capture(&self, event: Event) {
-        let provider = self.provider.clone();
+    fn capture(&self, mut event: Event) {
+        event
+            .properties
+            .insert("machine_id_source".into(), self.source.as_str().into());
+
+        let provider = Arc::clone(&self.provider);
 
         let handler = move || {
             if let Err(e) = provider.capture(event) {
@@ -199,17 +220,9 @@ where
         // instead waits for a yield to do shutdown. By using blocking, the runtime will
         // continue running until the event is successfully reported.
         //
-        // Note: it is possible to send events *after* the runtime has shutdown. If it
-        // has, just spawn a new one and send the event.
-        if let Ok(current) = tokio::runtime::Handle::try_current() {
-            current.spawn_blocking(handler);
-        } else {
-            tokio::runtime::Builder::new_multi_thread()
-                .enable_all()
-                .build()
-                .expect("Failed building the Runtime")
-                .spawn_blocking(handler);
-        };
+        // Note: it is possible to send events *after* the runtime has shutdown, the
+        // runtime takes care of that.
+        runtime::current().spawn_blocking(Box::new(handler));
     }
 }
 
@@ -251,6 +264,21 @@ pub struct Event {
     properties: HashMap<String, serde_json::Value>,
 }
 
+impl Event {
+    /// Create an event named `name` for `user_id`.
+    pub fn new(
+        name: impl Into<String>,
+        user_id: String,
+        properties: HashMap<String, serde_json::Value>,
+    ) -> Self {
+        Self {
+            name: name.into(),
+            user_id,
+            properties,
+        }
+    }
+}
+
 impl From<Event> for posthog_rs::Event {
     fn from(ev: Event) -> Self {
         let mut ph = posthog_rs::Event::new(ev.name, ev.user_id);
@@ -20,6 +20,8 @@
 //! - `level`: The level of the span/event.
 //! - `module`: The module path of the span/event.
 //! - `version`: The version of the crate.
+//! - `machine_id_source`: How the machine ID was found, see
+//!   [`crate::machine::Source`].
 //! - Any additional fields passed to tracing will be included in the payload.
 //!   The Debug representation of the field is used for any of the non-standard
 //!   types.
@@ -69,7 +71,7 @@ fn props(meta: &tracing_core::Metadata, visitor: &Visitor) -> HashMap<String, se
     );
     props.insert(
         "module".to_string(),
-        serde_json::Value::String(meta.module_path().unwrap().into()),
+        serde_json::Value::String(meta.module_path().unwrap_or_default().into()),
     );
     props.insert("version".to_string(), VERSION.into());
 
@@ -0,0 +1,304 @@
+//! Helpers for testing CLIs built with cata.
+//!
+//! Command trees run in-process, without spawning the binary. [`Harness`]
+//! parses the arguments, injects services on the [`crate::Context`] and
+//! executes the tree, capturing what it printed into an [`Outcome`] that tests
+//! assert on.
+//!
+//! Some things to note:
+//! - Output printed through [`crate::output::Format`] and cata's own warnings,
+//!   such as deprecations, are captured. Anything written with `println!` or
+//!   from other tasks still goes to the real stdout and stderr.
+//! - Arguments that fail to parse are an outcome like any other. Help and
+//!   version requests are captured with exit code 0.
+//! - [`snapshot`]: compare the output of commands against files on disk.
+//!
+//! # Examples
+//!
+//! ```
+//! use std::sync::Arc;
+//!
+//! use cata::{output::Format, testing::Harness, Command, Container, Context};
+//! use clap::Parser;
+//!
+//! #[derive(Clone)]
+//! struct Client {
+//!   projects: Vec<String>,
+//! }
+//!
+//! #[derive(Parser, Container)]
+//! struct Root {
+//!   #[arg(short, long, value_enum, default_value_t)]
+//!   output: Format,
+//! }
+//!
+//! #[async_trait::async_trait]
+//! impl Command for Root {
+//!   async fn run(&self, ctx: &mut Context) -> eyre::Result<()> {
+//!     let client = ctx.require::<Client>()?;
+//!
+//!     self.output.document(&client.projects.join(", "))
+//!   }
+//! }
+//!
+//! # #[tokio::main]
+//! # async fn main() {
+//! let client = Client {
+//!   projects: vec!["cata".into(), "other".into()],
+//! };
+//!
+//! Harness::<Root>::new(["root"])
+//!   .with_service(client)
+//!   .run()
+//!   .await
+//!   .assert_success()
+//!   .assert_stdout("cata, other\n");
+//!
+//! Harness::<Root>::new(["root", "--bogus"])
+//!   .run()
+//!   .await
+//!   .assert_exit_code(2);
+//! # }
+//! ```
+pub mod snapshot;
+
+use std::marker::PhantomData;
+
+use eyre::Result;
+
+use crate::{Command, ExecuteOptions};
+
+/// Runs a command tree in-process for a test.
+///
+/// See the module documentation for usage.
+pub struct Harness<C> {
+    args: Vec<String>,
+    options: ExecuteOptions,
+    cmd: PhantomData<fn() -> C>,
+}
+
+impl<C> std::fmt::Debug for Harness<C> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Harness")
+            .field("args", &self.args)
+            .field("options", &self.options)
+            .finish()
+    }
+}
+
+impl<C: clap::Parser + Command> Harness<C> {
+    /// Parse `args` as `C`. The first argument is the binary name, as with
+    /// [`clap::Parser::parse_from`].
+    pub fn new(args: impl IntoIterator<Item = impl Into<String>>) -> Self {
+        Self {
+            args: args.into_iter().map(Into::into).collect(),
+            // Tests run in parallel and shouldn't swallow each other's ctrl-c.
+            options: ExecuteOptions::default().with_signals(false),
+            cmd: PhantomData,
+        }
+    }
+
+    /// Provide `value` on the context, see [`ExecuteOptions::with_service`].
+    #[must_use]
+    pub fn with_service<T: Clone + Send + Sync + 'static>(mut self, value: T) -> Self {
+        self.options = self.options.with_service(value);
+        self
+    }
+
+    /// Set the options the tree is executed with.
+    ///
+    /// By default, signals aren't handled.
+    #[must_use]
+    pub fn with_options(mut self, options: ExecuteOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Parse the arguments and execute the tree.
+    pub async fn run(self) -> Outcome {
+        let cmd = match C::try_parse_from(&self.args) {
+            Ok(cmd) => cmd,
+            Err(e) => {
+                let rendered = e.render().to_string();
+                let (stdout, stderr) = if e.use_stderr() {
+                    (String::new(), rendered)
+                } else {
+                    (rendered, String::new())
+                };
+
+                let result = match e.exit_code() {
+                    0 => Ok(()),
+                    _ => Err(e.into()),
+                };
+
+                return Outcome {
+                    result,
+                    stdout,
+                    stderr,
+                };
+            }
+        };
+
+        let (result, stdout, stderr) =
+            crate::output::capture(crate::execute_with(&cmd, self.options)).await;
+
+        Outcome {
+            result,
+            stdout,
+            stderr,
+        }
+    }
+}
+
+/// What happened when a [`Harness`] ran.
+///
+/// The `assert_*` methods panic with the outcome when they don't hold and
+/// return the outcome so that they can be chained.
+#[derive(Debug)]
+pub struct Outcome {
+    result: Result<()>,
+    stdout: String,
+    stderr: String,
+}
+
+impl Outcome {
+    /// What [`crate::execute`] returned, or the parse error.
+    pub fn result(&self) -> &Result<()> {
+        &self.result
+    }
+
+    /// Everything captured from stdout.
+    #[must_use]
+    pub fn stdout(&self) -> &str {
+        &self.stdout
+    }
+
+    /// Everything captured from stderr.
+    #[must_use]
+    pub fn stderr(&self) -> &str {
+        &self.stderr
+    }
+
+    /// The code the process would exit with, see [`crate::error::exit_code`].
+    #[must_use]
+    pub fn exit_code(&self) -> i32 {
+        self.result
+            .as_ref()
+            .map_or_else(crate::error::exit_code, |()| 0)
+    }
+}
+
+// Assertions are usually the last call in a chain, the returned outcome is only
+// there for the next one.
+#[allow(clippy::must_use_candidate)]
+impl Outcome {
+    /// Assert that the tree succeeded.
+    ///
+    /// # Panics
+    ///
+    /// When the tree failed.
+    #[track_caller]
+    pub fn assert_success(&self) -> &Self {
+        if let Err(e) = &self.result {
+            panic!("expected success, got: {e:?}\n{self}");
+        }
+
+        self
+    }
+
+    /// Assert that the process would exit with `code`.
+    ///
+    /// # Panics
+    ///
+    /// When the exit code is different.
+    #[track_caller]
+    pub fn assert_exit_code(&self, code: i32) -> &Self {
+        assert!(
+            self.exit_code() == code,
+            "expected exit code {code}, got {}\n{self}",
+            self.exit_code()
+        );
+
+        self
+    }
+
+    /// Assert that stdout is exactly `expected`.
+    ///
+    /// # Panics
+    ///
+    /// When stdout is different.
+    #[track_caller]
+    pub fn assert_stdout(&self, expected: &str) -> &Self {
+        assert!(
+            self.stdout == expected,
+            "stdout doesn't match, expected:\n{expected}\n{self}"
+        );
+
+        self
+    }
+
+    /// Assert that stderr contains `expected`.
+    ///
+    /// # Panics
+    ///
+    /// When stderr doesn't contain `expected`.
+    #[track_caller]
+    pub fn assert_stderr_contains(&self, expected: &str) -> &Self {
+        assert!(
+            self.stderr.contains(expected),
+            "stderr doesn't contain {expected:?}\n{self}"
+        );
+
+        self
+    }
+}
+
+impl std::fmt::Display for Outcome {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "exit code: {}", self.exit_code())?;
+        writeln!(f, "stdout:\n{}", self.stdout)?;
+        write!(f, "stderr:\n{}", self.stderr)
+    }
+}
+
+/// Parse `args` as `C` and execute the command tree, capturing its output.
+///
+/// Everything printed through [`crate::output::Format`] is returned instead
+/// of being written to stdout. The first argument is the binary name, as with
+/// [`clap::Parser::parse_from`]. See [`Harness`] for more control.
+/// ```
+/// use cata::{output::Format, Command, Container};
+/// use clap::Parser;
+///
+/// #[derive(Parser, Container)]
+/// struct Root {
+///   #[arg(short, long, value_enum, default_value_t)]
+///   output: Format,
+/// }
+///
+/// #[async_trait::async_trait]
+/// impl Command for Root {
+///   async fn run(&self, _: &mut cata::Context) -> eyre::Result<()> {
+///     self.output.document(&"hello")
+///   }
+/// }
+///
+/// # #[tokio::main]
+/// # async fn main() -> eyre::Result<()> {
+/// let (result, out) = cata::testing::run::<Root>(&["root", "-o", "json"]).await;
+/// result?;
+///
+/// assert_eq!(out, "\"hello\"\n");
+/// # Ok(())
+/// # }
+/// ```
+pub async fn run<C: clap::Parser + Command>(args: &[&str]) -> (Result<()>, String) {
+    let cmd = match C::try_parse_from(args) {
+        Ok(cmd) => cmd,
+        Err(e) => return (Err(e.into()), String::new()),
+    };
+
+    let (result, stdout, _) = crate::output::capture(crate::execute(&cmd)).await;
+
+    (result, stdout)
+}
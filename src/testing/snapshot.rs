@@ -0,0 +1,205 @@
+//! Catch regressions in output by comparing it against files on disk.
+//!
+//! A [`Snapshot`] runs a command tree once for every output format and
+//! compares what was printed against a `.snap` file per format. When there is
+//! a difference, the new output is written next to it as `.snap.new` and the
+//! check fails.
+//!
+//! Some things to note:
+//! - Files use the same layout as [insta], so `cargo insta review` can be used
+//!   to accept or reject changes.
+//! - Setting `CATA_SNAPSHOTS=overwrite` accepts every change, writing the
+//!   `.snap` files directly.
+//! - The format is picked by appending `--output <format>` to the arguments.
+//!   Use [`Snapshot::with_flag`] when the flag is named something else.
+//!
+//! # Examples
+//!
+//! ```
+//! use cata::{output::Format, testing::snapshot::Snapshot, Command, Container};
+//! use clap::Parser;
+//!
+//! #[derive(serde::Serialize, tabled::Tabled)]
+//! struct Cluster {
+//!   name: String,
+//! }
+//!
+//! #[derive(Parser, Container)]
+//! struct Root {
+//!   #[arg(short, long, value_enum, default_value_t)]
+//!   output: Format,
+//! }
+//!
+//! #[async_trait::async_trait]
+//! impl Command for Root {
+//!   async fn run(&self, _: &mut cata::Context) -> eyre::Result<()> {
+//!     self.output.list(&[Cluster { name: "production".into() }])
+//!   }
+//! }
+//!
+//! # #[tokio::main]
+//! # async fn main() -> eyre::Result<()> {
+//! # let dir = std::env::temp_dir().join("cata-snapshot-example");
+//! let snapshot = Snapshot::new("tests/snapshots").with_source(file!());
+//! # let snapshot = snapshot.with_dir(&dir);
+//! # std::env::set_var("CATA_SNAPSHOTS", "overwrite");
+//! # snapshot.check::<Root>("clusters", &["root"]).await?;
+//! # std::env::remove_var("CATA_SNAPSHOTS");
+//!
+//! snapshot.check::<Root>("clusters", &["root"]).await?;
+//! # std::fs::remove_dir_all(dir)?;
+//! # Ok(())
+//! # }
+//! ```
+//!
+//! [insta]: https://insta.rs
+use std::path::PathBuf;
+
+use clap::ValueEnum;
+use eyre::{eyre, Result};
+
+use crate::{output::Format, Command};
+
+static ENV: &str = "CATA_SNAPSHOTS";
+
+/// Compares the output of a command against files on disk.
+///
+/// See the module documentation for usage.
+#[derive(Clone, Debug)]
+pub struct Snapshot {
+    dir: PathBuf,
+    flag: String,
+    formats: Vec<Format>,
+    source: Option<String>,
+}
+
+impl Snapshot {
+    /// Store snapshots in `dir`.
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: dir.into(),
+            flag: "--output".into(),
+            formats: Format::value_variants().to_vec(),
+            source: None,
+        }
+    }
+
+    /// Set the directory snapshots are stored in.
+    #[must_use]
+    pub fn with_dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.dir = dir.into();
+        self
+    }
+
+    /// Set the flag used to pick the output format.
+    #[must_use]
+    pub fn with_flag(mut self, flag: impl Into<String>) -> Self {
+        self.flag = flag.into();
+        self
+    }
+
+    /// Only check these formats.
+    ///
+    /// By default, every format is checked.
+    #[must_use]
+    pub fn with_formats(mut self, formats: impl IntoIterator<Item = Format>) -> Self {
+        self.formats = formats.into_iter().collect();
+        self
+    }
+
+    /// Record the test file in the snapshot, typically from `file!()`.
+    #[must_use]
+    pub fn with_source(mut self, source: impl Into<String>) -> Self {
+        self.source = Some(source.into());
+        self
+    }
+
+    /// Run `C` with `args` in every format and compare the output against the
+    /// snapshots called `name`.
+    ///
+    /// Every format is run, even after a mismatch, so that all of the
+    /// `.snap.new` files are written in one go.
+    /// Like with [`super::Harness`], signals aren't handled.
+    pub async fn check<C: clap::Parser + Command>(&self, name: &str, args: &[&str]) -> Result<()> {
+        let overwrite = std::env::var(ENV).is_ok_and(|v| v == "overwrite");
+        let mut failures = Vec::new();
+
+        for format in &self.formats {
+            let format = format
+                .to_possible_value()
+                .ok_or_else(|| eyre!("{format:?} has no name"))?;
+            let format = format.get_name();
+
+            let mut full = args.to_vec();
+            full.extend([self.flag.as_str(), format]);
+
+            let super::Outcome {
+                result,
+                stdout: actual,
+                ..
+            } = super::Harness::<C>::new(full.iter().copied()).run().await;
+            result.map_err(|e| e.wrap_err(format!("{name} failed with {format} output")))?;
+
+            let path = self.dir.join(format!("{name}@{format}.snap"));
+            let pending = path.with_extension("snap.new");
+            let expected = std::fs::read_to_string(&path)
+                .ok()
+                .map(|raw| contents(&raw));
+
+            if expected.as_deref() == Some(actual.as_str()) {
+                if pending.exists() {
+                    std::fs::remove_file(&pending)?;
+                }
+
+                continue;
+            }
+
+            std::fs::create_dir_all(&self.dir)?;
+            let snap = self.render(&full, &actual);
+
+            if overwrite {
+                std::fs::write(&path, snap)?;
+
+                continue;
+            }
+
+            std::fs::write(&pending, snap)?;
+            failures.push(format!(
+                "{}\n--- expected\n{}\n+++ actual\n{actual}",
+                path.display(),
+                expected.unwrap_or_default(),
+            ));
+        }
+
+        if failures.is_empty() {
+            return Ok(());
+        }
+
+        Err(eyre!(
+            "snapshots for {name} did not match. Review the .snap.new files or set \
+             {ENV}=overwrite to accept them.\n\n{}",
+            failures.join("\n")
+        ))
+    }
+
+    fn render(&self, args: &[&str], actual: &str) -> String {
+        let source = self
+            .source
+            .as_ref()
+            .map(|source| format!("source: {source}\n"))
+            .unwrap_or_default();
+
+        format!(
+            "---\n{source}expression: {}\n---\n{actual}",
+            serde_json::to_string(&args.join(" ")).unwrap_or_default(),
+        )
+    }
+}
+
+/// Strip the header from a snapshot file.
+fn contents(raw: &str) -> String {
+    raw.strip_prefix("---\n")
+        .and_then(|rest| rest.split_once("\n---\n"))
+        .map_or(raw, |(_, body)| body)
+        .to_string()
+}
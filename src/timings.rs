@@ -0,0 +1,297 @@
+//! A report of everything that happened while executing the command tree.
+//!
+//! Finding out why a CLI is slow, or shipping that to telemetry, needs more
+//! than the final result. A [`Recorder`] added with
+//! [`crate::ExecuteOptions::with_report`] times every command and hook, counts
+//! retries and notices cancellation. Once execution is done,
+//! [`Recorder::report`] hands back an [`ExecutionReport`].
+//!
+//! Some things to note:
+//! - Reports implement [`serde::Serialize`] and `Display`, they can be
+//!   printed with `Format::document` from [`crate::output`] for a `--timings`
+//!   flag or serialized for telemetry.
+//! - Hooks are timed from outside of every other layer, the durations include
+//!   whatever the layers do.
+//! - Every attempt of a retried `run` is a hook of its own, see
+//!   [`crate::Command::retry`].
+//! - A recorder keeps collecting across executions, use a new one for each
+//!   report.
+//!
+//! # Examples
+//!
+//! ```
+//! use cata::{timings::Recorder, Command, Container, ExecuteOptions};
+//! use clap::Parser;
+//!
+//! #[derive(Parser, Container)]
+//! struct Root {}
+//!
+//! #[async_trait::async_trait]
+//! impl Command for Root {
+//!   async fn run(&self, _: &mut cata::Context) -> eyre::Result<()> {
+//!     tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+//!
+//!     Ok(())
+//!   }
+//! }
+//!
+//! # #[tokio::main]
+//! # async fn main() -> eyre::Result<()> {
+//! let recorder = Recorder::default();
+//! let options = ExecuteOptions::default().with_report(recorder.clone());
+//!
+//! cata::execute_with(&Root::parse_from(["root"]), options).await?;
+//!
+//! let report = recorder.report();
+//! assert!(!report.cancelled);
+//! assert_eq!(report.commands.len(), 1);
+//!
+//! let hooks = report.commands[0]
+//!   .hooks
+//!   .iter()
+//!   .map(|hook| hook.hook)
+//!   .collect::<Vec<_>>();
+//! assert_eq!(hooks, ["pre_run", "run", "post_run"]);
+//! assert!(report.commands[0].hooks[1].duration_ms >= 10);
+//!
+//! println!("{report}");
+//! # Ok(())
+//! # }
+//! ```
+use std::{
+    fmt::{self, Display},
+    sync::{Arc, Mutex, PoisonError},
+    time::{Duration, Instant},
+};
+
+use eyre::Result;
+use serde::Serialize;
+
+use crate::{
+    command::Interrupted,
+    events::{Event, Subscriber},
+    layer::{Layer, Next},
+    Context,
+};
+
+/// How a single hook call went.
+#[derive(Clone, Debug, Serialize)]
+pub struct HookReport {
+    /// The name of the hook, see [`crate::layer::Hook::name`].
+    pub hook: &'static str,
+    /// How long the hook took, in milliseconds.
+    pub duration_ms: u128,
+    /// The error, if there was one.
+    pub error: Option<String>,
+}
+
+/// How a single command in the tree went.
+#[derive(Clone, Debug, Serialize)]
+pub struct CommandReport {
+    /// The command's [`crate::Context::path`].
+    pub path: String,
+    /// How long the command took, from `pre_run` to `post_run`, in
+    /// milliseconds.
+    pub duration_ms: u128,
+    /// Every hook that was called, in the order they were called.
+    pub hooks: Vec<HookReport>,
+    /// How many times `run` was retried.
+    pub retries: usize,
+    /// The outcome of the command and everything below it, if it failed.
+    pub error: Option<String>,
+}
+
+/// Everything that happened during an execution.
+///
+/// See the module documentation for usage.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct ExecutionReport {
+    /// Every command that was reached, parents first.
+    pub commands: Vec<CommandReport>,
+    /// How long the whole execution took, in milliseconds.
+    pub duration_ms: u128,
+    /// Whether execution was cancelled, such as by ctrl-c.
+    pub cancelled: bool,
+}
+
+impl Display for ExecutionReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let width = self
+            .commands
+            .iter()
+            .map(|command| command.path.len())
+            .chain(std::iter::once("post_run".len() + 2))
+            .max()
+            .unwrap_or(0);
+
+        for command in &self.commands {
+            write!(f, "{:width$}  {:>6}ms", command.path, command.duration_ms)?;
+            if command.retries > 0 {
+                write!(f, " ({} retries)", command.retries)?;
+            }
+            if command.error.is_some() {
+                write!(f, " failed")?;
+            }
+            writeln!(f)?;
+
+            for hook in &command.hooks {
+                write!(
+                    f,
+                    "  {:inner$}  {:>6}ms",
+                    hook.hook,
+                    hook.duration_ms,
+                    inner = width - 2,
+                )?;
+                if let Some(error) = &hook.error {
+                    write!(f, " {error}")?;
+                }
+                writeln!(f)?;
+            }
+        }
+
+        write!(f, "{:width$}  {:>6}ms", "total", self.duration_ms)?;
+        if self.cancelled {
+            write!(f, " cancelled")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// A command that was reached, for as long as it is being recorded.
+#[derive(Debug)]
+struct Entry {
+    report: CommandReport,
+    started: Instant,
+    finished: bool,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    started: Option<Instant>,
+    finished: Option<Instant>,
+    entries: Vec<Entry>,
+    cancelled: bool,
+}
+
+impl State {
+    /// The command at `path` that is still running.
+    fn running(&mut self, path: &str) -> Option<&mut Entry> {
+        self.entries
+            .iter_mut()
+            .rev()
+            .find(|entry| !entry.finished && entry.report.path == path)
+    }
+}
+
+/// Collects an [`ExecutionReport`].
+///
+/// Clones share what has been collected. See the module documentation for
+/// usage.
+#[derive(Clone, Debug, Default)]
+pub struct Recorder {
+    state: Arc<Mutex<State>>,
+}
+
+impl Recorder {
+    /// What has been recorded so far.
+    ///
+    /// Commands that are still running are reported with how long they have
+    /// been running for.
+    #[must_use]
+    pub fn report(&self) -> ExecutionReport {
+        let state = self.lock();
+        let now = Instant::now();
+
+        ExecutionReport {
+            commands: state
+                .entries
+                .iter()
+                .map(|entry| {
+                    let mut report = entry.report.clone();
+                    if !entry.finished {
+                        report.duration_ms = now.duration_since(entry.started).as_millis();
+                    }
+
+                    report
+                })
+                .collect(),
+            duration_ms: state
+                .started
+                .map_or(Duration::ZERO, |started| {
+                    state.finished.unwrap_or(now).duration_since(started)
+                })
+                .as_millis(),
+            cancelled: state.cancelled,
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+#[async_trait::async_trait]
+impl Layer for Recorder {
+    async fn call(&self, ctx: &mut Context, next: Next<'_>) -> Result<()> {
+        let hook = next.hook().name();
+        let started = Instant::now();
+        let result = next.run(ctx).await;
+        let duration = started.elapsed();
+
+        let mut state = self.lock();
+        state.cancelled |= ctx.token().is_cancelled();
+
+        if let Some(entry) = state.running(&ctx.path()) {
+            if hook == "run" && entry.report.hooks.iter().any(|hook| hook.hook == "run") {
+                entry.report.retries += 1;
+            }
+
+            entry.report.hooks.push(HookReport {
+                hook,
+                duration_ms: duration.as_millis(),
+                error: result.as_ref().err().map(ToString::to_string),
+            });
+        }
+
+        result
+    }
+}
+
+impl Subscriber for Recorder {
+    fn on_event(&self, event: &Event<'_>) {
+        let mut state = self.lock();
+
+        match event {
+            Event::CommandStarted { path } => {
+                let now = Instant::now();
+                state.started.get_or_insert(now);
+                state.entries.push(Entry {
+                    report: CommandReport {
+                        path: path.clone(),
+                        duration_ms: 0,
+                        hooks: Vec::new(),
+                        retries: 0,
+                        error: None,
+                    },
+                    started: now,
+                    finished: false,
+                });
+            }
+            Event::CommandFinished { path, result } => {
+                let now = Instant::now();
+                state.finished = Some(now);
+                state.cancelled |= result
+                    .as_ref()
+                    .is_err_and(|e| e.downcast_ref::<Interrupted>().is_some());
+
+                if let Some(entry) = state.running(path) {
+                    entry.finished = true;
+                    entry.report.duration_ms = now.duration_since(entry.started).as_millis();
+                    entry.report.error = result.as_ref().err().map(ToString::to_string);
+                }
+            }
+            Event::HookFailed { .. } => {}
+        }
+    }
+}
@@ -0,0 +1,284 @@
+//! The full command tree, not just the path that was parsed.
+//!
+//! [`crate::execute`] only ever sees the commands that were typed. Audits,
+//! permission maps and documentation need every command the CLI knows about,
+//! which is what [`Tree`] provides. It is built from the root
+//! `clap::Command` and can carry cata metadata, such as deprecations, that
+//! isn't part of clap.
+//!
+//! Some things to note:
+//! - Hidden commands are included, check [`Node::is_hidden`] to leave them out.
+//!   Commands behind a feature flag have [`Node::flag`] once
+//!   [`Tree::with_flags`] has been called, the same goes for
+//!   [`Node::examples`] and [`Tree::with_examples`].
+//! - Paths start with the name of the root command, `["my-cli", "projects",
+//!   "list"]`.
+//! - Clap's generated `help` subcommand is disabled, it isn't part of the tree.
+//!
+//! # Examples
+//!
+//! ```
+//! use cata::{
+//!   command::Deprecation,
+//!   tree::{Tree, Node, Visitor},
+//! };
+//! use clap::{CommandFactory, Parser, Subcommand};
+//!
+//! #[derive(Parser)]
+//! #[command(name = "my-cli")]
+//! struct Root {
+//!   #[command(subcommand)]
+//!   cmd: RootCmd,
+//! }
+//!
+//! #[derive(Subcommand)]
+//! enum RootCmd {
+//!   /// List projects.
+//!   List,
+//!   /// Remove a project.
+//!   Remove,
+//! }
+//!
+//! #[derive(Default)]
+//! struct Paths(Vec<String>);
+//!
+//! impl Visitor for Paths {
+//!   fn enter(&mut self, node: &Node) -> bool {
+//!     self.0.push(node.path().join(" "));
+//!
+//!     true
+//!   }
+//! }
+//!
+//! let tree = Tree::new(Root::command())
+//!   .with_deprecation(&["my-cli", "remove"], Deprecation::default());
+//!
+//! let mut paths = Paths::default();
+//! tree.walk(&mut paths);
+//!
+//! assert_eq!(paths.0, ["my-cli", "my-cli list", "my-cli remove"]);
+//! assert!(tree.find(&["my-cli", "remove"]).unwrap().deprecation().is_some());
+//! ```
+use crate::command::{Container, Deprecation};
+
+/// Called for every command while walking a [`Tree`].
+///
+/// Commands are visited depth first, parents before their children.
+pub trait Visitor {
+    /// Called before any of the children of `node`. Returning `false` skips
+    /// them.
+    fn enter(&mut self, _: &Node) -> bool {
+        true
+    }
+
+    /// Called after all of the children of `node`.
+    fn leave(&mut self, _: &Node) {}
+}
+
+/// A single command in a [`Tree`].
+#[derive(Clone, Debug)]
+pub struct Node {
+    path: Vec<String>,
+    command: clap::Command,
+    deprecation: Option<Deprecation>,
+    flag: Option<&'static str>,
+    examples: &'static [&'static str],
+    children: Vec<Node>,
+}
+
+impl Node {
+    fn new(command: clap::Command, parent: &[String]) -> Self {
+        let mut path = parent.to_vec();
+        path.push(command.get_name().to_string());
+
+        let children = command
+            .get_subcommands()
+            .map(|sub| Node::new(sub.clone(), &path))
+            .collect();
+
+        Self {
+            path,
+            command,
+            deprecation: None,
+            flag: None,
+            examples: &[],
+            children,
+        }
+    }
+
+    /// The name of the command.
+    #[must_use]
+    pub fn name(&self) -> &str {
+        self.command.get_name()
+    }
+
+    /// The names of every command from the root down to and including this
+    /// one.
+    #[must_use]
+    pub fn path(&self) -> &[String] {
+        &self.path
+    }
+
+    /// The clap definition of the command, for arguments, help and the rest.
+    #[must_use]
+    pub fn command(&self) -> &clap::Command {
+        &self.command
+    }
+
+    /// Whether the command is hidden from help.
+    #[must_use]
+    pub fn is_hidden(&self) -> bool {
+        self.command.is_hide_set()
+    }
+
+    /// The deprecation attached with [`Tree::with_deprecation`].
+    #[must_use]
+    pub fn deprecation(&self) -> Option<&Deprecation> {
+        self.deprecation.as_ref()
+    }
+
+    /// The feature flag attached with [`Tree::with_flags`], see
+    /// [`crate::flags`].
+    #[must_use]
+    pub fn flag(&self) -> Option<&'static str> {
+        self.flag
+    }
+
+    /// The examples attached with [`Tree::with_examples`], see
+    /// [`crate::examples`].
+    #[must_use]
+    pub fn examples(&self) -> &'static [&'static str] {
+        self.examples
+    }
+
+    /// The subcommands of this command.
+    #[must_use]
+    pub fn children(&self) -> &[Node] {
+        &self.children
+    }
+
+    fn walk(&self, visitor: &mut impl Visitor) {
+        if visitor.enter(self) {
+            for child in &self.children {
+                child.walk(visitor);
+            }
+        }
+
+        visitor.leave(self);
+    }
+}
+
+/// Every command a CLI knows about.
+///
+/// See the module documentation for usage.
+#[derive(Clone, Debug)]
+pub struct Tree {
+    root: Node,
+}
+
+impl Tree {
+    /// Build the tree rooted at `cmd`.
+    #[must_use]
+    pub fn new(cmd: clap::Command) -> Self {
+        let mut cmd = cmd.disable_help_subcommand(true);
+        cmd.build();
+
+        Self {
+            root: Node::new(cmd, &[]),
+        }
+    }
+
+    /// Mark the command at `path` as deprecated.
+    ///
+    /// Paths that don't exist are ignored.
+    #[must_use]
+    pub fn with_deprecation(mut self, path: &[&str], deprecation: Deprecation) -> Self {
+        if let Some(node) = self.find_mut(path) {
+            node.deprecation = Some(deprecation);
+        }
+
+        self
+    }
+
+    /// Attach the feature flags of `T`, usually the root the tree was built
+    /// from, see [`crate::flags`].
+    #[must_use]
+    pub fn with_flags<T: Container>(mut self) -> Self {
+        let root = self.root.name().to_string();
+
+        for (path, flag) in T::flags() {
+            let path: Vec<&str> = std::iter::once(root.as_str()).chain(path).collect();
+
+            if let Some(node) = self.find_mut(&path) {
+                node.flag = Some(flag);
+            }
+        }
+
+        self
+    }
+
+    /// Attach the examples of `T`, usually the root the tree was built from,
+    /// see [`crate::examples`].
+    #[must_use]
+    pub fn with_examples<T: Container>(mut self) -> Self {
+        let root = self.root.name().to_string();
+
+        for (path, examples) in T::all_examples() {
+            let path: Vec<&str> = std::iter::once(root.as_str()).chain(path).collect();
+
+            if let Some(node) = self.find_mut(&path) {
+                node.examples = examples;
+            }
+        }
+
+        self
+    }
+
+    /// The root command.
+    #[must_use]
+    pub fn root(&self) -> &Node {
+        &self.root
+    }
+
+    /// The command at `path`, if there is one.
+    #[must_use]
+    pub fn find(&self, path: &[&str]) -> Option<&Node> {
+        let (first, mut rest) = path.split_first()?;
+
+        if first != &self.root.name() {
+            return None;
+        }
+
+        let mut node = &self.root;
+        while let Some((name, remaining)) = rest.split_first() {
+            node = node.children.iter().find(|child| child.name() == *name)?;
+            rest = remaining;
+        }
+
+        Some(node)
+    }
+
+    fn find_mut(&mut self, path: &[&str]) -> Option<&mut Node> {
+        let (first, mut rest) = path.split_first()?;
+
+        if first != &self.root.name() {
+            return None;
+        }
+
+        let mut node = &mut self.root;
+        while let Some((name, remaining)) = rest.split_first() {
+            node = node
+                .children
+                .iter_mut()
+                .find(|child| child.name() == *name)?;
+            rest = remaining;
+        }
+
+        Some(node)
+    }
+
+    /// Visit every command in the tree, see [`Visitor`].
+    pub fn walk(&self, visitor: &mut impl Visitor) {
+        self.root.walk(visitor);
+    }
+}
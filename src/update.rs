@@ -0,0 +1,321 @@
+//! Notify users when a new version of the CLI is available.
+//!
+//! The check runs in the background while the command executes and only
+//! reports once the command has completed. If the check hasn't finished by
+//! then, it gets a short grace period (250ms by default) before being dropped
+//! instead of delaying the process from exiting.
+//!
+//! Some things to note:
+//! - The latest version is fetched from crates.io, or GitHub Releases with
+//!   [`Check::with_github`], at most once per interval (one day by default).
+//!   The result is kept in a [`crate::cache::Cache`], so subsequent runs do
+//!   not hit the network.
+//! - The check is disabled when stderr isn't a terminal, when the `CI`
+//!   environment variable is set or when `{NAME}_NO_UPDATE_CHECK` is set.
+//! - Notices are printed to stderr so that structured output is not corrupted.
+//! - [`crate::ExecuteOptions::with_update_check`] starts the check along with
+//!   the command tree and prints the notice after the last `post_run`.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use cata::{update::Check, Command, Container};
+//! use clap::Parser;
+//!
+//! #[derive(Parser, Container)]
+//! struct Root {}
+//!
+//! impl Command for Root {}
+//!
+//! #[tokio::main]
+//! async fn main() -> eyre::Result<()> {
+//!   let update = Check::new(env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION")).spawn();
+//!
+//!   cata::execute(&Root::parse()).await?;
+//!
+//!   update.notify().await;
+//!
+//!   Ok(())
+//! }
+//! ```
+//!
+//! The same, as part of execution:
+//!
+//! ```no_run
+//! use cata::{update::Check, Command, Container, ExecuteOptions};
+//! use clap::Parser;
+//!
+//! #[derive(Parser, Container)]
+//! struct Root {}
+//!
+//! impl Command for Root {}
+//!
+//! #[tokio::main]
+//! async fn main() -> eyre::Result<()> {
+//!   let check = Check::new(env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"))
+//!     .with_github("grampelberg/cata");
+//!
+//!   cata::execute_with(
+//!     &Root::parse(),
+//!     ExecuteOptions::default().with_update_check(check),
+//!   )
+//!   .await
+//! }
+//! ```
+use std::{io::IsTerminal, time::Duration};
+
+use eyre::Result;
+use serde::Deserialize;
+use tokio::sync::oneshot;
+
+use crate::{cache::Cache, dirs, runtime};
+
+static CACHE_KEY: &str = "update";
+static CRATES_IO: &str = "https://crates.io/api/v1/crates";
+static GITHUB: &str = "https://api.github.com/repos";
+static DEFAULT_INTERVAL: Duration = Duration::from_secs(60 * 60 * 24);
+static DEFAULT_GRACE: Duration = Duration::from_millis(250);
+
+#[derive(Deserialize)]
+struct CrateResponse {
+    #[serde(rename = "crate")]
+    krate: CrateInfo,
+}
+
+#[derive(Deserialize)]
+struct CrateInfo {
+    max_stable_version: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct Release {
+    tag_name: String,
+}
+
+/// Where the latest version is published.
+#[derive(Clone, Debug)]
+enum Source {
+    CratesIo,
+    GitHub(String),
+}
+
+/// A rate-limited check for a newer version of the CLI.
+///
+/// See the module documentation for usage.
+#[derive(Clone, Debug)]
+pub struct Check {
+    name: String,
+    current: String,
+    grace: Duration,
+    cache: Cache,
+    source: Source,
+}
+
+impl Check {
+    /// Create a new check for the crate `name`, currently running `version`.
+    pub fn new(name: impl AsRef<str>, version: impl AsRef<str>) -> Self {
+        Self {
+            name: name.as_ref().into(),
+            current: version.as_ref().into(),
+            grace: DEFAULT_GRACE,
+            cache: Cache::new(&name).with_ttl(DEFAULT_INTERVAL),
+            source: Source::CratesIo,
+        }
+    }
+
+    /// Set the minimum amount of time between requests for the latest version.
+    ///
+    /// By default, this is one day.
+    #[must_use]
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.cache = self.cache.with_ttl(interval);
+        self
+    }
+
+    /// Set how long to wait for an in-flight check once the command has
+    /// completed.
+    ///
+    /// By default, this is 250ms.
+    #[must_use]
+    pub fn with_grace(mut self, grace: Duration) -> Self {
+        self.grace = grace;
+        self
+    }
+
+    /// Look for the latest release of the GitHub repository `repo`, as
+    /// `owner/name`, instead of crates.io.
+    ///
+    /// Release tags can have a leading `v`, such as `v1.2.3`.
+    #[must_use]
+    pub fn with_github(mut self, repo: impl Into<String>) -> Self {
+        self.source = Source::GitHub(repo.into());
+        self
+    }
+
+    /// The variable that turns the check off.
+    fn disable_var(&self) -> String {
+        dirs::env_var(&self.name, "NO_UPDATE_CHECK")
+    }
+
+    /// Whether the check should run at all in this environment.
+    fn enabled(&self) -> bool {
+        std::io::stderr().is_terminal()
+            && std::env::var_os("CI").is_none()
+            && std::env::var_os(self.disable_var()).is_none()
+            && self.cache.is_available()
+    }
+
+    /// Start the check in the background.
+    ///
+    /// It runs on the current [`crate::runtime::Runtime`]. Call
+    /// [`Pending::notify`] once the command has completed to print a notice if
+    /// there is a newer version.
+    #[must_use]
+    pub fn spawn(self) -> Pending {
+        if !self.enabled() {
+            return Pending {
+                grace: self.grace,
+                handle: None,
+            };
+        }
+
+        let grace = self.grace;
+        let (mut tx, rx) = oneshot::channel();
+
+        runtime::current().spawn(Box::pin(async move {
+            let check = async {
+                let latest = self.latest().await?;
+
+                Ok(latest.and_then(|latest| {
+                    self.newer(&latest).then(|| Notice {
+                        name: self.name.clone(),
+                        current: self.current.clone(),
+                        latest,
+                    })
+                }))
+            };
+
+            // Dropping `Pending` cancels the check.
+            tokio::select! {
+                () = tx.closed() => {}
+                result = check => {
+                    tx.send(result).ok();
+                }
+            }
+        }));
+
+        Pending {
+            grace,
+            handle: Some(rx),
+        }
+    }
+
+    fn newer(&self, latest: &str) -> bool {
+        match (
+            semver::Version::parse(&self.current),
+            semver::Version::parse(latest),
+        ) {
+            (Ok(current), Ok(latest)) => latest > current,
+            _ => false,
+        }
+    }
+
+    /// Fetch the latest version, preferring the cache if it is fresh enough.
+    async fn latest(&self) -> Result<Option<String>> {
+        self.cache
+            .get_or_insert_with(CACHE_KEY, || self.fetch())
+            .await
+    }
+
+    /// Ask the source for the latest version.
+    async fn fetch(&self) -> Result<Option<String>> {
+        let client = reqwest::Client::builder()
+            .user_agent(format!("{}/{}", self.name, self.current))
+            .build()?;
+
+        match &self.source {
+            Source::CratesIo => {
+                let response: CrateResponse = client
+                    .get(format!("{CRATES_IO}/{}", self.name))
+                    .send()
+                    .await?
+                    .error_for_status()?
+                    .json()
+                    .await?;
+
+                Ok(response.krate.max_stable_version)
+            }
+            Source::GitHub(repo) => {
+                let release: Release = client
+                    .get(format!("{GITHUB}/{repo}/releases/latest"))
+                    .header("Accept", "application/vnd.github+json")
+                    .send()
+                    .await?
+                    .error_for_status()?
+                    .json()
+                    .await?;
+
+                Ok(Some(
+                    release
+                        .tag_name
+                        .strip_prefix('v')
+                        .unwrap_or(&release.tag_name)
+                        .to_string(),
+                ))
+            }
+        }
+    }
+}
+
+/// A newer version that is available.
+#[derive(Clone, Debug)]
+pub struct Notice {
+    /// Name of the crate.
+    pub name: String,
+    /// The version currently running.
+    pub current: String,
+    /// The latest version that has been published.
+    pub latest: String,
+}
+
+impl std::fmt::Display for Notice {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "A new version of {} is available: {} -> {}",
+            self.name, self.current, self.latest
+        )
+    }
+}
+
+/// An update check running in the background.
+#[derive(Debug)]
+pub struct Pending {
+    grace: Duration,
+    handle: Option<oneshot::Receiver<Result<Option<Notice>>>>,
+}
+
+impl Pending {
+    /// Returns the notice if the check completes within the grace period.
+    ///
+    /// Checks that are still in flight after the grace period are cancelled.
+    /// Errors are logged and otherwise ignored.
+    pub async fn result(self) -> Option<Notice> {
+        match runtime::timeout(self.grace, self.handle?).await {
+            Some(Ok(Ok(notice))) => notice,
+            Some(Ok(Err(e))) => {
+                tracing::debug!("update check failed: {e:?}");
+
+                None
+            }
+            Some(Err(_)) | None => None,
+        }
+    }
+
+    /// Print a notice to stderr if a newer version is available.
+    pub async fn notify(self) {
+        if let Some(notice) = self.result().await {
+            eprintln!("{notice}");
+        }
+    }
+}
@@ -0,0 +1,97 @@
+//! `-v` flags that control what gets logged.
+//!
+//! Every CLI ends up wiring a verbosity flag to a `tracing_subscriber` stack.
+//! Instead, commands expose their level with [`Command::verbosity`] and
+//! [`crate::execute`] installs a subscriber that logs to stderr before the
+//! first `pre_run`.
+//!
+//! Some things to note:
+//! - The root is asked first, the first command with a level wins. Flatten
+//!   [`Verbosity`] into the root to get a global `-v` flag.
+//! - `RUST_LOG` takes precedence over the level, as long as it is valid.
+//! - When a global subscriber has already been installed, it is left alone.
+//! - Other layers, such as [`crate::telemetry::Telemetry`], can be added to the
+//!   stack with [`crate::ExecuteOptions::with_tracing_layer`].
+//!
+//! # Examples
+//!
+//! ```
+//! use cata::{verbosity::Verbosity, Command, Container};
+//! use clap::Parser;
+//! use tracing::level_filters::LevelFilter;
+//!
+//! #[derive(Parser, Container)]
+//! struct Root {
+//!   #[command(flatten)]
+//!   verbosity: Verbosity,
+//! }
+//!
+//! #[async_trait::async_trait]
+//! impl Command for Root {
+//!   fn verbosity(&self) -> Option<LevelFilter> {
+//!     Some(self.verbosity.level())
+//!   }
+//!
+//!   async fn run(&self, _: &mut cata::Context) -> eyre::Result<()> {
+//!     tracing::debug!("only shown with -vv");
+//!
+//!     Ok(())
+//!   }
+//! }
+//!
+//! # #[tokio::main]
+//! # async fn main() -> eyre::Result<()> {
+//! cata::execute(&Root::parse_from(["root", "-vv"])).await
+//! # }
+//! ```
+//!
+//! [`Command::verbosity`]: crate::Command::verbosity
+use tracing::level_filters::LevelFilter;
+use tracing_subscriber::{prelude::*, EnvFilter, Layer, Registry};
+
+/// A layer that can be added to the subscriber installed by
+/// [`crate::execute`].
+pub type BoxLayer = Box<dyn Layer<Registry> + Send + Sync>;
+
+/// A `-v` flag, repeat it for more output.
+///
+/// The flag is global, so it can be passed after any subcommand.
+#[derive(clap::Args, Clone, Debug, Default)]
+pub struct Verbosity {
+    /// Log more, repeat for even more.
+    #[arg(short, long, action = clap::ArgAction::Count, global = true)]
+    pub verbose: u8,
+}
+
+impl Verbosity {
+    /// The level to log at.
+    ///
+    /// Warnings and errors are always logged, each `-v` adds a level starting
+    /// with info.
+    #[must_use]
+    pub fn level(&self) -> LevelFilter {
+        match self.verbose {
+            0 => LevelFilter::WARN,
+            1 => LevelFilter::INFO,
+            2 => LevelFilter::DEBUG,
+            _ => LevelFilter::TRACE,
+        }
+    }
+}
+
+/// Install a global subscriber logging at `level` along with `layers`.
+pub(crate) fn init(level: LevelFilter, layers: Vec<BoxLayer>) {
+    let filter = EnvFilter::builder()
+        .with_default_directive(level.into())
+        .from_env_lossy();
+
+    let format = tracing_subscriber::fmt::layer()
+        .with_writer(std::io::stderr)
+        .with_filter(filter);
+
+    tracing_subscriber::registry()
+        .with(layers)
+        .with(format)
+        .try_init()
+        .ok();
+}
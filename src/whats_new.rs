@@ -0,0 +1,157 @@
+//! Tell users what changed the first time they run a new version.
+//!
+//! The last version that ran is kept in [`crate::state`]. When the current
+//! version is newer, the notes supplied by the CLI are printed once and the new
+//! version is remembered.
+//!
+//! Some things to note:
+//! - Nothing is shown on a fresh install, there is nothing to compare to.
+//! - Versions are compared as semver. Downgrades, and versions that aren't
+//!   semver, are remembered without showing anything.
+//! - Notes are printed to stderr so that structured output is not corrupted.
+//! - Notes are suppressed when stderr isn't a terminal, when the `CI`
+//!   environment variable is set, when `{NAME}_NO_WHATS_NEW` is set or when
+//!   [`WhatsNew::with_quiet`] is used, for example from a `--quiet` flag.
+//!   Suppressed runs don't remember the version, so the notes are still shown
+//!   on the next interactive run.
+//!
+//! # Examples
+//!
+//! ```
+//! use cata::whats_new::WhatsNew;
+//!
+//! # let dir = std::env::temp_dir().join("cata-whats-new-example");
+//! let notes = WhatsNew::new(
+//!   "my-cli",
+//!   env!("CARGO_PKG_VERSION"),
+//!   "- `sync` now runs in parallel\n- `--output yaml` is available everywhere",
+//! );
+//! # let notes = notes.with_state(cata::state::State::new("my-cli").with_path(dir.join("state.json")));
+//!
+//! notes.show()?;
+//! # std::fs::remove_dir_all(dir).ok();
+//! # Ok::<(), eyre::Report>(())
+//! ```
+use std::io::IsTerminal;
+
+use eyre::Result;
+
+use crate::{dirs, state::State};
+
+static VERSION_KEY: &str = "whats_new.version";
+
+/// Notes for the current version, shown once after an upgrade.
+///
+/// See the module documentation for usage.
+#[derive(Clone, Debug)]
+pub struct WhatsNew {
+    name: String,
+    version: String,
+    notes: String,
+    state: State,
+    quiet: bool,
+}
+
+impl WhatsNew {
+    /// Create notes for the CLI `name`, currently running `version`.
+    pub fn new(name: impl AsRef<str>, version: impl AsRef<str>, notes: impl Into<String>) -> Self {
+        Self {
+            name: name.as_ref().into(),
+            version: version.as_ref().into(),
+            notes: notes.into(),
+            state: State::new(&name),
+            quiet: false,
+        }
+    }
+
+    /// Set the store used to remember the last version that ran.
+    ///
+    /// By default, this is [`State::new`] with the CLI's name.
+    #[must_use]
+    pub fn with_state(mut self, state: State) -> Self {
+        self.state = state;
+        self
+    }
+
+    /// Never show the notes, for example when `--quiet` was passed.
+    #[must_use]
+    pub fn with_quiet(mut self, quiet: bool) -> Self {
+        self.quiet = quiet;
+        self
+    }
+
+    /// The variable that turns the notes off.
+    fn disable_var(&self) -> String {
+        dirs::env_var(&self.name, "NO_WHATS_NEW")
+    }
+
+    /// Whether the notes can be shown in this environment.
+    fn enabled(&self) -> bool {
+        !self.quiet
+            && std::env::var_os("CI").is_none()
+            && std::env::var_os(self.disable_var()).is_none()
+    }
+
+    /// Record the current version, returning the notes when it is the first
+    /// run after an upgrade.
+    ///
+    /// This only checks the explicit suppressions, use [`WhatsNew::show`] to
+    /// also skip non-interactive runs.
+    ///
+    /// ```
+    /// use cata::{state::State, whats_new::WhatsNew};
+    ///
+    /// # std::env::remove_var("CI");
+    /// let dir = std::env::temp_dir().join("cata-whats-new-check");
+    /// let state = State::new("my-cli").with_path(dir.join("state.json"));
+    /// let notes = |version| WhatsNew::new("my-cli", version, "notes").with_state(state.clone());
+    ///
+    /// assert_eq!(notes("1.2.0").check()?, None);
+    /// assert_eq!(notes("1.10.0").check()?, Some("notes"));
+    /// assert_eq!(notes("1.9.0").check()?, None);
+    /// # std::fs::remove_dir_all(dir).ok();
+    /// # Ok::<(), eyre::Report>(())
+    /// ```
+    pub fn check(&self) -> Result<Option<&str>> {
+        if !self.enabled() {
+            return Ok(None);
+        }
+
+        let mut previous = None;
+        self.state.update(VERSION_KEY, |last: Option<String>| {
+            previous = last;
+            self.version.clone()
+        })?;
+
+        Ok(previous
+            .filter(|last| self.newer(last))
+            .map(|_| self.notes.as_str()))
+    }
+
+    /// Whether the current version comes after `last`.
+    fn newer(&self, last: &str) -> bool {
+        match (
+            semver::Version::parse(&self.version),
+            semver::Version::parse(last),
+        ) {
+            (Ok(current), Ok(last)) => current > last,
+            _ => false,
+        }
+    }
+
+    /// Print the notes to stderr when this is the first run after an upgrade.
+    pub fn show(&self) -> Result<()> {
+        if !std::io::stderr().is_terminal() {
+            return Ok(());
+        }
+
+        if let Some(notes) = self.check()? {
+            eprintln!(
+                "What's new in {} {}:\n\n{}\n",
+                self.name, self.version, notes
+            );
+        }
+
+        Ok(())
+    }
+}